@@ -10,7 +10,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "locators",
             "#[serde(with = \"crate::ser::serde_vec_bytes\")]",
         )
+        .field_attribute(
+            "siblings",
+            "#[serde(with = \"crate::ser::serde_vec_bytes\")]",
+        )
         .field_attribute("encrypted_blob", "#[serde(with = \"hex::serde\")]")
+        .field_attribute("challenge", "#[serde(with = \"hex::serde\", default)]")
+        .field_attribute("renewal_due", "#[serde(default)]")
+        .field_attribute("batch_receipt", "#[serde(default)]")
+        .field_attribute("n_leaves", "#[serde(default)]")
+        .field_attribute("n_appointments", "#[serde(default)]")
+        .field_attribute(
+            "RegisterRequest.payment_preimage",
+            "#[serde(with = \"hex::serde\", default)]",
+        )
+        .field_attribute("payment_hash", "#[serde(with = \"hex::serde\")]")
+        .field_attribute("dispute_on_chain", "#[serde(default)]")
+        .field_attribute("blocks_behind", "#[serde(default)]")
+        .field_attribute("AddAppointmentResult.error_code", "#[serde(default)]")
+        .field_attribute("AddAppointmentResult.error", "#[serde(default)]")
+        .field_attribute("attestation_signature", "#[serde(default)]")
+        .field_attribute("attestation_height", "#[serde(default)]")
+        .field_attribute("RegisterResponse.expiry_delta", "#[serde(default)]")
+        .field_attribute("RegisterResponse.broadcast_delay", "#[serde(default)]")
+        .field_attribute(
+            "RegisterResponse.previous_subscription_signature",
+            "#[serde(default)]",
+        )
+        .field_attribute("previous_signature", "#[serde(default)]")
+        .field_attribute("reissued_receipt", "#[serde(default)]")
         .field_attribute("dispute_txid", "#[serde(with = \"crate::ser::serde_be\")]")
         .field_attribute("penalty_txid", "#[serde(with = \"crate::ser::serde_be\")]")
         .field_attribute("penalty_rawtx", "#[serde(with = \"hex::serde\")]")
@@ -0,0 +1,609 @@
+//! Async client for the tower public (HTTP) API.
+//!
+//! Wraps the requests a user can send to a tower, taking care of signing them and verifying the receipts handed back, so
+//! Rust projects can integrate a tower client without re-implementing the protocol. Towers behind an onion address are
+//! reached through a SOCKS5 proxy (e.g. Tor).
+//!
+//! Notice appointments cannot be deleted once sent: the tower keeps them until the subscription that covers them expires.
+
+use std::fmt;
+
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::appointment::{Appointment, Locator};
+use crate::cryptography;
+use crate::protos as msgs;
+use crate::receipts::{AppointmentReceipt, RegistrationReceipt, TowerAttestation};
+use crate::{TowerId, UserId};
+
+/// Error returned by the tower API. Error codes match [errors](crate::errors).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub error: String,
+    pub error_code: u8,
+}
+
+/// Errors that may arise when interacting with a tower.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientError {
+    /// The tower cannot be reached.
+    ConnectionError(String),
+    /// The tower response cannot be parsed.
+    DeserializeError(String),
+    /// The tower rejected the request.
+    ApiError(ApiError),
+    /// The tower handed back a receipt that is not properly signed. This could be used as proof of misbehavior.
+    InvalidReceipt(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::ConnectionError(e) => write!(f, "Connection error: {}", e),
+            ClientError::DeserializeError(e) => write!(f, "Unexpected response: {}", e),
+            ClientError::ApiError(e) => write!(f, "{} (error_code={})", e.error, e.error_code),
+            ClientError::InvalidReceipt(e) => write!(f, "Invalid receipt: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl ClientError {
+    /// Whether the error was caused by the tower being unreachable.
+    pub fn is_connection(&self) -> bool {
+        matches!(self, ClientError::ConnectionError(_))
+    }
+}
+
+/// A client for a given tower, acting on behalf of a given user.
+#[derive(Debug, Clone)]
+pub struct TowerClient {
+    tower_id: TowerId,
+    net_addr: String,
+    user_sk: SecretKey,
+    user_id: UserId,
+    http: reqwest::Client,
+}
+
+impl TowerClient {
+    /// Creates a new [TowerClient] instance.
+    ///
+    /// `net_addr` is the tower public API address (e.g. `http://localhost:9814`). If a `proxy` (`host:port`) is provided,
+    /// all requests are sent through it. A proxy is required to reach onion addresses.
+    pub fn new(
+        tower_id: TowerId,
+        net_addr: &str,
+        user_sk: SecretKey,
+        proxy: Option<&str>,
+    ) -> Result<Self, ClientError> {
+        let url = reqwest::Url::parse(net_addr).map_err(|e| {
+            ClientError::ConnectionError(format!("Cannot connect to the given URL. {}", e))
+        })?;
+
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::all(format!("socks5h://{}", proxy))
+                    .map_err(|e| ClientError::ConnectionError(e.to_string()))?,
+            );
+        } else if url.host_str().map_or(false, |h| h.ends_with(".onion")) {
+            return Err(ClientError::ConnectionError(
+                "Cannot connect to an onion address without a proxy".to_owned(),
+            ));
+        }
+
+        Ok(TowerClient {
+            tower_id,
+            net_addr: net_addr.trim_end_matches('/').to_owned(),
+            user_id: UserId(PublicKey::from_secret_key(&Secp256k1::new(), &user_sk)),
+            user_sk,
+            http: builder
+                .build()
+                .map_err(|e| ClientError::ConnectionError(e.to_string()))?,
+        })
+    }
+
+    /// Gets the identifier of the tower the client talks to.
+    pub fn tower_id(&self) -> TowerId {
+        self.tower_id
+    }
+
+    /// Gets the identifier of the user the client acts on behalf of.
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    /// Gets the terms (slots, duration and price) new subscriptions are offered under.
+    pub async fn get_registration_terms(
+        &self,
+    ) -> Result<msgs::GetRegistrationTermsResponse, ClientError> {
+        self.post(
+            "get_registration_terms",
+            &msgs::GetRegistrationTermsRequest {},
+        )
+        .await
+    }
+
+    /// Gets an invoice to pay for the registration of the user. Only towers that require payments issue invoices.
+    pub async fn get_registration_invoice(
+        &self,
+    ) -> Result<msgs::GetRegistrationInvoiceResponse, ClientError> {
+        self.post(
+            "get_registration_invoice",
+            &msgs::GetRegistrationInvoiceRequest {
+                user_id: self.user_id.to_vec(),
+            },
+        )
+        .await
+    }
+
+    /// Registers the user with the tower (or renews the subscription if already registered).
+    ///
+    /// The registration receipt is checked to be signed by the tower.
+    pub async fn register(&self) -> Result<RegistrationReceipt, ClientError> {
+        self.register_with_payment(Vec::new()).await
+    }
+
+    /// Registers the user with the tower (or renews the subscription if already registered), proving the payment of
+    /// the subscription with the preimage of a paid invoice (check
+    /// [get_registration_invoice](Self::get_registration_invoice)).
+    ///
+    /// The registration receipt is checked to be signed by the tower.
+    pub async fn register_with_payment(
+        &self,
+        payment_preimage: Vec<u8>,
+    ) -> Result<RegistrationReceipt, ClientError> {
+        let r: msgs::RegisterResponse = self
+            .post(
+                "register",
+                &msgs::RegisterRequest {
+                    user_id: self.user_id.to_vec(),
+                    payment_preimage,
+                },
+            )
+            .await?;
+
+        let mut receipt = RegistrationReceipt::with_signature(
+            self.user_id,
+            r.available_slots,
+            r.subscription_start,
+            r.subscription_expiry,
+            r.subscription_signature,
+        );
+        if !receipt.verify(&self.tower_id) {
+            return Err(ClientError::InvalidReceipt(
+                "The registration receipt is not signed by the tower".to_owned(),
+            ));
+        }
+
+        // Towers running older versions do not attest the registration
+        if !r.attestation_signature.is_empty() {
+            receipt.set_attestation(TowerAttestation::new(
+                self.tower_id,
+                r.attestation_height,
+                r.attestation_signature,
+            ));
+            if !receipt.verify_attestation(&self.tower_id) {
+                return Err(ClientError::InvalidReceipt(
+                    "The registration attestation is not signed by the tower".to_owned(),
+                ));
+            }
+        }
+
+        Ok(receipt)
+    }
+
+    /// Sends an appointment to the tower. Returns the appointment receipt alongside the available slots of the user.
+    ///
+    /// The receipt is checked to be signed by the tower. If the tower signs receipts in batches, the receipt is returned
+    /// with no signature, and the signed one can be fetched from the tower once the next block is mined.
+    pub async fn add_appointment(
+        &self,
+        appointment: &Appointment,
+    ) -> Result<(AppointmentReceipt, u32), ClientError> {
+        let signature = cryptography::sign(&appointment.to_vec(), &self.user_sk).unwrap();
+        let r: msgs::AddAppointmentResponse = self
+            .post(
+                "add_appointment",
+                &msgs::AddAppointmentRequest {
+                    appointment: Some(appointment.clone().into()),
+                    signature: signature.clone(),
+                    batch_receipt: false,
+                },
+            )
+            .await?;
+
+        if r.signature.is_empty() {
+            return Ok((
+                AppointmentReceipt::new(signature, r.start_block),
+                r.available_slots,
+            ));
+        }
+
+        let receipt = AppointmentReceipt::with_signature(signature, r.start_block, r.signature);
+        if receipt.verify(&self.tower_id) {
+            Ok((receipt, r.available_slots))
+        } else {
+            Err(ClientError::InvalidReceipt(format!(
+                "The receipt for appointment {} is not signed by the tower",
+                appointment.locator
+            )))
+        }
+    }
+
+    /// Gets a one-time challenge from the tower, to be signed alongside the next authenticated request.
+    pub async fn get_auth_challenge(&self) -> Result<Vec<u8>, ClientError> {
+        let r: msgs::GetAuthChallengeResponse = self
+            .post(
+                "get_auth_challenge",
+                &msgs::GetAuthChallengeRequest {
+                    user_id: self.user_id.to_vec(),
+                },
+            )
+            .await?;
+        Ok(r.challenge)
+    }
+
+    /// Gets the data the tower holds about a given appointment.
+    ///
+    /// The request is signed alongside a fresh challenge (check [get_auth_challenge](Self::get_auth_challenge)), so it
+    /// cannot be replayed.
+    pub async fn get_appointment(
+        &self,
+        locator: Locator,
+    ) -> Result<msgs::GetAppointmentResponse, ClientError> {
+        let challenge = self.get_auth_challenge().await?;
+        let message = format!("get appointment {}", locator);
+        self.post(
+            "get_appointment",
+            &msgs::GetAppointmentRequest {
+                locator: locator.to_vec(),
+                signature: self.sign_challenged(message.as_bytes(), &challenge),
+                challenge,
+            },
+        )
+        .await
+    }
+
+    /// Gets the subscription information the tower holds about the user.
+    ///
+    /// The request is signed alongside a fresh challenge (check [get_auth_challenge](Self::get_auth_challenge)), so it
+    /// cannot be replayed.
+    pub async fn get_subscription_info(
+        &self,
+    ) -> Result<msgs::GetSubscriptionInfoResponse, ClientError> {
+        let challenge = self.get_auth_challenge().await?;
+        self.post(
+            "get_subscription_info",
+            &msgs::GetSubscriptionInfoRequest {
+                signature: self.sign_challenged("get subscription info".as_bytes(), &challenge),
+                challenge,
+            },
+        )
+        .await
+    }
+
+    /// Signs a message alongside a challenge, the way the tower expects challenged requests to be signed.
+    fn sign_challenged(&self, message: &[u8], challenge: &[u8]) -> String {
+        cryptography::sign(&[message, challenge].concat(), &self.user_sk).unwrap()
+    }
+
+    /// Posts a request to a given endpoint of the tower and parses the response.
+    async fn post<S: Serialize, T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        data: &S,
+    ) -> Result<T, ClientError> {
+        let response = self
+            .http
+            .post(format!("{}/{}", self.net_addr, endpoint))
+            .json(data)
+            .send()
+            .await
+            .map_err(|e| ClientError::ConnectionError(e.to_string()))?;
+
+        if response.status().is_success() {
+            response
+                .json()
+                .await
+                .map_err(|e| ClientError::DeserializeError(e.to_string()))
+        } else {
+            Err(ClientError::ApiError(response.json().await.map_err(
+                |e| ClientError::DeserializeError(e.to_string()),
+            )?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use serde_json::json;
+
+    use crate::appointment::AppointmentStatus;
+    use crate::cryptography::get_random_keypair;
+    use crate::errors;
+    use crate::test_utils::generate_random_appointment;
+
+    fn get_client(server: &MockServer) -> (TowerClient, SecretKey) {
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let (user_sk, _) = get_random_keypair();
+        (
+            TowerClient::new(TowerId(tower_pk), &server.base_url(), user_sk, None).unwrap(),
+            tower_sk,
+        )
+    }
+
+    #[test]
+    fn test_new_onion_requires_proxy() {
+        let (_, tower_pk) = get_random_keypair();
+        let (user_sk, _) = get_random_keypair();
+        let onion = "http://rfngq5ibcafxxo4jb7bmnbzpwbzp2ruvbmusxizeimbn3gpmoyqvptid.onion:9814";
+
+        assert!(matches!(
+            TowerClient::new(TowerId(tower_pk), onion, user_sk, None),
+            Err(ClientError::ConnectionError(_))
+        ));
+        assert!(
+            TowerClient::new(TowerId(tower_pk), onion, user_sk, Some("127.0.0.1:9050")).is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let server = MockServer::start();
+        let (client, tower_sk) = get_client(&server);
+
+        let mut receipt = RegistrationReceipt::new(client.user_id(), 21, 42, 420);
+        receipt.sign(&tower_sk);
+        receipt.attest(&tower_sk, 42);
+        let api_mock = server.mock(|when, then| {
+            when.method(POST).path("/register");
+            then.status(200).json_body(json!(msgs::RegisterResponse {
+                user_id: client.user_id().to_vec(),
+                available_slots: receipt.available_slots(),
+                subscription_start: receipt.subscription_start(),
+                subscription_expiry: receipt.subscription_expiry(),
+                subscription_signature: receipt.signature().unwrap(),
+                attestation_signature: receipt.attestation().unwrap().signature().to_owned(),
+                attestation_height: 42,
+                expiry_delta: 6,
+                broadcast_delay: 0,
+                previous_subscription_signature: String::new(),
+            }));
+        });
+
+        assert_eq!(client.register().await, Ok(receipt));
+        api_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_register_invalid_receipt() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+
+        // The receipt is signed by someone else
+        let (other_sk, _) = get_random_keypair();
+        let mut receipt = RegistrationReceipt::new(client.user_id(), 21, 42, 420);
+        receipt.sign(&other_sk);
+        server.mock(|when, then| {
+            when.method(POST).path("/register");
+            then.status(200).json_body(json!(msgs::RegisterResponse {
+                user_id: client.user_id().to_vec(),
+                available_slots: receipt.available_slots(),
+                subscription_start: receipt.subscription_start(),
+                subscription_expiry: receipt.subscription_expiry(),
+                subscription_signature: receipt.signature().unwrap(),
+                attestation_signature: String::new(),
+                attestation_height: 0,
+                expiry_delta: 6,
+                broadcast_delay: 0,
+                previous_subscription_signature: String::new(),
+            }));
+        });
+
+        assert!(matches!(
+            client.register().await,
+            Err(ClientError::InvalidReceipt(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_register_wrong_payment() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+
+        let api_error = ApiError {
+            error: "The payment preimage does not match any pending invoice of the user".to_owned(),
+            error_code: errors::REGISTRATION_WRONG_PAYMENT,
+        };
+        let api_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/register")
+                .json_body_partial(format!(r#"{{"payment_preimage": "{}"}}"#, "00".repeat(32)));
+            then.status(402).json_body(json!(api_error));
+        });
+
+        assert_eq!(
+            client.register_with_payment(vec![0; 32]).await,
+            Err(ClientError::ApiError(api_error))
+        );
+        api_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_get_registration_terms() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+
+        let terms = msgs::GetRegistrationTermsResponse {
+            subscription_slots: 21,
+            subscription_duration: 42,
+            subscription_price_per_slot_msat: 2,
+            subscription_price_per_block_msat: 1,
+            subscription_price_msat: 84,
+            payments_required: true,
+            accepting_registrations: true,
+            max_users: 0,
+        };
+        server.mock(|when, then| {
+            when.method(POST).path("/get_registration_terms");
+            then.status(200).json_body(json!(terms));
+        });
+
+        assert_eq!(client.get_registration_terms().await, Ok(terms));
+    }
+
+    #[tokio::test]
+    async fn test_get_registration_invoice() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+
+        let invoice = msgs::GetRegistrationInvoiceResponse {
+            payment_hash: vec![1; 32],
+            amount_msat: 84,
+        };
+        let api_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/get_registration_invoice")
+                .json_body_partial(format!(r#"{{"user_id": "{}"}}"#, client.user_id()));
+            then.status(200).json_body(json!(invoice));
+        });
+
+        assert_eq!(client.get_registration_invoice().await, Ok(invoice));
+        api_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment() {
+        let server = MockServer::start();
+        let (client, tower_sk) = get_client(&server);
+
+        let appointment = generate_random_appointment(None);
+        let user_signature = cryptography::sign(&appointment.to_vec(), &client.user_sk).unwrap();
+        let mut receipt = AppointmentReceipt::new(user_signature, 42);
+        receipt.sign(&tower_sk);
+        server.mock(|when, then| {
+            when.method(POST).path("/add_appointment");
+            then.status(200)
+                .json_body(json!(msgs::AddAppointmentResponse {
+                    locator: appointment.locator.to_vec(),
+                    start_block: receipt.start_block(),
+                    signature: receipt.signature().unwrap(),
+                    available_slots: 20,
+                    subscription_expiry: 420,
+                    renewal_due: false,
+                    dispute_on_chain: false,
+                    blocks_behind: 0,
+                    previous_signature: String::new(),
+                }));
+        });
+
+        assert_eq!(
+            client.add_appointment(&appointment).await,
+            Ok((receipt, 20))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_api_error() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+
+        let api_error = ApiError {
+            error: "Invalid signature or user does not have enough slots available".to_owned(),
+            error_code: errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+        };
+        server.mock(|when, then| {
+            when.method(POST).path("/add_appointment");
+            then.status(400).json_body(json!(api_error));
+        });
+
+        assert_eq!(
+            client
+                .add_appointment(&generate_random_appointment(None))
+                .await,
+            Err(ClientError::ApiError(api_error))
+        );
+    }
+
+    /// Mocks the challenge issued by the tower and returns it.
+    fn mock_challenge(server: &MockServer) -> Vec<u8> {
+        let challenge = vec![7; 32];
+        let response = msgs::GetAuthChallengeResponse {
+            challenge: challenge.clone(),
+        };
+        server.mock(|when, then| {
+            when.method(POST).path("/get_auth_challenge");
+            then.status(200).json_body(json!(response));
+        });
+        challenge
+    }
+
+    #[tokio::test]
+    async fn test_get_appointment() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+        let challenge = mock_challenge(&server);
+
+        let appointment = generate_random_appointment(None);
+        let response = msgs::GetAppointmentResponse {
+            appointment_data: None,
+            status: AppointmentStatus::NotFound as i32,
+        };
+        let api_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/get_appointment")
+                .json_body_partial(json!({ "challenge": hex::encode(&challenge) }).to_string());
+            then.status(200).json_body(json!(response));
+        });
+
+        assert_eq!(
+            client.get_appointment(appointment.locator).await,
+            Ok(response)
+        );
+        api_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_info() {
+        let server = MockServer::start();
+        let (client, _) = get_client(&server);
+        let challenge = mock_challenge(&server);
+
+        let response = msgs::GetSubscriptionInfoResponse {
+            available_slots: 21,
+            subscription_expiry: 420,
+            locators: Vec::new(),
+            renewal_due: false,
+            n_appointments: 0,
+            reissued_receipt: None,
+        };
+        let api_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/get_subscription_info")
+                .json_body_partial(json!({ "challenge": hex::encode(&challenge) }).to_string());
+            then.status(200).json_body(json!(response));
+        });
+
+        assert_eq!(client.get_subscription_info().await, Ok(response));
+        api_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_connection_error() {
+        let (_, tower_pk) = get_random_keypair();
+        let (user_sk, _) = get_random_keypair();
+        // Nothing is listening here
+        let client =
+            TowerClient::new(TowerId(tower_pk), "http://127.0.0.1:1", user_sk, None).unwrap();
+
+        assert!(matches!(
+            client.get_subscription_info().await,
+            Err(ClientError::ConnectionError(_))
+        ));
+    }
+}
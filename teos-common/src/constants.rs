@@ -7,3 +7,5 @@ pub const IRREVOCABLY_RESOLVED: u32 = 100;
 // Temporary constants, may be changed
 /// Maximum size of encrypted blobs in appointments.
 pub const ENCRYPTED_BLOB_MAX_SIZE: usize = 2048;
+/// Maximum number of appointments that can be sent to the tower in a single `add_appointments` request.
+pub const MAX_APPOINTMENTS_PER_BATCH: usize = 100;
@@ -6,6 +6,8 @@ pub const WRONG_FIELD_SIZE: u8 = 4;
 pub const WRONG_FIELD_FORMAT: u8 = 5;
 pub const INVALID_REQUEST_FORMAT: u8 = 6;
 pub const INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR: u8 = 7;
+pub const TOO_MANY_REQUESTS: u8 = 8;
+pub const SERVICE_DISABLED: u8 = 9;
 pub const SERVICE_UNAVAILABLE: u8 = 32;
 
 /// Appointment errors [33, 64]
@@ -13,9 +15,13 @@ pub const APPOINTMENT_FIELD_TOO_SMALL: u8 = 33;
 pub const APPOINTMENT_FIELD_TOO_BIG: u8 = 34;
 pub const APPOINTMENT_ALREADY_TRIGGERED: u8 = 35;
 pub const APPOINTMENT_NOT_FOUND: u8 = 36;
+pub const APPOINTMENT_REJECTED_BY_POLICY: u8 = 37;
+pub const APPOINTMENT_DUPLICATE_LOCATOR: u8 = 38;
 
 /// Registration errors [65, 96]
 pub const REGISTRATION_RESOURCE_EXHAUSTED: u8 = 65;
+pub const REGISTRATION_WRONG_PAYMENT: u8 = 66;
+pub const REGISTRATION_MAX_USERS_REACHED: u8 = 67;
 
 /// UNHANDLED
 pub const UNEXPECTED_ERROR: u8 = 255;
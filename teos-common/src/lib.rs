@@ -9,10 +9,14 @@ pub mod protos {
 }
 
 pub mod appointment;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod constants;
 pub mod cryptography;
 pub mod dbm;
 pub mod errors;
+pub mod merkle;
+pub mod migration;
 pub mod net;
 pub mod receipts;
 pub mod ser;
@@ -0,0 +1,246 @@
+//! Logic related to Merkle trees, used by towers to batch the signature of multiple receipts into a single one.
+//!
+//! Leaves and inner nodes are hashed using different prefixes (`0x00` and `0x01` respectively) so an inner node
+//! cannot be passed as a leaf. If a level has an odd number of nodes, the last one is paired with itself. Given that
+//! makes a tree with an odd number of leaves indistinguishable from one with the last leaf duplicated, the number of
+//! leaves is committed to in the root (using the `0x02` prefix), and proofs for leaves past that number are rejected.
+
+use serde::Serialize;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const ROOT_PREFIX: u8 = 0x02;
+
+/// Computes the hash of a leaf given its data.
+pub fn leaf_hash(data: &[u8]) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[LEAF_PREFIX]);
+    engine.input(data);
+    sha256::Hash::from_engine(engine)
+}
+
+/// Computes the hash of an inner node given its children.
+fn node_hash(left: &sha256::Hash, right: &sha256::Hash) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[NODE_PREFIX]);
+    engine.input(&left[..]);
+    engine.input(&right[..]);
+    sha256::Hash::from_engine(engine)
+}
+
+/// Computes the root of a tree given the number of leaves and the top inner node.
+fn root_hash(n_leaves: u32, top: &sha256::Hash) -> sha256::Hash {
+    let mut engine = sha256::Hash::engine();
+    engine.input(&[ROOT_PREFIX]);
+    engine.input(&n_leaves.to_be_bytes());
+    engine.input(&top[..]);
+    sha256::Hash::from_engine(engine)
+}
+
+/// A Merkle tree built over a non-empty list of leaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// The tree levels, from the leaves (first) to the root (last).
+    levels: Vec<Vec<sha256::Hash>>,
+}
+
+impl MerkleTree {
+    /// Builds a new [MerkleTree] from the leaves data. Returns `None` if no leaves are provided.
+    pub fn new<T: AsRef<[u8]>>(leaves: &[T]) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| leaf_hash(leaf.as_ref()))
+            .collect::<Vec<_>>()];
+
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            levels.push(next);
+        }
+
+        Some(MerkleTree { levels })
+    }
+
+    /// Gets the root of the tree, which commits to the number of leaves.
+    pub fn root(&self) -> sha256::Hash {
+        root_hash(self.len() as u32, &self.levels.last().unwrap()[0])
+    }
+
+    /// Gets the number of leaves in the tree.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Returns whether the tree has no leaves. Always false, given empty trees cannot be built.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Gets the inclusion proof of the leaf at a given index. Returns `None` if the index is out of bounds.
+    pub fn get_proof(&self, index: usize) -> Option<MerkleProof> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::with_capacity(self.levels.len() - 1);
+        let mut i = index;
+        for level in self.levels.iter().take(self.levels.len() - 1) {
+            siblings.push(*level.get(i ^ 1).unwrap_or(&level[i]));
+            i >>= 1;
+        }
+
+        Some(MerkleProof::new(index as u32, self.len() as u32, siblings))
+    }
+}
+
+/// Proof that a given leaf is part of a [MerkleTree] with a given root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MerkleProof {
+    /// The position of the leaf in the tree.
+    index: u32,
+    /// The number of leaves in the tree.
+    n_leaves: u32,
+    /// The sibling of each node in the path from the leaf to the root (leaf level first).
+    siblings: Vec<sha256::Hash>,
+}
+
+impl MerkleProof {
+    pub fn new(index: u32, n_leaves: u32, siblings: Vec<sha256::Hash>) -> Self {
+        MerkleProof {
+            index,
+            n_leaves,
+            siblings,
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn n_leaves(&self) -> u32 {
+        self.n_leaves
+    }
+
+    pub fn siblings(&self) -> &Vec<sha256::Hash> {
+        &self.siblings
+    }
+
+    /// Computes the root of the tree the proof belongs to given the leaf data.
+    ///
+    /// Returns `None` if the leaf index is out of the bounds of the tree, given a proof for a leaf past the end of
+    /// the tree could be built out of the duplicated nodes.
+    pub fn compute_root(&self, leaf: &[u8]) -> Option<sha256::Hash> {
+        if self.index >= self.n_leaves {
+            return None;
+        }
+
+        let mut hash = leaf_hash(leaf);
+        let mut i = self.index;
+        for sibling in self.siblings.iter() {
+            hash = if i & 1 == 0 {
+                node_hash(&hash, sibling)
+            } else {
+                node_hash(sibling, &hash)
+            };
+            i >>= 1;
+        }
+
+        Some(root_hash(self.n_leaves, &hash))
+    }
+
+    /// Verifies the given leaf data is part of the tree with the given root.
+    pub fn verify(&self, leaf: &[u8], root: &sha256::Hash) -> bool {
+        self.compute_root(leaf) == Some(*root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cryptography::get_random_bytes;
+
+    #[test]
+    fn test_empty_tree() {
+        assert!(MerkleTree::new::<Vec<u8>>(&[]).is_none());
+    }
+
+    #[test]
+    fn test_single_leaf() {
+        let leaf = get_random_bytes(32);
+        let tree = MerkleTree::new(&[leaf.clone()]).unwrap();
+
+        assert_eq!(tree.root(), root_hash(1, &leaf_hash(&leaf)));
+        let proof = tree.get_proof(0).unwrap();
+        assert!(proof.siblings().is_empty());
+        assert!(proof.verify(&leaf, &tree.root()));
+    }
+
+    #[test]
+    fn test_get_proof_verify() {
+        // Check both balanced and unbalanced trees
+        for n in 1..20 {
+            let leaves: Vec<Vec<u8>> = (0..n).map(|_| get_random_bytes(32)).collect();
+            let tree = MerkleTree::new(&leaves).unwrap();
+            assert_eq!(tree.len(), n);
+
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.get_proof(i).unwrap();
+                assert_eq!(proof.index(), i as u32);
+                assert!(proof.verify(leaf, &tree.root()));
+
+                // The proof does not work for any other leaf
+                assert!(!proof.verify(&get_random_bytes(32), &tree.root()));
+            }
+
+            // Proofs cannot be generated for out of bound indexes
+            assert!(tree.get_proof(n).is_none());
+        }
+    }
+
+    #[test]
+    fn test_proof_wrong_index() {
+        let leaves: Vec<Vec<u8>> = (0..4).map(|_| get_random_bytes(32)).collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        let proof = tree.get_proof(0).unwrap();
+        let wrong_proof = MerkleProof::new(1, proof.n_leaves(), proof.siblings().clone());
+        assert!(!wrong_proof.verify(&leaves[0], &tree.root()));
+    }
+
+    #[test]
+    fn test_proof_duplicated_leaf() {
+        // The last leaf of an unbalanced tree is paired with itself, so it cannot be proved a second time past the end
+        let leaves: Vec<Vec<u8>> = (0..3).map(|_| get_random_bytes(32)).collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        let proof = tree.get_proof(2).unwrap();
+        let duplicate_proof = MerkleProof::new(3, proof.n_leaves(), proof.siblings().clone());
+        assert!(duplicate_proof.compute_root(&leaves[2]).is_none());
+        assert!(!duplicate_proof.verify(&leaves[2], &tree.root()));
+
+        // Neither can it be proved by claiming the tree has the last leaf duplicated
+        let mut duplicated_leaves = leaves.clone();
+        duplicated_leaves.push(leaves[2].clone());
+        let duplicated_tree = MerkleTree::new(&duplicated_leaves).unwrap();
+        assert_ne!(duplicated_tree.root(), tree.root());
+        assert!(!duplicated_tree
+            .get_proof(3)
+            .unwrap()
+            .verify(&leaves[2], &tree.root()));
+
+        // The leaf count is also bound to the proof
+        let wrong_count = MerkleProof::new(2, 4, proof.siblings().clone());
+        assert!(!wrong_count.verify(&leaves[2], &tree.root()));
+    }
+}
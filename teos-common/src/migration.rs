@@ -0,0 +1,141 @@
+//! Logic related to moving user data from one tower to another.
+
+use bitcoin::secp256k1::SecretKey;
+
+use crate::{cryptography, TowerId, UserId};
+
+/// Consent given by a user to have their data moved to a given tower.
+///
+/// The consent is a user signature over the destination tower identity (`tower_id`) and the block height the consent is
+/// valid until (`expiry`). The tower holding the user data (source) requires it to export it, and the tower receiving it
+/// (destination) to import it, so user data can only be moved to the tower the user chose, and only for a limited time.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MigrationConsent {
+    user_id: UserId,
+    tower_id: TowerId,
+    expiry: u32,
+    signature: Option<String>,
+}
+
+impl MigrationConsent {
+    pub fn new(user_id: UserId, tower_id: TowerId, expiry: u32) -> Self {
+        MigrationConsent {
+            user_id,
+            tower_id,
+            expiry,
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(
+        user_id: UserId,
+        tower_id: TowerId,
+        expiry: u32,
+        signature: String,
+    ) -> Self {
+        MigrationConsent {
+            user_id,
+            tower_id,
+            expiry,
+            signature: Some(signature),
+        }
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id
+    }
+
+    pub fn tower_id(&self) -> TowerId {
+        self.tower_id
+    }
+
+    pub fn expiry(&self) -> u32 {
+        self.expiry
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    /// Whether the consent has expired at a given block height. Consents are valid up to (and including) `expiry`.
+    pub fn is_expired(&self, block_height: u32) -> bool {
+        block_height > self.expiry
+    }
+
+    /// Serializes the consent so it can be signed by the user:
+    ///
+    /// `"migrate to" | tower_id (33 bytes) | expiry (4-byte BE)`
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut ser = Vec::new();
+        ser.extend_from_slice(b"migrate to");
+        ser.extend_from_slice(&self.tower_id.to_vec());
+        ser.extend_from_slice(&self.expiry.to_be_bytes());
+
+        ser
+    }
+
+    pub fn sign(&mut self, sk: &SecretKey) {
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        self.signature = Some(cryptography::sign(&self.to_vec(), sk).unwrap());
+    }
+
+    /// Verifies the consent was signed by the user.
+    pub fn verify(&self) -> bool {
+        if let Some(signature) = self.signature() {
+            cryptography::verify(&self.to_vec(), &signature, &self.user_id.0)
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cryptography::get_random_keypair;
+
+    const EXPIRY: u32 = 500;
+
+    #[test]
+    fn test_migration_consent() {
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let tower_id = TowerId(get_random_keypair().1);
+
+        // A consent with no signature cannot be verified
+        let mut consent = MigrationConsent::new(user_id, tower_id, EXPIRY);
+        assert!(!consent.verify());
+
+        // Once signed by the user, it can be verified
+        consent.sign(&user_sk);
+        assert!(consent.verify());
+
+        // But not if it was signed by someone else, or for a different tower
+        let (another_sk, _) = get_random_keypair();
+        let mut forged_consent = MigrationConsent::new(user_id, tower_id, EXPIRY);
+        forged_consent.sign(&another_sk);
+        assert!(!forged_consent.verify());
+
+        let another_tower_consent = MigrationConsent::with_signature(
+            user_id,
+            TowerId(get_random_keypair().1),
+            EXPIRY,
+            consent.signature().unwrap(),
+        );
+        assert!(!another_tower_consent.verify());
+
+        // Nor can the expiry be extended
+        let extended_consent = MigrationConsent::with_signature(
+            user_id,
+            tower_id,
+            EXPIRY + 1,
+            consent.signature().unwrap(),
+        );
+        assert!(!extended_consent.verify());
+
+        // The consent is valid up to its expiry
+        assert!(!consent.is_expired(EXPIRY));
+        assert!(consent.is_expired(EXPIRY + 1));
+    }
+}
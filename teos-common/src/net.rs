@@ -4,6 +4,7 @@ use std::fmt;
 pub enum AddressType {
     IpV4 = 0,
     TorV3 = 1,
+    IpV6 = 2,
 }
 
 impl From<i32> for AddressType {
@@ -11,6 +12,7 @@ impl From<i32> for AddressType {
         match x {
             0 => AddressType::IpV4,
             1 => AddressType::TorV3,
+            2 => AddressType::IpV6,
             x => panic!("Unknown address type {}", x),
         }
     }
@@ -23,6 +25,7 @@ impl std::str::FromStr for AddressType {
         match s {
             "ipv4" => Ok(AddressType::IpV4),
             "torv3" => Ok(AddressType::TorV3),
+            "ipv6" => Ok(AddressType::IpV6),
             _ => Err(format!("Unknown type: {}", s)),
         }
     }
@@ -33,6 +36,7 @@ impl fmt::Display for AddressType {
         let s = match self {
             AddressType::IpV4 => "ipv4",
             AddressType::TorV3 => "torv3",
+            AddressType::IpV6 => "ipv6",
         };
         write!(f, "{}", s)
     }
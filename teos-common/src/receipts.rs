@@ -2,9 +2,49 @@
 
 use serde::Serialize;
 
-use bitcoin::secp256k1::SecretKey;
+use std::convert::TryFrom;
 
-use crate::{cryptography, UserId};
+use bitcoin::hashes::{self, sha256, Hash};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::Txid;
+
+use crate::merkle::MerkleProof;
+use crate::protos as msgs;
+use crate::{cryptography, TowerId, UserId};
+
+/// Attestation of the subscription terms a tower has agreed on with a user.
+///
+/// The attestation is a tower signature over the subscription terms (`user_id`, `available_slots` and `subscription_expiry`),
+/// bound to the tower identity (`tower_id`) and to the block height at which the terms were agreed (`height`). This lets
+/// users prove which tower sold them a given subscription, and when.
+#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+pub struct TowerAttestation {
+    tower_id: TowerId,
+    height: u32,
+    signature: String,
+}
+
+impl TowerAttestation {
+    pub fn new(tower_id: TowerId, height: u32, signature: String) -> Self {
+        TowerAttestation {
+            tower_id,
+            height,
+            signature,
+        }
+    }
+
+    pub fn tower_id(&self) -> TowerId {
+        self.tower_id
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+}
 
 /// Proof that a user has registered with a tower. This serves two purposes:
 ///
@@ -27,6 +67,8 @@ pub struct RegistrationReceipt {
     subscription_expiry: u32,
     #[serde(rename = "subscription_signature")]
     signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<TowerAttestation>,
 }
 
 impl RegistrationReceipt {
@@ -42,6 +84,7 @@ impl RegistrationReceipt {
             subscription_start,
             subscription_expiry,
             signature: None,
+            attestation: None,
         }
     }
 
@@ -58,6 +101,7 @@ impl RegistrationReceipt {
             subscription_start,
             subscription_expiry,
             signature: Some(signature),
+            attestation: None,
         }
     }
 
@@ -103,6 +147,52 @@ impl RegistrationReceipt {
             false
         }
     }
+
+    pub fn attestation(&self) -> Option<&TowerAttestation> {
+        self.attestation.as_ref()
+    }
+
+    pub fn set_attestation(&mut self, attestation: TowerAttestation) {
+        self.attestation = Some(attestation)
+    }
+
+    /// Serializes the data covered by a [TowerAttestation] issued by `tower_id` at `height`:
+    ///
+    /// `user_id (33 bytes) | available_slots (4 bytes) | subscription_expiry (4 bytes) | tower_id (33 bytes) | height (4 bytes)`
+    ///
+    /// Integers are serialized in big endian.
+    pub fn attestation_to_vec(&self, tower_id: TowerId, height: u32) -> Vec<u8> {
+        let mut ser = Vec::new();
+        ser.extend_from_slice(&self.user_id.to_vec());
+        ser.extend_from_slice(&self.available_slots.to_be_bytes());
+        ser.extend_from_slice(&self.subscription_expiry.to_be_bytes());
+        ser.extend_from_slice(&tower_id.to_vec());
+        ser.extend_from_slice(&height.to_be_bytes());
+
+        ser
+    }
+
+    /// Attests the subscription terms using the tower secret key. `height` is the block height at which the terms are agreed.
+    pub fn attest(&mut self, sk: &SecretKey, height: u32) {
+        let tower_id = TowerId(PublicKey::from_secret_key(&Secp256k1::new(), sk));
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        let signature = cryptography::sign(&self.attestation_to_vec(tower_id, height), sk).unwrap();
+        self.attestation = Some(TowerAttestation::new(tower_id, height, signature));
+    }
+
+    /// Verifies the receipt attestation was issued by the given tower.
+    pub fn verify_attestation(&self, tower_id: &TowerId) -> bool {
+        if let Some(attestation) = self.attestation() {
+            attestation.tower_id == *tower_id
+                && cryptography::verify(
+                    &self.attestation_to_vec(attestation.tower_id, attestation.height),
+                    &attestation.signature,
+                    &tower_id.0,
+                )
+        } else {
+            false
+        }
+    }
 }
 
 /// Proof that a certain state was backed up with the tower.
@@ -165,3 +255,332 @@ impl AppointmentReceipt {
         }
     }
 }
+
+/// Serializes the root of a receipt batch so it can be signed by the tower:
+///
+/// `"receipt batch" | root (32 bytes)`
+pub fn batch_root_to_vec(root: &sha256::Hash) -> Vec<u8> {
+    let mut ser = Vec::new();
+    ser.extend_from_slice(b"receipt batch");
+    ser.extend_from_slice(&root[..]);
+
+    ser
+}
+
+/// An [AppointmentReceipt] whose tower signature covers a whole batch of receipts instead of a single one.
+///
+/// The batch is organized as a Merkle tree with one [AppointmentReceipt] per leaf. The tower signs the tree root
+/// and hands users an inclusion proof of their receipt, which is as binding as an individually signed receipt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchedAppointmentReceipt {
+    receipt: AppointmentReceipt,
+    proof: MerkleProof,
+    root_signature: String,
+}
+
+impl BatchedAppointmentReceipt {
+    pub fn new(receipt: AppointmentReceipt, proof: MerkleProof, root_signature: String) -> Self {
+        BatchedAppointmentReceipt {
+            receipt,
+            proof,
+            root_signature,
+        }
+    }
+
+    pub fn receipt(&self) -> &AppointmentReceipt {
+        &self.receipt
+    }
+
+    pub fn proof(&self) -> &MerkleProof {
+        &self.proof
+    }
+
+    pub fn root_signature(&self) -> &str {
+        &self.root_signature
+    }
+
+    /// Computes the root of the batch the receipt belongs to. Returns `None` if the proof is malformed.
+    pub fn root(&self) -> Option<sha256::Hash> {
+        self.proof.compute_root(&self.receipt.to_vec())
+    }
+
+    /// Verifies the receipt is part of a batch signed by the given tower.
+    pub fn verify(&self, id: &TowerId) -> bool {
+        match self.root() {
+            Some(root) => {
+                cryptography::verify(&batch_root_to_vec(&root), &self.root_signature, &id.0)
+            }
+            None => false,
+        }
+    }
+}
+
+impl TryFrom<msgs::GetBatchedReceiptResponse> for BatchedAppointmentReceipt {
+    type Error = hashes::Error;
+
+    fn try_from(r: msgs::GetBatchedReceiptResponse) -> Result<Self, Self::Error> {
+        let siblings = r
+            .siblings
+            .iter()
+            .map(|sibling| sha256::Hash::from_slice(sibling))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BatchedAppointmentReceipt::new(
+            AppointmentReceipt::new(r.user_signature, r.start_block),
+            MerkleProof::new(r.leaf_index, r.n_leaves, siblings),
+            r.root_signature,
+        ))
+    }
+}
+
+/// Signed page of the public log of breaches a tower has responded to.
+///
+/// The log covers a range of blocks (`from_height` - `to_height`) and contains the penalty transactions the tower got
+/// confirmed within it, alongside their confirmation height. No user data is included, so third parties can audit the
+/// tower liveness without learning who its users are. Given the range is part of the signed data, the tower commits
+/// to not having responded to any other breach within it.
+#[derive(Serialize, Debug, Eq, PartialEq, Clone)]
+pub struct BreachLog {
+    from_height: u32,
+    to_height: u32,
+    entries: Vec<(Txid, u32)>,
+    signature: Option<String>,
+}
+
+impl BreachLog {
+    pub fn new(from_height: u32, to_height: u32, entries: Vec<(Txid, u32)>) -> Self {
+        BreachLog {
+            from_height,
+            to_height,
+            entries,
+            signature: None,
+        }
+    }
+
+    pub fn from_height(&self) -> u32 {
+        self.from_height
+    }
+
+    pub fn to_height(&self) -> u32 {
+        self.to_height
+    }
+
+    pub fn entries(&self) -> &Vec<(Txid, u32)> {
+        &self.entries
+    }
+
+    pub fn signature(&self) -> Option<String> {
+        self.signature.clone()
+    }
+
+    /// Serializes the log so it can be signed by the tower:
+    ///
+    /// `"breach log" | from_height (4 bytes) | to_height (4 bytes) | [penalty_txid (32 bytes) | height (4 bytes)]*`
+    ///
+    /// Integers are serialized in big endian.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut ser = Vec::new();
+        ser.extend_from_slice(b"breach log");
+        ser.extend_from_slice(&self.from_height.to_be_bytes());
+        ser.extend_from_slice(&self.to_height.to_be_bytes());
+        for (txid, height) in self.entries.iter() {
+            ser.extend_from_slice(&txid[..]);
+            ser.extend_from_slice(&height.to_be_bytes());
+        }
+
+        ser
+    }
+
+    pub fn sign(&mut self, sk: &SecretKey) {
+        // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+        self.signature = Some(cryptography::sign(&self.to_vec(), sk).unwrap());
+    }
+
+    /// Verifies the log was signed by the given tower.
+    pub fn verify(&self, id: &TowerId) -> bool {
+        if let Some(signature) = self.signature() {
+            cryptography::verify(&self.to_vec(), &signature, &id.0)
+        } else {
+            false
+        }
+    }
+}
+
+impl TryFrom<msgs::GetBreachLogResponse> for BreachLog {
+    type Error = hashes::Error;
+
+    fn try_from(r: msgs::GetBreachLogResponse) -> Result<Self, Self::Error> {
+        let entries = r
+            .entries
+            .iter()
+            .map(|entry| Ok((Txid::from_slice(&entry.penalty_txid)?, entry.height)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut log = BreachLog::new(r.from_height, r.to_height, entries);
+        log.signature = Some(r.signature);
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cryptography::get_random_keypair;
+    use crate::merkle::MerkleTree;
+    use crate::test_utils::get_random_registration_receipt;
+
+    #[test]
+    fn test_attestation() {
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+        let mut receipt = get_random_registration_receipt();
+        let height = receipt.subscription_start();
+
+        // A receipt with no attestation cannot be verified
+        assert!(!receipt.verify_attestation(&tower_id));
+
+        // Once attested, it can be verified by the attesting tower
+        receipt.attest(&tower_sk, height);
+        assert!(receipt.verify_attestation(&tower_id));
+        assert_eq!(receipt.attestation().unwrap().tower_id(), tower_id);
+        assert_eq!(receipt.attestation().unwrap().height(), height);
+
+        // But not by any other
+        let (_, another_pk) = get_random_keypair();
+        assert!(!receipt.verify_attestation(&TowerId(another_pk)));
+
+        // Tampering with the attested data invalidates the attestation
+        let attestation = receipt.attestation().unwrap().clone();
+        let mut tampered_receipt = RegistrationReceipt::new(
+            receipt.user_id(),
+            receipt.available_slots() + 1,
+            receipt.subscription_start(),
+            receipt.subscription_expiry(),
+        );
+        tampered_receipt.set_attestation(attestation.clone());
+        assert!(!tampered_receipt.verify_attestation(&tower_id));
+
+        let mut tampered_height = receipt.clone();
+        tampered_height.set_attestation(TowerAttestation::new(
+            tower_id,
+            height + 1,
+            attestation.signature().to_owned(),
+        ));
+        assert!(!tampered_height.verify_attestation(&tower_id));
+    }
+
+    #[test]
+    fn test_batched_appointment_receipt() {
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+
+        let receipts: Vec<AppointmentReceipt> = (0..5)
+            .map(|i| AppointmentReceipt::new(format!("user_signature_{}", i), 42))
+            .collect();
+        let tree = MerkleTree::new(
+            &receipts
+                .iter()
+                .map(|receipt| receipt.to_vec())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let root_signature =
+            cryptography::sign(&batch_root_to_vec(&tree.root()), &tower_sk).unwrap();
+
+        for (i, receipt) in receipts.iter().enumerate() {
+            let batched = BatchedAppointmentReceipt::new(
+                receipt.clone(),
+                tree.get_proof(i).unwrap(),
+                root_signature.clone(),
+            );
+            assert_eq!(batched.root(), Some(tree.root()));
+            assert!(batched.verify(&tower_id));
+
+            // The receipt does not verify for any other tower
+            let (_, another_pk) = get_random_keypair();
+            assert!(!batched.verify(&TowerId(another_pk)));
+
+            // Nor using the proof of a different receipt
+            let wrong_proof = BatchedAppointmentReceipt::new(
+                receipt.clone(),
+                tree.get_proof((i + 1) % receipts.len()).unwrap(),
+                root_signature.clone(),
+            );
+            assert!(!wrong_proof.verify(&tower_id));
+        }
+    }
+
+    #[test]
+    fn test_batched_appointment_receipt_from_response() {
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let receipts: Vec<Vec<u8>> = (0..3)
+            .map(|i| AppointmentReceipt::new(format!("user_signature_{}", i), 42).to_vec())
+            .collect();
+        let tree = MerkleTree::new(&receipts).unwrap();
+        let proof = tree.get_proof(2).unwrap();
+
+        let response = msgs::GetBatchedReceiptResponse {
+            locator: Vec::new(),
+            user_signature: "user_signature_2".to_owned(),
+            start_block: 42,
+            leaf_index: proof.index(),
+            n_leaves: proof.n_leaves(),
+            siblings: proof.siblings().iter().map(|s| s[..].to_vec()).collect(),
+            root_signature: cryptography::sign(&batch_root_to_vec(&tree.root()), &tower_sk)
+                .unwrap(),
+        };
+        let batched = BatchedAppointmentReceipt::try_from(response.clone()).unwrap();
+        assert_eq!(batched.proof(), &proof);
+        assert!(batched.verify(&TowerId(tower_pk)));
+
+        // Siblings must be 32-byte hashes
+        let mut wrong_response = response;
+        wrong_response.siblings.push(vec![0; 31]);
+        assert!(BatchedAppointmentReceipt::try_from(wrong_response).is_err());
+    }
+
+    #[test]
+    fn test_breach_log() {
+        let (tower_sk, tower_pk) = get_random_keypair();
+        let tower_id = TowerId(tower_pk);
+        let entries = (0..3)
+            .map(|i| (Txid::hash(&[i]), 100 + i as u32))
+            .collect::<Vec<_>>();
+
+        // An unsigned log cannot be verified
+        let mut log = BreachLog::new(100, 150, entries.clone());
+        assert!(!log.verify(&tower_id));
+
+        // Once signed, it can be verified by the signing tower but not by any other
+        log.sign(&tower_sk);
+        assert!(log.verify(&tower_id));
+        let (_, another_pk) = get_random_keypair();
+        assert!(!log.verify(&TowerId(another_pk)));
+
+        // Tampering with either the range or the entries invalidates the signature
+        let mut tampered_range = BreachLog::new(100, 149, entries.clone());
+        tampered_range.signature = log.signature();
+        assert!(!tampered_range.verify(&tower_id));
+
+        let mut tampered_entries = BreachLog::new(100, 150, entries[1..].to_vec());
+        tampered_entries.signature = log.signature();
+        assert!(!tampered_entries.verify(&tower_id));
+
+        // The log can be rebuilt from its message counterpart
+        let msg = msgs::GetBreachLogResponse {
+            from_height: log.from_height(),
+            to_height: log.to_height(),
+            entries: log
+                .entries()
+                .iter()
+                .map(|(txid, height)| msgs::BreachLogEntry {
+                    penalty_txid: txid.to_vec(),
+                    height: *height,
+                })
+                .collect(),
+            signature: log.signature().unwrap(),
+        };
+        assert_eq!(BreachLog::try_from(msg).unwrap(), log);
+    }
+}
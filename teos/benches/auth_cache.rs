@@ -0,0 +1,66 @@
+//! Measures the cost of authenticating user requests, with and without the authentication cache.
+//!
+//! Run with `cargo bench --bench auth_cache`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use teos::dbm::DBM;
+use teos::gatekeeper::{Gatekeeper, GatekeeperSettings, SubscriptionPricing};
+use teos_common::cryptography;
+use teos_common::UserId;
+
+const N_USERS: usize = 100;
+const N_REQUESTS_PER_USER: usize = 100;
+
+fn init_gatekeeper(auth_cache_ttl: Duration) -> Gatekeeper {
+    Gatekeeper::new(
+        0,
+        GatekeeperSettings {
+            subscription_slots: 10_000,
+            subscription_duration: 4320,
+            expiry_delta: 42,
+            renewal_window: 144,
+            max_users: u32::MAX,
+            pricing: SubscriptionPricing::new(0, 0, false),
+            auth_cache_ttl,
+            require_auth_challenges: false,
+        },
+        Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+    )
+}
+
+/// Sends [N_REQUESTS_PER_USER] identical requests per user (like a client polling an appointment) and returns the
+/// average time it took to authenticate each of them.
+fn bench(auth_cache_ttl: Duration) -> Duration {
+    let gatekeeper = init_gatekeeper(auth_cache_ttl);
+    let message = "get appointment".as_bytes();
+
+    let signatures: Vec<String> = (0..N_USERS)
+        .map(|_| {
+            let (sk, pk) = cryptography::get_random_keypair();
+            gatekeeper.add_update_user(UserId(pk)).unwrap();
+            cryptography::sign(message, &sk).unwrap()
+        })
+        .collect();
+
+    let start = Instant::now();
+    for _ in 0..N_REQUESTS_PER_USER {
+        for signature in signatures.iter() {
+            gatekeeper.authenticate_user(message, signature).unwrap();
+        }
+    }
+    start.elapsed() / (N_USERS * N_REQUESTS_PER_USER) as u32
+}
+
+fn main() {
+    let uncached = bench(Duration::ZERO);
+    let cached = bench(Duration::from_secs(60));
+
+    println!(
+        "authenticate_user ({} users, {} requests each)",
+        N_USERS, N_REQUESTS_PER_USER
+    );
+    println!("  no cache:   {:?}/request", uncached);
+    println!("  with cache: {:?}/request", cached);
+}
@@ -0,0 +1,88 @@
+//! Measures the cost of removing users (alongside all their appointments) from the database, with and without the
+//! indices over the per-user data.
+//!
+//! Run with `cargo bench --bench batch_remove_users`.
+
+use std::collections::HashSet;
+use std::iter::FromIterator;
+use std::time::{Duration, Instant};
+
+use teos::dbm::DBM;
+use teos::extended_appointment::{AppointmentState, ExtendedAppointment, UUID};
+use teos::gatekeeper::UserInfo;
+use teos::storage::Storage;
+use teos_common::dbm::DatabaseConnection;
+use teos_common::test_utils::{generate_random_appointment, get_random_user_id};
+use teos_common::UserId;
+
+const N_USERS: usize = 50;
+const N_APPOINTMENTS_PER_USER: usize = 2000;
+const START_HEIGHT: u32 = 100;
+
+/// Stores [N_USERS] users with [N_APPOINTMENTS_PER_USER] watched appointments each.
+fn populate(dbm: &DBM) -> Vec<UserId> {
+    let mut users = Vec::new();
+    dbm.get_connection().execute("BEGIN", []).unwrap();
+    for _ in 0..N_USERS {
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(
+            N_APPOINTMENTS_PER_USER as u32,
+            START_HEIGHT,
+            START_HEIGHT + 4320,
+        );
+        Storage::store_user(dbm, user_id, &user).unwrap();
+        for _ in 0..N_APPOINTMENTS_PER_USER {
+            let appointment = ExtendedAppointment::new(
+                generate_random_appointment(None),
+                user_id,
+                String::new(),
+                START_HEIGHT,
+            );
+            let uuid = UUID::new(appointment.locator(), user_id);
+            Storage::store_appointment(dbm, uuid, &appointment).unwrap();
+            Storage::update_appointment_state(
+                dbm,
+                uuid,
+                user_id,
+                AppointmentState::Watched,
+                START_HEIGHT,
+            );
+        }
+        users.push(user_id);
+    }
+    dbm.get_connection().execute("COMMIT", []).unwrap();
+    users
+}
+
+/// Removes half of the users, one at a time, and returns the time it took.
+fn bench(indexed: bool) -> Duration {
+    let mut dbm = DBM::in_memory().unwrap();
+    if !indexed {
+        for index in ["appointments_user_id", "appointment_states_user_id"] {
+            dbm.get_connection()
+                .execute(&format!("DROP INDEX {}", index), [])
+                .unwrap();
+        }
+    }
+    let users = populate(&dbm);
+
+    let start = Instant::now();
+    for user_id in users.iter().take(N_USERS / 2) {
+        Storage::batch_remove_users(&mut dbm, &HashSet::from_iter([*user_id]));
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let indexed = bench(true);
+    let unindexed = bench(false);
+
+    println!(
+        "batch_remove_users ({} out of {} users, {} appointments each)",
+        N_USERS / 2,
+        N_USERS,
+        N_APPOINTMENTS_PER_USER
+    );
+    println!("  indexed:   {:?}", indexed);
+    println!("  unindexed: {:?}", unindexed);
+}
@@ -12,6 +12,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "GetUserResponse.appointments",
             "#[serde(serialize_with = \"teos_common::ser::serde_vec_bytes::serialize\")]",
         )
+        .field_attribute(
+            "ExportedAppointment.dispute_tx",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute("ExportedTracker.uuid", "#[serde(with = \"hex::serde\")]")
+        .field_attribute("ExportedTracker.locator", "#[serde(with = \"hex::serde\")]")
+        .field_attribute(
+            "RebroadcastTrackerResponse.uuid",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute("TrackerUnderReview.uuid", "#[serde(with = \"hex::serde\")]")
+        .field_attribute(
+            "TrackerUnderReview.locator",
+            "#[serde(with = \"hex::serde\")]",
+        )
+        .field_attribute(
+            "TrackerUnderReview.penalty_txid",
+            "#[serde(with = \"teos_common::ser::serde_be\")]",
+        )
         .field_attribute(
             "NetworkAddress.address_type",
             "#[serde(rename = \"type\", with = \"crate::api::serde::serde_address_type\")]",
@@ -2,23 +2,40 @@ use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::error::Error;
 use std::net::SocketAddr;
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
 use tonic::transport::Channel;
+use tracing::Span;
 use triggered::{Listener, Trigger};
+use warp::cors::Cors;
 use warp::{http::StatusCode, reject, reply, Filter, Rejection, Reply};
 
-use teos_common::appointment::LOCATOR_LEN;
+use teos_common::constants::MAX_APPOINTMENTS_PER_BATCH;
+use teos_common::errors;
 use teos_common::protos as common_msgs;
-use teos_common::{errors, USER_ID_LEN};
 
+use crate::api::validation::{self, FieldError};
 use crate::protos::public_tower_services_client::PublicTowerServicesClient;
+use crate::telemetry;
 
 // TODO: Limit the body length for /add_appointment should not be needed, since slots are consumed proportionally to it.
 // Setting a limit for now just to prevent spam to some extend, but this is likely to be lifted.
-const REGISTER_BODY_LEN: u64 = 87;
+// Registrations can optionally carry the hex-encoded preimage of the subscription invoice (up to 87 extra bytes)
+const REGISTER_BODY_LEN: u64 = 174;
 const ADD_APPOINTMENT_BODY_LEN: u64 = 2048;
-const GET_APPOINTMENT_BODY_LEN: u64 = 178;
-const GET_SUBSCRIPTION_INFO_BODY_LEN: u64 = 127;
+const ADD_APPOINTMENTS_BODY_LEN: u64 = ADD_APPOINTMENT_BODY_LEN * MAX_APPOINTMENTS_PER_BATCH as u64;
+const GET_AUTH_CHALLENGE_BODY_LEN: u64 = 87;
+const GET_REGISTRATION_INVOICE_BODY_LEN: u64 = 87;
+// These requests can optionally carry a hex-encoded auth challenge (up to 79 extra bytes)
+const GET_APPOINTMENT_BODY_LEN: u64 = 257;
+const GET_SUBSCRIPTION_INFO_BODY_LEN: u64 = 206;
+const WAIT_RENEWAL_REMINDER_BODY_LEN: u64 = 206;
+const GET_BATCHED_RECEIPT_BODY_LEN: u64 = 178;
+const GET_BREACH_LOG_BODY_LEN: u64 = 32;
+const GET_REGISTRATION_TERMS_BODY_LEN: u64 = 16;
+
+/// How long a /wait_renewal_reminder request is held open waiting for a reminder before replying with no content.
+const RENEWAL_REMINDER_POLL_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub(crate) struct ApiError {
@@ -33,28 +50,13 @@ impl ApiError {
         ApiError { error, error_code }
     }
 
-    fn missing_field(field_name: &str) -> Rejection {
-        reject::custom(Self::new(
-            format!("missing field `{}`", field_name),
-            errors::MISSING_FIELD,
-        ))
-    }
-
-    fn empty_field(field_name: &str) -> Rejection {
-        reject::custom(Self::new(
-            format!("`{}` field is empty", field_name),
-            errors::EMPTY_FIELD,
-        ))
-    }
-
-    fn wrong_field_length(field_name: &str, field_size: usize, expected_size: usize) -> Rejection {
-        reject::custom(Self::new(
-            format!(
-                "Wrong `{}` field size. Expected {}, received {}",
-                field_name, expected_size, field_size
-            ),
-            errors::WRONG_FIELD_SIZE,
-        ))
+    fn invalid_field(e: FieldError) -> Rejection {
+        let error_code = match e {
+            FieldError::Missing(_) => errors::MISSING_FIELD,
+            FieldError::Empty(_) => errors::EMPTY_FIELD,
+            FieldError::WrongLength { .. } => errors::WRONG_FIELD_SIZE,
+        };
+        reject::custom(Self::new(e.to_string(), error_code))
     }
 }
 
@@ -73,7 +75,16 @@ fn match_status(s: &tonic::Status) -> (StatusCode, u8) {
             errors::APPOINTMENT_NOT_FOUND
         }
         tonic::Code::AlreadyExists => errors::APPOINTMENT_ALREADY_TRIGGERED,
+        tonic::Code::PermissionDenied => {
+            status_code = StatusCode::FORBIDDEN;
+            errors::APPOINTMENT_REJECTED_BY_POLICY
+        }
         tonic::Code::ResourceExhausted => errors::REGISTRATION_RESOURCE_EXHAUSTED,
+        tonic::Code::OutOfRange => errors::REGISTRATION_MAX_USERS_REACHED,
+        tonic::Code::FailedPrecondition => {
+            status_code = StatusCode::PAYMENT_REQUIRED;
+            errors::REGISTRATION_WRONG_PAYMENT
+        }
         tonic::Code::Unauthenticated => {
             status_code = StatusCode::UNAUTHORIZED;
             errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
@@ -82,6 +93,10 @@ fn match_status(s: &tonic::Status) -> (StatusCode, u8) {
             status_code = StatusCode::SERVICE_UNAVAILABLE;
             errors::SERVICE_UNAVAILABLE
         }
+        tonic::Code::Unimplemented => {
+            status_code = StatusCode::NOT_IMPLEMENTED;
+            errors::SERVICE_DISABLED
+        }
         _ => {
             log::debug!("Unexpected error ocurred: {}", s.message());
             errors::UNEXPECTED_ERROR
@@ -113,110 +128,356 @@ fn parse_grpc_response<T: serde::Serialize>(
     }
 }
 
+/// Like [parse_grpc_response], but for rate limited endpoints, where [tonic::Code::ResourceExhausted] means the
+/// requester needs to slow down (so it is not covered by [match_status]).
+fn parse_rate_limited_grpc_response<T: serde::Serialize>(
+    result: Result<tonic::Response<T>, tonic::Status>,
+) -> (reply::Json, StatusCode) {
+    match result {
+        Err(s) if s.code() == tonic::Code::ResourceExhausted => {
+            log::info!("Request failed, error_code={}", errors::TOO_MANY_REQUESTS);
+            (
+                reply::json(&ApiError::new(
+                    s.message().into(),
+                    errors::TOO_MANY_REQUESTS,
+                )),
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+        }
+        result => parse_grpc_response(result),
+    }
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "register", request_id)
+)]
 async fn register(
     req: common_msgs::RegisterRequest,
     addr: Option<std::net::SocketAddr>,
     mut grpc_conn: PublicTowerServicesClient<Channel>,
 ) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
     match addr {
         Some(a) => log::info!("Received register request from {}", a),
         None => log::info!("Received register request from unknown address"),
     }
 
-    let user_id = req.user_id.clone();
-    if user_id.is_empty() {
-        return Err(ApiError::empty_field("user_id"));
-    }
-    if user_id.len() != USER_ID_LEN {
-        return Err(ApiError::wrong_field_length(
-            "user_id",
-            user_id.len(),
-            USER_ID_LEN,
-        ));
-    }
+    validation::check_register(&req).map_err(ApiError::invalid_field)?;
 
-    let (body, status) = parse_grpc_response(grpc_conn.register(req).await);
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .register(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
     Ok(reply::with_status(body, status))
 }
 
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "add_appointment", request_id)
+)]
 async fn add_appointment(
     req: common_msgs::AddAppointmentRequest,
     addr: Option<std::net::SocketAddr>,
     mut grpc_conn: PublicTowerServicesClient<Channel>,
 ) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
     match addr {
         Some(a) => log::info!("Received add_appointment request from {}", a),
         None => log::info!("Received add_appointment request from unknown address"),
     }
 
-    if let Some(a) = &req.appointment {
-        if a.locator.is_empty() {
-            return Err(ApiError::empty_field("locator"));
-        }
-        if a.locator.len() != LOCATOR_LEN {
-            return Err(ApiError::wrong_field_length(
-                "locator",
-                a.locator.len(),
-                LOCATOR_LEN,
-            ));
-        }
-    } else {
-        return Err(ApiError::missing_field("appointment"));
-    }
-    if req.signature.is_empty() {
-        return Err(ApiError::empty_field("signature"));
+    validation::check_add_appointment(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .add_appointment(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "add_appointments", request_id)
+)]
+async fn add_appointments(
+    req: common_msgs::AddAppointmentsRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received add_appointments request from {}", a),
+        None => log::info!("Received add_appointments request from unknown address"),
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.add_appointment(req).await);
+    validation::check_add_appointments(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .add_appointments(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
     Ok(reply::with_status(body, status))
 }
 
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_appointment", request_id)
+)]
 async fn get_appointment(
     req: common_msgs::GetAppointmentRequest,
     addr: Option<std::net::SocketAddr>,
     mut grpc_conn: PublicTowerServicesClient<Channel>,
 ) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
     match addr {
         Some(a) => log::info!("Received get_appointment request from {}", a),
         None => log::info!("Received get_appointment request from unknown address"),
     }
 
-    if req.locator.is_empty() {
-        return Err(ApiError::empty_field("locator"));
-    }
-    if req.locator.len() != LOCATOR_LEN {
-        return Err(ApiError::wrong_field_length(
-            "locator",
-            req.locator.len(),
-            LOCATOR_LEN,
-        ));
-    }
-    if req.signature.is_empty() {
-        return Err(ApiError::empty_field("signature"));
-    }
+    validation::check_get_appointment(&req).map_err(ApiError::invalid_field)?;
 
-    let (body, status) = parse_grpc_response(grpc_conn.get_appointment(req).await);
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .get_appointment(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
     Ok(reply::with_status(body, status))
 }
 
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_subscription_info", request_id)
+)]
 async fn get_subscription_info(
     req: common_msgs::GetSubscriptionInfoRequest,
     addr: Option<std::net::SocketAddr>,
     mut grpc_conn: PublicTowerServicesClient<Channel>,
 ) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
     match addr {
         Some(a) => log::info!("Received get_subscription_info request from {}", a),
         None => log::info!("Received get_subscription_info request from unknown address"),
     }
 
-    if req.signature.is_empty() {
-        return Err(ApiError::empty_field("signature"));
+    validation::check_get_subscription_info(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .get_subscription_info(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_registration_terms", request_id)
+)]
+async fn get_registration_terms(
+    req: common_msgs::GetRegistrationTermsRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received get_registration_terms request from {}", a),
+        None => log::info!("Received get_registration_terms request from unknown address"),
+    }
+
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .get_registration_terms(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_registration_invoice", request_id)
+)]
+async fn get_registration_invoice(
+    req: common_msgs::GetRegistrationInvoiceRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received get_registration_invoice request from {}", a),
+        None => log::info!("Received get_registration_invoice request from unknown address"),
+    }
+
+    validation::check_get_registration_invoice(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_rate_limited_grpc_response(
+        grpc_conn
+            .get_registration_invoice(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_auth_challenge", request_id)
+)]
+async fn get_auth_challenge(
+    req: common_msgs::GetAuthChallengeRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received get_auth_challenge request from {}", a),
+        None => log::info!("Received get_auth_challenge request from unknown address"),
+    }
+
+    validation::check_get_auth_challenge(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_rate_limited_grpc_response(
+        grpc_conn
+            .get_auth_challenge(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_batched_receipt", request_id)
+)]
+async fn get_batched_receipt(
+    req: common_msgs::GetBatchedReceiptRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received get_batched_receipt request from {}", a),
+        None => log::info!("Received get_batched_receipt request from unknown address"),
+    }
+
+    validation::check_get_batched_receipt(&req).map_err(ApiError::invalid_field)?;
+
+    let (body, status) = parse_grpc_response(
+        grpc_conn
+            .get_batched_receipt(telemetry::with_request_id(req, &request_id))
+            .await,
+    );
+    Ok(reply::with_status(body, status))
+}
+
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "get_breach_log", request_id)
+)]
+async fn get_breach_log(
+    req: common_msgs::GetBreachLogRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received get_breach_log request from {}", a),
+        None => log::info!("Received get_breach_log request from unknown address"),
     }
 
-    let (body, status) = parse_grpc_response(grpc_conn.get_subscription_info(req).await);
+    let mut request = telemetry::with_request_id(req, &request_id);
+    telemetry::set_client_addr(&mut request, addr);
+    let (body, status) = parse_rate_limited_grpc_response(grpc_conn.get_breach_log(request).await);
     Ok(reply::with_status(body, status))
 }
 
+/// Long-polling counterpart of the renewal reminders stream served by the public gRPC API (subscribe_renewal_reminders).
+///
+/// Replies with the first reminder sent to the user, or with no content if none is sent within
+/// [RENEWAL_REMINDER_POLL_TIMEOUT], so the user can poll again. Subscriptions already due for renewal get a reminder
+/// straightaway.
+#[tracing::instrument(
+    name = "request",
+    skip_all,
+    fields(api = "http", method = "wait_renewal_reminder", request_id)
+)]
+async fn wait_renewal_reminder(
+    req: common_msgs::RenewalRemindersRequest,
+    addr: Option<std::net::SocketAddr>,
+    mut grpc_conn: PublicTowerServicesClient<Channel>,
+) -> std::result::Result<impl Reply, Rejection> {
+    let request_id = telemetry::new_request_id();
+    Span::current().record("request_id", &request_id.as_str());
+
+    match addr {
+        Some(a) => log::info!("Received wait_renewal_reminder request from {}", a),
+        None => log::info!("Received wait_renewal_reminder request from unknown address"),
+    }
+
+    validation::check_renewal_reminders(&req).map_err(ApiError::invalid_field)?;
+
+    let mut reminders = match grpc_conn
+        .subscribe_renewal_reminders(telemetry::with_request_id(req, &request_id))
+        .await
+    {
+        Ok(r) => r.into_inner(),
+        Err(s) => {
+            let (body, status) = parse_rate_limited_grpc_response::<()>(Err(s));
+            return Ok(reply::with_status(body, status));
+        }
+    };
+
+    // The stream is dropped once replying, which closes the subscription
+    match tokio::time::timeout(RENEWAL_REMINDER_POLL_TIMEOUT, reminders.message()).await {
+        Ok(Ok(Some(reminder))) => {
+            let (body, status) = parse_grpc_response::<common_msgs::RenewalReminder>(Ok(
+                tonic::Response::new(reminder),
+            ));
+            Ok(reply::with_status(body, status))
+        }
+        Ok(Err(s)) => {
+            let (body, status) = parse_grpc_response::<()>(Err(s));
+            Ok(reply::with_status(body, status))
+        }
+        // Either no reminder was sent in time or the tower is shutting down
+        Ok(Ok(None)) | Err(_) => {
+            log::info!("No renewal reminder sent");
+            Ok(reply::with_status(reply::json(&()), StatusCode::NO_CONTENT))
+        }
+    }
+}
+
 fn router(
     grpc_conn: PublicTowerServicesClient<Channel>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -234,6 +495,13 @@ fn router(
         .and(with_grpc(grpc_conn.clone()))
         .and_then(add_appointment);
 
+    let add_appointments = warp::post()
+        .and(warp::path("add_appointments"))
+        .and(warp::body::content_length_limit(ADD_APPOINTMENTS_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(add_appointments);
+
     let get_appointment = warp::post()
         .and(warp::path("get_appointment"))
         .and(warp::body::content_length_limit(GET_APPOINTMENT_BODY_LEN).and(warp::body::json()))
@@ -248,13 +516,71 @@ fn router(
                 .and(warp::body::json()),
         )
         .and(warp::addr::remote())
-        .and(with_grpc(grpc_conn))
+        .and(with_grpc(grpc_conn.clone()))
         .and_then(get_subscription_info);
 
+    let get_registration_terms = warp::post()
+        .and(warp::path("get_registration_terms"))
+        .and(
+            warp::body::content_length_limit(GET_REGISTRATION_TERMS_BODY_LEN)
+                .and(warp::body::json()),
+        )
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_registration_terms);
+
+    let get_registration_invoice = warp::post()
+        .and(warp::path("get_registration_invoice"))
+        .and(
+            warp::body::content_length_limit(GET_REGISTRATION_INVOICE_BODY_LEN)
+                .and(warp::body::json()),
+        )
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_registration_invoice);
+
+    let get_auth_challenge = warp::post()
+        .and(warp::path("get_auth_challenge"))
+        .and(warp::body::content_length_limit(GET_AUTH_CHALLENGE_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_auth_challenge);
+
+    let get_batched_receipt = warp::post()
+        .and(warp::path("get_batched_receipt"))
+        .and(warp::body::content_length_limit(GET_BATCHED_RECEIPT_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_batched_receipt);
+
+    let get_breach_log = warp::post()
+        .and(warp::path("get_breach_log"))
+        .and(warp::body::content_length_limit(GET_BREACH_LOG_BODY_LEN).and(warp::body::json()))
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn.clone()))
+        .and_then(get_breach_log);
+
+    let wait_renewal_reminder = warp::post()
+        .and(warp::path("wait_renewal_reminder"))
+        .and(
+            warp::body::content_length_limit(WAIT_RENEWAL_REMINDER_BODY_LEN)
+                .and(warp::body::json()),
+        )
+        .and(warp::addr::remote())
+        .and(with_grpc(grpc_conn))
+        .and_then(wait_renewal_reminder);
+
     register
         .or(add_appointment)
+        .or(add_appointments)
         .or(get_appointment)
         .or(get_subscription_info)
+        .or(get_registration_terms)
+        .or(get_registration_invoice)
+        .or(get_auth_challenge)
+        .or(get_batched_receipt)
+        .or(get_breach_log)
+        .or(wait_renewal_reminder)
         .recover(handle_rejection)
 }
 
@@ -288,9 +614,53 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
     }
 }
 
+/// Builds the CORS policy of the public HTTP API, so browser-based clients can reach the tower.
+///
+/// Returns `None` (CORS disabled) if no origin is allowed. A single `*` origin allows any origin.
+/// Preflight (`OPTIONS`) requests are answered by the policy itself and never reach the routes.
+pub fn cors(origins: &[String], methods: &[String], headers: &[String]) -> Option<Cors> {
+    if origins.is_empty() {
+        return None;
+    }
+
+    let cors = if origins.iter().any(|origin| origin == "*") {
+        warp::cors().allow_any_origin()
+    } else {
+        warp::cors().allow_origins(origins.iter().map(String::as_str))
+    };
+
+    Some(
+        cors.allow_methods(methods.iter().map(String::as_str))
+            .allow_headers(headers.iter().map(String::as_str))
+            .build(),
+    )
+}
+
+/// Spawns a server for the given routes on every given address.
+fn spawn_servers<F>(
+    routes: F,
+    http_binds: Vec<SocketAddr>,
+    shutdown_signal: Listener,
+) -> Vec<JoinHandle<()>>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    http_binds
+        .into_iter()
+        .map(|http_bind| {
+            let (_, server) = warp::serve(routes.clone())
+                .bind_with_graceful_shutdown(http_bind, shutdown_signal.clone());
+            tokio::spawn(server)
+        })
+        .collect()
+}
+
+/// Serves the public HTTP API on every given address until the shutdown signal is received.
 pub async fn serve(
-    http_bind: SocketAddr,
+    http_binds: Vec<SocketAddr>,
     grpc_bind: String,
+    cors: Option<Cors>,
     service_ready: Trigger,
     shutdown_signal: Listener,
 ) {
@@ -303,10 +673,15 @@ pub async fn serve(
             }
         }
     };
-    let (_, server) =
-        warp::serve(router(grpc_conn)).bind_with_graceful_shutdown(http_bind, shutdown_signal);
+    let routes = router(grpc_conn);
+    let servers = match cors {
+        Some(cors) => spawn_servers(routes.with(cors), http_binds, shutdown_signal),
+        None => spawn_servers(routes, http_binds, shutdown_signal),
+    };
     service_ready.trigger();
-    server.await
+    for server in servers {
+        server.await.unwrap();
+    }
 }
 
 #[cfg(test)]
@@ -623,6 +998,92 @@ mod tests_failures {
 
         assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
+
+    #[test]
+    fn test_cors_disabled() {
+        assert!(cors(&[], &["POST".to_owned()], &["content-type".to_owned()]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors() {
+        let (server_addr, _s) = run_tower_in_background().await;
+        let grpc_conn = PublicTowerServicesClient::connect(format!(
+            "http://{}:{}",
+            server_addr.ip(),
+            server_addr.port()
+        ))
+        .await
+        .unwrap();
+        let routes = router(grpc_conn).with(
+            cors(
+                &["https://wallet.example.com".to_owned()],
+                &["POST".to_owned()],
+                &["content-type".to_owned()],
+            )
+            .unwrap(),
+        );
+
+        // Preflight requests from an allowed origin are answered without hitting the routes
+        let res = warp::test::request()
+            .method("OPTIONS")
+            .path("/register")
+            .header("origin", "https://wallet.example.com")
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()["access-control-allow-origin"],
+            "https://wallet.example.com"
+        );
+        assert!(res.headers()["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+
+        // Actual requests get the allow origin header on top of the regular response
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .header("origin", "https://wallet.example.com")
+            .json(&"")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.headers()["access-control-allow-origin"],
+            "https://wallet.example.com"
+        );
+
+        // Neither disallowed origins nor methods make it through
+        let res = warp::test::request()
+            .method("OPTIONS")
+            .path("/register")
+            .header("origin", "https://evil.example.com")
+            .header("access-control-request-method", "POST")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = warp::test::request()
+            .method("OPTIONS")
+            .path("/register")
+            .header("origin", "https://wallet.example.com")
+            .header("access-control-request-method", "DELETE")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        // Requests without an origin are not affected
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .json(&"")
+            .reply(&routes)
+            .await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(!res.headers().contains_key("access-control-allow-origin"));
+    }
 }
 
 #[cfg(test)]
@@ -633,8 +1094,13 @@ mod tests_methods {
     };
     use super::*;
 
+    use bitcoin::hashes::{sha256, Hash};
+
     use crate::extended_appointment::UUID;
-    use crate::test_utils::{generate_dummy_appointment, ApiConfig, DURATION, SLOTS};
+    use crate::gatekeeper::SubscriptionPricing;
+    use crate::test_utils::{
+        generate_dummy_appointment, ApiConfig, DURATION, RENEWAL_WINDOW, SLOTS,
+    };
 
     use teos_common::test_utils::get_random_user_id;
     use teos_common::{cryptography, UserId};
@@ -647,6 +1113,7 @@ mod tests_methods {
                 "/register",
                 common_msgs::RegisterRequest {
                     user_id: get_random_user_id().to_vec(),
+                    payment_preimage: Vec::new(),
                 },
                 server_addr,
             )
@@ -654,6 +1121,56 @@ mod tests_methods {
         assert!(matches!(response, Ok(common_msgs::RegisterResponse { .. })));
     }
 
+    #[tokio::test]
+    async fn test_register_max_users() {
+        let (server_addr, _, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(SLOTS, DURATION).max_users(1)).await;
+
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            "/register",
+            common_msgs::RegisterRequest {
+                user_id: get_random_user_id().to_vec(),
+                payment_preimage: Vec::new(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // The tower is full, so new users are rejected
+        assert_eq!(
+            check_api_error(
+                "/register",
+                RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
+                    user_id: get_random_user_id().to_vec(),
+                    payment_preimage: Vec::new(),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "The tower is not accepting new users at the moment".into(),
+                    errors::REGISTRATION_MAX_USERS_REACHED
+                ),
+                StatusCode::BAD_REQUEST
+            )
+        );
+
+        let response = request_to_api::<
+            common_msgs::GetRegistrationTermsRequest,
+            common_msgs::GetRegistrationTermsResponse,
+        >(
+            "/get_registration_terms",
+            common_msgs::GetRegistrationTermsRequest {},
+            server_addr,
+        )
+        .await
+        .unwrap();
+        assert!(!response.accepting_registrations);
+        assert_eq!(response.max_users, 1);
+    }
+
     #[tokio::test]
     async fn test_register_max_slots() {
         let (server_addr, _, _s) =
@@ -665,6 +1182,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_id.to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -677,6 +1195,7 @@ mod tests_methods {
                 "/register",
                 RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
                     user_id: user_id.to_vec(),
+                    payment_preimage: Vec::new(),
                 })),
                 server_addr,
             )
@@ -691,6 +1210,90 @@ mod tests_methods {
         );
     }
 
+    #[tokio::test]
+    async fn test_register_wrong_payment() {
+        let pricing = SubscriptionPricing::new(2, 3, true);
+        let (server_addr, internal_api, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(SLOTS, DURATION).pricing(pricing))
+                .await;
+        let user_id = get_random_user_id();
+
+        assert_eq!(
+            check_api_error(
+                "/register",
+                RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
+                    user_id: user_id.to_vec(),
+                    payment_preimage: cryptography::get_random_bytes(32),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "The payment preimage does not match any pending invoice of the user".into(),
+                    errors::REGISTRATION_WRONG_PAYMENT
+                ),
+                StatusCode::PAYMENT_REQUIRED
+            )
+        );
+
+        // Providing the preimage of an invoice issued by the tower is enough to register
+        let invoice = request_to_api::<
+            common_msgs::GetRegistrationInvoiceRequest,
+            common_msgs::GetRegistrationInvoiceResponse,
+        >(
+            "/get_registration_invoice",
+            common_msgs::GetRegistrationInvoiceRequest {
+                user_id: user_id.to_vec(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+        assert_eq!(invoice.amount_msat, pricing.price(SLOTS, DURATION).unwrap());
+
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            "/register",
+            common_msgs::RegisterRequest {
+                user_id: user_id.to_vec(),
+                payment_preimage: internal_api
+                    .get_watcher()
+                    .get_invoice_preimage(&sha256::Hash::from_slice(&invoice.payment_hash).unwrap())
+                    .unwrap(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_registration_terms() {
+        let pricing = SubscriptionPricing::new(2, 3, false);
+        let (server_addr, _, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(SLOTS, DURATION).pricing(pricing))
+                .await;
+
+        let response = request_to_api::<
+            common_msgs::GetRegistrationTermsRequest,
+            common_msgs::GetRegistrationTermsResponse,
+        >(
+            "/get_registration_terms",
+            common_msgs::GetRegistrationTermsRequest {},
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.subscription_slots, SLOTS);
+        assert_eq!(response.subscription_duration, DURATION);
+        assert_eq!(
+            response.subscription_price_msat,
+            pricing.price(SLOTS, DURATION).unwrap()
+        );
+        assert!(!response.payments_required);
+    }
+
     #[tokio::test]
     async fn test_register_service_unavailable() {
         let (server_addr, _, _s) = run_tower_in_background_with_config(
@@ -705,6 +1308,7 @@ mod tests_methods {
                 "/register",
                 RequestBody::Json(serde_json::json!(common_msgs::RegisterRequest {
                     user_id: user_id.to_vec(),
+                    payment_preimage: Vec::new(),
                 })),
                 server_addr,
             )
@@ -729,6 +1333,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -747,6 +1352,7 @@ mod tests_methods {
             common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.into()),
                 signature,
+                batch_receipt: false,
             },
             server_addr,
         )
@@ -758,6 +1364,52 @@ mod tests_methods {
         ));
     }
 
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            "/register",
+            common_msgs::RegisterRequest {
+                user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        let appointments = (0..3)
+            .map(|_| {
+                let appointment = generate_dummy_appointment(None).inner;
+                common_msgs::AddAppointmentRequest {
+                    signature: cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                    appointment: Some(appointment.into()),
+                    batch_receipt: false,
+                }
+            })
+            .collect();
+
+        let response = request_to_api::<
+            common_msgs::AddAppointmentsRequest,
+            common_msgs::AddAppointmentsResponse,
+        >(
+            "/add_appointments",
+            common_msgs::AddAppointmentsRequest {
+                user_id: user_pk.serialize().to_vec(),
+                appointments,
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.results.len(), 3);
+        assert!(response.results.iter().all(|r| r.error_code == 0));
+        assert_eq!(response.available_slots, SLOTS - 3);
+    }
+
     #[tokio::test]
     async fn test_add_appointment_non_registered() {
         let (server_addr, _s) = run_tower_in_background().await;
@@ -771,6 +1423,7 @@ mod tests_methods {
                 RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentRequest {
                     appointment: Some(appointment.into()),
                     signature,
+                    batch_receipt: false,
                 })),
                 server_addr,
             )
@@ -797,6 +1450,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -817,6 +1471,7 @@ mod tests_methods {
                 RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentRequest {
                     appointment: Some(appointment.into()),
                     signature,
+                    batch_receipt: false,
                 })),
                 server_addr,
             )
@@ -847,6 +1502,7 @@ mod tests_methods {
                 RequestBody::Json(serde_json::json!(common_msgs::AddAppointmentRequest {
                     appointment: Some(appointment.into()),
                     signature,
+                    batch_receipt: false,
                 })),
                 server_addr,
             )
@@ -871,6 +1527,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -886,6 +1543,7 @@ mod tests_methods {
             common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature,
+                batch_receipt: false,
             },
             server_addr,
         )
@@ -905,6 +1563,7 @@ mod tests_methods {
                     &user_sk,
                 )
                 .unwrap(),
+                challenge: Vec::new(),
             },
             server_addr,
         )
@@ -934,7 +1593,8 @@ mod tests_methods {
                         format!("get appointment {}", appointment.locator).as_bytes(),
                         &user_sk,
                     )
-                    .unwrap()
+                    .unwrap(),
+                    challenge: Vec::new()
                 })),
                 server_addr,
             )
@@ -959,6 +1619,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -977,7 +1638,8 @@ mod tests_methods {
                         format!("get appointment {}", appointment.locator).as_bytes(),
                         &user_sk,
                     )
-                    .unwrap()
+                    .unwrap(),
+                    challenge: Vec::new()
                 })),
                 server_addr,
             )
@@ -1012,7 +1674,8 @@ mod tests_methods {
                         format!("get appointment {}", appointment.locator).as_bytes(),
                         &user_sk,
                     )
-                    .unwrap()
+                    .unwrap(),
+                    challenge: Vec::new()
                 })),
                 server_addr,
             )
@@ -1037,6 +1700,7 @@ mod tests_methods {
             "/register",
             common_msgs::RegisterRequest {
                 user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
             },
             server_addr,
         )
@@ -1052,6 +1716,7 @@ mod tests_methods {
             common_msgs::GetSubscriptionInfoRequest {
                 signature: cryptography::sign("get subscription info".as_bytes(), &user_sk)
                     .unwrap(),
+                challenge: Vec::new(),
             },
             server_addr,
         )
@@ -1063,6 +1728,39 @@ mod tests_methods {
         ));
     }
 
+    #[tokio::test]
+    async fn test_get_auth_challenge() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        // Register first
+        let (_, user_pk) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            "/register",
+            common_msgs::RegisterRequest {
+                user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        let response = request_to_api::<
+            common_msgs::GetAuthChallengeRequest,
+            common_msgs::GetAuthChallengeResponse,
+        >(
+            "/get_auth_challenge",
+            common_msgs::GetAuthChallengeRequest {
+                user_id: user_pk.serialize().to_vec(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        assert!(!response.challenge.is_empty());
+    }
+
     #[tokio::test]
     async fn test_get_subscription_info_non_registered() {
         let (server_addr, _s) = run_tower_in_background().await;
@@ -1076,6 +1774,82 @@ mod tests_methods {
                 RequestBody::Json(serde_json::json!(common_msgs::GetSubscriptionInfoRequest {
                     signature: cryptography::sign("get subscription info".as_bytes(), &user_sk)
                         .unwrap(),
+                    challenge: Vec::new(),
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "User not found. Have you registered?".into(),
+                    errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR
+                ),
+                StatusCode::UNAUTHORIZED
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_renewal_reminder() {
+        // Subscriptions are due for renewal straightaway
+        let (server_addr, _, _s) =
+            run_tower_in_background_with_config(ApiConfig::new(SLOTS, RENEWAL_WINDOW)).await;
+
+        // Register first
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        let registration =
+            request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+                "/register",
+                common_msgs::RegisterRequest {
+                    user_id: user_pk.serialize().to_vec(),
+                    payment_preimage: Vec::new(),
+                },
+                server_addr,
+            )
+            .await
+            .unwrap();
+
+        let response = request_to_api::<
+            common_msgs::RenewalRemindersRequest,
+            common_msgs::RenewalReminder,
+        >(
+            "/wait_renewal_reminder",
+            common_msgs::RenewalRemindersRequest {
+                signature: cryptography::sign("subscribe renewal reminders".as_bytes(), &user_sk)
+                    .unwrap(),
+                challenge: Vec::new(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            response,
+            common_msgs::RenewalReminder {
+                user_id: user_pk.serialize().to_vec(),
+                subscription_expiry: registration.subscription_expiry,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_renewal_reminder_non_registered() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        // User is not registered
+        let (user_sk, _) = cryptography::get_random_keypair();
+
+        assert_eq!(
+            check_api_error(
+                "/wait_renewal_reminder",
+                RequestBody::Json(serde_json::json!(common_msgs::RenewalRemindersRequest {
+                    signature: cryptography::sign(
+                        "subscribe renewal reminders".as_bytes(),
+                        &user_sk
+                    )
+                    .unwrap(),
+                    challenge: Vec::new(),
                 })),
                 server_addr,
             )
@@ -1104,6 +1878,7 @@ mod tests_methods {
                 RequestBody::Json(serde_json::json!(common_msgs::GetSubscriptionInfoRequest {
                     signature: cryptography::sign("get subscription info".as_bytes(), &user_sk)
                         .unwrap(),
+                    challenge: Vec::new(),
                 })),
                 server_addr,
             )
@@ -1117,4 +1892,88 @@ mod tests_methods {
             )
         );
     }
+
+    #[tokio::test]
+    async fn test_get_batched_receipt_batching_disabled() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        // Register first
+        let (user_sk, user_pk) = cryptography::get_random_keypair();
+        request_to_api::<common_msgs::RegisterRequest, common_msgs::RegisterResponse>(
+            "/register",
+            common_msgs::RegisterRequest {
+                user_id: user_pk.serialize().to_vec(),
+                payment_preimage: Vec::new(),
+            },
+            server_addr,
+        )
+        .await
+        .unwrap();
+
+        // The tower signs receipts one by one by default
+        let appointment = generate_dummy_appointment(None).inner;
+
+        assert_eq!(
+            check_api_error(
+                "/get_batched_receipt",
+                RequestBody::Json(serde_json::json!(common_msgs::GetBatchedReceiptRequest {
+                    locator: appointment.locator.to_vec(),
+                    signature: cryptography::sign(
+                        format!("get batched receipt {}", appointment.locator).as_bytes(),
+                        &user_sk,
+                    )
+                    .unwrap()
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "The tower does not batch receipts".into(),
+                    errors::APPOINTMENT_NOT_FOUND
+                ),
+                StatusCode::NOT_FOUND
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_breach_log() {
+        let (server_addr, _, _s) =
+            run_tower_in_background_with_config(ApiConfig::default().breach_log()).await;
+
+        let response =
+            request_to_api::<common_msgs::GetBreachLogRequest, common_msgs::GetBreachLogResponse>(
+                "/get_breach_log",
+                common_msgs::GetBreachLogRequest { from_height: 0 },
+                server_addr,
+            )
+            .await
+            .unwrap();
+        assert!(response.entries.is_empty());
+        assert!(!response.signature.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_breach_log_disabled() {
+        let (server_addr, _s) = run_tower_in_background().await;
+
+        assert_eq!(
+            check_api_error(
+                "/get_breach_log",
+                RequestBody::Json(serde_json::json!(common_msgs::GetBreachLogRequest {
+                    from_height: 0
+                })),
+                server_addr,
+            )
+            .await,
+            (
+                ApiError::new(
+                    "The tower does not publish a breach log".into(),
+                    errors::SERVICE_DISABLED
+                ),
+                StatusCode::NOT_IMPLEMENTED
+            )
+        );
+    }
 }
@@ -1,23 +1,124 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Code, Request, Response, Status};
 use triggered::Trigger;
 
+use crate::api::operator_auth;
+use crate::api::validation::{self, FieldError};
+use crate::extended_appointment::UUID;
+use crate::gatekeeper::{ChallengeFailure, InvoiceFailure, PaymentFailure, RegistrationFailure};
+use crate::metrics::LatencyStats;
 use crate::protos as msgs;
 use crate::protos::private_tower_services_server::PrivateTowerServices;
 use crate::protos::public_tower_services_server::PublicTowerServices;
+use crate::responder::{ConfirmationStatus, TrackerActionFailure};
+use crate::sync_monitor::SyncStatus;
+use crate::telemetry;
 use crate::watcher::{
-    AddAppointmentFailure, AppointmentInfo, GetAppointmentFailure, GetSubscriptionInfoFailure,
-    Watcher,
+    AcceptedAppointment, AddAppointmentFailure, AddedAppointment, AddedAppointments,
+    AppointmentInfo, ExportUserFailure, GetAppointmentFailure, GetBatchedReceiptFailure,
+    GetBreachLogFailure, GetSubscriptionInfoFailure, ImportUserFailure, Watcher,
 };
 
+use bitcoin::consensus;
+use bitcoin::secp256k1::PublicKey;
+
 use teos_common::appointment::{Appointment, AppointmentStatus, Locator};
+use teos_common::constants::MAX_APPOINTMENTS_PER_BATCH;
+use teos_common::errors;
+use teos_common::migration::MigrationConsent;
 use teos_common::protos as common_msgs;
-use teos_common::UserId;
+use teos_common::{TowerId, UserId};
+
+/// Maximum number of renewal reminder streams a user can have open at the same time.
+const MAX_REMINDER_STREAMS_PER_USER: usize = 3;
+
+/// Channel renewal reminders are streamed to the user through.
+type ReminderSender = mpsc::Sender<Result<common_msgs::RenewalReminder, Status>>;
+
+/// Maps the reasons why a manual action over a tracker may fail to the corresponding gRPC [Status].
+fn tracker_action_error(failure: TrackerActionFailure) -> Status {
+    match failure {
+        TrackerActionFailure::NotFound => Status::new(Code::NotFound, "Tracker not found"),
+        TrackerActionFailure::AlreadyConfirmed => Status::new(
+            Code::FailedPrecondition,
+            "The penalty transaction has already been confirmed",
+        ),
+        TrackerActionFailure::Rejected => Status::new(
+            Code::FailedPrecondition,
+            "The penalty transaction was rejected by bitcoind",
+        ),
+        TrackerActionFailure::NotUnderReview => Status::new(
+            Code::FailedPrecondition,
+            "The tracker is not pending review",
+        ),
+    }
+}
+
+/// Maps the reasons why adding an appointment within a batch may fail to an error code and message.
+fn add_appointment_error(failure: AddAppointmentFailure) -> (u8, String) {
+    match failure {
+        AddAppointmentFailure::AuthenticationFailure | AddAppointmentFailure::NotEnoughSlots => (
+            errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+            "Invalid signature or user does not have enough slots available".to_owned(),
+        ),
+        AddAppointmentFailure::SubscriptionExpired(x) => (
+            errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR,
+            format!("Your subscription expired at {}", x),
+        ),
+        AddAppointmentFailure::AlreadyTriggered => (
+            errors::APPOINTMENT_ALREADY_TRIGGERED,
+            "The provided appointment has already been triggered".to_owned(),
+        ),
+        AddAppointmentFailure::RejectedByPolicy(reason) => (
+            errors::APPOINTMENT_REJECTED_BY_POLICY,
+            format!("Appointment rejected by tower policy: {}", reason),
+        ),
+        AddAppointmentFailure::DuplicateLocator => (
+            errors::APPOINTMENT_DUPLICATE_LOCATOR,
+            "The appointment locator is duplicated within the batch".to_owned(),
+        ),
+    }
+}
+
+/// Maps a malformed public request to the corresponding gRPC [Status].
+fn invalid_field(e: FieldError) -> Status {
+    Status::new(Code::InvalidArgument, e.to_string())
+}
+
+/// Builds a [MigrationConsent] out of the raw data received within an export or import request.
+fn parse_migration_consent(
+    user_id: &[u8],
+    tower_id: &[u8],
+    expiry: u32,
+    signature: String,
+) -> Result<MigrationConsent, Status> {
+    let user_id = UserId::from_slice(user_id).map_err(|_| {
+        Status::new(
+            Code::InvalidArgument,
+            "Provided public key does not match expected format (33-byte compressed key)",
+        )
+    })?;
+    let tower_id = TowerId::from_slice(tower_id).map_err(|_| {
+        Status::new(
+            Code::InvalidArgument,
+            "Provided tower id does not match expected format (33-byte compressed key)",
+        )
+    })?;
+
+    Ok(MigrationConsent::with_signature(
+        user_id, tower_id, expiry, signature,
+    ))
+}
 
 /// Internal API of the tower.
 /// Holds the [Watcher] (which is the single entry point of the tower's core) and offers interfaces
 /// to all available methods. The [InternalAPI] has two interfaces, a public one, reachable from the [API]
-/// and a private one, only accessible from the [RPCServer].
+/// (and straight through gRPC if the public gRPC API is enabled) and a private one, only accessible from the [RPCServer].
 pub struct InternalAPI {
     /// A [Watcher] instance.
     watcher: Arc<Watcher>,
@@ -25,23 +126,46 @@ pub struct InternalAPI {
     addresses: Vec<msgs::NetworkAddress>,
     /// A flag that indicates wether bitcoind is reachable or not.
     bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
+    /// A flag that indicates whether the tower is running low on disk space.
+    low_disk_space: Arc<AtomicBool>,
+    /// How far behind the best known chain the tower is, and what to do about it.
+    sync_status: Arc<Mutex<SyncStatus>>,
+    /// The latency stats of the tower endpoints and internal stages.
+    latency_stats: Arc<LatencyStats>,
     /// A signal indicating the tower is shuting down.
     shutdown_trigger: Trigger,
+    /// The key destructive private requests must be signed with, if any.
+    operator_key: Option<PublicKey>,
+    /// Operator signatures already used, alongside their timestamps. Used to prevent replays.
+    used_operator_signatures: Mutex<HashMap<String, u64>>,
+    /// The renewal reminder streams opened by each user. Used to cap the number of concurrent streams.
+    reminder_streams: Mutex<HashMap<UserId, Vec<ReminderSender>>>,
 }
 
 impl InternalAPI {
     /// Creates a new [InternalAPI] instance.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         watcher: Arc<Watcher>,
         addresses: Vec<msgs::NetworkAddress>,
         bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
+        low_disk_space: Arc<AtomicBool>,
+        sync_status: Arc<Mutex<SyncStatus>>,
+        latency_stats: Arc<LatencyStats>,
         shutdown_trigger: Trigger,
+        operator_key: Option<PublicKey>,
     ) -> Self {
         Self {
             watcher,
             addresses,
             bitcoind_reachable,
+            low_disk_space,
+            sync_status,
+            latency_stats,
             shutdown_trigger,
+            operator_key,
+            used_operator_signatures: Mutex::new(HashMap::new()),
+            reminder_streams: Mutex::new(HashMap::new()),
         }
     }
 
@@ -49,6 +173,40 @@ impl InternalAPI {
         &self.addresses
     }
 
+    /// Checks the operator signature of a destructive request, if the tower has an operator key set.
+    ///
+    /// Signatures can only be used once, so a captured request cannot be replayed.
+    fn check_operator_signature<T: prost::Message>(
+        &self,
+        request: &Request<T>,
+        method: &str,
+    ) -> Result<(), Status> {
+        let operator_key = match self.operator_key.as_ref() {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let (signature, timestamp) = operator_auth::verify_request(request, method, operator_key)
+            .map_err(|e| {
+                log::warn!(target: telemetry::AUDIT_TARGET, "Rejected {} request: {}", method, e.message());
+                e
+            })?;
+
+        let mut used_signatures = self.used_operator_signatures.lock().unwrap();
+        // Signatures outside the accepted window are rejected anyway, so there's no need to remember them
+        used_signatures
+            .retain(|_, t| timestamp.saturating_sub(*t) <= 2 * operator_auth::MAX_CLOCK_DRIFT);
+        if used_signatures.insert(signature, timestamp).is_some() {
+            log::warn!(target: telemetry::AUDIT_TARGET, "Rejected {} request: operator signature replayed", method);
+            return Err(Status::new(
+                Code::Unauthenticated,
+                "The operator signature has already been used",
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Checks whether bitcoind is reachable.
     fn check_service_unavailable(&self) -> Result<(), Status> {
         if *self.bitcoind_reachable.0.lock().unwrap() {
@@ -61,18 +219,66 @@ impl InternalAPI {
             ))
         }
     }
+
+    /// Checks whether the tower has enough disk space to accept new data.
+    ///
+    /// Writing to a full disk may corrupt the database, so new users and appointments are rejected while the
+    /// disk is low on space. The error is retriable, since the tower will accept data again once space is freed.
+    fn check_disk_space(&self) -> Result<(), Status> {
+        if self.low_disk_space.load(Ordering::SeqCst) {
+            log::warn!("Rejecting request: running low on disk space");
+            Err(Status::new(
+                Code::Unavailable,
+                "The tower is running low on disk space. Try again later",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks whether the tower is synced enough to accept new data according to its
+    /// [SyncPolicy](crate::sync_monitor::SyncPolicy).
+    ///
+    /// Returns how many blocks behind the best known header the tower is if so.
+    fn check_synced(&self) -> Result<u32, Status> {
+        self.sync_status
+            .lock()
+            .unwrap()
+            .check(self.watcher.get_last_known_block_height())
+            .map_err(|e| {
+                log::info!(
+                    "Rejecting request: tower syncing ({} blocks behind)",
+                    e.blocks_behind
+                );
+                let message = match e.eta {
+                    Some(eta) => format!(
+                        "Tower syncing ({} blocks behind). Retry later (ETA: {} seconds)",
+                        e.blocks_behind, eta
+                    ),
+                    None => format!(
+                        "Tower syncing ({} blocks behind). Retry later",
+                        e.blocks_behind
+                    ),
+                };
+                Status::new(Code::Unavailable, message)
+            })
+    }
 }
 
 /// Public tower API. Accessible by users.
 #[tonic::async_trait]
 impl PublicTowerServices for Arc<InternalAPI> {
-    /// Register endpoint. Part of the public API. Internally calls [Watcher::register].
+    /// Register endpoint. Part of the public API. Internally calls [Watcher::check_payment] and [Watcher::register].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "register", request_id = %telemetry::request_id(&request)))]
     async fn register(
         &self,
         request: Request<common_msgs::RegisterRequest>,
     ) -> Result<Response<common_msgs::RegisterResponse>, Status> {
         self.check_service_unavailable()?;
+        self.check_disk_space()?;
+        self.check_synced()?;
         let req_data = request.into_inner();
+        validation::check_register(&req_data).map_err(invalid_field)?;
 
         let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
             Status::new(
@@ -81,28 +287,60 @@ impl PublicTowerServices for Arc<InternalAPI> {
             )
         })?;
 
+        self.watcher
+            .check_payment(user_id, &req_data.payment_preimage)
+            .map_err(|e| match e {
+                PaymentFailure::MissingPreimage => Status::new(
+                    Code::FailedPrecondition,
+                    "Payment required. Pay an invoice from get_registration_invoice and provide its preimage",
+                ),
+                PaymentFailure::UnknownInvoice => Status::new(
+                    Code::FailedPrecondition,
+                    "The payment preimage does not match any pending invoice of the user",
+                ),
+            })?;
+
         match self.watcher.register(user_id) {
-            Ok(receipt) => Ok(Response::new(common_msgs::RegisterResponse {
-                user_id: req_data.user_id,
-                available_slots: receipt.available_slots(),
-                subscription_start: receipt.subscription_start(),
-                subscription_expiry: receipt.subscription_expiry(),
-                subscription_signature: receipt.signature().unwrap(),
-            })),
-            Err(_) => Err(Status::new(
+            Ok(receipt) => {
+                let attestation = receipt.attestation().unwrap();
+                Ok(Response::new(common_msgs::RegisterResponse {
+                    user_id: req_data.user_id,
+                    available_slots: receipt.available_slots(),
+                    subscription_start: receipt.subscription_start(),
+                    subscription_expiry: receipt.subscription_expiry(),
+                    subscription_signature: receipt.signature().unwrap(),
+                    attestation_signature: attestation.signature().to_owned(),
+                    attestation_height: attestation.height(),
+                    expiry_delta: self.watcher.get_expiry_delta(),
+                    broadcast_delay: self.watcher.get_broadcast_delay(),
+                    previous_subscription_signature: self
+                        .watcher
+                        .sign_with_previous_key(&receipt.to_vec())
+                        .unwrap_or_default(),
+                }))
+            }
+            Err(RegistrationFailure::MaxSlotsReached) => Err(Status::new(
                 Code::ResourceExhausted,
                 "Subscription maximum slots count reached",
             )),
+            Err(RegistrationFailure::MaxUsersReached) => Err(Status::new(
+                Code::OutOfRange,
+                "The tower is not accepting new users at the moment",
+            )),
         }
     }
 
     /// Add appointment endpoint. Part of the public API. Internally calls [Watcher::add_appointment].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "add_appointment", request_id = %telemetry::request_id(&request)))]
     async fn add_appointment(
         &self,
         request: Request<common_msgs::AddAppointmentRequest>,
     ) -> Result<Response<common_msgs::AddAppointmentResponse>, Status> {
         self.check_service_unavailable()?;
+        self.check_disk_space()?;
+        let blocks_behind = self.check_synced()?;
         let req_data = request.into_inner();
+        validation::check_add_appointment(&req_data).map_err(invalid_field)?;
         let app_data = req_data.appointment.unwrap();
 
         let appointment = Appointment::new(
@@ -114,15 +352,28 @@ impl PublicTowerServices for Arc<InternalAPI> {
 
         match self
             .watcher
-            .add_appointment(appointment, req_data.signature)
+            .add_appointment(appointment, req_data.signature, req_data.batch_receipt)
         {
-            Ok((receipt, available_slots, subscription_expiry)) => {
+            Ok(AddedAppointment {
+                receipt,
+                available_slots,
+                subscription_expiry,
+                dispute_on_chain,
+            }) => {
                 Ok(Response::new(common_msgs::AddAppointmentResponse {
                     locator: locator.to_vec(),
                     start_block: receipt.start_block(),
-                    signature: receipt.signature().unwrap(),
+                    // Batched receipts are not signed straightaway
+                    signature: receipt.signature().unwrap_or_default(),
                     available_slots,
                     subscription_expiry,
+                    renewal_due: self.watcher.is_renewal_due(subscription_expiry),
+                    dispute_on_chain,
+                    blocks_behind,
+                    previous_signature: receipt
+                        .signature()
+                        .and_then(|_| self.watcher.sign_with_previous_key(&receipt.to_vec()))
+                        .unwrap_or_default(),
                 }))
             }
             Err(e) => match e {
@@ -139,21 +390,144 @@ impl PublicTowerServices for Arc<InternalAPI> {
                     Code::AlreadyExists,
                     "The provided appointment has already been triggered",
                 )),
+                AddAppointmentFailure::RejectedByPolicy(reason) => Err(Status::new(
+                    Code::PermissionDenied,
+                    format!("Appointment rejected by tower policy: {}", reason),
+                )),
+                // Duplicates can only be found within batches
+                AddAppointmentFailure::DuplicateLocator => unreachable!(),
+            },
+        }
+    }
+
+    /// Add appointments endpoint. Part of the public API. Internally calls [Watcher::add_appointments].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "add_appointments", request_id = %telemetry::request_id(&request)))]
+    async fn add_appointments(
+        &self,
+        request: Request<common_msgs::AddAppointmentsRequest>,
+    ) -> Result<Response<common_msgs::AddAppointmentsResponse>, Status> {
+        self.check_service_unavailable()?;
+        self.check_disk_space()?;
+        let blocks_behind = self.check_synced()?;
+        let req_data = request.into_inner();
+        validation::check_add_appointments(&req_data).map_err(invalid_field)?;
+
+        let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+        if req_data.appointments.len() > MAX_APPOINTMENTS_PER_BATCH {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!(
+                    "Too many appointments (max: {}, received: {})",
+                    MAX_APPOINTMENTS_PER_BATCH,
+                    req_data.appointments.len()
+                ),
+            ));
+        }
+
+        // Receipts are only batched if all the appointments opted in
+        let batch_receipts = req_data.appointments.iter().all(|req| req.batch_receipt);
+        let mut appointments = Vec::with_capacity(req_data.appointments.len());
+        for req in req_data.appointments.into_iter() {
+            let app_data = req
+                .appointment
+                .ok_or_else(|| Status::new(Code::InvalidArgument, "Missing appointment data"))?;
+            let locator = Locator::from_slice(&app_data.locator)
+                .map_err(|_| Status::new(Code::InvalidArgument, "Invalid locator"))?;
+            appointments.push((
+                Appointment::new(locator, app_data.encrypted_blob, app_data.to_self_delay),
+                req.signature,
+            ));
+        }
+
+        match self
+            .watcher
+            .add_appointments(user_id, appointments, batch_receipts)
+        {
+            Ok(AddedAppointments {
+                results,
+                available_slots,
+                subscription_expiry,
+            }) => {
+                Ok(Response::new(common_msgs::AddAppointmentsResponse {
+                    results: results
+                        .into_iter()
+                        .map(|(locator, result)| match result {
+                            Ok(AcceptedAppointment {
+                                receipt,
+                                dispute_on_chain,
+                            }) => common_msgs::AddAppointmentResult {
+                                locator: locator.to_vec(),
+                                start_block: receipt.start_block(),
+                                // Batched receipts are not signed straightaway
+                                signature: receipt.signature().unwrap_or_default(),
+                                previous_signature: receipt
+                                    .signature()
+                                    .and_then(|_| {
+                                        self.watcher.sign_with_previous_key(&receipt.to_vec())
+                                    })
+                                    .unwrap_or_default(),
+                                dispute_on_chain,
+                                ..Default::default()
+                            },
+                            Err(e) => {
+                                let (error_code, error) = add_appointment_error(e);
+                                common_msgs::AddAppointmentResult {
+                                    locator: locator.to_vec(),
+                                    error_code: error_code as u32,
+                                    error,
+                                    ..Default::default()
+                                }
+                            }
+                        })
+                        .collect(),
+                    available_slots,
+                    subscription_expiry,
+                    renewal_due: self.watcher.is_renewal_due(subscription_expiry),
+                    blocks_behind,
+                }))
+            }
+            Err(e) => match e {
+                AddAppointmentFailure::AuthenticationFailure
+                | AddAppointmentFailure::NotEnoughSlots => Err(Status::new(
+                    Code::Unauthenticated,
+                    "Invalid signature or user does not have enough slots available",
+                )),
+                AddAppointmentFailure::SubscriptionExpired(x) => Err(Status::new(
+                    Code::Unauthenticated,
+                    format!("Your subscription expired at {}", x),
+                )),
+                // Individual appointment failures are reported within the response
+                AddAppointmentFailure::AlreadyTriggered
+                | AddAppointmentFailure::RejectedByPolicy(_)
+                | AddAppointmentFailure::DuplicateLocator => unreachable!(),
             },
         }
     }
 
     /// Get appointment endpoint. Part of the public API. Internally calls [Watcher::get_appointment].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_appointment", request_id = %telemetry::request_id(&request)))]
     async fn get_appointment(
         &self,
         request: Request<common_msgs::GetAppointmentRequest>,
     ) -> Result<Response<common_msgs::GetAppointmentResponse>, Status> {
         self.check_service_unavailable()?;
         let req_data = request.into_inner();
+        validation::check_get_appointment(&req_data).map_err(invalid_field)?;
         let locator = Locator::from_slice(&req_data.locator).unwrap();
 
-        match self.watcher.get_appointment(locator, &req_data.signature) {
-            Ok(info) => {
+        // An empty challenge means no challenge was provided
+        let challenge = Some(req_data.challenge.as_slice()).filter(|c| !c.is_empty());
+
+        match self
+            .watcher
+            .get_appointment(locator, &req_data.signature, challenge)
+        {
+            Ok((info, subscription_expiry)) => {
                 let (appointment_data, status) = match info {
                     AppointmentInfo::Appointment(appointment) => (
                         common_msgs::AppointmentData {
@@ -179,6 +553,7 @@ impl PublicTowerServices for Arc<InternalAPI> {
                 Ok(Response::new(common_msgs::GetAppointmentResponse {
                     appointment_data: Some(appointment_data),
                     status: status as i32,
+                    renewal_due: self.watcher.is_renewal_due(subscription_expiry),
                 }))
             }
             Err(e) => match e {
@@ -198,14 +573,18 @@ impl PublicTowerServices for Arc<InternalAPI> {
     }
 
     /// Get subscription info endpoint. Part of the public API. Internally calls [Watcher::get_subscription_info].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_subscription_info", request_id = %telemetry::request_id(&request)))]
     async fn get_subscription_info(
         &self,
         request: Request<common_msgs::GetSubscriptionInfoRequest>,
     ) -> Result<Response<common_msgs::GetSubscriptionInfoResponse>, Status> {
         self.check_service_unavailable()?;
-        let (subscription_info, locators) = self
+        let req_data = request.into_inner();
+        validation::check_get_subscription_info(&req_data).map_err(invalid_field)?;
+        let challenge = Some(req_data.challenge.as_slice()).filter(|c| !c.is_empty());
+        let (subscription_info, locators, reissued_receipt) = self
             .watcher
-            .get_subscription_info(&request.into_inner().signature)
+            .get_subscription_info(&req_data.signature, challenge)
             .map_err(|e| match e {
                 GetSubscriptionInfoFailure::AuthenticationFailure => Status::new(
                     Code::Unauthenticated,
@@ -221,8 +600,303 @@ impl PublicTowerServices for Arc<InternalAPI> {
             available_slots: subscription_info.available_slots,
             subscription_expiry: subscription_info.subscription_expiry,
             locators: locators.iter().map(|x| x.to_vec()).collect(),
+            renewal_due: self
+                .watcher
+                .is_renewal_due(subscription_info.subscription_expiry),
+            n_appointments: subscription_info.appointments.len() as u32,
+            reissued_receipt: reissued_receipt.map(|reissued| {
+                let attestation = reissued.receipt.attestation().unwrap();
+                common_msgs::ReissuedReceipt {
+                    available_slots: reissued.receipt.available_slots(),
+                    subscription_start: reissued.receipt.subscription_start(),
+                    subscription_expiry: reissued.receipt.subscription_expiry(),
+                    subscription_signature: reissued.receipt.signature().unwrap(),
+                    attestation_signature: attestation.signature().to_owned(),
+                    attestation_height: attestation.height(),
+                    previous_signature: reissued.previous_signature,
+                }
+            }),
+        }))
+    }
+
+    /// Get auth challenge endpoint. Part of the public API. Internally calls [Watcher::get_auth_challenge].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_auth_challenge", request_id = %telemetry::request_id(&request)))]
+    async fn get_auth_challenge(
+        &self,
+        request: Request<common_msgs::GetAuthChallengeRequest>,
+    ) -> Result<Response<common_msgs::GetAuthChallengeResponse>, Status> {
+        let req_data = request.into_inner();
+        validation::check_get_auth_challenge(&req_data).map_err(invalid_field)?;
+
+        let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+
+        match self.watcher.get_auth_challenge(user_id) {
+            Ok(challenge) => Ok(Response::new(common_msgs::GetAuthChallengeResponse {
+                challenge,
+            })),
+            Err(ChallengeFailure::UserNotFound) => Err(Status::new(
+                Code::Unauthenticated,
+                "User not found. Have you registered?",
+            )),
+        }
+    }
+
+    /// Get registration terms endpoint. Part of the public API. Internally calls [Watcher::get_subscription_terms] and
+    /// [Watcher::get_subscription_pricing].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_registration_terms", request_id = %telemetry::request_id(&request)))]
+    async fn get_registration_terms(
+        &self,
+        request: Request<common_msgs::GetRegistrationTermsRequest>,
+    ) -> Result<Response<common_msgs::GetRegistrationTermsResponse>, Status> {
+        let (subscription_slots, subscription_duration) = self.watcher.get_subscription_terms();
+        let pricing = self.watcher.get_subscription_pricing();
+        let subscription_price_msat = pricing
+            .price(subscription_slots, subscription_duration)
+            .ok_or_else(|| {
+                Status::new(Code::Internal, "The subscription price cannot be computed")
+            })?;
+
+        Ok(Response::new(common_msgs::GetRegistrationTermsResponse {
+            subscription_slots,
+            subscription_duration,
+            subscription_price_per_slot_msat: pricing.price_per_slot_msat,
+            subscription_price_per_block_msat: pricing.price_per_block_msat,
+            subscription_price_msat,
+            payments_required: pricing.payments_required,
+            accepting_registrations: self.watcher.is_accepting_registrations(),
+            max_users: self.watcher.get_max_users(),
         }))
     }
+
+    /// Get registration invoice endpoint. Part of the public API. Internally calls [Watcher::get_registration_invoice].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_registration_invoice", request_id = %telemetry::request_id(&request)))]
+    async fn get_registration_invoice(
+        &self,
+        request: Request<common_msgs::GetRegistrationInvoiceRequest>,
+    ) -> Result<Response<common_msgs::GetRegistrationInvoiceResponse>, Status> {
+        let req_data = request.into_inner();
+        validation::check_get_registration_invoice(&req_data).map_err(invalid_field)?;
+
+        let user_id = UserId::from_slice(&req_data.user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+
+        match self.watcher.get_registration_invoice(user_id) {
+            Ok(invoice) => Ok(Response::new(common_msgs::GetRegistrationInvoiceResponse {
+                payment_hash: invoice.payment_hash[..].to_vec(),
+                amount_msat: invoice.amount_msat,
+            })),
+            Err(InvoiceFailure::PaymentsNotRequired) => Err(Status::new(
+                Code::Unimplemented,
+                "The tower does not require payments",
+            )),
+            Err(InvoiceFailure::PriceOverflow) => Err(Status::new(
+                Code::Internal,
+                "The subscription price cannot be computed",
+            )),
+            Err(InvoiceFailure::TooManyInvoices) => Err(Status::new(
+                Code::ResourceExhausted,
+                "Too many pending invoices. Try again later",
+            )),
+        }
+    }
+
+    /// Get batched receipt endpoint. Part of the public API. Internally calls [Watcher::get_batched_receipt].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_batched_receipt", request_id = %telemetry::request_id(&request)))]
+    async fn get_batched_receipt(
+        &self,
+        request: Request<common_msgs::GetBatchedReceiptRequest>,
+    ) -> Result<Response<common_msgs::GetBatchedReceiptResponse>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+        validation::check_get_batched_receipt(&req_data).map_err(invalid_field)?;
+        let locator = Locator::from_slice(&req_data.locator).unwrap();
+
+        match self
+            .watcher
+            .get_batched_receipt(locator, &req_data.signature)
+        {
+            Ok(batched_receipt) => Ok(Response::new(common_msgs::GetBatchedReceiptResponse {
+                locator: locator.to_vec(),
+                user_signature: batched_receipt.receipt().user_signature().to_owned(),
+                start_block: batched_receipt.receipt().start_block(),
+                leaf_index: batched_receipt.proof().index(),
+                n_leaves: batched_receipt.proof().n_leaves(),
+                siblings: batched_receipt
+                    .proof()
+                    .siblings()
+                    .iter()
+                    .map(|x| x[..].to_vec())
+                    .collect(),
+                root_signature: batched_receipt.root_signature().to_owned(),
+            })),
+            Err(e) => match e {
+                GetBatchedReceiptFailure::AuthenticationFailure => Err(Status::new(
+                    Code::Unauthenticated,
+                    "User cannot be authenticated",
+                )),
+                GetBatchedReceiptFailure::SubscriptionExpired(x) => Err(Status::new(
+                    Code::Unauthenticated,
+                    format!("Your subscription expired at {}", x),
+                )),
+                GetBatchedReceiptFailure::BatchingDisabled => Err(Status::new(
+                    Code::NotFound,
+                    "The tower does not batch receipts",
+                )),
+                GetBatchedReceiptFailure::Pending => Err(Status::new(
+                    Code::NotFound,
+                    "Receipt batch not closed yet. Try again after the next block",
+                )),
+                GetBatchedReceiptFailure::NotFound => {
+                    Err(Status::new(Code::NotFound, "Batched receipt not found"))
+                }
+            },
+        }
+    }
+
+    /// Get breach log endpoint. Part of the public API. Internally calls [Watcher::get_breach_log].
+    #[tracing::instrument(name = "request", skip_all, fields(api = "public", method = "get_breach_log", request_id = %telemetry::request_id(&request)))]
+    async fn get_breach_log(
+        &self,
+        request: Request<common_msgs::GetBreachLogRequest>,
+    ) -> Result<Response<common_msgs::GetBreachLogResponse>, Status> {
+        let client = telemetry::client_addr(&request);
+        match self
+            .watcher
+            .get_breach_log(request.into_inner().from_height, client)
+        {
+            Ok(log) => Ok(Response::new(common_msgs::GetBreachLogResponse {
+                from_height: log.from_height(),
+                to_height: log.to_height(),
+                entries: log
+                    .entries()
+                    .iter()
+                    .map(|(txid, height)| common_msgs::BreachLogEntry {
+                        penalty_txid: txid.to_vec(),
+                        height: *height,
+                    })
+                    .collect(),
+                signature: log.signature().unwrap(),
+            })),
+            Err(e) => match e {
+                GetBreachLogFailure::Disabled => Err(Status::new(
+                    Code::Unimplemented,
+                    "The tower does not publish a breach log",
+                )),
+                GetBreachLogFailure::RateLimited => Err(Status::new(
+                    Code::ResourceExhausted,
+                    "Too many breach log requests. Try again after the next block",
+                )),
+                GetBreachLogFailure::FutureHeight(final_height) => Err(Status::new(
+                    Code::InvalidArgument,
+                    format!(
+                        "from_height is ahead of the last final block ({})",
+                        final_height
+                    ),
+                )),
+            },
+        }
+    }
+
+    type subscribe_renewal_remindersStream =
+        ReceiverStream<Result<common_msgs::RenewalReminder, Status>>;
+
+    /// Subscribe renewal reminders endpoint. Part of the public API. Internally calls [Watcher::subscribe_renewal_reminders].
+    ///
+    /// Streams a reminder to the user every time their subscription enters the renewal window. If the subscription is
+    /// already due for renewal when subscribing, a reminder is sent straightaway.
+    async fn subscribe_renewal_reminders(
+        &self,
+        request: Request<common_msgs::RenewalRemindersRequest>,
+    ) -> Result<Response<Self::subscribe_renewal_remindersStream>, Status> {
+        self.check_service_unavailable()?;
+        let req_data = request.into_inner();
+        validation::check_renewal_reminders(&req_data).map_err(invalid_field)?;
+        // An empty challenge means no challenge was provided
+        let challenge = Some(req_data.challenge.as_slice()).filter(|c| !c.is_empty());
+        let (user_id, mut reminders) = self
+            .watcher
+            .subscribe_renewal_reminders(&req_data.signature, challenge)
+            .map_err(|e| match e {
+                GetSubscriptionInfoFailure::AuthenticationFailure => Status::new(
+                    Code::Unauthenticated,
+                    "User not found. Have you registered?",
+                ),
+                GetSubscriptionInfoFailure::SubscriptionExpired(x) => Status::new(
+                    Code::Unauthenticated,
+                    format!("Your subscription expired at {}", x),
+                ),
+            })?;
+
+        let (tx, rx) = mpsc::channel(4);
+        {
+            let mut reminder_streams = self.reminder_streams.lock().unwrap();
+            // Streams whose receiving end is gone do not count towards the limit. Users left with no open streams
+            // are dropped so the map only holds the ones currently subscribed
+            reminder_streams.retain(|_, streams| {
+                streams.retain(|stream| !stream.is_closed());
+                !streams.is_empty()
+            });
+            let streams = reminder_streams.entry(user_id).or_default();
+            if streams.len() >= MAX_REMINDER_STREAMS_PER_USER {
+                return Err(Status::new(
+                    Code::ResourceExhausted,
+                    "Too many renewal reminder streams open. Close one and try again",
+                ));
+            }
+            streams.push(tx.clone());
+        }
+
+        let subscription_expiry = self
+            .watcher
+            .get_user_info(user_id)
+            .map(|info| info.subscription_expiry)
+            .unwrap_or_default();
+        let renewal_due = self.watcher.is_renewal_due(subscription_expiry);
+
+        tokio::spawn(async move {
+            if renewal_due {
+                let reminder = common_msgs::RenewalReminder {
+                    user_id: user_id.to_vec(),
+                    subscription_expiry,
+                };
+                if tx.send(Ok(reminder)).await.is_err() {
+                    return;
+                }
+            }
+
+            loop {
+                match reminders.recv().await {
+                    Ok(reminder) => {
+                        if reminder.user_id == user_id {
+                            let reminder = common_msgs::RenewalReminder {
+                                user_id: user_id.to_vec(),
+                                subscription_expiry: reminder.subscription_expiry,
+                            };
+                            if tx.send(Ok(reminder)).await.is_err() {
+                                // The user is gone
+                                break;
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        log::warn!("Renewal reminders channel lagged. {} reminders missed", n)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
 }
 
 /// Private tower API. Only accessible by the tower admin via RPC.
@@ -230,6 +904,11 @@ impl PublicTowerServices for Arc<InternalAPI> {
 impl PrivateTowerServices for Arc<InternalAPI> {
     /// Get all appointments endpoint. Gets all appointments in the tower. Part of the private API.
     /// Internally calls [Watcher::get_all_watcher_appointments] and [Watcher::get_all_responder_trackers].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_all_appointments")
+    )]
     async fn get_all_appointments(
         &self,
         _: Request<()>,
@@ -261,6 +940,11 @@ impl PrivateTowerServices for Arc<InternalAPI> {
 
     /// Get appointments endpoint. Gets the appointments with a specific locator. Part of the private API.
     /// Internally calls [Watcher::get_watcher_appointments_using_locator] and [Watcher::get_responder_trackers_using_locator].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_appointments")
+    )]
     async fn get_appointments(
         &self,
         request: tonic::Request<msgs::GetAppointmentsRequest>,
@@ -307,10 +991,16 @@ impl PrivateTowerServices for Arc<InternalAPI> {
     /// Get tower info endpoint. Gets information about the tower state. Part of the private API.
     /// Internally calls [Watcher::get_registered_users_count], [Watcher::get_appointments_count]
     /// and [Watcher::get_trackers_count].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_tower_info")
+    )]
     async fn get_tower_info(
         &self,
         _: Request<()>,
     ) -> Result<Response<msgs::GetTowerInfoResponse>, Status> {
+        let pricing = self.watcher.get_subscription_pricing();
         Ok(Response::new(msgs::GetTowerInfoResponse {
             tower_id: self.watcher.tower_id.to_vec(),
             addresses: self.get_addresses().clone(),
@@ -318,63 +1008,442 @@ impl PrivateTowerServices for Arc<InternalAPI> {
             n_watcher_appointments: self.watcher.get_appointments_count() as u32,
             n_responder_trackers: self.watcher.get_trackers_count() as u32,
             bitcoind_reachable: self.check_service_unavailable().is_ok(),
+            low_disk_space: self.low_disk_space.load(Ordering::SeqCst),
+            blocks_behind: self
+                .sync_status
+                .lock()
+                .unwrap()
+                .blocks_behind(self.watcher.get_last_known_block_height()),
+            subscription_price_per_slot_msat: pricing.price_per_slot_msat,
+            subscription_price_per_block_msat: pricing.price_per_block_msat,
+            locator_cache_depth: self.watcher.get_locator_cache_depth() as u32,
+            locator_cache_size: self.watcher.get_locator_cache_size() as u32,
+            n_penalties_below_min_fee: self.watcher.get_low_fee_trackers_count() as u32,
+            n_trackers_under_review: self.watcher.get_trackers_under_review_count() as u32,
+            expiry_delta: self.watcher.get_expiry_delta(),
+            accepting_registrations: self.watcher.is_accepting_registrations(),
+            max_users: self.watcher.get_max_users(),
+            broadcast_delay: self.watcher.get_broadcast_delay(),
+            appointment_states: self
+                .watcher
+                .get_appointment_state_counts()
+                .into_iter()
+                .map(|(state, count)| (state.to_string(), count as u32))
+                .collect(),
         }))
     }
 
-    /// Get user endpoint. Gets all users in the tower. Part of the private API.
-    /// Internally calls [Watcher::get_user_ids].
-    async fn get_users(&self, _: Request<()>) -> Result<Response<msgs::GetUsersResponse>, Status> {
-        let user_ids = self
+    /// Export trackers endpoint. Gets all the trackers pending resolution in the tower, including the penalty
+    /// transactions, so they can be broadcast elsewhere if needed. Part of the private API.
+    /// Internally calls [Watcher::get_all_responder_trackers].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "export_trackers")
+    )]
+    async fn export_trackers(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<msgs::ExportTrackersResponse>, Status> {
+        let trackers = self
             .watcher
-            .get_user_ids()
-            .iter()
-            .map(|x| x.to_vec())
+            .get_all_responder_trackers()
+            .into_iter()
+            .map(|(uuid, tracker)| {
+                let (status_height, _) = tracker.status.to_db_data().unwrap_or_default();
+                let confirmed = matches!(tracker.status, ConfirmationStatus::ConfirmedIn(_));
+                msgs::ExportedTracker {
+                    uuid: uuid.to_vec(),
+                    user_id: tracker.user_id.to_vec(),
+                    locator: Locator::new(tracker.dispute_tx.txid()).to_vec(),
+                    confirmed,
+                    status_height,
+                    tracker: Some(tracker.into()),
+                }
+            })
             .collect();
 
-        Ok(Response::new(msgs::GetUsersResponse { user_ids }))
+        Ok(Response::new(msgs::ExportTrackersResponse { trackers }))
     }
 
-    /// Get user endpoint. Gets information about a given user. Part of the private API.
-    /// Internally calls [Watcher::get_user].
-    async fn get_user(
+    /// Rebroadcast tracker endpoint. Rebroadcasts the penalty of a given tracker right away. Part of the private API.
+    /// Internally calls [Watcher::rebroadcast_tracker].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "rebroadcast_tracker")
+    )]
+    async fn rebroadcast_tracker(
         &self,
-        request: Request<msgs::GetUserRequest>,
-    ) -> Result<Response<msgs::GetUserResponse>, Status> {
-        let user_id = UserId::from_slice(&request.into_inner().user_id).map_err(|_| {
+        request: Request<msgs::RebroadcastTrackerRequest>,
+    ) -> Result<Response<msgs::RebroadcastTrackerResponse>, Status> {
+        self.check_operator_signature(&request, "rebroadcast_tracker")?;
+        let uuid = UUID::from_slice(&request.into_inner().uuid).map_err(|_| {
             Status::new(
                 Code::InvalidArgument,
-                "Provided public key does not match expected format (33-byte compressed key)",
+                "The provided uuid does not match the expected format (20-byte hexadecimal string)",
             )
         })?;
 
-        match self.watcher.get_user_info(user_id) {
-            Some(info) => Ok(Response::new(msgs::GetUserResponse {
-                available_slots: info.available_slots,
-                subscription_expiry: info.subscription_expiry,
-                appointments: info
-                    .appointments
-                    .iter()
-                    .map(|(uuid, _)| uuid.to_vec())
-                    .collect(),
-            })),
-            None => Err(Status::new(Code::NotFound, "User not found")),
+        match self.watcher.rebroadcast_tracker(uuid) {
+            Ok(status) => {
+                let (status_height, _) = status.to_db_data().unwrap_or_default();
+                let confirmed = matches!(status, ConfirmationStatus::ConfirmedIn(_));
+                Ok(Response::new(msgs::RebroadcastTrackerResponse {
+                    uuid: uuid.to_vec(),
+                    status_height,
+                    confirmed,
+                    accepted_by: self.watcher.get_penalty_accepted_by(uuid),
+                }))
+            }
+            Err(e) => Err(tracker_action_error(e)),
         }
     }
 
-    /// Stop endpoint. Stops the tower daemon. Part of the private API.
-    async fn stop(&self, _: Request<()>) -> Result<Response<()>, Status> {
-        self.shutdown_trigger.trigger();
+    /// Abandon tracker endpoint. Stops responding for a given tracker, deleting it from the tower. Part of the private API.
+    /// Internally calls [Watcher::abandon_tracker]. Requires an operator signature if the tower has an operator key set.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "abandon_tracker")
+    )]
+    async fn abandon_tracker(
+        &self,
+        request: Request<msgs::AbandonTrackerRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.check_operator_signature(&request, "abandon_tracker")?;
+        let req_data = request.into_inner();
+        let uuid = UUID::from_slice(&req_data.uuid).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "The provided uuid does not match the expected format (20-byte hexadecimal string)",
+            )
+        })?;
 
-        log::debug!("Received shutting down signal, notifying components");
-        Ok(Response::new(()))
-    }
-}
+        if req_data.reason.trim().is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "A reason must be provided to abandon a tracker",
+            ));
+        }
 
-#[cfg(test)]
-mod tests_private_helpers {
-    use super::*;
+        self.watcher
+            .abandon_tracker(uuid, req_data.reason.trim())
+            .map(|_| Response::new(()))
+            .map_err(tracker_action_error)
+    }
 
-    impl InternalAPI {
+    /// Get review queue endpoint. Gets the trackers whose penalty can never confirm (its inputs were spent by a different
+    /// transaction), pending review by the operator. Part of the private API. Internally calls [Watcher::get_review_queue].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_review_queue")
+    )]
+    async fn get_review_queue(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<msgs::GetReviewQueueResponse>, Status> {
+        let review_timeout = self.watcher.get_review_timeout();
+        let mut trackers: Vec<msgs::TrackerUnderReview> = self
+            .watcher
+            .get_review_queue()
+            .into_iter()
+            .map(|(uuid, tracker, flagged_at)| msgs::TrackerUnderReview {
+                uuid: uuid.to_vec(),
+                user_id: tracker.user_id.to_vec(),
+                locator: Locator::new(tracker.dispute_tx.txid()).to_vec(),
+                penalty_txid: tracker.penalty_tx.txid().to_vec(),
+                flagged_at,
+                expires_at: if review_timeout == 0 {
+                    0
+                } else {
+                    flagged_at + review_timeout
+                },
+            })
+            .collect();
+        trackers.sort_by_key(|tracker| tracker.flagged_at);
+
+        Ok(Response::new(msgs::GetReviewQueueResponse { trackers }))
+    }
+
+    /// Acknowledge tracker endpoint. Garbage collects a given tracker under review straightaway, deleting it from the
+    /// tower. Part of the private API. Internally calls [Watcher::acknowledge_tracker]. Requires an operator signature if
+    /// the tower has an operator key set.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "acknowledge_tracker")
+    )]
+    async fn acknowledge_tracker(
+        &self,
+        request: Request<msgs::AcknowledgeTrackerRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.check_operator_signature(&request, "acknowledge_tracker")?;
+        let uuid = UUID::from_slice(&request.into_inner().uuid).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "The provided uuid does not match the expected format (20-byte hexadecimal string)",
+            )
+        })?;
+
+        self.watcher
+            .acknowledge_tracker(uuid)
+            .map(|_| Response::new(()))
+            .map_err(tracker_action_error)
+    }
+
+    /// Get latency stats endpoint. Gets the response-time histograms of every endpoint and internal stage, so the
+    /// operator can tell where slow responses come from. Part of the private API.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_latency_stats")
+    )]
+    async fn get_latency_stats(
+        &self,
+        _: Request<()>,
+    ) -> Result<Response<msgs::GetLatencyStatsResponse>, Status> {
+        let as_ms = |d: Duration| d.as_micros() as f64 / 1000.0;
+        let stats = self
+            .latency_stats
+            .snapshot()
+            .into_iter()
+            .map(|((kind, name), histogram)| msgs::LatencyStats {
+                kind,
+                name,
+                count: histogram.count(),
+                mean_ms: as_ms(histogram.mean()),
+                p50_ms: as_ms(histogram.quantile(0.5)),
+                p90_ms: as_ms(histogram.quantile(0.9)),
+                p99_ms: as_ms(histogram.quantile(0.99)),
+                max_ms: as_ms(histogram.max()),
+            })
+            .collect();
+
+        Ok(Response::new(msgs::GetLatencyStatsResponse { stats }))
+    }
+
+    /// Get user endpoint. Gets all users in the tower. Part of the private API.
+    /// Internally calls [Watcher::get_user_ids].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_users")
+    )]
+    async fn get_users(&self, _: Request<()>) -> Result<Response<msgs::GetUsersResponse>, Status> {
+        let user_ids = self
+            .watcher
+            .get_user_ids()
+            .iter()
+            .map(|x| x.to_vec())
+            .collect();
+
+        Ok(Response::new(msgs::GetUsersResponse { user_ids }))
+    }
+
+    /// Get user endpoint. Gets information about a given user. Part of the private API.
+    /// Internally calls [Watcher::get_user].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "get_user")
+    )]
+    async fn get_user(
+        &self,
+        request: Request<msgs::GetUserRequest>,
+    ) -> Result<Response<msgs::GetUserResponse>, Status> {
+        let user_id = UserId::from_slice(&request.into_inner().user_id).map_err(|_| {
+            Status::new(
+                Code::InvalidArgument,
+                "Provided public key does not match expected format (33-byte compressed key)",
+            )
+        })?;
+
+        match self.watcher.get_user_info(user_id) {
+            Some(info) => Ok(Response::new(msgs::GetUserResponse {
+                available_slots: info.available_slots,
+                subscription_expiry: info.subscription_expiry,
+                appointments: info
+                    .appointments
+                    .iter()
+                    .map(|(uuid, _)| uuid.to_vec())
+                    .collect(),
+            })),
+            None => Err(Status::new(Code::NotFound, "User not found")),
+        }
+    }
+
+    /// Export user endpoint. Exports the appointments of a given user so they can be imported by another tower,
+    /// provided the user has consented to it. Part of the private API.
+    /// Internally calls [Watcher::export_user].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "export_user")
+    )]
+    async fn export_user(
+        &self,
+        request: Request<msgs::ExportUserRequest>,
+    ) -> Result<Response<msgs::UserExport>, Status> {
+        self.check_operator_signature(&request, "export_user")?;
+        let req_data = request.into_inner();
+        let consent = parse_migration_consent(
+            &req_data.user_id,
+            &req_data.tower_id,
+            req_data.consent_expiry,
+            req_data.consent_signature,
+        )?;
+
+        match self.watcher.export_user(&consent) {
+            Ok(appointments) => Ok(Response::new(msgs::UserExport {
+                user_id: req_data.user_id,
+                tower_id: req_data.tower_id,
+                consent_signature: consent.signature().unwrap(),
+                appointments: appointments
+                    .into_iter()
+                    .map(
+                        |(appointment, user_signature, dispute_tx)| msgs::ExportedAppointment {
+                            appointment: Some(appointment.into()),
+                            user_signature,
+                            dispute_tx: dispute_tx
+                                .map(|tx| consensus::serialize(&tx))
+                                .unwrap_or_default(),
+                        },
+                    )
+                    .collect(),
+                consent_expiry: consent.expiry(),
+            })),
+            Err(ExportUserFailure::InvalidConsent) => Err(Status::new(
+                Code::PermissionDenied,
+                "The provided consent signature is not valid for the given user and tower",
+            )),
+            Err(ExportUserFailure::ConsentExpired(expiry)) => Err(Status::new(
+                Code::PermissionDenied,
+                format!("The provided consent expired at block {}", expiry),
+            )),
+            Err(ExportUserFailure::NotFound) => Err(Status::new(Code::NotFound, "User not found")),
+        }
+    }
+
+    /// Import user endpoint. Imports the appointments of a given user exported by another tower, giving the user a
+    /// fresh subscription. Part of the private API.
+    /// Internally calls [Watcher::import_user].
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(api = "private", method = "import_user")
+    )]
+    async fn import_user(
+        &self,
+        request: Request<msgs::UserExport>,
+    ) -> Result<Response<msgs::ImportUserResponse>, Status> {
+        self.check_operator_signature(&request, "import_user")?;
+        let req_data = request.into_inner();
+        let consent = parse_migration_consent(
+            &req_data.user_id,
+            &req_data.tower_id,
+            req_data.consent_expiry,
+            req_data.consent_signature,
+        )?;
+
+        let mut appointments = Vec::with_capacity(req_data.appointments.len());
+        for exported in req_data.appointments.into_iter() {
+            let appointment = exported.appointment.ok_or_else(|| {
+                Status::new(
+                    Code::InvalidArgument,
+                    "All exported appointments must contain appointment data",
+                )
+            })?;
+            let locator = Locator::from_slice(&appointment.locator).map_err(|_| {
+                Status::new(
+                    Code::InvalidArgument,
+                    "The provided locator does not match the expected format (16-byte hexadecimal string)",
+                )
+            })?;
+            let dispute_tx = if exported.dispute_tx.is_empty() {
+                None
+            } else {
+                Some(consensus::deserialize(&exported.dispute_tx).map_err(|_| {
+                    Status::new(
+                        Code::InvalidArgument,
+                        "The provided dispute transaction does not match the expected format",
+                    )
+                })?)
+            };
+            appointments.push((
+                Appointment::new(
+                    locator,
+                    appointment.encrypted_blob,
+                    appointment.to_self_delay,
+                ),
+                exported.user_signature,
+                dispute_tx,
+            ));
+        }
+
+        match self.watcher.import_user(&consent, appointments) {
+            Ok((imported, skipped, user_info)) => Ok(Response::new(msgs::ImportUserResponse {
+                imported_appointments: imported as u32,
+                skipped_appointments: skipped as u32,
+                available_slots: user_info.available_slots,
+                subscription_expiry: user_info.subscription_expiry,
+            })),
+            Err(ImportUserFailure::WrongTower) => Err(Status::new(
+                Code::InvalidArgument,
+                "The user data was exported to a different tower",
+            )),
+            Err(ImportUserFailure::InvalidConsent) => Err(Status::new(
+                Code::PermissionDenied,
+                "The provided consent signature is not valid for the given user and tower",
+            )),
+            Err(ImportUserFailure::ConsentExpired(expiry)) => Err(Status::new(
+                Code::PermissionDenied,
+                format!("The provided consent expired at block {}", expiry),
+            )),
+            Err(ImportUserFailure::AlreadyRegistered) => Err(Status::new(
+                Code::AlreadyExists,
+                "The user is already registered with the tower",
+            )),
+            Err(ImportUserFailure::InvalidAppointmentSignature(locator)) => Err(Status::new(
+                Code::PermissionDenied,
+                format!(
+                    "The appointment with locator {} is not signed by the user",
+                    locator
+                ),
+            )),
+            Err(ImportUserFailure::RegistrationFailure(RegistrationFailure::MaxSlotsReached)) => {
+                Err(Status::new(
+                    Code::ResourceExhausted,
+                    "Subscription maximum slots count reached",
+                ))
+            }
+            Err(ImportUserFailure::RegistrationFailure(RegistrationFailure::MaxUsersReached)) => {
+                Err(Status::new(
+                    Code::OutOfRange,
+                    "The tower is not accepting new users at the moment",
+                ))
+            }
+        }
+    }
+
+    /// Stop endpoint. Stops the tower daemon. Part of the private API.
+    /// Requires an operator signature if the tower has an operator key set.
+    #[tracing::instrument(name = "request", skip_all, fields(api = "private", method = "stop"))]
+    async fn stop(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        self.check_operator_signature(&request, "stop")?;
+        self.shutdown_trigger.trigger();
+
+        log::debug!("Received shutting down signal, notifying components");
+        Ok(Response::new(()))
+    }
+}
+
+#[cfg(test)]
+mod tests_private_helpers {
+    use super::*;
+
+    impl InternalAPI {
         pub(crate) fn get_watcher(&self) -> &Watcher {
             &self.watcher
         }
@@ -384,7 +1453,7 @@ mod tests_private_helpers {
 #[cfg(test)]
 mod tests_private_api {
     use super::*;
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
 
     use bitcoin::hashes::Hash;
@@ -393,8 +1462,8 @@ mod tests_private_api {
     use crate::extended_appointment::UUID;
     use crate::responder::{ConfirmationStatus, TransactionTracker};
     use crate::test_utils::{
-        create_api, generate_dummy_appointment, generate_uuid, get_random_tx, DURATION, SLOTS,
-        START_HEIGHT,
+        create_api, create_api_with_config, generate_dummy_appointment, generate_uuid,
+        get_random_tx, ApiConfig, DURATION, EXPIRY_DELTA, REVIEW_TIMEOUT, SLOTS, START_HEIGHT,
     };
     use crate::watcher::Breach;
 
@@ -426,7 +1495,7 @@ mod tests_private_api {
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
         internal_api
             .watcher
-            .add_appointment(appointment.clone(), user_signature)
+            .add_appointment(appointment.clone(), user_signature, false)
             .unwrap();
 
         let response = internal_api
@@ -464,6 +1533,229 @@ mod tests_private_api {
         ));
     }
 
+    #[tokio::test]
+    async fn test_export_trackers() {
+        let (internal_api, _s) = create_api().await;
+
+        // With no trackers in the tower, nothing is exported
+        let response = internal_api
+            .export_trackers(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.trackers.is_empty());
+
+        // Add data to the Responder so we can export it
+        let uuid = generate_uuid();
+        let tracker = internal_api.watcher.add_random_tracker_to_responder(uuid);
+
+        let response = internal_api
+            .export_trackers(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            response.trackers,
+            vec![msgs::ExportedTracker {
+                uuid: uuid.to_vec(),
+                user_id: tracker.user_id.to_vec(),
+                locator: Locator::new(tracker.dispute_tx.txid()).to_vec(),
+                confirmed: true,
+                status_height: 100,
+                tracker: Some(tracker.into()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_tracker() {
+        let (internal_api, _s) = create_api().await;
+
+        // Wrong uuid format
+        match internal_api
+            .rebroadcast_tracker(Request::new(msgs::RebroadcastTrackerRequest {
+                uuid: vec![0; 3],
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Unknown tracker
+        match internal_api
+            .rebroadcast_tracker(Request::new(msgs::RebroadcastTrackerRequest {
+                uuid: generate_uuid().to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::NotFound);
+                assert_eq!(status.message(), "Tracker not found")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Confirmed trackers cannot be rebroadcast
+        let uuid = generate_uuid();
+        internal_api.watcher.add_random_tracker_to_responder(uuid);
+        match internal_api
+            .rebroadcast_tracker(Request::new(msgs::RebroadcastTrackerRequest {
+                uuid: uuid.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::FailedPrecondition),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_abandon_tracker() {
+        let (internal_api, _s) = create_api().await;
+
+        let uuid = generate_uuid();
+        internal_api.watcher.add_random_tracker_to_responder(uuid);
+
+        // A reason must be provided
+        match internal_api
+            .abandon_tracker(Request::new(msgs::AbandonTrackerRequest {
+                uuid: uuid.to_vec(),
+                reason: " ".to_owned(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+        assert_eq!(internal_api.watcher.get_trackers_count(), 1);
+
+        internal_api
+            .abandon_tracker(Request::new(msgs::AbandonTrackerRequest {
+                uuid: uuid.to_vec(),
+                reason: "superseded".to_owned(),
+            }))
+            .await
+            .unwrap();
+        assert_eq!(internal_api.watcher.get_trackers_count(), 0);
+        assert!(internal_api.watcher.get_all_responder_trackers().is_empty());
+
+        // Abandoning it again fails
+        match internal_api
+            .abandon_tracker(Request::new(msgs::AbandonTrackerRequest {
+                uuid: uuid.to_vec(),
+                reason: "superseded".to_owned(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::NotFound),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_review_queue() {
+        let (internal_api, _s) = create_api().await;
+
+        let response = internal_api
+            .get_review_queue(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.trackers.is_empty());
+
+        // Only trackers flagged for review are returned
+        let uuid = generate_uuid();
+        let tracker = internal_api.watcher.add_random_tracker_to_responder(uuid);
+        internal_api
+            .watcher
+            .add_random_tracker_to_responder(generate_uuid());
+        internal_api
+            .watcher
+            .flag_responder_tracker_for_review(uuid, START_HEIGHT as u32);
+
+        let response = internal_api
+            .get_review_queue(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response.trackers,
+            vec![msgs::TrackerUnderReview {
+                uuid: uuid.to_vec(),
+                user_id: tracker.user_id.to_vec(),
+                locator: Locator::new(tracker.dispute_tx.txid()).to_vec(),
+                penalty_txid: tracker.penalty_tx.txid().to_vec(),
+                flagged_at: START_HEIGHT as u32,
+                expires_at: START_HEIGHT as u32 + REVIEW_TIMEOUT,
+            }]
+        );
+
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.n_trackers_under_review, 1);
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_tracker() {
+        let (internal_api, _s) = create_api().await;
+
+        // Wrong uuid format
+        match internal_api
+            .acknowledge_tracker(Request::new(msgs::AcknowledgeTrackerRequest {
+                uuid: vec![0; 3],
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Trackers that are not under review cannot be acknowledged
+        let uuid = generate_uuid();
+        internal_api.watcher.add_random_tracker_to_responder(uuid);
+        match internal_api
+            .acknowledge_tracker(Request::new(msgs::AcknowledgeTrackerRequest {
+                uuid: uuid.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::FailedPrecondition);
+                assert_eq!(status.message(), "The tracker is not pending review")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+        assert_eq!(internal_api.watcher.get_trackers_count(), 1);
+
+        internal_api
+            .watcher
+            .flag_responder_tracker_for_review(uuid, START_HEIGHT as u32);
+        internal_api
+            .acknowledge_tracker(Request::new(msgs::AcknowledgeTrackerRequest {
+                uuid: uuid.to_vec(),
+            }))
+            .await
+            .unwrap();
+        assert_eq!(internal_api.watcher.get_trackers_count(), 0);
+        assert_eq!(internal_api.watcher.get_trackers_under_review_count(), 0);
+
+        // Acknowledging it again fails
+        match internal_api
+            .acknowledge_tracker(Request::new(msgs::AcknowledgeTrackerRequest {
+                uuid: uuid.to_vec(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::NotFound),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
     #[tokio::test]
     async fn test_get_appointments() {
         let (internal_api, _s) = create_api().await;
@@ -497,7 +1789,7 @@ mod tests_private_api {
                 let signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
                 internal_api
                     .watcher
-                    .add_appointment(appointment, signature)
+                    .add_appointment(appointment, signature, false)
                     .unwrap();
             }
 
@@ -593,24 +1885,75 @@ mod tests_private_api {
         assert_eq!(response.n_registered_users, 0);
         assert_eq!(response.n_watcher_appointments, 0);
         assert_eq!(response.n_responder_trackers, 0);
+        // Subscriptions are free by default
+        assert_eq!(response.subscription_price_per_slot_msat, 0);
+        assert_eq!(response.subscription_price_per_block_msat, 0);
+        // The locator cache is fully populated on bootstrap
+        assert_eq!(response.locator_cache_depth, response.locator_cache_size);
+        assert_eq!(response.locator_cache_size, 6);
+        assert_eq!(response.n_penalties_below_min_fee, 0);
+        // There is no registered users limit by default
+        assert_eq!(response.expiry_delta, EXPIRY_DELTA);
+        assert!(response.accepting_registrations);
+        assert_eq!(response.max_users, 0);
+        // Penalties are broadcast straightaway by default
+        assert_eq!(response.broadcast_delay, 0);
+        assert!(!response.low_disk_space);
+        assert_eq!(response.blocks_behind, 0);
     }
 
     #[tokio::test]
-    async fn test_get_tower_info() {
-        let (internal_api, _s) = create_api().await;
+    async fn test_get_tower_info_low_disk_space() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).low_disk_space()).await;
 
-        // Register a user
-        let (user_sk, user_pk) = get_random_keypair();
-        let user_id = UserId(user_pk);
-        internal_api.watcher.register(user_id).unwrap();
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
 
-        // Add data to the Watcher
-        for _ in 0..2 {
-            let appointment = generate_dummy_appointment(None).inner;
+        assert!(response.low_disk_space);
+    }
+
+    #[tokio::test]
+    async fn test_get_tower_info_max_users() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, DURATION).max_users(1)).await;
+
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.accepting_registrations);
+        assert_eq!(response.max_users, 1);
+
+        internal_api.watcher.register(get_random_user_id()).unwrap();
+        let response = internal_api
+            .get_tower_info(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.accepting_registrations);
+    }
+
+    #[tokio::test]
+    async fn test_get_tower_info() {
+        let (internal_api, _s) = create_api().await;
+
+        // Register a user
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id).unwrap();
+
+        // Add data to the Watcher
+        for _ in 0..2 {
+            let appointment = generate_dummy_appointment(None).inner;
             let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
             internal_api
                 .watcher
-                .add_appointment(appointment.clone(), user_signature)
+                .add_appointment(appointment.clone(), user_signature, false)
                 .unwrap();
         }
 
@@ -632,6 +1975,57 @@ mod tests_private_api {
         assert_eq!(response.n_registered_users, 1);
         assert_eq!(response.n_watcher_appointments, 2);
         assert_eq!(response.n_responder_trackers, 3);
+        assert_eq!(
+            response.appointment_states,
+            HashMap::from_iter([("watched".to_owned(), 2), ("triggered".to_owned(), 3)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_latency_stats() {
+        let (internal_api, _s) = create_api().await;
+
+        // No stats are reported if nothing has been measured
+        let response = internal_api
+            .get_latency_stats(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.stats.is_empty());
+
+        internal_api
+            .latency_stats
+            .record("public", "register", Duration::from_millis(2));
+        internal_api
+            .latency_stats
+            .record("public", "register", Duration::from_millis(4));
+        internal_api
+            .latency_stats
+            .record("stage", "db_write", Duration::from_millis(1));
+
+        let response = internal_api
+            .get_latency_stats(Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.stats.len(), 2);
+
+        let register = &response.stats[0];
+        assert_eq!(
+            (register.kind.as_str(), register.name.as_str()),
+            ("public", "register")
+        );
+        assert_eq!(register.count, 2);
+        assert_eq!(register.mean_ms, 3.0);
+        assert_eq!(register.max_ms, 4.0);
+        assert_eq!(register.p99_ms, 4.0);
+
+        let db_write = &response.stats[1];
+        assert_eq!(
+            (db_write.kind.as_str(), db_write.name.as_str()),
+            ("stage", "db_write")
+        );
+        assert_eq!(db_write.count, 1);
     }
 
     #[tokio::test]
@@ -696,7 +2090,7 @@ mod tests_private_api {
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
         internal_api
             .watcher
-            .add_appointment(appointment.clone(), user_signature)
+            .add_appointment(appointment.clone(), user_signature, false)
             .unwrap();
 
         let response = internal_api
@@ -733,6 +2127,108 @@ mod tests_private_api {
         }
     }
 
+    #[tokio::test]
+    async fn test_export_import_user() {
+        let (source_api, _s) = create_api().await;
+        let (destination_api, _d) = create_api().await;
+        let destination_id = destination_api.watcher.tower_id;
+
+        // Register a user with the source tower and add an appointment
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        source_api.watcher.register(user_id).unwrap();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        source_api
+            .watcher
+            .add_appointment(appointment.clone(), user_signature.clone(), false)
+            .unwrap();
+
+        // Data cannot be exported without the user consent
+        let (another_sk, _) = get_random_keypair();
+        let consent_expiry = START_HEIGHT as u32 + 6;
+        let mut forged_consent = MigrationConsent::new(user_id, destination_id, consent_expiry);
+        forged_consent.sign(&another_sk);
+        match source_api
+            .export_user(Request::new(msgs::ExportUserRequest {
+                user_id: user_id.to_vec(),
+                tower_id: destination_id.to_vec(),
+                consent_signature: forged_consent.signature().unwrap(),
+                consent_expiry,
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::PermissionDenied),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Once the user consents, the data can be exported...
+        let mut consent = MigrationConsent::new(user_id, destination_id, consent_expiry);
+        consent.sign(&user_sk);
+        let export = source_api
+            .export_user(Request::new(msgs::ExportUserRequest {
+                user_id: user_id.to_vec(),
+                tower_id: destination_id.to_vec(),
+                consent_signature: consent.signature().unwrap(),
+                consent_expiry,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            export.appointments,
+            vec![msgs::ExportedAppointment {
+                appointment: Some(appointment.clone().into()),
+                user_signature,
+                dispute_tx: Vec::new(),
+            }]
+        );
+        assert_eq!(export.consent_expiry, consent_expiry);
+
+        // ...but only imported by the destination tower
+        match source_api.import_user(Request::new(export.clone())).await {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    "The user data was exported to a different tower"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+
+        let response = destination_api
+            .import_user(Request::new(export.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            response,
+            msgs::ImportUserResponse {
+                imported_appointments: 1,
+                skipped_appointments: 0,
+                available_slots: SLOTS - 1,
+                subscription_expiry: START_HEIGHT as u32 + DURATION,
+            }
+        );
+        assert!(destination_api
+            .watcher
+            .get_all_watcher_appointments()
+            .contains_key(&UUID::new(appointment.locator, user_id)));
+
+        // The same data cannot be imported twice
+        match destination_api.import_user(Request::new(export)).await {
+            Err(status) => {
+                assert_eq!(status.code(), Code::AlreadyExists);
+                assert_eq!(
+                    status.message(),
+                    "The user is already registered with the tower"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
     #[tokio::test]
     async fn test_stop() {
         let (internal_api, _s) = create_api().await;
@@ -741,17 +2237,141 @@ mod tests_private_api {
         internal_api.stop(Request::new(())).await.unwrap();
         assert!(internal_api.shutdown_trigger.is_triggered());
     }
+
+    #[tokio::test]
+    async fn test_stop_with_operator_key() {
+        let (operator_sk, operator_pk) = get_random_keypair();
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().operator_key(operator_pk)).await;
+
+        // Unsigned requests are rejected
+        match internal_api.stop(Request::new(())).await {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // So are requests signed by someone else, or for a different action
+        let (other_sk, _) = get_random_keypair();
+        for request in [
+            operator_auth::sign_request("stop", (), &other_sk),
+            operator_auth::sign_request("abandon_tracker", (), &operator_sk),
+        ] {
+            match internal_api.stop(request).await {
+                Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+                _ => panic!("Test should have returned Err"),
+            }
+        }
+        assert!(!internal_api.shutdown_trigger.is_triggered());
+
+        // A request signed by the operator goes through, but cannot be replayed
+        let request = operator_auth::sign_request("stop", (), &operator_sk);
+        let mut replayed = Request::new(());
+        *replayed.metadata_mut() = request.metadata().clone();
+        internal_api.stop(request).await.unwrap();
+        assert!(internal_api.shutdown_trigger.is_triggered());
+
+        match internal_api.stop(replayed).await {
+            Err(status) => assert_eq!(
+                status.message(),
+                "The operator signature has already been used"
+            ),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracker_and_migration_rpcs_with_operator_key() {
+        let (operator_sk, operator_pk) = get_random_keypair();
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::default().operator_key(operator_pk)).await;
+
+        // Unsigned requests are rejected before being processed
+        match internal_api
+            .rebroadcast_tracker(Request::new(msgs::RebroadcastTrackerRequest {
+                uuid: generate_uuid().to_vec(),
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+        match internal_api
+            .export_user(Request::new(msgs::ExportUserRequest::default()))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+        match internal_api
+            .import_user(Request::new(msgs::UserExport::default()))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Signed requests go through (and fail for their own reasons)
+        match internal_api
+            .rebroadcast_tracker(operator_auth::sign_request(
+                "rebroadcast_tracker",
+                msgs::RebroadcastTrackerRequest {
+                    uuid: generate_uuid().to_vec(),
+                },
+                &operator_sk,
+            ))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::NotFound),
+            _ => panic!("Test should have returned Err"),
+        }
+        match internal_api
+            .export_user(operator_auth::sign_request(
+                "export_user",
+                msgs::ExportUserRequest::default(),
+                &operator_sk,
+            ))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+        match internal_api
+            .import_user(operator_auth::sign_request(
+                "import_user",
+                msgs::UserExport::default(),
+                &operator_sk,
+            ))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests_public_api {
     use super::*;
 
+    use std::convert::TryFrom;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use bitcoin::Txid;
+
     use crate::extended_appointment::UUID;
+    use crate::gatekeeper::SubscriptionPricing;
+    use crate::sync_monitor::SyncPolicy;
     use crate::test_utils::{
-        create_api, create_api_with_config, generate_dummy_appointment, ApiConfig, DURATION, SLOTS,
+        create_api, create_api_with_config, generate_dummy_appointment, ApiConfig, DURATION,
+        EXPIRY_DELTA, RENEWAL_WINDOW, SLOTS, START_HEIGHT,
     };
+    use teos_common::constants::IRREVOCABLY_RESOLVED;
     use teos_common::cryptography::{self, get_random_keypair};
+    use teos_common::receipts::{AppointmentReceipt, BreachLog, RegistrationReceipt};
+    use teos_common::test_utils::get_random_user_id;
+    use tokio_stream::StreamExt;
+
+    use crate::watcher::PreviousKey;
 
     #[tokio::test]
     async fn test_register() {
@@ -764,12 +2384,14 @@ mod tests_public_api {
             let response = internal_api
                 .register(Request::new(common_msgs::RegisterRequest {
                     user_id: UserId(user_pk).to_vec(),
+                    payment_preimage: Vec::new(),
                 }))
                 .await
                 .unwrap()
                 .into_inner();
 
-            assert!(matches!(response, common_msgs::RegisterResponse { .. }))
+            assert!(matches!(response, common_msgs::RegisterResponse { .. }));
+            assert_eq!(response.expiry_delta, EXPIRY_DELTA);
         }
     }
 
@@ -783,27 +2405,88 @@ mod tests_public_api {
         let (_, user_pk) = get_random_keypair();
         let mut user_id_vec = UserId(user_pk).to_vec();
         user_id_vec.pop();
-        user_ids.push(user_id_vec);
+        user_ids.push((
+            user_id_vec,
+            "Wrong `user_id` field size. Expected 33, received 32",
+        ));
 
         // Wrong format (does not start with 2 nor 3)
         user_id_vec = UserId(user_pk).to_vec();
         user_id_vec[0] = 1;
-        user_ids.push(user_id_vec);
+        user_ids.push((
+            user_id_vec,
+            "Provided public key does not match expected format (33-byte compressed key)",
+        ));
 
-        for user_id in user_ids {
+        for (user_id, message) in user_ids {
             match internal_api
-                .register(Request::new(common_msgs::RegisterRequest { user_id }))
+                .register(Request::new(common_msgs::RegisterRequest {
+                    user_id,
+                    payment_preimage: Vec::new(),
+                }))
                 .await
             {
                 Err(status) => {
                     assert_eq!(status.code(), Code::InvalidArgument);
-                    assert_eq!(status.message(), "Provided public key does not match expected format (33-byte compressed key)")
+                    assert_eq!(status.message(), message)
                 }
                 _ => panic!("Test should have returned Err"),
             }
         }
     }
 
+    #[tokio::test]
+    async fn test_malformed_requests() {
+        // Requests may reach the public API straight through gRPC, so malformed ones must be rejected the same way
+        // the HTTP API would
+        let (internal_api, _s) = create_api().await;
+
+        let status = internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: None,
+                signature: "sig".to_owned(),
+                batch_receipt: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "missing field `appointment`");
+
+        let status = internal_api
+            .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
+                locator: vec![0; 3],
+                signature: "sig".to_owned(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(
+            status.message(),
+            "Wrong `locator` field size. Expected 16, received 3"
+        );
+
+        let status = internal_api
+            .get_batched_receipt(Request::new(common_msgs::GetBatchedReceiptRequest {
+                locator: Vec::new(),
+                signature: "sig".to_owned(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "`locator` field is empty");
+
+        let status = internal_api
+            .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
+                signature: String::new(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "`signature` field is empty");
+    }
+
     #[tokio::test]
     async fn test_register_max_slots() {
         let (internal_api, _s) = create_api_with_config(ApiConfig::new(u32::MAX, DURATION)).await;
@@ -815,13 +2498,17 @@ mod tests_public_api {
         internal_api
             .register(Request::new(common_msgs::RegisterRequest {
                 user_id: user_id.clone(),
+                payment_preimage: Vec::new(),
             }))
             .await
             .unwrap();
 
         // Trying to add more slots (re-register) must fail
         match internal_api
-            .register(Request::new(common_msgs::RegisterRequest { user_id }))
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id,
+                payment_preimage: Vec::new(),
+            }))
             .await
         {
             Err(status) => {
@@ -833,57 +2520,329 @@ mod tests_public_api {
     }
 
     #[tokio::test]
-    async fn test_register_service_unavailable() {
+    async fn test_register_max_users() {
         let (internal_api, _s) =
-            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
+            create_api_with_config(ApiConfig::new(SLOTS, DURATION).max_users(1)).await;
 
         let (_, user_pk) = get_random_keypair();
         let user_id = UserId(user_pk).to_vec();
 
+        // First registration should go trough, as well as renewals
+        for _ in 0..2 {
+            internal_api
+                .register(Request::new(common_msgs::RegisterRequest {
+                    user_id: user_id.clone(),
+                    payment_preimage: Vec::new(),
+                }))
+                .await
+                .unwrap();
+        }
+
+        // But new users are rejected
+        let (_, another_user_pk) = get_random_keypair();
         match internal_api
-            .register(Request::new(common_msgs::RegisterRequest { user_id }))
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: UserId(another_user_pk).to_vec(),
+                payment_preimage: Vec::new(),
+            }))
             .await
         {
             Err(status) => {
-                assert_eq!(status.code(), Code::Unavailable);
-                assert_eq!(status.message(), "Service currently unavailable")
+                assert_eq!(status.code(), Code::OutOfRange);
+                assert_eq!(
+                    status.message(),
+                    "The tower is not accepting new users at the moment"
+                )
             }
             _ => panic!("Test should have returned Err"),
         }
     }
 
     #[tokio::test]
-    async fn test_add_appointment() {
-        let (internal_api, _s) = create_api().await;
+    async fn test_register_payments_required() {
+        let pricing = SubscriptionPricing::new(2, 3, true);
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, DURATION).pricing(pricing)).await;
 
-        // User must be registered
-        let (user_sk, user_pk) = get_random_keypair();
-        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
 
-        let appointment = generate_dummy_appointment(None).inner;
-        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        // Registrations with no proof of payment, or with a preimage not matching any invoice, are rejected
+        for (payment_preimage, message) in [
+            (
+                Vec::new(),
+                "Payment required. Pay an invoice from get_registration_invoice and provide its preimage",
+            ),
+            (
+                cryptography::get_random_bytes(32),
+                "The payment preimage does not match any pending invoice of the user",
+            ),
+        ] {
+            match internal_api
+                .register(Request::new(common_msgs::RegisterRequest {
+                    user_id: user_id.to_vec(),
+                    payment_preimage,
+                }))
+                .await
+            {
+                Err(status) => {
+                    assert_eq!(status.code(), Code::FailedPrecondition);
+                    assert_eq!(status.message(), message)
+                }
+                _ => panic!("Test should have returned Err"),
+            }
+        }
+        assert!(internal_api.watcher.get_user_info(user_id).is_none());
 
-        let response = internal_api
-            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
-                appointment: Some(appointment.clone().into()),
-                signature: user_signature.clone(),
+        // Invoices are issued for the subscription price
+        let invoice = internal_api
+            .get_registration_invoice(Request::new(common_msgs::GetRegistrationInvoiceRequest {
+                user_id: user_id.to_vec(),
             }))
             .await
             .unwrap()
             .into_inner();
+        assert_eq!(invoice.amount_msat, pricing.price(SLOTS, DURATION).unwrap());
 
-        assert!(matches!(
-            response,
-            common_msgs::AddAppointmentResponse { .. }
-        ));
+        // The preimage is revealed to the payer once the invoice is settled, and is enough to register (only once)
+        let payment_preimage = internal_api
+            .watcher
+            .get_invoice_preimage(&sha256::Hash::from_slice(&invoice.payment_hash).unwrap())
+            .unwrap();
+        internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: user_id.to_vec(),
+                payment_preimage: payment_preimage.clone(),
+            }))
+            .await
+            .unwrap();
+        assert!(internal_api.watcher.get_user_info(user_id).is_some());
+
+        match internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: user_id.to_vec(),
+                payment_preimage,
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::FailedPrecondition),
+            _ => panic!("Test should have returned Err"),
+        }
     }
 
     #[tokio::test]
-    async fn test_add_appointment_non_registered() {
+    async fn test_get_registration_invoice_payments_not_required() {
         let (internal_api, _s) = create_api().await;
 
-        // User is not registered this time
-        let (user_sk, _) = get_random_keypair();
+        match internal_api
+            .get_registration_invoice(Request::new(common_msgs::GetRegistrationInvoiceRequest {
+                user_id: get_random_user_id().to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unimplemented);
+                assert_eq!(status.message(), "The tower does not require payments")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_registration_terms() {
+        // Prices are advertised even if payments are not required
+        for payments_required in [false, true] {
+            let pricing = SubscriptionPricing::new(2, 3, payments_required);
+            let (internal_api, _s) =
+                create_api_with_config(ApiConfig::new(SLOTS, DURATION).pricing(pricing)).await;
+
+            let response = internal_api
+                .get_registration_terms(Request::new(common_msgs::GetRegistrationTermsRequest {}))
+                .await
+                .unwrap()
+                .into_inner();
+
+            assert_eq!(
+                response,
+                common_msgs::GetRegistrationTermsResponse {
+                    subscription_slots: SLOTS,
+                    subscription_duration: DURATION,
+                    subscription_price_per_slot_msat: 2,
+                    subscription_price_per_block_msat: 3,
+                    subscription_price_msat: pricing.price(SLOTS, DURATION).unwrap(),
+                    payments_required,
+                    accepting_registrations: true,
+                    max_users: 0,
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_service_unavailable() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).bitcoind_unreachable()).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk).to_vec();
+
+        match internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id,
+                payment_preimage: Vec::new(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(status.message(), "Service currently unavailable")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_low_disk_space() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).low_disk_space()).await;
+
+        let (_, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk).to_vec();
+
+        match internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id,
+                payment_preimage: Vec::new(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(
+                    status.message(),
+                    "The tower is running low on disk space. Try again later"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment() {
+        let (internal_api, _s) = create_api().await;
+
+        // User must be registered
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        let response = internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+                batch_receipt: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(matches!(
+            response,
+            common_msgs::AddAppointmentResponse { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        // Valid appointments are accepted while the invalid ones are reported individually
+        let valid = generate_dummy_appointment(None).inner;
+        let invalid = generate_dummy_appointment(None).inner;
+        let (another_sk, _) = get_random_keypair();
+        let request = common_msgs::AddAppointmentsRequest {
+            user_id: UserId(user_pk).to_vec(),
+            appointments: vec![
+                common_msgs::AddAppointmentRequest {
+                    appointment: Some(valid.clone().into()),
+                    signature: cryptography::sign(&valid.to_vec(), &user_sk).unwrap(),
+                    batch_receipt: false,
+                },
+                common_msgs::AddAppointmentRequest {
+                    appointment: Some(invalid.clone().into()),
+                    signature: cryptography::sign(&invalid.to_vec(), &another_sk).unwrap(),
+                    batch_receipt: false,
+                },
+            ],
+        };
+
+        let response = internal_api
+            .add_appointments(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.available_slots, SLOTS - 1);
+        assert_eq!(response.results.len(), 2);
+        assert_eq!(response.results[0].locator, valid.locator.to_vec());
+        assert_eq!(response.results[0].error_code, 0);
+        assert!(!response.results[0].signature.is_empty());
+        assert_eq!(response.results[1].locator, invalid.locator.to_vec());
+        assert_eq!(
+            response.results[1].error_code,
+            errors::INVALID_SIGNATURE_OR_SUBSCRIPTION_ERROR as u32
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments_too_many() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        let appointments = (0..MAX_APPOINTMENTS_PER_BATCH + 1)
+            .map(|_| {
+                let appointment = generate_dummy_appointment(None).inner;
+                common_msgs::AddAppointmentRequest {
+                    signature: cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                    appointment: Some(appointment.into()),
+                    batch_receipt: false,
+                }
+            })
+            .collect();
+
+        match internal_api
+            .add_appointments(Request::new(common_msgs::AddAppointmentsRequest {
+                user_id: UserId(user_pk).to_vec(),
+                appointments,
+            }))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::InvalidArgument),
+            _ => panic!("Test should have returned Err"),
+        }
+        assert_eq!(
+            internal_api
+                .watcher
+                .get_user_info(UserId(user_pk))
+                .unwrap()
+                .available_slots,
+            SLOTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_non_registered() {
+        let (internal_api, _s) = create_api().await;
+
+        // User is not registered this time
+        let (user_sk, _) = get_random_keypair();
 
         let appointment = generate_dummy_appointment(None).inner;
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
@@ -892,6 +2851,7 @@ mod tests_public_api {
             .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature: user_signature.clone(),
+                batch_receipt: false,
             }))
             .await
         {
@@ -921,6 +2881,7 @@ mod tests_public_api {
             .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature: user_signature.clone(),
+                batch_receipt: false,
             }))
             .await
         {
@@ -950,6 +2911,7 @@ mod tests_public_api {
             .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature: user_signature.clone(),
+                batch_receipt: false,
             }))
             .await
         {
@@ -979,6 +2941,7 @@ mod tests_public_api {
             .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature: user_signature.clone(),
+                batch_receipt: false,
             }))
             .await
         {
@@ -1005,6 +2968,7 @@ mod tests_public_api {
             .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
                 appointment: Some(appointment.clone().into()),
                 signature: user_signature.clone(),
+                batch_receipt: false,
             }))
             .await
         {
@@ -1016,6 +2980,139 @@ mod tests_public_api {
         }
     }
 
+    #[tokio::test]
+    async fn test_add_appointment_low_disk_space() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(u32::MAX, DURATION).low_disk_space()).await;
+
+        // Registered users are rejected too
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+                batch_receipt: false,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(
+                    status.message(),
+                    "The tower is running low on disk space. Try again later"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+        assert_eq!(internal_api.watcher.get_appointments_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_register_syncing() {
+        // Users can register while syncing if the policy is accept
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::new(u32::MAX, DURATION).syncing(SyncPolicy::Accept, 100),
+        )
+        .await;
+        let (_, user_pk) = get_random_keypair();
+        assert!(internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: UserId(user_pk).to_vec(),
+                payment_preimage: Vec::new(),
+            }))
+            .await
+            .is_ok());
+
+        // Or if the tower is not too far behind
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::new(u32::MAX, DURATION).syncing(SyncPolicy::Reject(6), 6),
+        )
+        .await;
+        assert!(internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: UserId(user_pk).to_vec(),
+                payment_preimage: Vec::new(),
+            }))
+            .await
+            .is_ok());
+
+        // Otherwise they are rejected with a retriable error
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::new(u32::MAX, DURATION).syncing(SyncPolicy::Reject(6), 7),
+        )
+        .await;
+        match internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: UserId(user_pk).to_vec(),
+                payment_preimage: Vec::new(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert_eq!(
+                    status.message(),
+                    "Tower syncing (7 blocks behind). Retry later"
+                )
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_syncing() {
+        // Appointments accepted while syncing report how far behind the tower is
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::new(u32::MAX, DURATION).syncing(SyncPolicy::Accept, 100),
+        )
+        .await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        let response = internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.clone().into()),
+                signature: user_signature.clone(),
+                batch_receipt: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.blocks_behind, 100);
+
+        // If the policy is reject they are turned down instead
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::new(u32::MAX, DURATION).syncing(SyncPolicy::Reject(6), 100),
+        )
+        .await;
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        match internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.into()),
+                signature: user_signature,
+                batch_receipt: false,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unavailable);
+                assert!(status
+                    .message()
+                    .starts_with("Tower syncing (100 blocks behind)"));
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+        assert_eq!(internal_api.watcher.get_appointments_count(), 0);
+    }
+
     #[tokio::test]
     async fn test_get_appointment() {
         let (internal_api, _s) = create_api().await;
@@ -1029,7 +3126,7 @@ mod tests_public_api {
         let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
         internal_api
             .watcher
-            .add_appointment(appointment.clone(), user_signature)
+            .add_appointment(appointment.clone(), user_signature, false)
             .unwrap();
 
         // Get the appointment through the API
@@ -1038,6 +3135,7 @@ mod tests_public_api {
             .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
                 locator: appointment.locator.to_vec(),
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
             .unwrap()
@@ -1066,6 +3164,7 @@ mod tests_public_api {
             .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
                 locator: appointment.locator.to_vec(),
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1093,6 +3192,7 @@ mod tests_public_api {
             .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
                 locator: appointment.locator.to_vec(),
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1121,6 +3221,7 @@ mod tests_public_api {
             .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
                 locator: appointment.locator.to_vec(),
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1144,6 +3245,7 @@ mod tests_public_api {
             .get_appointment(Request::new(common_msgs::GetAppointmentRequest {
                 locator: appointment.locator.to_vec(),
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1168,6 +3270,7 @@ mod tests_public_api {
         let response = internal_api
             .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
             .unwrap()
@@ -1175,10 +3278,358 @@ mod tests_public_api {
 
         assert!(matches!(
             response,
-            common_msgs::GetSubscriptionInfoResponse { .. }
+            common_msgs::GetSubscriptionInfoResponse {
+                renewal_due: false,
+                reissued_receipt: None,
+                n_appointments: 0,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_key_rotation_overlap() {
+        let (previous_sk, previous_pk) = get_random_keypair();
+        let (internal_api, _s) = create_api_with_config(
+            ApiConfig::default()
+                .previous_key(PreviousKey::new(previous_sk, START_HEIGHT as u32 + 10)),
+        )
+        .await;
+
+        // Registration receipts are co-signed by the previous key
+        let (user_sk, user_pk) = get_random_keypair();
+        let response = internal_api
+            .register(Request::new(common_msgs::RegisterRequest {
+                user_id: UserId(user_pk).to_vec(),
+                payment_preimage: Vec::new(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let receipt = RegistrationReceipt::new(
+            UserId(user_pk),
+            response.available_slots,
+            response.subscription_start,
+            response.subscription_expiry,
+        );
+        assert!(cryptography::verify(
+            &receipt.to_vec(),
+            &response.previous_subscription_signature,
+            &previous_pk
+        ));
+
+        // And so are appointment receipts
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        let response = internal_api
+            .add_appointment(Request::new(common_msgs::AddAppointmentRequest {
+                appointment: Some(appointment.into()),
+                signature: user_signature.clone(),
+                batch_receipt: false,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let receipt = AppointmentReceipt::new(user_signature, response.start_block);
+        assert!(cryptography::verify(
+            &receipt.to_vec(),
+            &response.previous_signature,
+            &previous_pk
+        ));
+
+        // Re-issued subscription receipts are served alongside the subscription info
+        assert_eq!(internal_api.watcher.reattest_subscriptions(), 1);
+        let response = internal_api
+            .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
+                signature: cryptography::sign("get subscription info".as_bytes(), &user_sk)
+                    .unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let reissued = response.reissued_receipt.unwrap();
+        let receipt = RegistrationReceipt::with_signature(
+            UserId(user_pk),
+            reissued.available_slots,
+            reissued.subscription_start,
+            reissued.subscription_expiry,
+            reissued.subscription_signature,
+        );
+        assert!(receipt.verify(&internal_api.watcher.tower_id));
+        assert!(cryptography::verify(
+            &receipt.to_vec(),
+            &reissued.previous_signature,
+            &previous_pk
         ));
     }
 
+    #[tokio::test]
+    async fn test_get_subscription_info_with_challenge() {
+        let (internal_api, _s) = create_api().await;
+
+        // Challenges are only issued to registered users
+        let (user_sk, user_pk) = get_random_keypair();
+        match internal_api
+            .get_auth_challenge(Request::new(common_msgs::GetAuthChallengeRequest {
+                user_id: user_pk.serialize().to_vec(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(status.message(), "User not found. Have you registered?")
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        let challenge = internal_api
+            .get_auth_challenge(Request::new(common_msgs::GetAuthChallengeRequest {
+                user_id: user_pk.serialize().to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .challenge;
+
+        // The signature covers both the message and the challenge
+        let message = [b"get subscription info".as_slice(), &challenge].concat();
+        let request = common_msgs::GetSubscriptionInfoRequest {
+            signature: cryptography::sign(&message, &user_sk).unwrap(),
+            challenge,
+        };
+        assert!(internal_api
+            .get_subscription_info(Request::new(request.clone()))
+            .await
+            .is_ok());
+
+        // Replaying the same request fails
+        match internal_api
+            .get_subscription_info(Request::new(request))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_info_with_appointments() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let receipt = internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        // Add an appointment
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .watcher
+            .add_appointment(appointment.clone(), user_signature, false)
+            .unwrap();
+
+        // The response reflects the appointment count and the slots it used
+        let message = "get subscription info".to_string();
+        let response = internal_api
+            .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.n_appointments, 1);
+        assert_eq!(response.locators, vec![appointment.locator.to_vec()]);
+        assert_eq!(response.available_slots, receipt.available_slots() - 1);
+        assert_eq!(response.subscription_expiry, receipt.subscription_expiry());
+    }
+
+    #[tokio::test]
+    async fn test_get_subscription_info_renewal_due() {
+        // Subscriptions shorter than the renewal window are due for renewal straightaway
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, RENEWAL_WINDOW)).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        let message = "get subscription info".to_string();
+        let response = internal_api
+            .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(response.renewal_due);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_renewal_reminders() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, RENEWAL_WINDOW)).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let receipt = internal_api.watcher.register(user_id).unwrap();
+
+        // Since the subscription is already due for renewal, a reminder is sent as soon as the user subscribes
+        let message = "subscribe renewal reminders".to_string();
+        let mut stream = internal_api
+            .subscribe_renewal_reminders(Request::new(common_msgs::RenewalRemindersRequest {
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(
+            stream.next().await.unwrap().unwrap(),
+            common_msgs::RenewalReminder {
+                user_id: user_id.to_vec(),
+                subscription_expiry: receipt.subscription_expiry(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_renewal_reminders_with_challenge() {
+        let (internal_api, _s) =
+            create_api_with_config(ApiConfig::new(SLOTS, RENEWAL_WINDOW)).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        internal_api.watcher.register(user_id).unwrap();
+
+        let challenge = internal_api.watcher.get_auth_challenge(user_id).unwrap();
+        let message = [b"subscribe renewal reminders".as_slice(), &challenge].concat();
+        let request = common_msgs::RenewalRemindersRequest {
+            signature: cryptography::sign(&message, &user_sk).unwrap(),
+            challenge,
+        };
+        assert!(internal_api
+            .subscribe_renewal_reminders(Request::new(request.clone()))
+            .await
+            .is_ok());
+
+        // Challenges can only be used once
+        match internal_api
+            .subscribe_renewal_reminders(Request::new(request))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::Unauthenticated),
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_renewal_reminders_too_many_streams() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+
+        let message = "subscribe renewal reminders".to_string();
+        let request = common_msgs::RenewalRemindersRequest {
+            signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+            challenge: Vec::new(),
+        };
+
+        let mut streams = Vec::new();
+        for _ in 0..MAX_REMINDER_STREAMS_PER_USER {
+            streams.push(
+                internal_api
+                    .subscribe_renewal_reminders(Request::new(request.clone()))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        match internal_api
+            .subscribe_renewal_reminders(Request::new(request.clone()))
+            .await
+        {
+            Err(status) => assert_eq!(status.code(), Code::ResourceExhausted),
+            _ => panic!("Test should have returned Err"),
+        }
+
+        // Once a stream is closed, a new one can be opened
+        streams.pop();
+        assert!(internal_api
+            .subscribe_renewal_reminders(Request::new(request))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_renewal_reminders_closed_streams_are_dropped() {
+        let (internal_api, _s) = create_api().await;
+
+        let message = "subscribe renewal reminders".to_string();
+        let mut users = Vec::new();
+        for _ in 0..2 {
+            let (user_sk, user_pk) = get_random_keypair();
+            internal_api.watcher.register(UserId(user_pk)).unwrap();
+            users.push((UserId(user_pk), user_sk));
+        }
+
+        let (user_id, user_sk) = users[0];
+        let stream = internal_api
+            .subscribe_renewal_reminders(Request::new(common_msgs::RenewalRemindersRequest {
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        assert!(internal_api
+            .reminder_streams
+            .lock()
+            .unwrap()
+            .contains_key(&user_id));
+
+        // Once the stream is closed, the user is dropped the next time someone subscribes
+        drop(stream);
+        let (other_id, other_sk) = users[1];
+        let _other_stream = internal_api
+            .subscribe_renewal_reminders(Request::new(common_msgs::RenewalRemindersRequest {
+                signature: cryptography::sign(message.as_bytes(), &other_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let reminder_streams = internal_api.reminder_streams.lock().unwrap();
+        assert!(!reminder_streams.contains_key(&user_id));
+        assert!(reminder_streams.contains_key(&other_id));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_renewal_reminders_non_registered() {
+        let (internal_api, _s) = create_api().await;
+
+        // The user is not registered
+        let (user_sk, _) = get_random_keypair();
+
+        let message = "subscribe renewal reminders".to_string();
+        match internal_api
+            .subscribe_renewal_reminders(Request::new(common_msgs::RenewalRemindersRequest {
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(status.message(), "User not found. Have you registered?");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
     #[tokio::test]
     async fn test_get_subscription_info_non_registered() {
         let (internal_api, _s) = create_api_with_config(ApiConfig::new(SLOTS, 0)).await;
@@ -1191,6 +3642,7 @@ mod tests_public_api {
         match internal_api
             .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1215,6 +3667,7 @@ mod tests_public_api {
         match internal_api
             .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1236,6 +3689,7 @@ mod tests_public_api {
         match internal_api
             .get_subscription_info(Request::new(common_msgs::GetSubscriptionInfoRequest {
                 signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+                challenge: Vec::new(),
             }))
             .await
         {
@@ -1246,4 +3700,126 @@ mod tests_public_api {
             _ => panic!("Test should have returned Err"),
         }
     }
+
+    #[tokio::test]
+    async fn test_get_breach_log() {
+        let (internal_api, _s) = create_api_with_config(ApiConfig::default().breach_log()).await;
+
+        // Only blocks that are IRREVOCABLY_RESOLVED deep can be queried
+        let final_height = START_HEIGHT as u32 - IRREVOCABLY_RESOLVED;
+        let entries = vec![(Txid::hash(&[0]), final_height)];
+        internal_api
+            .watcher
+            .get_dbm()
+            .lock()
+            .unwrap()
+            .store_breach_log_entries(&entries);
+
+        let response = internal_api
+            .get_breach_log(Request::new(common_msgs::GetBreachLogRequest {
+                from_height: final_height,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let log = BreachLog::try_from(response).unwrap();
+        assert_eq!(
+            (log.from_height(), log.to_height()),
+            (final_height, final_height)
+        );
+        assert_eq!(log.entries(), &entries);
+        assert!(log.verify(&internal_api.watcher.tower_id));
+
+        // Heights that are not final yet cannot be queried
+        match internal_api
+            .get_breach_log(Request::new(common_msgs::GetBreachLogRequest {
+                from_height: final_height + 1,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::InvalidArgument);
+                assert_eq!(
+                    status.message(),
+                    format!(
+                        "from_height is ahead of the last final block ({})",
+                        final_height
+                    )
+                );
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_breach_log_disabled() {
+        let (internal_api, _s) = create_api().await;
+
+        match internal_api
+            .get_breach_log(Request::new(common_msgs::GetBreachLogRequest {
+                from_height: 0,
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unimplemented);
+                assert_eq!(status.message(), "The tower does not publish a breach log");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_batched_receipt_batching_disabled() {
+        let (internal_api, _s) = create_api().await;
+
+        // Register a user and add an appointment
+        let (user_sk, user_pk) = get_random_keypair();
+        internal_api.watcher.register(UserId(user_pk)).unwrap();
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_signature = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        internal_api
+            .watcher
+            .add_appointment(appointment.clone(), user_signature, false)
+            .unwrap();
+
+        // Receipts are signed individually by default, so there is no batched receipt to be found
+        let message = format!("get batched receipt {}", appointment.locator);
+        match internal_api
+            .get_batched_receipt(Request::new(common_msgs::GetBatchedReceiptRequest {
+                locator: appointment.locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::NotFound);
+                assert_eq!(status.message(), "The tower does not batch receipts");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_batched_receipt_non_registered() {
+        let (internal_api, _s) = create_api().await;
+
+        let (user_sk, _) = get_random_keypair();
+        let appointment = generate_dummy_appointment(None).inner;
+        let message = format!("get batched receipt {}", appointment.locator);
+        match internal_api
+            .get_batched_receipt(Request::new(common_msgs::GetBatchedReceiptRequest {
+                locator: appointment.locator.to_vec(),
+                signature: cryptography::sign(message.as_bytes(), &user_sk).unwrap(),
+            }))
+            .await
+        {
+            Err(status) => {
+                assert_eq!(status.code(), Code::Unauthenticated);
+                assert_eq!(status.message(), "User cannot be authenticated");
+            }
+            _ => panic!("Test should have returned Err"),
+        }
+    }
 }
@@ -1,4 +1,6 @@
 pub mod http;
 pub mod internal;
+pub mod operator_auth;
 pub mod serde;
 pub mod tor;
+pub(crate) mod validation;
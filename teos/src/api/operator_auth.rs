@@ -0,0 +1,164 @@
+//! Logic related to the operator authentication, an additional factor required by the destructive private RPCs.
+//!
+//! If the tower is configured with an operator key, destructive requests (e.g. `stop` or `import_user`) must be signed
+//! with the corresponding secret key on top of being sent over the (mTLS) RPC channel, so a leaked client certificate
+//! is not enough to wipe or bring down the tower. The signature and the time it was produced are sent as request metadata.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use prost::Message;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request, Status};
+
+use teos_common::cryptography;
+
+/// Metadata key used to send the operator signature.
+pub const SIGNATURE_KEY: &str = "x-operator-signature";
+
+/// Metadata key used to send the time (in seconds since epoch) the operator signature was produced.
+pub const TIMESTAMP_KEY: &str = "x-operator-timestamp";
+
+/// Maximum difference (in seconds) between the tower clock and the signature timestamp.
+pub const MAX_CLOCK_DRIFT: u64 = 60;
+
+/// Gets the current time in seconds since epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Builds the message signed by the operator: the method name, the timestamp and the encoded request payload.
+///
+/// Covering the method and the payload prevents a signature from being used for a different action or target.
+pub fn signing_message(method: &str, timestamp: u64, payload: &[u8]) -> Vec<u8> {
+    [format!("{} {} ", method, timestamp).as_bytes(), payload].concat()
+}
+
+/// Builds a gRPC request for the given method signed by the operator.
+pub fn sign_request<T: Message>(method: &str, message: T, operator_sk: &SecretKey) -> Request<T> {
+    let timestamp = now();
+    let signature = cryptography::sign(
+        &signing_message(method, timestamp, &message.encode_to_vec()),
+        operator_sk,
+    )
+    .unwrap();
+
+    let mut request = Request::new(message);
+    let metadata = request.metadata_mut();
+    metadata.insert(SIGNATURE_KEY, MetadataValue::from_str(&signature).unwrap());
+    metadata.insert(TIMESTAMP_KEY, MetadataValue::from(timestamp));
+    request
+}
+
+/// Verifies the operator signature of a request for the given method.
+///
+/// Returns the signature alongside its timestamp so the caller can make sure signatures are not reused.
+pub(crate) fn verify_request<T: Message>(
+    request: &Request<T>,
+    method: &str,
+    operator_key: &PublicKey,
+) -> Result<(String, u64), Status> {
+    let metadata = request.metadata();
+    let signature = metadata
+        .get(SIGNATURE_KEY)
+        .and_then(|s| s.to_str().ok())
+        .ok_or_else(|| {
+            Status::new(
+                Code::Unauthenticated,
+                "This action requires an operator signature",
+            )
+        })?;
+    let timestamp = metadata
+        .get(TIMESTAMP_KEY)
+        .and_then(|t| t.to_str().ok())
+        .and_then(|t| t.parse::<u64>().ok())
+        .ok_or_else(|| {
+            Status::new(
+                Code::Unauthenticated,
+                "The operator signature timestamp is missing or malformed",
+            )
+        })?;
+
+    let current_time = now();
+    if current_time.max(timestamp) - current_time.min(timestamp) > MAX_CLOCK_DRIFT {
+        return Err(Status::new(
+            Code::Unauthenticated,
+            "The operator signature has expired",
+        ));
+    }
+
+    let message = signing_message(method, timestamp, &request.get_ref().encode_to_vec());
+    match cryptography::recover_pk(&message, signature) {
+        Ok(pk) if &pk == operator_key => Ok((signature.to_owned(), timestamp)),
+        _ => Err(Status::new(
+            Code::Unauthenticated,
+            "Invalid operator signature",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teos_common::cryptography::get_random_keypair;
+
+    use crate::protos as msgs;
+
+    #[test]
+    fn test_verify_request() {
+        let (operator_sk, operator_pk) = get_random_keypair();
+        let message = msgs::AbandonTrackerRequest {
+            uuid: vec![1; 20],
+            reason: "reason".to_owned(),
+        };
+
+        // A properly signed request is accepted
+        let request = sign_request("abandon_tracker", message.clone(), &operator_sk);
+        assert!(verify_request(&request, "abandon_tracker", &operator_pk).is_ok());
+
+        // But it is not valid for a different method or payload
+        assert!(verify_request(&request, "stop", &operator_pk).is_err());
+        let mut tampered = sign_request("abandon_tracker", message.clone(), &operator_sk);
+        tampered.get_mut().reason = "another reason".to_owned();
+        assert!(verify_request(&tampered, "abandon_tracker", &operator_pk).is_err());
+
+        // Nor if it is signed by someone else
+        let (other_sk, _) = get_random_keypair();
+        let request = sign_request("abandon_tracker", message.clone(), &other_sk);
+        assert!(verify_request(&request, "abandon_tracker", &operator_pk).is_err());
+
+        // Unsigned requests are rejected
+        let request = Request::new(message.clone());
+        assert_eq!(
+            verify_request(&request, "abandon_tracker", &operator_pk)
+                .unwrap_err()
+                .code(),
+            Code::Unauthenticated
+        );
+
+        // So are outdated signatures
+        let timestamp = now() - MAX_CLOCK_DRIFT - 1;
+        let signature = cryptography::sign(
+            &signing_message("abandon_tracker", timestamp, &message.encode_to_vec()),
+            &operator_sk,
+        )
+        .unwrap();
+        let mut request = Request::new(message);
+        request
+            .metadata_mut()
+            .insert(SIGNATURE_KEY, MetadataValue::from_str(&signature).unwrap());
+        request
+            .metadata_mut()
+            .insert(TIMESTAMP_KEY, MetadataValue::from(timestamp));
+        assert_eq!(
+            verify_request(&request, "abandon_tracker", &operator_pk)
+                .unwrap_err()
+                .message(),
+            "The operator signature has expired"
+        );
+    }
+}
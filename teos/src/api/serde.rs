@@ -1,8 +1,22 @@
+use std::net::SocketAddr;
+
 use crate::protos as msgs;
 
 use teos_common::net::AddressType;
 
 impl msgs::NetworkAddress {
+    pub fn from_socket_addr(addr: SocketAddr) -> Self {
+        let address_type = match addr {
+            SocketAddr::V4(_) => AddressType::IpV4,
+            SocketAddr::V6(_) => AddressType::IpV6,
+        };
+        Self {
+            address_type: address_type as i32,
+            address: addr.ip().to_string(),
+            port: addr.port() as u32,
+        }
+    }
+
     pub fn from_ipv4(address: String, port: u16) -> Self {
         Self {
             address_type: AddressType::IpV4 as i32,
@@ -0,0 +1,186 @@
+//! Field checks of the public requests, shared by the HTTP and gRPC public APIs so both reject malformed
+//! requests in the same way.
+
+use std::fmt;
+
+use teos_common::appointment::LOCATOR_LEN;
+use teos_common::protos as common_msgs;
+use teos_common::USER_ID_LEN;
+
+/// Reasons why a public request is malformed.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum FieldError {
+    Missing(&'static str),
+    Empty(&'static str),
+    WrongLength {
+        field: &'static str,
+        size: usize,
+        expected: usize,
+    },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FieldError::Missing(field) => write!(f, "missing field `{}`", field),
+            FieldError::Empty(field) => write!(f, "`{}` field is empty", field),
+            FieldError::WrongLength {
+                field,
+                size,
+                expected,
+            } => write!(
+                f,
+                "Wrong `{}` field size. Expected {}, received {}",
+                field, expected, size
+            ),
+        }
+    }
+}
+
+/// Checks a field is neither empty nor of the wrong length.
+fn check_len(field: &'static str, value: &[u8], expected: usize) -> Result<(), FieldError> {
+    if value.is_empty() {
+        return Err(FieldError::Empty(field));
+    }
+    if value.len() != expected {
+        return Err(FieldError::WrongLength {
+            field,
+            size: value.len(),
+            expected,
+        });
+    }
+    Ok(())
+}
+
+/// Checks a field is not empty.
+fn check_not_empty(field: &'static str, value: &[u8]) -> Result<(), FieldError> {
+    if value.is_empty() {
+        return Err(FieldError::Empty(field));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_register(req: &common_msgs::RegisterRequest) -> Result<(), FieldError> {
+    check_len("user_id", &req.user_id, USER_ID_LEN)
+}
+
+pub(crate) fn check_add_appointment(
+    req: &common_msgs::AddAppointmentRequest,
+) -> Result<(), FieldError> {
+    match &req.appointment {
+        Some(a) => check_len("locator", &a.locator, LOCATOR_LEN)?,
+        None => return Err(FieldError::Missing("appointment")),
+    }
+    check_not_empty("signature", req.signature.as_bytes())
+}
+
+pub(crate) fn check_add_appointments(
+    req: &common_msgs::AddAppointmentsRequest,
+) -> Result<(), FieldError> {
+    check_len("user_id", &req.user_id, USER_ID_LEN)?;
+    if req.appointments.is_empty() {
+        return Err(FieldError::Empty("appointments"));
+    }
+    req.appointments.iter().try_for_each(check_add_appointment)
+}
+
+pub(crate) fn check_get_appointment(
+    req: &common_msgs::GetAppointmentRequest,
+) -> Result<(), FieldError> {
+    check_len("locator", &req.locator, LOCATOR_LEN)?;
+    check_not_empty("signature", req.signature.as_bytes())
+}
+
+pub(crate) fn check_get_subscription_info(
+    req: &common_msgs::GetSubscriptionInfoRequest,
+) -> Result<(), FieldError> {
+    check_not_empty("signature", req.signature.as_bytes())
+}
+
+pub(crate) fn check_renewal_reminders(
+    req: &common_msgs::RenewalRemindersRequest,
+) -> Result<(), FieldError> {
+    check_not_empty("signature", req.signature.as_bytes())
+}
+
+pub(crate) fn check_get_registration_invoice(
+    req: &common_msgs::GetRegistrationInvoiceRequest,
+) -> Result<(), FieldError> {
+    check_len("user_id", &req.user_id, USER_ID_LEN)
+}
+
+pub(crate) fn check_get_auth_challenge(
+    req: &common_msgs::GetAuthChallengeRequest,
+) -> Result<(), FieldError> {
+    check_len("user_id", &req.user_id, USER_ID_LEN)
+}
+
+pub(crate) fn check_get_batched_receipt(
+    req: &common_msgs::GetBatchedReceiptRequest,
+) -> Result<(), FieldError> {
+    check_len("locator", &req.locator, LOCATOR_LEN)?;
+    check_not_empty("signature", req.signature.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_add_appointment() {
+        let mut req = common_msgs::AddAppointmentRequest {
+            appointment: Some(common_msgs::Appointment {
+                locator: vec![0; LOCATOR_LEN],
+                encrypted_blob: vec![1],
+                to_self_delay: 42,
+            }),
+            signature: "sig".to_owned(),
+            batch_receipt: false,
+        };
+        assert_eq!(check_add_appointment(&req), Ok(()));
+
+        req.signature = String::new();
+        assert_eq!(
+            check_add_appointment(&req),
+            Err(FieldError::Empty("signature"))
+        );
+
+        req.appointment.as_mut().unwrap().locator = vec![0; LOCATOR_LEN - 1];
+        assert_eq!(
+            check_add_appointment(&req),
+            Err(FieldError::WrongLength {
+                field: "locator",
+                size: LOCATOR_LEN - 1,
+                expected: LOCATOR_LEN
+            })
+        );
+
+        req.appointment = None;
+        assert_eq!(
+            check_add_appointment(&req),
+            Err(FieldError::Missing("appointment"))
+        );
+    }
+
+    #[test]
+    fn test_field_error_display() {
+        // These messages are part of the public API, so they must not change
+        assert_eq!(
+            FieldError::Missing("appointment").to_string(),
+            "missing field `appointment`"
+        );
+        assert_eq!(
+            FieldError::Empty("locator").to_string(),
+            "`locator` field is empty"
+        );
+        assert_eq!(
+            FieldError::WrongLength {
+                field: "user_id",
+                size: 32,
+                expected: USER_ID_LEN
+            }
+            .to_string(),
+            "Wrong `user_id` field size. Expected 33, received 32"
+        );
+    }
+}
@@ -20,24 +20,27 @@ use bitcoin::hashes::hex::ToHex;
 use bitcoin::{Block, Transaction};
 use lightning::util::ser::Writeable;
 use lightning_block_sync::http::{HttpEndpoint, JsonResponse};
+use lightning_block_sync::rest::RestClient;
 use lightning_block_sync::rpc::RpcClient;
 use lightning_block_sync::{AsyncBlockSourceResult, BlockHeaderData, BlockSource};
 
 /// A simple implementation of a bitcoind client (`bitcoin-cli`) with the minimal functionality required by the tower.
-pub struct BitcoindClient<'a> {
+pub struct BitcoindClient {
     /// The underlying RPC client.
     bitcoind_rpc_client: Arc<Mutex<RpcClient>>,
+    /// The underlying REST client, if blocks are fetched through `bitcoind`'s REST interface.
+    bitcoind_rest_client: Option<Arc<Mutex<RestClient>>>,
     /// The hostname to connect to.
-    host: &'a str,
+    host: String,
     /// The port to connect to.
     port: u16,
     /// The RPC user `bitcoind` is configured with.
-    rpc_user: &'a str,
+    rpc_user: String,
     /// The RPC password for the given user.
-    rpc_password: &'a str,
+    rpc_password: String,
 }
 
-impl BlockSource for &BitcoindClient<'_> {
+impl BlockSource for BitcoindClient {
     /// Gets a block header given its hash.
     fn get_header<'a>(
         &'a self,
@@ -51,8 +54,22 @@ impl BlockSource for &BitcoindClient<'_> {
     }
 
     /// Gets a block given its hash.
+    ///
+    /// The block is fetched through the REST interface if enabled, falling back to RPC if the request fails.
     fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, Block> {
         Box::pin(async move {
+            if let Some(rest_client) = &self.bitcoind_rest_client {
+                let rest = rest_client.lock().await;
+                match rest.get_block(header_hash).await {
+                    Ok(block) => return Ok(block),
+                    Err(e) => log::warn!(
+                        "Cannot fetch block {} over REST, falling back to RPC. Error: {:?}",
+                        header_hash,
+                        e
+                    ),
+                }
+            }
+
             let rpc = self.bitcoind_rpc_client.lock().await;
             rpc.get_block(header_hash).await
         })
@@ -67,27 +84,52 @@ impl BlockSource for &BitcoindClient<'_> {
     }
 }
 
-// TODO: This is not being used atm since we're using bitcoincore-rpc.
-// Not deleting it since wd should need it once both get merged.
-impl<'a> BitcoindClient<'a> {
+impl BitcoindClient {
     /// Creates a new [BitcoindClient] instance.
+    ///
+    /// If `use_rest` is set, blocks are fetched through `bitcoind`'s REST interface (binary format), which has a
+    /// lower overhead than RPC. RPC is used instead if the REST interface is not reachable (e.g. if `bitcoind` is not
+    /// running with `-rest`).
     pub async fn new(
-        host: &'a str,
+        host: &str,
         port: u16,
-        rpc_user: &'a str,
-        rpc_password: &'a str,
-        teos_network: &'a str,
-    ) -> std::io::Result<BitcoindClient<'a>> {
+        rpc_user: &str,
+        rpc_password: &str,
+        teos_network: &str,
+        use_rest: bool,
+    ) -> std::io::Result<BitcoindClient> {
         let http_endpoint = HttpEndpoint::for_host(host.to_owned()).with_port(port);
         let rpc_credentials = base64::encode(&format!("{}:{}", rpc_user, rpc_password));
         let bitcoind_rpc_client = RpcClient::new(&rpc_credentials, http_endpoint)?;
 
+        let bitcoind_rest_client = if use_rest {
+            let rest_endpoint = HttpEndpoint::for_host(host.to_owned())
+                .with_port(port)
+                .with_path("/rest".to_owned());
+            let rest_client = RestClient::new(rest_endpoint)?;
+
+            // Check the REST interface is enabled before relying on it.
+            match rest_client.get_best_block().await {
+                Ok(_) => Some(Arc::new(Mutex::new(rest_client))),
+                Err(e) => {
+                    log::warn!(
+                        "bitcoind REST interface is not reachable (is bitcoind running with -rest?). Using RPC to fetch blocks. Error: {:?}",
+                        e
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         let client = Self {
             bitcoind_rpc_client: Arc::new(Mutex::new(bitcoind_rpc_client)),
-            host,
+            bitcoind_rest_client,
+            host: host.to_owned(),
             port,
-            rpc_user,
-            rpc_password,
+            rpc_user: rpc_user.to_owned(),
+            rpc_password: rpc_password.to_owned(),
         };
 
         // Test that bitcoind is reachable.
@@ -161,3 +203,95 @@ impl<'a> BitcoindClient<'a> {
         Ok(btc_network.0)
     }
 }
+
+#[cfg(test)]
+impl BitcoindClient {
+    /// Creates a new [BitcoindClient] instance without checking `bitcoind` is reachable.
+    pub(crate) fn new_unchecked(host: &str, port: u16) -> std::io::Result<BitcoindClient> {
+        let http_endpoint = HttpEndpoint::for_host(host.to_owned()).with_port(port);
+        Ok(Self {
+            bitcoind_rpc_client: Arc::new(Mutex::new(RpcClient::new("", http_endpoint)?)),
+            bitcoind_rest_client: None,
+            host: host.to_owned(),
+            port,
+            rpc_user: String::new(),
+            rpc_password: String::new(),
+        })
+    }
+
+    /// Fetches blocks through `bitcoind`'s REST interface, without checking it is enabled.
+    pub(crate) fn with_rest_unchecked(mut self) -> std::io::Result<BitcoindClient> {
+        let rest_endpoint = HttpEndpoint::for_host(self.host.clone())
+            .with_port(self.port)
+            .with_path("/rest".to_owned());
+        self.bitcoind_rest_client = Some(Arc::new(Mutex::new(RestClient::new(rest_endpoint)?)));
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bitcoin::blockdata::constants::genesis_block;
+    use bitcoin::consensus;
+    use bitcoin::network::constants::Network;
+    use httpmock::prelude::*;
+
+    fn get_rest_client(server: &MockServer) -> BitcoindClient {
+        BitcoindClient::new_unchecked(&server.host(), server.port())
+            .unwrap()
+            .with_rest_unchecked()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_block_rest() {
+        let server = MockServer::start();
+        let block = genesis_block(Network::Regtest);
+
+        let rest_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/rest/block/{}.bin", block.block_hash().to_hex()));
+            then.status(200).body(consensus::serialize(&block));
+        });
+        let rpc_mock = server.mock(|when, then| {
+            when.method(POST).path("/");
+            then.status(500);
+        });
+
+        // The block is fetched over REST, so RPC is never reached
+        let client = get_rest_client(&server);
+        assert_eq!(client.get_block(&block.block_hash()).await.unwrap(), block);
+        rest_mock.assert();
+        rpc_mock.assert_hits(0);
+    }
+
+    #[tokio::test]
+    async fn test_get_block_rest_fallback() {
+        let server = MockServer::start();
+        let block = genesis_block(Network::Regtest);
+
+        let rest_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/rest/block/{}.bin", block.block_hash().to_hex()));
+            then.status(404);
+        });
+        let rpc_mock = server.mock(|when, then| {
+            when.method(POST).path("/").body_contains("getblock");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(serde_json::json!({
+                    "result": consensus::encode::serialize_hex(&block),
+                    "error": null,
+                    "id": "0"
+                }));
+        });
+
+        // If the REST request fails, the block is fetched over RPC instead
+        let client = get_rest_client(&server);
+        assert_eq!(client.get_block(&block.block_hash()).await.unwrap(), block);
+        rest_mock.assert();
+        rpc_mock.assert();
+    }
+}
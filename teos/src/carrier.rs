@@ -1,22 +1,149 @@
 //! Logic related to the Carrier, the component in charge or sending/requesting transaction data from/to `bitcoind`.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
+use crate::chain_source::{ChainSource, ChainSourceError};
 use crate::responder::ConfirmationStatus;
+use crate::telemetry;
 use crate::{errors, rpc_errors};
 
-use bitcoin::{Transaction, Txid};
-use bitcoincore_rpc::{
-    jsonrpc::error::Error::Rpc as RpcError, jsonrpc::error::Error::Transport as TransportError,
-    Client as BitcoindClient, Error::JsonRpc as JsonRpcError, RpcApi,
-};
+use bitcoin::{consensus, Transaction, Txid};
+
+/// Name used to refer to `bitcoind` when recording which endpoints accepted a transaction.
+pub(crate) const BITCOIND_ENDPOINT: &str = "bitcoind";
+
+/// Timeout for requests sent to broadcast-only endpoints.
+const BROADCAST_ENDPOINT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Confirmation target (in blocks) used when estimating feerates.
+const FEE_ESTIMATION_TARGET: u16 = 6;
+
+/// Reject reasons (as returned by `sendrawtransaction`) meaning the transaction is already in the mempool.
+const ALREADY_IN_MEMPOOL: [&str; 2] = ["txn-already-in-mempool", "txn-already-known"];
+
+/// Reject reasons meaning the transaction inputs are missing or already spent.
+const INPUTS_SPENT: [&str; 2] = ["missingorspent", "missing inputs"];
+
+/// Reject reasons meaning the transaction conflicts with an unconfirmed transaction in the mempool.
+const MEMPOOL_CONFLICT: [&str; 2] = ["txn-mempool-conflict", "bad-txns-spends-conflicting-tx"];
+
+/// Reject reasons meaning the transaction does not pay enough fees.
+const LOW_FEE: [&str; 3] = [
+    "min relay fee not met",
+    "mempool min fee not met",
+    "insufficient fee",
+];
+
+/// Reject reasons meaning the transaction does not comply with the node standardness policy.
+const NON_STANDARD: [&str; 12] = [
+    "nonstandard",
+    "non-mandatory-script-verify-flag",
+    "non-final",
+    "non-bip68-final",
+    "scriptpubkey",
+    "scriptsig-size",
+    "scriptsig-not-pushonly",
+    "bare-multisig",
+    "multi-op-return",
+    "dust",
+    "tx-size",
+    "version",
+];
+
+/// Checks whether a `sendrawtransaction` error message means the transaction was already in the mempool.
+fn is_already_in_mempool(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ALREADY_IN_MEMPOOL.iter().any(|r| message.contains(r))
+}
+
+/// Reason why a transaction was rejected by `bitcoind` when broadcast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The transaction inputs are missing or have already been spent.
+    InputsSpent,
+    /// The transaction inputs are being spent by an unconfirmed transaction in the mempool. The conflicting transaction
+    /// may still be evicted or never confirm, so the inputs are not spent (yet).
+    MempoolConflict,
+    /// The transaction does not comply with the node standardness policy.
+    NonStandard,
+    /// The transaction does not pay enough fees to make it to the mempool.
+    LowFee,
+    /// Any other rejection. Holds the RPC error code.
+    Other(i32),
+}
+
+impl RejectionReason {
+    /// Classifies a `sendrawtransaction` error based on its code and message.
+    pub(crate) fn from_rpc_error(code: i32, message: &str) -> Self {
+        let message = message.to_lowercase();
+        let matches = |reasons: &[&str]| reasons.iter().any(|r| message.contains(r));
+
+        if matches(&INPUTS_SPENT) {
+            RejectionReason::InputsSpent
+        } else if matches(&MEMPOOL_CONFLICT) {
+            RejectionReason::MempoolConflict
+        } else if matches(&LOW_FEE) {
+            RejectionReason::LowFee
+        } else if matches(&NON_STANDARD) {
+            RejectionReason::NonStandard
+        } else {
+            RejectionReason::Other(code)
+        }
+    }
+
+    /// Whether the rejection is final. Non-final rejections may go away on their own (e.g. once the mempool feerates
+    /// drop or the conflicting transaction is evicted), so the transaction is worth retrying.
+    pub fn is_final(&self) -> bool {
+        !matches!(
+            self,
+            RejectionReason::LowFee | RejectionReason::MempoolConflict
+        )
+    }
+}
+
+/// Snapshot of the `bitcoind` mempool feerates. All feerates are in sat/kvB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MempoolFeerates {
+    /// Minimum feerate for a transaction to be accepted to the mempool.
+    pub min_fee: u64,
+    /// Feerate estimated for a transaction to confirm within [FEE_ESTIMATION_TARGET] blocks, if available.
+    pub estimated_fee: Option<u64>,
+}
+
+/// Sends a raw transaction to an Esplora endpoint (`POST /tx`).
+///
+/// Returns an error describing the rejection (or the connection issue) if the endpoint did not accept the transaction.
+fn broadcast_to_esplora(url: &str, rawtx: String) -> Result<(), String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(BROADCAST_ENDPOINT_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .post(format!("{}/tx", url))
+        .body(rawtx)
+        .send()
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {}",
+            response.status(),
+            response.text().unwrap_or_default()
+        ))
+    }
+}
 
 /// Component in charge of the interaction with Bitcoind by sending / querying transactions via RPC.
-#[derive(Debug)]
+///
+/// The node is reached through a [ChainSource], so it can be replaced by any other chain backend.
 pub struct Carrier {
-    /// The underlying bitcoin client used by the [Carrier].
-    bitcoin_cli: Arc<BitcoindClient>,
+    /// The underlying chain source used by the [Carrier].
+    chain_source: Arc<dyn ChainSource>,
     /// A flag that indicates wether bitcoind is reachable or not.
     bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
     /// A map of receipts already issued by the [Carrier].
@@ -24,20 +151,45 @@ pub struct Carrier {
     issued_receipts: HashMap<Txid, ConfirmationStatus>,
     /// The last known block height.
     block_height: u32,
+    /// Whether the [Carrier] is running in dry-run mode. If so, transactions are logged instead of sent to the network.
+    dry_run: bool,
+    /// Esplora endpoints used to broadcast transactions alongside `bitcoind`. These are broadcast-only, so they are never
+    /// queried for data.
+    esplora_endpoints: Vec<String>,
+    /// A map of the endpoints that accepted each of the transactions sent by the [Carrier].
+    accepted_by: HashMap<Txid, HashSet<String>>,
+}
+
+impl std::fmt::Debug for Carrier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Carrier")
+            .field("bitcoind_reachable", &self.bitcoind_reachable)
+            .field("issued_receipts", &self.issued_receipts)
+            .field("block_height", &self.block_height)
+            .field("dry_run", &self.dry_run)
+            .field("esplora_endpoints", &self.esplora_endpoints)
+            .field("accepted_by", &self.accepted_by)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Carrier {
     /// Creates a new [Carrier] instance.
     pub fn new(
-        bitcoin_cli: Arc<BitcoindClient>,
+        chain_source: Arc<dyn ChainSource>,
         bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
         last_known_block_height: u32,
+        dry_run: bool,
+        esplora_endpoints: Vec<String>,
     ) -> Self {
         Carrier {
-            bitcoin_cli,
+            chain_source,
             bitcoind_reachable,
             issued_receipts: HashMap::new(),
             block_height: last_known_block_height,
+            dry_run,
+            esplora_endpoints,
+            accepted_by: HashMap::new(),
         }
     }
 
@@ -52,6 +204,15 @@ impl Carrier {
         if !self.issued_receipts.is_empty() {
             self.issued_receipts = HashMap::new()
         }
+        if !self.accepted_by.is_empty() {
+            self.accepted_by = HashMap::new()
+        }
+    }
+
+    /// Gets the endpoints that accepted a given transaction, as long as it is still cached (check
+    /// [clear_receipts](Self::clear_receipts)). `bitcoind` is referred to as [BITCOIND_ENDPOINT].
+    pub(crate) fn get_accepted_by(&self, txid: &Txid) -> Option<&HashSet<String>> {
+        self.accepted_by.get(txid)
     }
 
     /// Updates the last known block height by the [Carrier].
@@ -76,32 +237,119 @@ impl Carrier {
 
     /// Sends a [Transaction] to the Bitcoin network.
     ///
-    /// Returns a [ConfirmationStatus] indicating whether the transaction was accepted by the node or not.
+    /// The transaction is sent to `bitcoind` and to all the configured Esplora endpoints simultaneously, recording which
+    /// of them accepted it (check [get_accepted_by](Self::get_accepted_by)). Returns a [ConfirmationStatus] indicating whether the transaction was accepted by `bitcoind`
+    /// or not, given it is the only backend the tower can track the transaction with.
+    ///
+    /// In dry-run mode, the transaction is logged instead of sent and [Simulated](ConfirmationStatus::Simulated) is returned.
     pub(crate) fn send_transaction(&mut self, tx: &Transaction) -> ConfirmationStatus {
-        self.hang_until_bitcoind_reachable();
+        let _stage = telemetry::stage_span("chain_backend").entered();
 
         if let Some(receipt) = self.issued_receipts.get(&tx.txid()) {
             log::info!("Transaction already sent: {}", tx.txid());
             return *receipt;
         }
 
+        if self.dry_run {
+            log::info!(
+                "Dry-run mode. Transaction would have been pushed to the network: {} (rawtx: {})",
+                tx.txid(),
+                consensus::encode::serialize_hex(tx)
+            );
+            let receipt = ConfirmationStatus::Simulated(self.block_height);
+            self.issued_receipts.insert(tx.txid(), receipt);
+            return receipt;
+        }
+
         log::info!("Pushing transaction to the network: {}", tx.txid());
-        let receipt = match self.bitcoin_cli.send_raw_transaction(tx) {
+        let handles = self.broadcast_to_endpoints(tx);
+        let receipt = self.send_to_bitcoind(tx);
+
+        let mut accepted_by = HashSet::new();
+        if let ConfirmationStatus::InMempoolSince(_) = receipt {
+            accepted_by.insert(BITCOIND_ENDPOINT.to_owned());
+        }
+        for (url, handle) in handles {
+            match handle.join() {
+                Ok(Ok(())) => {
+                    accepted_by.insert(url);
+                }
+                Ok(Err(e)) => log::warn!(
+                    "Transaction couldn't be broadcast through {}: {} ({})",
+                    url,
+                    tx.txid(),
+                    e
+                ),
+                Err(_) => log::error!("Broadcast through {} panicked: {}", url, tx.txid()),
+            }
+        }
+
+        if !self.esplora_endpoints.is_empty() {
+            log::info!(
+                "Transaction {} accepted by {}/{} endpoints: {:?}",
+                tx.txid(),
+                accepted_by.len(),
+                self.esplora_endpoints.len() + 1,
+                accepted_by
+            );
+        }
+        self.accepted_by.insert(tx.txid(), accepted_by);
+        self.issued_receipts.insert(tx.txid(), receipt);
+
+        receipt
+    }
+
+    /// Sends a [Transaction] to all the Esplora endpoints, each one from its own thread.
+    ///
+    /// Returns the handles of the threads, so their results can be collected once `bitcoind` has been reached.
+    fn broadcast_to_endpoints(
+        &self,
+        tx: &Transaction,
+    ) -> Vec<(String, JoinHandle<Result<(), String>>)> {
+        let rawtx = consensus::encode::serialize_hex(tx);
+        self.esplora_endpoints
+            .iter()
+            .map(|url| {
+                let (url, rawtx) = (url.clone(), rawtx.clone());
+                (
+                    url.clone(),
+                    thread::spawn(move || broadcast_to_esplora(&url, rawtx)),
+                )
+            })
+            .collect()
+    }
+
+    /// Sends a [Transaction] to `bitcoind`, waiting for the node to be reachable (and retrying if it goes down meanwhile).
+    ///
+    /// Returns a [ConfirmationStatus] indicating whether the transaction was accepted by the node or not.
+    fn send_to_bitcoind(&self, tx: &Transaction) -> ConfirmationStatus {
+        self.hang_until_bitcoind_reachable();
+
+        match self.chain_source.send_raw_transaction(tx) {
             Ok(_) => {
                 // Here the transaction could, potentially, have been in mempool before the current height.
                 // This shouldn't really matter though.
                 log::info!("Transaction successfully delivered: {}", tx.txid());
                 ConfirmationStatus::InMempoolSince(self.block_height)
             }
-            Err(JsonRpcError(RpcError(rpcerr))) => match rpcerr.code {
+            Err(ChainSourceError::Rpc { code, message }) => match code {
                 // Since we're pushing a raw transaction to the network we can face several rejections
-                rpc_errors::RPC_VERIFY_REJECTED => {
-                    log::error!("Transaction couldn't be broadcast. {:?}", rpcerr);
-                    ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_REJECTED)
+                rpc_errors::RPC_VERIFY_REJECTED | rpc_errors::RPC_VERIFY_ERROR
+                    if is_already_in_mempool(&message) =>
+                {
+                    // Older versions of bitcoind reject transactions that are already in the mempool instead of returning their txid.
+                    log::info!("Transaction already in mempool: {}", tx.txid());
+                    ConfirmationStatus::InMempoolSince(self.block_height)
                 }
-                rpc_errors::RPC_VERIFY_ERROR => {
-                    log::error!("Transaction couldn't be broadcast. {:?}", rpcerr);
-                    ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_ERROR)
+                rpc_errors::RPC_VERIFY_REJECTED | rpc_errors::RPC_VERIFY_ERROR => {
+                    let reason = RejectionReason::from_rpc_error(code, &message);
+                    log::error!(
+                        "Transaction couldn't be broadcast (reason: {:?}). {} (code: {})",
+                        reason,
+                        message,
+                        code
+                    );
+                    ConfirmationStatus::Rejected(reason)
                 }
                 rpc_errors::RPC_VERIFY_ALREADY_IN_CHAIN => {
                     log::info!(
@@ -118,76 +366,112 @@ impl Carrier {
                     // Adding this here just for completeness. We should never end up here. The Carrier only sends txs handed by the Responder,
                     // who receives them from the Watcher, who checks that the tx can be properly deserialized.
                     log::info!("Transaction cannot be deserialized: {}", tx.txid());
-                    ConfirmationStatus::Rejected(rpc_errors::RPC_DESERIALIZATION_ERROR)
+                    ConfirmationStatus::Rejected(RejectionReason::Other(
+                        rpc_errors::RPC_DESERIALIZATION_ERROR,
+                    ))
                 }
                 _ => {
                     // If something else happens (unlikely but possible) log it so we can treat it in future releases.
                     log::error!(
-                        "Unexpected rpc error when calling sendrawtransaction: {:?}",
-                        rpcerr
+                        "Unexpected rpc error when calling sendrawtransaction: {} (code: {})",
+                        message,
+                        code
                     );
-                    ConfirmationStatus::Rejected(errors::UNKNOWN_JSON_RPC_EXCEPTION)
+                    ConfirmationStatus::Rejected(RejectionReason::Other(
+                        errors::UNKNOWN_JSON_RPC_EXCEPTION,
+                    ))
                 }
             },
-            Err(JsonRpcError(TransportError(_))) => {
+            Err(ChainSourceError::Unreachable) => {
                 // Connection refused, bitcoind is down.
                 log::error!("Connection lost with bitcoind, retrying request when possible");
                 self.flag_bitcoind_unreachable();
-                self.send_transaction(tx)
+                self.send_to_bitcoind(tx)
             }
-            Err(e) => {
+            Err(ChainSourceError::Other(e)) => {
                 // TODO: This may need finer catching.
-                log::error!("Unexpected error when calling sendrawtransaction: {:?}", e);
-                ConfirmationStatus::Rejected(errors::UNKNOWN_JSON_RPC_EXCEPTION)
+                log::error!("Unexpected error when calling sendrawtransaction: {}", e);
+                ConfirmationStatus::Rejected(RejectionReason::Other(
+                    errors::UNKNOWN_JSON_RPC_EXCEPTION,
+                ))
             }
-        };
+        }
+    }
 
-        self.issued_receipts.insert(tx.txid(), receipt);
+    /// Gets the current mempool feerates (`getmempoolinfo` and `estimatesmartfee`).
+    ///
+    /// This is only used for telemetry, so [None] is returned straightaway (instead of waiting for `bitcoind` to be
+    /// reachable) if the mempool min fee cannot be fetched.
+    pub(crate) fn get_mempool_feerates(&self) -> Option<MempoolFeerates> {
+        let _stage = telemetry::stage_span("chain_backend").entered();
+        let min_fee = match self.chain_source.get_mempool_min_fee() {
+            Ok(fee) => fee,
+            Err(e) => {
+                log::warn!("Cannot get the mempool min fee from bitcoind: {}", e);
+                return None;
+            }
+        };
 
-        receipt
+        // Fee estimation may not be available (e.g. if bitcoind has not seen enough blocks yet)
+        let estimated_fee = self
+            .chain_source
+            .estimate_fee(FEE_ESTIMATION_TARGET)
+            .ok()
+            .flatten();
+
+        Some(MempoolFeerates {
+            min_fee: min_fee.as_sat(),
+            estimated_fee: estimated_fee.map(|fee| fee.as_sat()),
+        })
     }
 
     /// Checks whether a given transaction can be found in the mempool.
-    ///
-    /// This uses `getrawtransaction` under the hood and, therefore, its behavior depends on whether `txindex` is enabled in bitcoind.
-    /// If `txindex` is disabled (default), it will only pull data from the mempool. Otherwise, it will also pull data from the transaction
-    /// index. Hence, we need to check whether the returned struct has any of the block related datum set (such as `blockhash`).
     pub(crate) fn in_mempool(&self, txid: &Txid) -> bool {
+        let _stage = telemetry::stage_span("chain_backend").entered();
         self.hang_until_bitcoind_reachable();
 
-        match self.bitcoin_cli.get_raw_transaction_info(txid, None) {
-            Ok(tx) => tx.blockhash.is_none(),
-            Err(JsonRpcError(RpcError(rpcerr))) => match rpcerr.code {
-                rpc_errors::RPC_INVALID_ADDRESS_OR_KEY => {
-                    log::info!("Transaction not found in mempool: {}", txid);
-                    false
-                }
-                e => {
-                    // DISCUSS: This could result in a silent error with unknown consequences
-                    log::error!(
-                        "Unexpected error code when calling getrawtransaction: {}",
-                        e
-                    );
-                    false
-                }
-            },
-            Err(JsonRpcError(TransportError(_))) => {
+        match self.chain_source.in_mempool(txid) {
+            Ok(in_mempool) => in_mempool,
+            Err(ChainSourceError::Unreachable) => {
                 // Connection refused, bitcoind is down.
                 log::error!("Connection lost with bitcoind, retrying request when possible");
                 self.flag_bitcoind_unreachable();
                 self.in_mempool(txid)
             }
-            // TODO: This may need finer catching.
             Err(e) => {
                 // DISCUSS: This could result in a silent error with unknown consequences
-                log::error!(
-                    "Unexpected JSONRPCError when calling getrawtransaction: {}",
-                    e
-                );
+                log::error!("Unexpected error when calling getrawtransaction: {}", e);
                 false
             }
         }
     }
+
+    /// Checks whether any of the inputs of a given transaction has been spent by a confirmed transaction.
+    ///
+    /// Inputs that cannot be checked are considered not spent, so transactions are only given up on when there is
+    /// proof they can never confirm.
+    pub(crate) fn inputs_spent_on_chain(&self, tx: &Transaction) -> bool {
+        let _stage = telemetry::stage_span("chain_backend").entered();
+        self.hang_until_bitcoind_reachable();
+
+        for input in tx.input.iter() {
+            match self.chain_source.is_spent_on_chain(&input.previous_output) {
+                Ok(true) => return true,
+                Ok(false) => (),
+                Err(ChainSourceError::Unreachable) => {
+                    // Connection refused, bitcoind is down.
+                    log::error!("Connection lost with bitcoind, retrying request when possible");
+                    self.flag_bitcoind_unreachable();
+                    return self.inputs_spent_on_chain(tx);
+                }
+                Err(e) => {
+                    log::error!("Unexpected error when calling gettxout: {}", e);
+                }
+            }
+        }
+
+        false
+    }
 }
 
 #[cfg(test)]
@@ -195,12 +479,14 @@ mod tests {
     use super::*;
     use std::thread;
 
-    use crate::test_utils::{get_random_tx, start_server, BitcoindMock, MockOptions, START_HEIGHT};
+    use crate::test_utils::{
+        create_chain_source, get_random_tx, start_server, BitcoindMock, MockOptions, ESTIMATED_FEE,
+        MEMPOOL_MIN_FEE, START_HEIGHT,
+    };
     use teos_common::test_utils::{TXID_HEX, TX_HEX};
 
-    use bitcoin::consensus;
     use bitcoin::hashes::hex::FromHex;
-    use bitcoincore_rpc::Auth;
+    use httpmock::prelude::*;
 
     impl Carrier {
         // Helper function to access issued_receipts in tests
@@ -218,10 +504,16 @@ mod tests {
     fn test_clear_receipts() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::default());
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
 
         // Lets add some dummy data into the cache
         for i in 0..10 {
@@ -231,21 +523,32 @@ mod tests {
             );
         }
 
+        carrier
+            .accepted_by
+            .insert(get_random_tx().txid(), HashSet::new());
+
         // Check it empties on request
         assert!(!carrier.issued_receipts.is_empty());
         carrier.clear_receipts();
         assert!(carrier.issued_receipts.is_empty());
+        assert!(carrier.accepted_by.is_empty());
     }
 
     #[test]
     fn test_send_transaction_ok() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::default());
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
@@ -255,15 +558,141 @@ mod tests {
         assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), &r);
     }
 
+    #[test]
+    fn test_send_transaction_multiple_endpoints() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // One of the endpoints accepts the transaction while the other rejects it
+        let accepting_server = MockServer::start();
+        let accepting_mock = accepting_server.mock(|when, then| {
+            when.method(POST).path("/tx").body(TX_HEX);
+            then.status(200).body(TXID_HEX);
+        });
+        let rejecting_server = MockServer::start();
+        let rejecting_mock = rejecting_server.mock(|when, then| {
+            when.method(POST).path("/tx");
+            then.status(400).body("sendrawtransaction RPC error");
+        });
+
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            vec![accepting_server.base_url(), rejecting_server.base_url()],
+        );
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let r = carrier.send_transaction(&tx);
+
+        // The receipt only depends on bitcoind
+        assert_eq!(r, ConfirmationStatus::InMempoolSince(start_height));
+        accepting_mock.assert();
+        rejecting_mock.assert();
+        assert_eq!(
+            carrier.get_accepted_by(&tx.txid()),
+            Some(
+                &vec![BITCOIND_ENDPOINT.to_owned(), accepting_server.base_url()]
+                    .into_iter()
+                    .collect::<HashSet<_>>()
+            )
+        );
+
+        // Sending the transaction again does not hit the endpoints
+        carrier.send_transaction(&tx);
+        accepting_mock.assert_hits(1);
+        rejecting_mock.assert_hits(1);
+    }
+
+    #[test]
+    fn test_get_mempool_feerates() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        assert_eq!(
+            carrier.get_mempool_feerates(),
+            Some(MempoolFeerates {
+                min_fee: MEMPOOL_MIN_FEE,
+                estimated_fee: Some(ESTIMATED_FEE)
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_mempool_feerates_error() {
+        let bitcoind_mock =
+            BitcoindMock::new(MockOptions::with_error(rpc_errors::RPC_MISC_ERROR as i64));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        // Feerates are only used for telemetry, so errors do not block the Carrier
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        assert_eq!(carrier.get_mempool_feerates(), None);
+    }
+
+    #[test]
+    fn test_send_transaction_dry_run() {
+        // The mock would reject any transaction, but it should not be reached in dry-run mode
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            true,
+            Vec::new(),
+        );
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let r = carrier.send_transaction(&tx);
+
+        assert_eq!(r, ConfirmationStatus::Simulated(start_height));
+
+        // Check the receipt is on the cache
+        assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), &r);
+    }
+
     #[test]
     fn test_send_transaction_ok_already_in_mempool() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
@@ -279,17 +708,23 @@ mod tests {
             rpc_errors::RPC_VERIFY_REJECTED as i64,
         ));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
         assert_eq!(
             r,
-            ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_REJECTED)
+            ConfirmationStatus::Rejected(RejectionReason::Other(rpc_errors::RPC_VERIFY_REJECTED))
         );
 
         // Check the receipt is on the cache
@@ -301,34 +736,149 @@ mod tests {
         let bitcoind_mock =
             BitcoindMock::new(MockOptions::with_error(rpc_errors::RPC_VERIFY_ERROR as i64));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
         assert_eq!(
             r,
-            ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_ERROR)
+            ConfirmationStatus::Rejected(RejectionReason::Other(rpc_errors::RPC_VERIFY_ERROR))
         );
 
         // Check the receipt is on the cache
         assert_eq!(carrier.issued_receipts.get(&tx.txid()).unwrap(), &r);
     }
 
+    #[test]
+    fn test_send_transaction_already_in_mempool() {
+        // Transactions already in mempool are reported as accepted
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error_message(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+            "txn-already-in-mempool",
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let r = carrier.send_transaction(&tx);
+
+        assert_eq!(r, ConfirmationStatus::InMempoolSince(start_height));
+    }
+
+    #[test]
+    fn test_send_transaction_inputs_spent() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error_message(
+            rpc_errors::RPC_VERIFY_ERROR as i64,
+            "bad-txns-inputs-missingorspent",
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let r = carrier.send_transaction(&tx);
+
+        assert_eq!(
+            r,
+            ConfirmationStatus::Rejected(RejectionReason::InputsSpent)
+        );
+    }
+
+    #[test]
+    fn test_send_transaction_mempool_conflict() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error_message(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+            "txn-mempool-conflict",
+        ));
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        let r = carrier.send_transaction(&tx);
+
+        // A conflicting transaction in mempool does not mean the inputs are spent, so the rejection is not final
+        assert_eq!(
+            r,
+            ConfirmationStatus::Rejected(RejectionReason::MempoolConflict)
+        );
+        assert!(!RejectionReason::MempoolConflict.is_final());
+        assert!(RejectionReason::InputsSpent.is_final());
+    }
+
+    #[test]
+    fn test_rejection_reason_from_rpc_error() {
+        let rejected = rpc_errors::RPC_VERIFY_REJECTED;
+        for (message, reason) in [
+            ("bad-txns-inputs-missingorspent", RejectionReason::InputsSpent),
+            ("Missing inputs", RejectionReason::InputsSpent),
+            ("txn-mempool-conflict", RejectionReason::MempoolConflict),
+            ("bad-txns-spends-conflicting-tx", RejectionReason::MempoolConflict),
+            ("min relay fee not met, 0 < 110", RejectionReason::LowFee),
+            ("mempool min fee not met, 100 < 2000", RejectionReason::LowFee),
+            ("insufficient fee, rejecting replacement", RejectionReason::LowFee),
+            ("dust", RejectionReason::NonStandard),
+            ("scriptpubkey", RejectionReason::NonStandard),
+            ("non-mandatory-script-verify-flag (Signature must be zero for failed CHECK(MULTI)SIG operation)", RejectionReason::NonStandard),
+            ("mandatory-script-verify-flag-failed (Script failed an OP_EQUALVERIFY operation)", RejectionReason::Other(rejected)),
+            ("Server error", RejectionReason::Other(rejected)),
+        ] {
+            assert_eq!(RejectionReason::from_rpc_error(rejected, message), reason);
+        }
+    }
+
     #[test]
     fn test_send_transaction_verify_already_in_chain() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::with_error(
             rpc_errors::RPC_VERIFY_ALREADY_IN_CHAIN as i64,
         ));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
@@ -343,17 +893,25 @@ mod tests {
         let bitcoind_mock =
             BitcoindMock::new(MockOptions::with_error(rpc_errors::RPC_MISC_ERROR as i64));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let r = carrier.send_transaction(&tx);
 
         assert_eq!(
             r,
-            ConfirmationStatus::Rejected(errors::UNKNOWN_JSON_RPC_EXCEPTION)
+            ConfirmationStatus::Rejected(RejectionReason::Other(
+                errors::UNKNOWN_JSON_RPC_EXCEPTION
+            ))
         );
 
         // Check the receipt is on the cache
@@ -365,9 +923,15 @@ mod tests {
         // Try to connect to an offline bitcoind.
         let bitcoind_mock = BitcoindMock::new(MockOptions::default());
         let bitcoind_reachable = Arc::new((Mutex::new(false), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
-        let mut carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+        let mut carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable.clone(),
+            start_height,
+            false,
+            Vec::new(),
+        );
 
         let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
         let delay = std::time::Duration::new(3, 0);
@@ -393,11 +957,17 @@ mod tests {
     fn test_in_mempool() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::in_mempool());
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let txid = Txid::from_hex(TXID_HEX).unwrap();
         assert!(carrier.in_mempool(&txid));
     }
@@ -406,11 +976,17 @@ mod tests {
     fn test_not_in_mempool() {
         let bitcoind_mock = BitcoindMock::new(MockOptions::default());
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let txid = Txid::from_hex(TXID_HEX).unwrap();
         assert!(!carrier.in_mempool(&txid));
     }
@@ -421,11 +997,17 @@ mod tests {
             rpc_errors::RPC_INVALID_ADDRESS_OR_KEY as i64,
         ));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let txid = Txid::from_hex(TXID_HEX).unwrap();
         assert!(!carrier.in_mempool(&txid));
     }
@@ -435,11 +1017,17 @@ mod tests {
         let bitcoind_mock =
             BitcoindMock::new(MockOptions::with_error(rpc_errors::RPC_MISC_ERROR as i64));
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
         start_server(bitcoind_mock.server);
 
-        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, start_height);
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
         let txid = Txid::from_hex(TXID_HEX).unwrap();
         assert!(!carrier.in_mempool(&txid));
     }
@@ -449,9 +1037,15 @@ mod tests {
         // Try to connect to an offline bitcoind.
         let bitcoind_mock = BitcoindMock::new(MockOptions::default());
         let bitcoind_reachable = Arc::new((Mutex::new(false), Condvar::new()));
-        let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+        let chain_source = create_chain_source(bitcoind_mock.url());
         let start_height = START_HEIGHT as u32;
-        let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable.clone(), start_height);
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable.clone(),
+            start_height,
+            false,
+            Vec::new(),
+        );
 
         let txid = Txid::from_hex(TXID_HEX).unwrap();
         let delay = std::time::Duration::new(3, 0);
@@ -472,4 +1066,40 @@ mod tests {
             delay.as_secs()
         );
     }
+
+    #[test]
+    fn test_inputs_spent_on_chain() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default().spent_on_chain());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        assert!(carrier.inputs_spent_on_chain(&get_random_tx()));
+    }
+
+    #[test]
+    fn test_inputs_not_spent_on_chain() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        let start_height = START_HEIGHT as u32;
+        start_server(bitcoind_mock.server);
+
+        let carrier = Carrier::new(
+            chain_source,
+            bitcoind_reachable,
+            start_height,
+            false,
+            Vec::new(),
+        );
+        assert!(!carrier.inputs_spent_on_chain(&get_random_tx()));
+    }
 }
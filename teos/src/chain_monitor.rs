@@ -11,8 +11,59 @@ use lightning::chain;
 use lightning_block_sync::poll::{ChainTip, Poll, ValidatedBlockHeader};
 use lightning_block_sync::{BlockSourceErrorKind, Cache, SpvClient};
 
+use crate::config::Config;
 use crate::dbm::DBM;
 
+/// Average time between two blocks in the Bitcoin network.
+const EXPECTED_BLOCK_INTERVAL: time::Duration = time::Duration::from_secs(600);
+
+/// Time without new blocks after which the chain is considered idle and [PollingStrategy::Adaptive] backs off.
+const IDLE_INTERVAL: time::Duration = time::Duration::from_secs(3 * 600);
+
+/// How often the [ChainMonitor] polls `bitcoind` for new tips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollingStrategy {
+    /// Polls at a constant interval.
+    Fixed(time::Duration),
+    /// Polls every `max` right after a new block is found and while the chain is idle, and every `min` in between,
+    /// that is, once the next block is expected (i.e. half the [EXPECTED_BLOCK_INTERVAL] has passed since the last one).
+    Adaptive {
+        min: time::Duration,
+        max: time::Duration,
+    },
+}
+
+impl PollingStrategy {
+    /// Creates a new [PollingStrategy] from the polling options of the given [Config].
+    pub fn from_config(conf: &Config) -> Self {
+        let max = time::Duration::from_secs(conf.polling_delta as u64);
+        if conf.adaptive_polling {
+            PollingStrategy::Adaptive {
+                min: time::Duration::from_secs(conf.min_polling_delta as u64),
+                max,
+            }
+        } else {
+            PollingStrategy::Fixed(max)
+        }
+    }
+
+    /// Computes the time to wait until the next poll given how long ago the last block was found.
+    pub fn next_delta(&self, since_last_block: time::Duration) -> time::Duration {
+        match *self {
+            PollingStrategy::Fixed(delta) => delta,
+            PollingStrategy::Adaptive { min, max } => {
+                if since_last_block < EXPECTED_BLOCK_INTERVAL / 2
+                    || since_last_block >= IDLE_INTERVAL
+                {
+                    max
+                } else {
+                    min
+                }
+            }
+        }
+    }
+}
+
 /// Component in charge of monitoring the chain for new blocks.
 ///
 /// Takes care of polling `bitcoind` for new tips and hand it to subscribers.
@@ -30,8 +81,10 @@ where
     last_known_block_header: ValidatedBlockHeader,
     /// A [DBM] (database manager) instance. Used to persist block data into disk.
     dbm: Arc<Mutex<DBM>>,
-    /// The time between polls.
-    polling_delta: time::Duration,
+    /// The strategy used to decide the time between polls.
+    polling: PollingStrategy,
+    /// When the last new tip was found (or when the [ChainMonitor] was created, if none has been found yet).
+    last_block_at: time::Instant,
     /// A signal from the main thread indicating the tower is shuting down.
     shutdown_signal: Listener,
     /// A flag that indicates wether bitcoind is reachable or not.
@@ -50,7 +103,7 @@ where
         spv_client: SpvClient<'a, P, C, L>,
         last_known_block_header: ValidatedBlockHeader,
         dbm: Arc<Mutex<DBM>>,
-        polling_delta_sec: u16,
+        polling: PollingStrategy,
         shutdown_signal: Listener,
         bitcoind_reachable: Arc<(Mutex<bool>, Condvar)>,
     ) -> ChainMonitor<'a, P, C, L> {
@@ -58,7 +111,8 @@ where
             spv_client,
             last_known_block_header,
             dbm,
-            polling_delta: time::Duration::from_secs(polling_delta_sec as u64),
+            polling,
+            last_block_at: time::Instant::now(),
             shutdown_signal,
             bitcoind_reachable,
         }
@@ -75,6 +129,7 @@ where
                     ChainTip::Better(new_best) => {
                         log::debug!("Updating best tip: {}", new_best.header.block_hash());
                         self.last_known_block_header = new_best;
+                        self.last_block_at = time::Instant::now();
                         self.dbm
                             .lock()
                             .unwrap()
@@ -111,12 +166,13 @@ where
         };
     }
 
-    /// Monitors `bitcoind` polling the best chain tip every [polling_delta](Self::polling_delta).
+    /// Monitors `bitcoind` polling the best chain tip as often as its [PollingStrategy] dictates.
     pub async fn monitor_chain(&mut self) {
         loop {
             self.poll_best_tip().await;
-            // Sleep for self.polling_delta seconds or shutdown if the signal is received.
-            if timeout(self.polling_delta, self.shutdown_signal.clone())
+            // Sleep until the next poll is due or shutdown if the signal is received.
+            let polling_delta = self.polling.next_delta(self.last_block_at.elapsed());
+            if timeout(polling_delta, self.shutdown_signal.clone())
                 .await
                 .is_ok()
             {
@@ -174,6 +230,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_polling_strategy_fixed() {
+        let delta = time::Duration::from_secs(60);
+        let polling = PollingStrategy::Fixed(delta);
+
+        for since_last_block in [0, 300, 600, 1800, 3600] {
+            assert_eq!(
+                polling.next_delta(time::Duration::from_secs(since_last_block)),
+                delta
+            );
+        }
+    }
+
+    #[test]
+    fn test_polling_strategy_adaptive() {
+        let min = time::Duration::from_secs(5);
+        let max = time::Duration::from_secs(60);
+        let polling = PollingStrategy::Adaptive { min, max };
+
+        // Right after a block is found the next one is not expected yet, so the poller backs off
+        assert_eq!(polling.next_delta(time::Duration::from_secs(0)), max);
+        assert_eq!(
+            polling.next_delta(EXPECTED_BLOCK_INTERVAL / 2 - time::Duration::from_secs(1)),
+            max
+        );
+
+        // Once the next block is expected the poller speeds up
+        assert_eq!(polling.next_delta(EXPECTED_BLOCK_INTERVAL / 2), min);
+        assert_eq!(polling.next_delta(EXPECTED_BLOCK_INTERVAL), min);
+        assert_eq!(
+            polling.next_delta(IDLE_INTERVAL - time::Duration::from_secs(1)),
+            min
+        );
+
+        // If no block is found for a long while the chain is considered idle and the poller backs off again
+        assert_eq!(polling.next_delta(IDLE_INTERVAL), max);
+        assert_eq!(polling.next_delta(IDLE_INTERVAL * 10), max);
+    }
+
+    #[test]
+    fn test_polling_strategy_from_config() {
+        let mut conf = Config::default();
+        assert_eq!(
+            PollingStrategy::from_config(&conf),
+            PollingStrategy::Fixed(time::Duration::from_secs(conf.polling_delta as u64))
+        );
+
+        conf.adaptive_polling = true;
+        assert_eq!(
+            PollingStrategy::from_config(&conf),
+            PollingStrategy::Adaptive {
+                min: time::Duration::from_secs(conf.min_polling_delta as u64),
+                max: time::Duration::from_secs(conf.polling_delta as u64)
+            }
+        );
+    }
+
     #[tokio::test]
     async fn test_poll_best_tip_common() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -188,11 +301,20 @@ mod tests {
         let spv_client = SpvClient::new(tip, poller, cache, &listener);
         let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
 
-        let mut cm =
-            ChainMonitor::new(spv_client, tip, dbm, 1, shutdown_signal, bitcoind_reachable).await;
+        let mut cm = ChainMonitor::new(
+            spv_client,
+            tip,
+            dbm,
+            PollingStrategy::Fixed(time::Duration::from_secs(1)),
+            shutdown_signal,
+            bitcoind_reachable,
+        )
+        .await;
 
         // If there's no new block nothing gets connected nor disconnected
+        let last_block_at = cm.last_block_at;
         cm.poll_best_tip().await;
+        assert_eq!(cm.last_block_at, last_block_at);
         assert!(listener.connected_blocks.borrow().is_empty());
         assert!(listener.disconnected_blocks.borrow().is_empty());
     }
@@ -216,14 +338,17 @@ mod tests {
             spv_client,
             old_tip,
             dbm,
-            1,
+            PollingStrategy::Fixed(time::Duration::from_secs(1)),
             shutdown_signal,
             bitcoind_reachable,
         )
         .await;
 
         // If a new (best) block gets mined, it should be connected
+        let last_block_at = cm.last_block_at;
+        thread::sleep(time::Duration::from_millis(1));
         cm.poll_best_tip().await;
+        assert!(cm.last_block_at > last_block_at);
         assert_eq!(cm.last_known_block_header, new_tip);
         assert_eq!(
             cm.dbm.lock().unwrap().load_last_known_block().unwrap(),
@@ -255,7 +380,7 @@ mod tests {
             spv_client,
             best_tip,
             dbm,
-            1,
+            PollingStrategy::Fixed(time::Duration::from_secs(1)),
             shutdown_signal,
             bitcoind_reachable,
         )
@@ -297,7 +422,7 @@ mod tests {
             spv_client,
             old_best,
             dbm,
-            1,
+            PollingStrategy::Fixed(time::Duration::from_secs(1)),
             shutdown_signal,
             bitcoind_reachable,
         )
@@ -336,7 +461,7 @@ mod tests {
             spv_client,
             tip,
             dbm,
-            1,
+            PollingStrategy::Fixed(time::Duration::from_secs(1)),
             shutdown_signal,
             bitcoind_reachable.clone(),
         )
@@ -0,0 +1,408 @@
+//! Logic related to the ChainSource, the interface through which the tower gets chain data and talks to the network.
+//!
+//! The [ChainMonitor](crate::chain_monitor::ChainMonitor) (blocks), the [Carrier](crate::carrier::Carrier)
+//! (transactions and fees) and the [SyncMonitor](crate::sync_monitor::SyncMonitor) (sync state) are built on top of a
+//! [ChainSource]. `teosd` uses [BitcoindChainSource], but projects embedding the tower (check [tower](crate::tower))
+//! can plug in their node's existing chain backend instead of running a second connection to `bitcoind`.
+
+use std::sync::Arc;
+
+use bitcoin::hash_types::{BlockHash, Txid};
+use bitcoin::{Amount, Block, OutPoint, Transaction};
+use bitcoincore_rpc::{
+    jsonrpc::error::Error::Rpc as RpcError, jsonrpc::error::Error::Transport as TransportError,
+    Client, Error::JsonRpc as JsonRpcError, RpcApi,
+};
+use lightning_block_sync::{AsyncBlockSourceResult, BlockHeaderData, BlockSource};
+
+use crate::bitcoin_cli::BitcoindClient;
+use crate::rpc_errors;
+
+/// Errors that may be returned by a [ChainSource] when sending or querying transaction data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainSourceError {
+    /// The chain backend cannot be reached. The [Carrier](crate::carrier::Carrier) waits for it to be back and
+    /// retries the request.
+    Unreachable,
+    /// The request was rejected by the chain backend. Error codes follow `bitcoind`'s (check [rpc_errors]), so
+    /// rejections can be classified regardless of the backend.
+    Rpc { code: i32, message: String },
+    /// Any other error.
+    Other(String),
+}
+
+impl std::fmt::Display for ChainSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainSourceError::Unreachable => write!(f, "chain backend unreachable"),
+            ChainSourceError::Rpc { code, message } => write!(f, "{} (code: {})", message, code),
+            ChainSourceError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ChainSourceError {}
+
+impl From<bitcoincore_rpc::Error> for ChainSourceError {
+    fn from(e: bitcoincore_rpc::Error) -> Self {
+        match e {
+            JsonRpcError(RpcError(rpcerr)) => ChainSourceError::Rpc {
+                code: rpcerr.code,
+                message: rpcerr.message,
+            },
+            // Connection refused, bitcoind is down.
+            JsonRpcError(TransportError(_)) => ChainSourceError::Unreachable,
+            e => ChainSourceError::Other(e.to_string()),
+        }
+    }
+}
+
+/// A source of chain data for the tower.
+///
+/// Blocks are fetched through the [BlockSource] methods, which are polled by the
+/// [ChainMonitor](crate::chain_monitor::ChainMonitor) to subscribe to new tips (check [BlockFeed]). Backends that
+/// push blocks instead can feed them straight to the [Tower](crate::tower::Tower) through its
+/// [listener](crate::tower::Tower::listener).
+///
+/// Transaction related methods are blocking, given they are called by the [Carrier](crate::carrier::Carrier) from
+/// the (synchronous) chain listener.
+pub trait ChainSource: BlockSource {
+    /// Sends a transaction to the network. Returns its id if it has been accepted.
+    ///
+    /// Rejections are expected to mirror `sendrawtransaction`'s, e.g. transactions that are already confirmed are
+    /// rejected with [RPC_VERIFY_ALREADY_IN_CHAIN](rpc_errors::RPC_VERIFY_ALREADY_IN_CHAIN).
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ChainSourceError>;
+
+    /// Checks whether a given transaction is in the mempool. Transactions not found, or already confirmed, are not.
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ChainSourceError>;
+
+    /// Checks whether a given output has been spent by a confirmed transaction. Outputs that are unspent, or only
+    /// spent by transactions in the mempool, are not.
+    fn is_spent_on_chain(&self, outpoint: &OutPoint) -> Result<bool, ChainSourceError>;
+
+    /// Gets the minimum feerate (per kvB) for a transaction to be accepted to the mempool.
+    fn get_mempool_min_fee(&self) -> Result<Amount, ChainSourceError>;
+
+    /// Estimates the feerate (per kvB) for a transaction to confirm within `target` blocks.
+    ///
+    /// Returns [None] if there is not enough data to estimate it.
+    fn estimate_fee(&self, target: u16) -> Result<Option<Amount>, ChainSourceError>;
+
+    /// Gets the height of the backend's chain tip alongside the height of the best header it knows about.
+    ///
+    /// Both differ while the backend is syncing (e.g. during `bitcoind`'s initial block download).
+    fn get_sync_state(&self) -> Result<(u32, u32), ChainSourceError>;
+}
+
+/// Block feed of a shared [ChainSource].
+///
+/// Allows polling a [ChainSource] (through a [ChainPoller](lightning_block_sync::poll::ChainPoller)) while it is also
+/// being used by the [Carrier](crate::carrier::Carrier).
+pub struct BlockFeed(Arc<dyn ChainSource>);
+
+impl BlockFeed {
+    /// Creates a new [BlockFeed] instance.
+    pub fn new(chain_source: Arc<dyn ChainSource>) -> Self {
+        BlockFeed(chain_source)
+    }
+}
+
+impl BlockSource for BlockFeed {
+    fn get_header<'a>(
+        &'a self,
+        header_hash: &'a BlockHash,
+        height_hint: Option<u32>,
+    ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+        self.0.get_header(header_hash, height_hint)
+    }
+
+    fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, Block> {
+        self.0.get_block(header_hash)
+    }
+
+    fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
+        self.0.get_best_block()
+    }
+}
+
+/// The default [ChainSource], backed by `bitcoind`.
+///
+/// Blocks are fetched using a [BitcoindClient], while transaction data is sent / queried via `bitcoincore-rpc`.
+pub struct BitcoindChainSource {
+    /// The client blocks are fetched with.
+    bitcoin_cli: Arc<BitcoindClient>,
+    /// The client transactions are sent / queried with.
+    rpc: Client,
+}
+
+impl BitcoindChainSource {
+    /// Creates a new [BitcoindChainSource] instance.
+    pub fn new(bitcoin_cli: Arc<BitcoindClient>, rpc: Client) -> Self {
+        BitcoindChainSource { bitcoin_cli, rpc }
+    }
+}
+
+impl BlockSource for BitcoindChainSource {
+    fn get_header<'a>(
+        &'a self,
+        header_hash: &'a BlockHash,
+        height_hint: Option<u32>,
+    ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+        self.bitcoin_cli.get_header(header_hash, height_hint)
+    }
+
+    fn get_block<'a>(&'a self, header_hash: &'a BlockHash) -> AsyncBlockSourceResult<'a, Block> {
+        self.bitcoin_cli.get_block(header_hash)
+    }
+
+    fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
+        self.bitcoin_cli.get_best_block()
+    }
+}
+
+impl ChainSource for BitcoindChainSource {
+    fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ChainSourceError> {
+        Ok(self.rpc.send_raw_transaction(tx)?)
+    }
+
+    /// This uses `getrawtransaction` under the hood and, therefore, its behavior depends on whether `txindex` is enabled in bitcoind.
+    /// If `txindex` is disabled (default), it will only pull data from the mempool. Otherwise, it will also pull data from the transaction
+    /// index. Hence, we need to check whether the returned struct has any of the block related datum set (such as `blockhash`).
+    fn in_mempool(&self, txid: &Txid) -> Result<bool, ChainSourceError> {
+        match self.rpc.get_raw_transaction_info(txid, None) {
+            Ok(tx) => Ok(tx.blockhash.is_none()),
+            Err(e) => match ChainSourceError::from(e) {
+                ChainSourceError::Rpc { code, .. }
+                    if code == rpc_errors::RPC_INVALID_ADDRESS_OR_KEY =>
+                {
+                    log::info!("Transaction not found in mempool: {}", txid);
+                    Ok(false)
+                }
+                e => Err(e),
+            },
+        }
+    }
+
+    /// `gettxout` only finds unspent outputs, so, leaving the mempool out, an output that cannot be found has either been
+    /// spent by a confirmed transaction or belongs to a transaction that has not confirmed yet. The latter is told apart
+    /// by looking for the transaction in the mempool.
+    fn is_spent_on_chain(&self, outpoint: &OutPoint) -> Result<bool, ChainSourceError> {
+        let utxo = self.rpc.call::<serde_json::Value>(
+            "gettxout",
+            &[
+                outpoint.txid.to_string().into(),
+                outpoint.vout.into(),
+                false.into(),
+            ],
+        )?;
+        if !utxo.is_null() {
+            return Ok(false);
+        }
+        Ok(!self.in_mempool(&outpoint.txid)?)
+    }
+
+    fn get_mempool_min_fee(&self) -> Result<Amount, ChainSourceError> {
+        let info = self.rpc.call::<serde_json::Value>("getmempoolinfo", &[])?;
+        info["mempoolminfee"]
+            .as_f64()
+            .and_then(|fee| Amount::from_btc(fee).ok())
+            .ok_or_else(|| ChainSourceError::Other("invalid getmempoolinfo response".to_owned()))
+    }
+
+    fn estimate_fee(&self, target: u16) -> Result<Option<Amount>, ChainSourceError> {
+        // Fee estimation may not be available (e.g. if bitcoind has not seen enough blocks yet), in which case no feerate is returned
+        let estimate = self
+            .rpc
+            .call::<serde_json::Value>("estimatesmartfee", &[target.into()])?;
+        Ok(estimate["feerate"]
+            .as_f64()
+            .and_then(|fee| Amount::from_btc(fee).ok()))
+    }
+
+    fn get_sync_state(&self) -> Result<(u32, u32), ChainSourceError> {
+        let info = self
+            .rpc
+            .call::<serde_json::Value>("getblockchaininfo", &[])?;
+        match (info["blocks"].as_u64(), info["headers"].as_u64()) {
+            (Some(blocks), Some(headers)) => Ok((blocks as u32, headers as u32)),
+            _ => Err(ChainSourceError::Other(
+                "invalid getblockchaininfo response".to_owned(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use lightning_block_sync::init::validate_best_block_header;
+    use lightning_block_sync::poll::{ChainPoller, Poll};
+
+    use teos_common::test_utils::TX_HEX;
+
+    use bitcoin::consensus;
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::network::constants::Network;
+    use bitcoincore_rpc::Auth;
+
+    use crate::test_utils::{
+        create_chain_source, start_server, BitcoindMock, Blockchain, MockOptions, ESTIMATED_FEE,
+        MEMPOOL_MIN_FEE, START_HEIGHT, SYNC_HEADERS,
+    };
+
+    /// A [ChainSource] backed by something other than bitcoind, e.g. the chain backend of a node embedding the tower.
+    struct MockChainSource {
+        chain: Blockchain,
+        broadcast: Mutex<Vec<Txid>>,
+    }
+
+    impl BlockSource for MockChainSource {
+        fn get_header<'a>(
+            &'a self,
+            header_hash: &'a BlockHash,
+            height_hint: Option<u32>,
+        ) -> AsyncBlockSourceResult<'a, BlockHeaderData> {
+            self.chain.get_header(header_hash, height_hint)
+        }
+
+        fn get_block<'a>(
+            &'a self,
+            header_hash: &'a BlockHash,
+        ) -> AsyncBlockSourceResult<'a, Block> {
+            self.chain.get_block(header_hash)
+        }
+
+        fn get_best_block(&self) -> AsyncBlockSourceResult<(BlockHash, Option<u32>)> {
+            self.chain.get_best_block()
+        }
+    }
+
+    impl ChainSource for MockChainSource {
+        fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid, ChainSourceError> {
+            self.broadcast.lock().unwrap().push(tx.txid());
+            Ok(tx.txid())
+        }
+
+        fn in_mempool(&self, txid: &Txid) -> Result<bool, ChainSourceError> {
+            Ok(self.broadcast.lock().unwrap().contains(txid))
+        }
+
+        fn is_spent_on_chain(&self, _: &OutPoint) -> Result<bool, ChainSourceError> {
+            Ok(false)
+        }
+
+        fn get_mempool_min_fee(&self) -> Result<Amount, ChainSourceError> {
+            Ok(Amount::from_sat(MEMPOOL_MIN_FEE))
+        }
+
+        fn estimate_fee(&self, _: u16) -> Result<Option<Amount>, ChainSourceError> {
+            Ok(None)
+        }
+
+        fn get_sync_state(&self) -> Result<(u32, u32), ChainSourceError> {
+            let height = self.chain.tip().height;
+            Ok((height, height))
+        }
+    }
+
+    #[test]
+    fn test_chain_source_error_from_rpc_error() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::with_error_message(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+            "dust",
+        ));
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        start_server(bitcoind_mock.server);
+
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(
+            chain_source.send_raw_transaction(&tx),
+            Err(ChainSourceError::Rpc {
+                code: rpc_errors::RPC_VERIFY_REJECTED,
+                message: "dust".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_chain_source_error_unreachable() {
+        // Nothing is listening on the RPC port, so the connection is refused. The block client needs to connect
+        // straightaway though, so it is pointed to a mock
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let addr = bitcoind_mock.server.address();
+        let chain_source = BitcoindChainSource::new(
+            Arc::new(BitcoindClient::new_unchecked(&addr.ip().to_string(), addr.port()).unwrap()),
+            Client::new("http://127.0.0.1:1", Auth::None).unwrap(),
+        );
+
+        let tx = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(
+            chain_source.send_raw_transaction(&tx),
+            Err(ChainSourceError::Unreachable)
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_chain_source_fees() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        start_server(bitcoind_mock.server);
+
+        assert_eq!(
+            chain_source.get_mempool_min_fee(),
+            Ok(Amount::from_sat(MEMPOOL_MIN_FEE))
+        );
+        assert_eq!(
+            chain_source.estimate_fee(6),
+            Ok(Some(Amount::from_sat(ESTIMATED_FEE)))
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_chain_source_sync_state() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        start_server(bitcoind_mock.server);
+
+        assert_eq!(
+            chain_source.get_sync_state(),
+            Ok((START_HEIGHT as u32, SYNC_HEADERS))
+        );
+    }
+
+    #[test]
+    fn test_bitcoind_chain_source_not_in_mempool() {
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let chain_source = create_chain_source(bitcoind_mock.url());
+        start_server(bitcoind_mock.server);
+
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert_eq!(chain_source.in_mempool(&tx.txid()), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_block_feed() {
+        let chain = Blockchain::default().with_height(10);
+        let chain_source: Arc<dyn ChainSource> = Arc::new(MockChainSource {
+            chain: chain.clone(),
+            broadcast: Mutex::new(Vec::new()),
+        });
+
+        // The same source can be polled for blocks and used to send transactions
+        let mut feed = BlockFeed::new(chain_source.clone());
+        let tip = validate_best_block_header(&mut feed).await.unwrap();
+        assert_eq!(tip, chain.tip());
+
+        let poller = ChainPoller::new(&mut feed, Network::Bitcoin);
+        let block = poller.fetch_block(&tip).await.unwrap();
+        assert_eq!(block.block_hash(), chain.tip().header.block_hash());
+
+        let tx: Transaction = consensus::deserialize(&Vec::from_hex(TX_HEX).unwrap()).unwrap();
+        assert!(!chain_source.in_mempool(&tx.txid()).unwrap());
+        chain_source.send_raw_transaction(&tx).unwrap();
+        assert!(chain_source.in_mempool(&tx.txid()).unwrap());
+    }
+}
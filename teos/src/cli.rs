@@ -1,3 +1,4 @@
+use bitcoin::secp256k1::SecretKey;
 use hex::FromHex;
 use serde_json::to_string_pretty as pretty_json;
 use std::str::FromStr;
@@ -6,12 +7,26 @@ use tokio::fs;
 use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
 use tonic::Request;
 
+use teos::api::operator_auth;
 use teos::cli_config::{Command, Config, Opt};
 use teos::config;
 use teos::protos as msgs;
 use teos::protos::private_tower_services_client::PrivateTowerServicesClient;
 use teos_common::appointment::Locator;
-use teos_common::UserId;
+use teos_common::cryptography;
+use teos_common::{TowerId, UserId};
+
+/// Builds a request signed with the operator key, if provided. Plain requests are only accepted by towers with no operator key set.
+fn operator_request<T: prost::Message>(
+    method: &str,
+    message: T,
+    operator_sk: Option<&SecretKey>,
+) -> Request<T> {
+    match operator_sk {
+        Some(sk) => operator_auth::sign_request(method, message, sk),
+        None => Request::new(message),
+    }
+}
 
 #[tokio::main]
 async fn main() {
@@ -29,7 +44,50 @@ async fn main() {
     // Load conf (from file or defaults) and patch it with the command line parameters received (if any)
     let mut conf = config::from_file::<Config>(path.join("teos.toml"));
     conf.patch_with_options(opt);
+    conf.verify().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
 
+    // Generating an operator key does not require talking to the tower.
+    if let Command::GenerateOperatorKey(key_data) = &command {
+        let (operator_sk, operator_pk) = cryptography::get_random_keypair();
+        match fs::write(&key_data.path, operator_sk.display_secret().to_string()).await {
+            Ok(_) => println!(
+                "Operator key written to {}. Set operator_key = \"{}\" in the tower config to enable it",
+                key_data.path, operator_pk
+            ),
+            Err(e) => println!("Cannot write to {}: {}", key_data.path, e),
+        }
+        return;
+    }
+
+    // Destructive requests are signed with the operator key, if provided.
+    let operator_sk = if conf.operator_key_path.is_empty() {
+        None
+    } else {
+        let data = fs::read_to_string(&conf.operator_key_path)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot read {}: {}", conf.operator_key_path, e);
+                std::process::exit(1);
+            });
+        Some(SecretKey::from_str(data.trim()).unwrap_or_else(|_| {
+            eprintln!(
+                "{} does not contain a valid secret key",
+                conf.operator_key_path
+            );
+            std::process::exit(1);
+        }))
+    };
+    // The tls certificates are stored alongside the rest of the network specific data. Towers that have not been
+    // restarted since upgrading still have them straight in the data dir.
+    let network_path = path.join(&conf.btc_network);
+    let path = if network_path.join("client.pem").exists() {
+        network_path
+    } else {
+        path
+    };
     let key = fs::read(&path.join("client-key.pem"))
         .await
         .expect("unable to read client key from disk");
@@ -90,6 +148,10 @@ async fn main() {
             let info = client.get_tower_info(Request::new(())).await.unwrap();
             println!("{}", pretty_json(&info.into_inner()).unwrap())
         }
+        Command::GetLatencyStats => {
+            let stats = client.get_latency_stats(Request::new(())).await.unwrap();
+            println!("{}", pretty_json(&stats.into_inner()).unwrap());
+        }
         Command::GetUsers => {
             let users = client.get_users(Request::new(())).await.unwrap();
             println!("{}", pretty_json(&users.into_inner()).unwrap());
@@ -112,9 +174,162 @@ async fn main() {
                 Err(e) => println!("{}", e),
             };
         }
+        Command::ExportUser(export_data) => {
+            match (
+                UserId::from_str(&export_data.user_id),
+                TowerId::from_str(&export_data.tower_id),
+            ) {
+                (Ok(user_id), Ok(tower_id)) => {
+                    match client
+                        .export_user(operator_request(
+                            "export_user",
+                            msgs::ExportUserRequest {
+                                user_id: user_id.to_vec(),
+                                tower_id: tower_id.to_vec(),
+                                consent_signature: export_data.consent_signature,
+                                consent_expiry: export_data.consent_expiry,
+                            },
+                            operator_sk.as_ref(),
+                        ))
+                        .await
+                    {
+                        Ok(response) => {
+                            let export = response.into_inner();
+                            match fs::write(&export_data.path, pretty_json(&export).unwrap()).await
+                            {
+                                Ok(_) => println!(
+                                    "{} appointments exported to {}",
+                                    export.appointments.len(),
+                                    export_data.path
+                                ),
+                                Err(e) => println!("Cannot write to {}: {}", export_data.path, e),
+                            }
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                (Err(e), _) | (_, Err(e)) => println!("{}", e),
+            };
+        }
+        Command::ImportUser(import_data) => {
+            let export = match fs::read(&import_data.path).await {
+                Ok(data) => match serde_json::from_slice::<msgs::UserExport>(&data) {
+                    Ok(export) => export,
+                    Err(e) => {
+                        println!("Cannot parse {}: {}", import_data.path, e);
+                        return;
+                    }
+                },
+                Err(e) => {
+                    println!("Cannot read {}: {}", import_data.path, e);
+                    return;
+                }
+            };
+            match client
+                .import_user(operator_request(
+                    "import_user",
+                    export,
+                    operator_sk.as_ref(),
+                ))
+                .await
+            {
+                Ok(response) => println!("{}", pretty_json(&response.into_inner()).unwrap()),
+                Err(status) => println!("{}", status.message()),
+            }
+        }
+        Command::ExportTrackers(export_data) => {
+            match client.export_trackers(Request::new(())).await {
+                Ok(response) => {
+                    let trackers = response.into_inner();
+                    match fs::write(&export_data.path, pretty_json(&trackers).unwrap()).await {
+                        Ok(_) => println!(
+                            "{} trackers exported to {}",
+                            trackers.trackers.len(),
+                            export_data.path
+                        ),
+                        Err(e) => println!("Cannot write to {}: {}", export_data.path, e),
+                    }
+                }
+                Err(status) => println!("{}", status.message()),
+            }
+        }
+        Command::RebroadcastTracker(tracker_data) => {
+            match Vec::from_hex(&tracker_data.uuid) {
+                Ok(uuid) => {
+                    match client
+                        .rebroadcast_tracker(operator_request(
+                            "rebroadcast_tracker",
+                            msgs::RebroadcastTrackerRequest { uuid },
+                            operator_sk.as_ref(),
+                        ))
+                        .await
+                    {
+                        Ok(response) => {
+                            println!("{}", pretty_json(&response.into_inner()).unwrap())
+                        }
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{}", e),
+            };
+        }
+        Command::AbandonTracker(tracker_data) => {
+            if !tracker_data.confirm {
+                println!("Abandoning a tracker cannot be undone. Run the command again with --confirm to proceed");
+                return;
+            }
+            match Vec::from_hex(&tracker_data.uuid) {
+                Ok(uuid) => {
+                    match client
+                        .abandon_tracker(operator_request(
+                            "abandon_tracker",
+                            msgs::AbandonTrackerRequest {
+                                uuid,
+                                reason: tracker_data.reason,
+                            },
+                            operator_sk.as_ref(),
+                        ))
+                        .await
+                    {
+                        Ok(_) => println!("Tracker {} abandoned", tracker_data.uuid),
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{}", e),
+            };
+        }
+        Command::GetReviewQueue => {
+            let queue = client.get_review_queue(Request::new(())).await.unwrap();
+            println!("{}", pretty_json(&queue.into_inner()).unwrap());
+        }
+        Command::AcknowledgeTracker(tracker_data) => {
+            match Vec::from_hex(&tracker_data.uuid) {
+                Ok(uuid) => {
+                    match client
+                        .acknowledge_tracker(operator_request(
+                            "acknowledge_tracker",
+                            msgs::AcknowledgeTrackerRequest { uuid },
+                            operator_sk.as_ref(),
+                        ))
+                        .await
+                    {
+                        Ok(_) => println!("Tracker {} acknowledged", tracker_data.uuid),
+                        Err(status) => println!("{}", status.message()),
+                    }
+                }
+                Err(e) => println!("{}", e),
+            };
+        }
         Command::Stop => {
-            println!("Shutting down tower");
-            client.stop(Request::new(())).await.unwrap();
+            match client
+                .stop(operator_request("stop", (), operator_sk.as_ref()))
+                .await
+            {
+                Ok(_) => println!("Shutting down tower"),
+                Err(status) => println!("{}", status.message()),
+            }
         }
+        // Handled before connecting to the tower
+        Command::GenerateOperatorKey(_) => unreachable!(),
     };
 }
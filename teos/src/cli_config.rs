@@ -3,6 +3,8 @@
 use serde::Deserialize;
 use structopt::StructOpt;
 
+use crate::config::{network_port_offset, normalize_network, offset_port, ConfigError};
+
 #[derive(Debug, StructOpt, Clone)]
 #[structopt(rename_all = "lower_case")]
 pub enum Command {
@@ -16,8 +18,26 @@ pub enum Command {
     GetUsers,
     /// Gets information about a specific user
     GetUser(GetUserData),
+    /// Exports the appointments of a specific user to a file, so they can be imported by another tower (requires the user consent)
+    ExportUser(ExportUserData),
+    /// Imports the appointments of a user exported by another tower from a file, giving the user a fresh subscription
+    ImportUser(ImportUserData),
+    /// Exports all the trackers pending resolution (including the penalty transactions) to a file
+    ExportTrackers(ExportTrackersData),
+    /// Rebroadcasts the penalty of a specific tracker right away
+    RebroadcastTracker(RebroadcastTrackerData),
+    /// Abandons a specific tracker (confirmed to be invalid or superseded), so the tower stops responding for it
+    AbandonTracker(AbandonTrackerData),
+    /// Gets the trackers whose penalty can never confirm (its inputs were spent by a different transaction), pending review
+    GetReviewQueue,
+    /// Acknowledges a specific tracker under review, so the tower garbage collects it straightaway
+    AcknowledgeTracker(AcknowledgeTrackerData),
+    /// Gets the response-time stats of every endpoint and internal stage (auth, slot check, DB write and chain backend)
+    GetLatencyStats,
     /// Requests a graceful shutdown of the tower
     Stop,
+    /// Generates an operator key to a file, so destructive requests can be signed with it. The public key is to be set as the tower operator_key
+    GenerateOperatorKey(GenerateOperatorKeyData),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -27,6 +47,63 @@ pub struct GetUserData {
     pub user_id: String,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+#[structopt(rename_all = "snake_case")]
+pub struct ExportUserData {
+    /// The user identifier (33-byte compressed public key).
+    pub user_id: String,
+    /// The identifier of the tower the data will be imported by (33-byte compressed public key).
+    pub tower_id: String,
+    /// The user consent (a user signature over "migrate to" followed by the serialized tower id and consent expiry).
+    pub consent_signature: String,
+    /// The block height the user consent is valid until.
+    pub consent_expiry: u32,
+    /// The path of the file the user data will be exported to.
+    pub path: String,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ImportUserData {
+    /// The path of the file the user data will be imported from.
+    pub path: String,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct ExportTrackersData {
+    /// The path of the file the trackers will be exported to.
+    pub path: String,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct RebroadcastTrackerData {
+    /// The uuid of the tracker (20-byte hexadecimal string).
+    pub uuid: String,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct AbandonTrackerData {
+    /// The uuid of the tracker (20-byte hexadecimal string).
+    pub uuid: String,
+    /// Why the tracker is being abandoned. Recorded in the tower audit log.
+    #[structopt(long)]
+    pub reason: String,
+    /// Confirms the tracker is to be abandoned. This cannot be undone.
+    #[structopt(long)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct AcknowledgeTrackerData {
+    /// The uuid of the tracker (20-byte hexadecimal string).
+    pub uuid: String,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct GenerateOperatorKeyData {
+    /// The path of the file the operator secret key will be written to.
+    pub path: String,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct GetAppointmentsData {
     /// The locator of the appointments (16-byte hexadecimal string).
@@ -50,6 +127,14 @@ pub struct Opt {
     #[structopt(long)]
     pub rpc_port: Option<u16>,
 
+    /// The Bitcoin network the tower is running on [default: mainnet]
+    #[structopt(long)]
+    pub btc_network: Option<String>,
+
+    /// Path of the operator secret key file destructive requests are signed with (check generateoperatorkey)
+    #[structopt(long)]
+    pub operator_key_path: Option<String>,
+
     /// Specify data directory
     #[structopt(long, default_value = "~/.teos")]
     pub data_dir: String,
@@ -70,6 +155,9 @@ pub struct Opt {
 pub struct Config {
     pub rpc_bind: String,
     pub rpc_port: u16,
+    pub btc_network: String,
+    pub network_port_offsets: bool,
+    pub operator_key_path: String,
 }
 
 impl Config {
@@ -81,6 +169,26 @@ impl Config {
         if options.rpc_port.is_some() {
             self.rpc_port = options.rpc_port.unwrap();
         }
+        if options.btc_network.is_some() {
+            self.btc_network = options.btc_network.unwrap();
+        }
+        if options.operator_key_path.is_some() {
+            self.operator_key_path = options.operator_key_path.unwrap();
+        }
+    }
+
+    /// Verifies that [Config] is properly built.
+    ///
+    /// This normalizes the network name and offsets the RPC port by network if `network_port_offsets` is set,
+    /// matching what the tower does on its side.
+    pub fn verify(&mut self) -> Result<(), ConfigError> {
+        self.btc_network = normalize_network(&self.btc_network);
+        network_port_offset(&self.btc_network)?;
+        if self.network_port_offsets {
+            self.rpc_port = offset_port(self.rpc_port, &self.btc_network)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -95,6 +203,9 @@ impl Default for Config {
         Self {
             rpc_bind: "localhost".into(),
             rpc_port: 8814,
+            btc_network: "mainnet".into(),
+            network_port_offsets: false,
+            operator_key_path: String::new(),
         }
     }
 }
@@ -1,9 +1,16 @@
 //! Logic related to the tower configuration and command line parameter parsing.
 
 use serde::Deserialize;
+use std::convert::TryFrom;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 
+use bitcoin::secp256k1::PublicKey;
+
+use teos_common::UserId;
+
 pub fn data_dir_absolute_path(data_dir: String) -> PathBuf {
     if let Some(a) = data_dir.strip_prefix('~') {
         if let Some(b) = data_dir.strip_prefix("~/") {
@@ -29,9 +36,75 @@ pub fn from_file<T: Default + serde::de::DeserializeOwned>(path: PathBuf) -> T {
     }
 }
 
+/// Normalizes the network name to the one used by `bitcoind` (i.e. `mainnet` -> `main`, `testnet` -> `test`).
+pub fn normalize_network(network: &str) -> String {
+    if ["mainnet", "testnet"].contains(&network) {
+        network.trim_end_matches("net").into()
+    } else {
+        network.into()
+    }
+}
+
+/// Gets the offset applied to the tower ports for a given (normalized) network when `network_port_offsets` is set.
+///
+/// This allows running instances for different networks (e.g. mainnet and testnet) off the same configuration file
+/// without their interfaces colliding. Fails if the network is not recognized.
+pub fn network_port_offset(network: &str) -> Result<u16, ConfigError> {
+    match network {
+        "main" => Ok(0),
+        "test" => Ok(1),
+        "signet" => Ok(2),
+        "regtest" => Ok(3),
+        _ => Err(ConfigError(format!(
+            "btc_network not recognized. Expected {{mainnet, testnet, signet, regtest}}, received {}",
+            network
+        ))),
+    }
+}
+
+/// Offsets a given port by the [network_port_offset] of the given (normalized) network.
+///
+/// Fails if the network is not recognized or if the offset port does not fit in the port type.
+pub fn offset_port<T: Into<u32> + TryFrom<u32>>(port: T, network: &str) -> Result<T, ConfigError> {
+    let offset = network_port_offset(network)?;
+    port.into()
+        .checked_add(offset as u32)
+        .and_then(|port| T::try_from(port).ok())
+        .ok_or_else(|| ConfigError("network port offset overflows the configured ports".to_owned()))
+}
+
+/// Parses the socket addresses a listener binds to.
+///
+/// These are the explicit `binds` if any, or the address built from `bind` and `port` otherwise. IPv6 addresses are
+/// expected in their bracketed form when given as socket addresses (e.g. `[::1]:9814`), and plain otherwise (e.g. `::1`).
+fn parse_binds(
+    name: &str,
+    binds: &[String],
+    bind: &str,
+    port: u16,
+) -> Result<Vec<SocketAddr>, ConfigError> {
+    if binds.is_empty() {
+        IpAddr::from_str(bind)
+            .map(|ip| vec![SocketAddr::new(ip, port)])
+            .map_err(|_| ConfigError(format!("{}_bind is not a valid ip address: {}", name, bind)))
+    } else {
+        binds
+            .iter()
+            .map(|addr| {
+                SocketAddr::from_str(addr).map_err(|_| {
+                    ConfigError(format!(
+                        "{}_binds contains an invalid socket address: {}",
+                        name, addr
+                    ))
+                })
+            })
+            .collect()
+    }
+}
+
 /// Error raised if something is wrong with the configuration.
 #[derive(PartialEq, Eq, Debug)]
-pub struct ConfigError(String);
+pub struct ConfigError(pub(crate) String);
 
 impl std::fmt::Display for ConfigError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -98,6 +171,54 @@ pub struct Opt {
     #[structopt(long)]
     pub overwrite_key: bool,
 
+    /// Runs the Responder in dry-run mode. Penalty transactions are logged instead of broadcast
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Signs appointment receipts in batches (one per block) instead of one by one, for the clients that opt in
+    #[structopt(long)]
+    pub batch_receipts: bool,
+
+    /// Publishes a signed log of the breaches responded by the tower (penalty txids and confirmation heights only)
+    #[structopt(long)]
+    pub breach_log: bool,
+
+    /// Requires a one-time challenge (check get_auth_challenge) to authenticate the requests that support it
+    #[structopt(long)]
+    pub require_auth_challenges: bool,
+
+    /// Requires registrations to pay the subscription price set by the subscription_price_per_* options
+    #[structopt(long)]
+    pub require_payments: bool,
+
+    /// Number of blocks before expiry when users start being reminded to renew their subscription [default: 144]
+    #[structopt(long)]
+    pub renewal_window: Option<u32>,
+
+    /// Maximum number of registered users, 0 meaning unlimited [default: 0]. Registered users can still renew once reached
+    #[structopt(long)]
+    pub max_users: Option<u32>,
+
+    /// Time (in seconds) between bitcoind polls [default: 60]. When adaptive polling is on, this is the slowest pace
+    #[structopt(long)]
+    pub polling_delta: Option<u16>,
+
+    /// Polls bitcoind faster (every min_polling_delta seconds) while a new block is expected, and every polling_delta otherwise
+    #[structopt(long)]
+    pub adaptive_polling: bool,
+
+    /// Time (in seconds) between bitcoind polls while a new block is expected, if adaptive polling is on [default: 10]
+    #[structopt(long)]
+    pub min_polling_delta: Option<u16>,
+
+    /// Fetches blocks through bitcoind's REST interface (requires bitcoind to run with -rest). Falls back to RPC if unavailable
+    #[structopt(long)]
+    pub btc_rest: bool,
+
+    /// Offsets the tower ports depending on the network, so instances for different networks can run side by side
+    #[structopt(long)]
+    pub network_port_offsets: bool,
+
     /// If set, creates a Tor endpoint to serve API data. This endpoint is additional to the clearnet HTTP API
     #[structopt(long)]
     pub tor_support: bool,
@@ -121,12 +242,31 @@ pub struct Opt {
 #[serde(default)]
 pub struct Config {
     // API
+    pub api_enabled: bool,
     pub api_bind: String,
     pub api_port: u16,
+    pub api_binds: Vec<String>,
+    pub api_cors_origins: Vec<String>,
+    pub api_cors_methods: Vec<String>,
+    pub api_cors_headers: Vec<String>,
 
     // RPC
+    pub rpc_enabled: bool,
     pub rpc_bind: String,
     pub rpc_port: u16,
+    pub rpc_binds: Vec<String>,
+
+    // Public gRPC API
+    pub grpc_api_enabled: bool,
+    pub grpc_api_bind: String,
+    pub grpc_api_port: u16,
+    pub grpc_api_binds: Vec<String>,
+
+    // Metrics
+    pub metrics_enabled: bool,
+    pub metrics_bind: String,
+    pub metrics_port: u16,
+    pub metrics_binds: Vec<String>,
 
     // Bitcoind
     pub btc_network: String,
@@ -135,22 +275,57 @@ pub struct Config {
     pub btc_rpc_connect: String,
     pub btc_rpc_port: u16,
 
+    // Broadcast
+    pub esplora_broadcast_urls: Vec<String>,
+
     // Flags
     pub debug: bool,
     pub deps_debug: bool,
     pub overwrite_key: bool,
+    pub dry_run: bool,
+    pub batch_receipts: bool,
+    pub breach_log: bool,
+    pub require_auth_challenges: bool,
+    pub require_payments: bool,
+    pub adaptive_polling: bool,
+    pub network_port_offsets: bool,
+    pub btc_rest: bool,
 
     // General
     pub subscription_slots: u32,
     pub subscription_duration: u32,
     pub expiry_delta: u32,
+    pub renewal_window: u32,
+    pub max_users: u32,
+    pub auth_cache_ttl: u64,
+    pub operator_key: String,
+    pub subscription_price_per_slot_msat: u64,
+    pub subscription_price_per_block_msat: u64,
     pub min_to_self_delay: u16,
+    pub broadcast_delay: u32,
+    pub review_timeout: u32,
     pub polling_delta: u16,
+    pub min_polling_delta: u16,
+    pub locator_cache_size: u32,
+    pub min_free_disk_space_mb: u64,
+    pub sync_policy: String,
+    pub max_blocks_behind: u32,
+    pub key_rotation_overlap: u32,
+
+    // Policies
+    pub min_blob_size: usize,
+    pub max_blob_size: usize,
+    pub denied_users: Vec<String>,
+    pub large_blob_size: usize,
+    pub large_blob_min_slots: u32,
 
     // Internal API
     pub internal_api_bind: String,
     pub internal_api_port: u32,
 
+    // Telemetry
+    pub otlp_endpoint: String,
+
     // Tor
     pub tor_support: bool,
     pub tor_control_port: u16,
@@ -193,10 +368,30 @@ impl Config {
         if options.onion_hidden_service_port.is_some() {
             self.onion_hidden_service_port = options.onion_hidden_service_port.unwrap();
         }
+        if options.renewal_window.is_some() {
+            self.renewal_window = options.renewal_window.unwrap();
+        }
+        if options.max_users.is_some() {
+            self.max_users = options.max_users.unwrap();
+        }
+        if options.polling_delta.is_some() {
+            self.polling_delta = options.polling_delta.unwrap();
+        }
+        if options.min_polling_delta.is_some() {
+            self.min_polling_delta = options.min_polling_delta.unwrap();
+        }
 
         self.tor_support |= options.tor_support;
         self.debug |= options.debug;
         self.deps_debug |= options.deps_debug;
+        self.dry_run |= options.dry_run;
+        self.batch_receipts |= options.batch_receipts;
+        self.breach_log |= options.breach_log;
+        self.require_auth_challenges |= options.require_auth_challenges;
+        self.require_payments |= options.require_payments;
+        self.adaptive_polling |= options.adaptive_polling;
+        self.network_port_offsets |= options.network_port_offsets;
+        self.btc_rest |= options.btc_rest;
         self.overwrite_key = options.overwrite_key;
     }
 
@@ -205,9 +400,19 @@ impl Config {
     /// This includes:
     /// - `bitcoind` credentials have been set
     /// - The Bitcoin network has been properly set (to either bitcoin, testnet, signet or regtest)
+    /// - The appointment acceptance policies are consistent
+    /// - The locator cache holds at least one block
+    /// - The sync policy is recognized
+    /// - The renewal window is shorter than the subscription duration
+    /// - The penalty broadcast delay is shorter than `min_to_self_delay`
+    /// - The polling intervals are non-zero and consistent
+    /// - The Esplora broadcast endpoints are HTTP(s) urls
+    /// - The API, RPC, public gRPC and metrics bind addresses are valid, and the API is enabled if Tor support is
     ///
     /// This will also assign the default `btc_rpc_port` depending on the network if it has not
-    /// been overwritten at this point.
+    /// been overwritten at this point, and offset the tower ports by network if `network_port_offsets` is set.
+    /// Notice the offset only applies to `api_port` and `rpc_port`, the addresses in `api_binds` and `rpc_binds`
+    /// are used as given.
     pub fn verify(&mut self) -> Result<(), ConfigError> {
         if self.btc_rpc_user == String::new() {
             return Err(ConfigError("btc_rpc_user must be set".to_owned()));
@@ -217,9 +422,7 @@ impl Config {
         }
 
         // Normalize the network option to the ones used by bitcoind.
-        if ["mainnet", "testnet"].contains(&self.btc_network.as_str()) {
-            self.btc_network = self.btc_network.trim_end_matches("net").into();
-        }
+        self.btc_network = normalize_network(&self.btc_network);
 
         let default_rpc_port = match self.btc_network.as_str() {
             "main" => 8332,
@@ -235,9 +438,195 @@ impl Config {
             self.btc_rpc_port = default_rpc_port;
         }
 
+        if self.network_port_offsets {
+            self.api_port = offset_port(self.api_port, &self.btc_network)?;
+            self.rpc_port = offset_port(self.rpc_port, &self.btc_network)?;
+            self.internal_api_port = offset_port(self.internal_api_port, &self.btc_network)?;
+        }
+
+        if self.locator_cache_size == 0 {
+            return Err(ConfigError(
+                "locator_cache_size must be at least 1".to_owned(),
+            ));
+        }
+
+        if !["accept", "reject"].contains(&self.sync_policy.as_str()) {
+            return Err(ConfigError(format!(
+                "sync_policy not recognized. Expected {{accept, reject}}, received {}",
+                self.sync_policy
+            )));
+        }
+
+        // Users would be reminded to renew straightaway after registering otherwise
+        if self.renewal_window >= self.subscription_duration {
+            return Err(ConfigError(
+                "renewal_window must be smaller than subscription_duration".to_owned(),
+            ));
+        }
+
+        // Penalties must be broadcast before the dispute CSV expires, otherwise the cheating party can sweep the funds
+        if self.broadcast_delay >= self.min_to_self_delay as u32 {
+            return Err(ConfigError(
+                "broadcast_delay must be smaller than min_to_self_delay".to_owned(),
+            ));
+        }
+
+        if self.polling_delta == 0 {
+            return Err(ConfigError("polling_delta must be at least 1".to_owned()));
+        }
+        if self.adaptive_polling
+            && (self.min_polling_delta == 0 || self.min_polling_delta > self.polling_delta)
+        {
+            return Err(ConfigError(
+                "min_polling_delta must be between 1 and polling_delta".to_owned(),
+            ));
+        }
+
+        // Normalize the Esplora urls so the same endpoint is not used twice.
+        let mut esplora_broadcast_urls: Vec<String> = Vec::new();
+        for url in self.esplora_broadcast_urls.iter() {
+            let url = url.trim_end_matches('/');
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(ConfigError(format!(
+                    "esplora_broadcast_urls contains an invalid url: {}",
+                    url
+                )));
+            }
+            if !esplora_broadcast_urls.iter().any(|u| u == url) {
+                esplora_broadcast_urls.push(url.to_owned());
+            }
+        }
+        self.esplora_broadcast_urls = esplora_broadcast_urls;
+
+        if self.max_blob_size != 0 && self.max_blob_size < self.min_blob_size {
+            return Err(ConfigError(
+                "max_blob_size cannot be smaller than min_blob_size".to_owned(),
+            ));
+        }
+
+        if self.large_blob_size != 0 && self.large_blob_min_slots == 0 {
+            return Err(ConfigError(
+                "large_blob_min_slots must be set if large_blob_size is".to_owned(),
+            ));
+        }
+
+        // Browsers send the origin as scheme://host[:port], so anything else would never match
+        for origin in self.api_cors_origins.iter() {
+            let valid = match origin.split_once("://") {
+                Some(("http", host)) | Some(("https", host)) => {
+                    !host.is_empty() && !host.contains('/')
+                }
+                _ => origin == "*" && self.api_cors_origins.len() == 1,
+            };
+            if !valid {
+                return Err(ConfigError(format!(
+                    "api_cors_origins contains an invalid origin: {}",
+                    origin
+                )));
+            }
+        }
+        for method in self.api_cors_methods.iter_mut() {
+            if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(ConfigError(format!(
+                    "api_cors_methods contains an invalid method: {}",
+                    method
+                )));
+            }
+            method.make_ascii_uppercase();
+        }
+        for header in self.api_cors_headers.iter_mut() {
+            if header.is_empty()
+                || !header
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError(format!(
+                    "api_cors_headers contains an invalid header: {}",
+                    header
+                )));
+            }
+            header.make_ascii_lowercase();
+        }
+
+        self.api_addresses()?;
+        self.rpc_addresses()?;
+        self.grpc_api_addresses()?;
+        self.metrics_addresses()?;
+        if self.tor_support && !self.api_enabled {
+            return Err(ConfigError(
+                "tor_support requires the API to be enabled".to_owned(),
+            ));
+        }
+
+        if !self.operator_key.is_empty() && PublicKey::from_str(&self.operator_key).is_err() {
+            return Err(ConfigError(
+                "operator_key must be a 33-byte compressed public key".to_owned(),
+            ));
+        }
+
+        for user_id in self.denied_users.iter() {
+            if UserId::from_str(user_id).is_err() {
+                return Err(ConfigError(format!(
+                    "denied_users contains an invalid user_id: {}",
+                    user_id
+                )));
+            }
+        }
+
         Ok(())
     }
 
+    /// Gets the socket addresses the public API binds to: `api_binds` if set, `api_bind:api_port` otherwise.
+    /// Returns an empty list if the API is disabled.
+    pub fn api_addresses(&self) -> Result<Vec<SocketAddr>, ConfigError> {
+        if !self.api_enabled {
+            return Ok(Vec::new());
+        }
+        parse_binds("api", &self.api_binds, &self.api_bind, self.api_port)
+    }
+
+    /// Gets the socket addresses the private RPC server binds to: `rpc_binds` if set, `rpc_bind:rpc_port` otherwise.
+    /// Returns an empty list if the RPC server is disabled.
+    pub fn rpc_addresses(&self) -> Result<Vec<SocketAddr>, ConfigError> {
+        if !self.rpc_enabled {
+            return Ok(Vec::new());
+        }
+        parse_binds("rpc", &self.rpc_binds, &self.rpc_bind, self.rpc_port)
+    }
+
+    /// Gets the socket addresses the public gRPC API binds to: `grpc_api_binds` if set, `grpc_api_bind:grpc_api_port`
+    /// otherwise. Returns an empty list if the public gRPC API is disabled.
+    pub fn grpc_api_addresses(&self) -> Result<Vec<SocketAddr>, ConfigError> {
+        if !self.grpc_api_enabled {
+            return Ok(Vec::new());
+        }
+        parse_binds(
+            "grpc_api",
+            &self.grpc_api_binds,
+            &self.grpc_api_bind,
+            self.grpc_api_port,
+        )
+    }
+
+    /// Gets the socket addresses the metrics endpoint binds to: `metrics_binds` if set, `metrics_bind:metrics_port`
+    /// otherwise. Returns an empty list if the metrics endpoint is disabled.
+    pub fn metrics_addresses(&self) -> Result<Vec<SocketAddr>, ConfigError> {
+        if !self.metrics_enabled {
+            return Ok(Vec::new());
+        }
+        parse_binds(
+            "metrics",
+            &self.metrics_binds,
+            &self.metrics_bind,
+            self.metrics_port,
+        )
+    }
+
+    /// Gets the key destructive private requests must be signed with, if any.
+    pub fn operator_key(&self) -> Option<PublicKey> {
+        PublicKey::from_str(&self.operator_key).ok()
+    }
+
     /// Checks whether the config has been set with only with default values.
     pub fn is_default(&self) -> bool {
         self == &Config::default()
@@ -253,29 +642,73 @@ impl Default for Config {
     /// user does not use any values provided here).
     fn default() -> Self {
         Self {
+            api_enabled: true,
             api_bind: "127.0.0.1".into(),
             api_port: 9814,
+            api_binds: Vec::new(),
+            api_cors_origins: Vec::new(),
+            api_cors_methods: vec!["POST".to_owned()],
+            api_cors_headers: vec!["content-type".to_owned()],
             tor_support: false,
             tor_control_port: 9051,
             onion_hidden_service_port: 9814,
+            rpc_enabled: true,
             rpc_bind: "127.0.0.1".into(),
             rpc_port: 8814,
+            rpc_binds: Vec::new(),
+            grpc_api_enabled: false,
+            grpc_api_bind: "127.0.0.1".into(),
+            grpc_api_port: 9816,
+            grpc_api_binds: Vec::new(),
+            metrics_enabled: false,
+            metrics_bind: "127.0.0.1".into(),
+            metrics_port: 9815,
+            metrics_binds: Vec::new(),
             btc_network: "mainnet".into(),
             btc_rpc_user: String::new(),
             btc_rpc_password: String::new(),
             btc_rpc_connect: "localhost".into(),
             btc_rpc_port: 0,
+            esplora_broadcast_urls: Vec::new(),
 
             debug: false,
             deps_debug: false,
             overwrite_key: false,
+            dry_run: false,
+            batch_receipts: false,
+            breach_log: false,
+            require_auth_challenges: false,
+            require_payments: false,
+            adaptive_polling: false,
+            network_port_offsets: false,
+            btc_rest: false,
             subscription_slots: 10000,
             subscription_duration: 4320,
             expiry_delta: 6,
+            renewal_window: 144,
+            max_users: 0,
+            auth_cache_ttl: 60,
+            operator_key: String::new(),
+            subscription_price_per_slot_msat: 0,
+            subscription_price_per_block_msat: 0,
             min_to_self_delay: 20,
+            broadcast_delay: 0,
+            review_timeout: 1008,
             polling_delta: 60,
+            min_polling_delta: 10,
+            locator_cache_size: 6,
+            min_free_disk_space_mb: 100,
+            sync_policy: "accept".to_owned(),
+            max_blocks_behind: 6,
+            key_rotation_overlap: 1008,
+            min_blob_size: 0,
+            max_blob_size: 0,
+            denied_users: Vec::new(),
+            large_blob_size: 0,
+            large_blob_min_slots: 0,
             internal_api_bind: "127.0.0.1".into(),
             internal_api_port: 50051,
+            otlp_endpoint: String::new(),
         }
     }
 }
@@ -299,11 +732,23 @@ mod tests {
                 btc_rpc_password: None,
                 btc_rpc_connect: None,
                 btc_rpc_port: None,
+                renewal_window: None,
+                max_users: None,
+                polling_delta: None,
+                min_polling_delta: None,
                 data_dir: String::from("~/.teos"),
 
                 debug: false,
                 deps_debug: false,
                 overwrite_key: false,
+                dry_run: false,
+                batch_receipts: false,
+                breach_log: false,
+                require_auth_challenges: false,
+                require_payments: false,
+                adaptive_polling: false,
+                network_port_offsets: false,
+                btc_rest: false,
             }
         }
     }
@@ -373,4 +818,425 @@ mod tests {
 
         config.verify().unwrap()
     }
+
+    #[test]
+    fn test_config_verify_wrong_blob_sizes() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            min_blob_size: 100,
+            max_blob_size: 10,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("max_blob_size cannot be smaller"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_large_blob_tier_with_no_slots() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            large_blob_size: 1000,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("large_blob_min_slots must be set"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_empty_locator_cache() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            locator_cache_size: 0,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("locator_cache_size must be at least 1"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_polling_deltas() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            polling_delta: 0,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("polling_delta must be at least 1"))
+        );
+
+        // min_polling_delta is only checked if adaptive polling is on
+        config.polling_delta = 5;
+        assert!(config.verify().is_ok());
+        config.adaptive_polling = true;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("min_polling_delta must be between 1 and polling_delta"))
+        );
+        config.min_polling_delta = 0;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("min_polling_delta must be between 1 and polling_delta"))
+        );
+        config.min_polling_delta = 5;
+        assert!(config.verify().is_ok());
+    }
+
+    #[test]
+    fn test_config_verify_sync_policy() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            sync_policy: "queue".to_owned(),
+            ..Default::default()
+        };
+
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("sync_policy not recognized"))
+        );
+
+        for policy in ["accept", "reject"] {
+            config.sync_policy = policy.to_owned();
+            assert!(config.verify().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_config_verify_renewal_window() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            renewal_window: u32::MAX,
+            ..Default::default()
+        };
+
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("renewal_window must be smaller than subscription_duration"))
+        );
+        config.renewal_window = config.subscription_duration - 1;
+        assert!(config.verify().is_ok());
+    }
+
+    #[test]
+    fn test_config_verify_broadcast_delay() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            broadcast_delay: 20,
+            ..Default::default()
+        };
+
+        // The broadcast delay must leave room for the penalty to be broadcast before the CSV expires
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("broadcast_delay must be smaller than min_to_self_delay"))
+        );
+        config.broadcast_delay = 19;
+        assert!(config.verify().is_ok());
+    }
+
+    #[test]
+    fn test_config_bind_addresses() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            ..Default::default()
+        };
+
+        // By default, each listener binds to a single address built from its bind and port
+        assert!(config.verify().is_ok());
+        assert_eq!(
+            config.api_addresses().unwrap(),
+            vec![SocketAddr::from_str("127.0.0.1:9814").unwrap()]
+        );
+        assert_eq!(
+            config.rpc_addresses().unwrap(),
+            vec![SocketAddr::from_str("127.0.0.1:8814").unwrap()]
+        );
+
+        // IPv6 binds are also accepted
+        config.api_bind = "::1".to_owned();
+        assert_eq!(
+            config.api_addresses().unwrap(),
+            vec![SocketAddr::from_str("[::1]:9814").unwrap()]
+        );
+
+        // Explicit binds take precedence over bind and port
+        config.api_binds = vec!["0.0.0.0:9814".to_owned(), "[::]:9815".to_owned()];
+        assert_eq!(
+            config.api_addresses().unwrap(),
+            vec![
+                SocketAddr::from_str("0.0.0.0:9814").unwrap(),
+                SocketAddr::from_str("[::]:9815").unwrap()
+            ]
+        );
+        assert!(config.verify().is_ok());
+
+        // Disabled listeners bind nowhere
+        config.rpc_enabled = false;
+        assert!(config.rpc_addresses().unwrap().is_empty());
+        assert!(config.verify().is_ok());
+    }
+
+    #[test]
+    fn test_config_verify_bind_addresses() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            rpc_bind: "localhost".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("rpc_bind is not a valid ip address"))
+        );
+
+        config.rpc_bind = "127.0.0.1".to_owned();
+        config.api_binds = vec!["::1".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_binds contains an invalid socket address"))
+        );
+
+        config.api_binds = Vec::new();
+        config.api_enabled = false;
+        config.tor_support = true;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("tor_support requires the API to be enabled"))
+        );
+    }
+
+    #[test]
+    fn test_config_metrics_addresses() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            metrics_bind: "localhost".to_owned(),
+            ..Default::default()
+        };
+
+        // The metrics endpoint is disabled by default, so the bind is not checked
+        assert!(config.verify().is_ok());
+        assert_eq!(config.metrics_addresses(), Ok(Vec::new()));
+
+        config.metrics_enabled = true;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("metrics_bind is not a valid ip address"))
+        );
+
+        config.metrics_bind = "127.0.0.1".to_owned();
+        assert!(config.verify().is_ok());
+        assert_eq!(
+            config.metrics_addresses(),
+            Ok(vec!["127.0.0.1:9815".parse().unwrap()])
+        );
+
+        config.metrics_binds = vec!["127.0.0.1:9815".to_owned(), "[::1]:9815".to_owned()];
+        assert_eq!(
+            config.metrics_addresses(),
+            Ok(vec![
+                "127.0.0.1:9815".parse().unwrap(),
+                "[::1]:9815".parse().unwrap()
+            ])
+        );
+
+        config.metrics_binds = vec!["localhost:9815".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("metrics_binds contains an invalid socket address"))
+        );
+    }
+
+    #[test]
+    fn test_config_grpc_api_addresses() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            grpc_api_bind: "localhost".to_owned(),
+            ..Default::default()
+        };
+
+        // The public gRPC API is disabled by default, so the bind is not checked
+        assert!(config.verify().is_ok());
+        assert_eq!(config.grpc_api_addresses(), Ok(Vec::new()));
+
+        config.grpc_api_enabled = true;
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("grpc_api_bind is not a valid ip address"))
+        );
+
+        config.grpc_api_bind = "0.0.0.0".to_owned();
+        assert!(config.verify().is_ok());
+        assert_eq!(
+            config.grpc_api_addresses(),
+            Ok(vec!["0.0.0.0:9816".parse().unwrap()])
+        );
+
+        config.grpc_api_binds = vec!["[::]:9816".to_owned()];
+        assert_eq!(
+            config.grpc_api_addresses(),
+            Ok(vec!["[::]:9816".parse().unwrap()])
+        );
+    }
+
+    #[test]
+    fn test_config_verify_esplora_urls() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            esplora_broadcast_urls: vec![
+                "https://blockstream.info/api/".to_owned(),
+                "https://blockstream.info/api".to_owned(),
+                "http://localhost:3000".to_owned(),
+            ],
+            ..Default::default()
+        };
+        config.verify().unwrap();
+        assert_eq!(
+            config.esplora_broadcast_urls,
+            vec!["https://blockstream.info/api", "http://localhost:3000"]
+        );
+
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            esplora_broadcast_urls: vec!["blockstream.info/api".to_owned()],
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("esplora_broadcast_urls contains an invalid url"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_api_cors() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            api_cors_origins: vec![
+                "https://wallet.example.com".to_owned(),
+                "http://localhost:3000".to_owned(),
+            ],
+            api_cors_methods: vec!["post".to_owned(), "GET".to_owned()],
+            api_cors_headers: vec!["Content-Type".to_owned()],
+            ..Default::default()
+        };
+        config.verify().unwrap();
+        assert_eq!(config.api_cors_methods, vec!["POST", "GET"]);
+        assert_eq!(config.api_cors_headers, vec!["content-type"]);
+
+        // A wildcard is only allowed on its own
+        config.api_cors_origins = vec!["*".to_owned()];
+        config.verify().unwrap();
+        config.api_cors_origins = vec!["*".to_owned(), "http://localhost:3000".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_cors_origins contains an invalid origin"))
+        );
+
+        // Origins carry no path
+        config.api_cors_origins = vec!["https://wallet.example.com/".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_cors_origins contains an invalid origin"))
+        );
+        config.api_cors_origins = vec!["wallet.example.com".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_cors_origins contains an invalid origin"))
+        );
+
+        config.api_cors_origins = Vec::new();
+        config.api_cors_methods = vec!["PO ST".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_cors_methods contains an invalid method"))
+        );
+
+        config.api_cors_methods = vec!["POST".to_owned()];
+        config.api_cors_headers = vec!["content type".to_owned()];
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("api_cors_headers contains an invalid header"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_wrong_denied_user() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            denied_users: vec!["not_a_user_id".to_owned()],
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("denied_users contains an invalid user_id"))
+        );
+    }
+
+    #[test]
+    fn test_config_verify_operator_key() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            operator_key: "not_a_key".to_owned(),
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("operator_key must be a 33-byte compressed public key"))
+        );
+
+        let (_, operator_pk) = teos_common::cryptography::get_random_keypair();
+        config.operator_key = operator_pk.to_string();
+        assert!(config.verify().is_ok());
+        assert_eq!(config.operator_key(), Some(operator_pk));
+    }
+
+    #[test]
+    fn test_config_verify_network_port_offsets() {
+        // Ports are left untouched unless network_port_offsets is set
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            btc_network: "testnet".to_owned(),
+            ..Default::default()
+        };
+        let default = Config::default();
+        config.verify().unwrap();
+        assert_eq!(config.api_port, default.api_port);
+        assert_eq!(config.rpc_port, default.rpc_port);
+        assert_eq!(config.internal_api_port, default.internal_api_port);
+
+        for (network, offset) in [
+            ("mainnet", 0),
+            ("testnet", 1),
+            ("signet", 2),
+            ("regtest", 3),
+        ] {
+            let mut config = Config {
+                btc_rpc_user: "user".to_owned(),
+                btc_rpc_password: "password".to_owned(),
+                btc_network: network.to_owned(),
+                network_port_offsets: true,
+                ..Default::default()
+            };
+            config.verify().unwrap();
+            assert_eq!(config.api_port, default.api_port + offset);
+            assert_eq!(config.rpc_port, default.rpc_port + offset);
+            assert_eq!(
+                config.internal_api_port,
+                default.internal_api_port + offset as u32
+            );
+        }
+    }
+
+    #[test]
+    fn test_config_verify_network_port_offsets_overflow() {
+        let mut config = Config {
+            btc_rpc_user: "user".to_owned(),
+            btc_rpc_password: "password".to_owned(),
+            btc_network: "regtest".to_owned(),
+            api_port: u16::MAX,
+            network_port_offsets: true,
+            ..Default::default()
+        };
+        assert!(
+            matches!(config.verify(), Err(ConfigError(e)) if e.contains("network port offset overflows"))
+        );
+    }
 }
@@ -12,23 +12,26 @@ use rusqlite::{params, params_from_iter, Connection, Error as SqliteError};
 use bitcoin::consensus;
 use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::SecretKey;
-use bitcoin::BlockHash;
+use bitcoin::{BlockHash, Txid};
 
 use teos_common::appointment::{compute_appointment_slots, Appointment, Locator};
 use teos_common::constants::ENCRYPTED_BLOB_MAX_SIZE;
 use teos_common::dbm::{DatabaseConnection, DatabaseManager, Error};
+use teos_common::receipts::AppointmentReceipt;
 use teos_common::UserId;
 
-use crate::extended_appointment::{ExtendedAppointment, UUID};
+use crate::extended_appointment::{AppointmentState, ExtendedAppointment, UUID};
 use crate::gatekeeper::UserInfo;
 use crate::responder::{ConfirmationStatus, TransactionTracker};
+use crate::telemetry;
 
-const TABLES: [&str; 5] = [
+const TABLES: [&str; 12] = [
     "CREATE TABLE IF NOT EXISTS users (
     user_id INT PRIMARY KEY,
     available_slots INT NOT NULL,
     subscription_start INT NOT NULL,
-    subscription_expiry INT NOT NULL
+    subscription_expiry INT NOT NULL,
+    granted_slots INT NOT NULL DEFAULT 0
 )",
     "CREATE TABLE IF NOT EXISTS appointments (
     UUID INT PRIMARY KEY,
@@ -40,6 +43,9 @@ const TABLES: [&str; 5] = [
     user_id INT NOT NULL,
     FOREIGN KEY(user_id)
         REFERENCES users(user_id)
+        ON DELETE CASCADE,
+    FOREIGN KEY(UUID)
+        REFERENCES appointment_states(UUID)
         ON DELETE CASCADE
 )",
     "CREATE TABLE IF NOT EXISTS trackers (
@@ -48,9 +54,39 @@ const TABLES: [&str; 5] = [
     penalty_tx BLOB NOT NULL,
     height INT NOT NULL,
     confirmed BOOL NOT NULL,
+    status INT NOT NULL DEFAULT 0,
     FOREIGN KEY(UUID)
         REFERENCES appointments(UUID)
         ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS review_queue (
+    UUID INT PRIMARY KEY,
+    height INT NOT NULL,
+    FOREIGN KEY(UUID)
+        REFERENCES trackers(UUID)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS low_fee_trackers (
+    UUID INT PRIMARY KEY,
+    FOREIGN KEY(UUID)
+        REFERENCES trackers(UUID)
+        ON DELETE CASCADE
+)",
+    "CREATE TABLE IF NOT EXISTS appointment_states (
+    UUID INT PRIMARY KEY,
+    user_id INT NOT NULL,
+    state INT NOT NULL,
+    height INT NOT NULL
+)",
+    "CREATE TABLE IF NOT EXISTS batch_receipts (
+    UUID INT NOT NULL,
+    user_signature BLOB NOT NULL,
+    start_block INT NOT NULL,
+    PRIMARY KEY (UUID, start_block)
+)",
+    "CREATE TABLE IF NOT EXISTS breach_log (
+    penalty_txid INT PRIMARY KEY,
+    height INT NOT NULL
 )",
     "CREATE TABLE IF NOT EXISTS last_known_block (
     id INT PRIMARY KEY,
@@ -60,8 +96,35 @@ const TABLES: [&str; 5] = [
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     key INT NOT NULL
 )",
+    "CREATE TABLE IF NOT EXISTS key_rotations (
+    key_id INT PRIMARY KEY NOT NULL,
+    height INT NOT NULL,
+    FOREIGN KEY(key_id)
+        REFERENCES keys(id)
+)",
+    "CREATE TABLE IF NOT EXISTS network (
+    id INT PRIMARY KEY,
+    name TEXT NOT NULL
+)",
+];
+
+/// Indices over the per-user data, so anything keyed by user (loading, deleting or cascading from `users`)
+/// only touches the rows of the given user instead of scanning the whole table.
+const INDICES: [&str; 2] = [
+    "CREATE INDEX IF NOT EXISTS appointments_user_id ON appointments (user_id, UUID)",
+    "CREATE INDEX IF NOT EXISTS appointment_states_user_id ON appointment_states (user_id, UUID)",
 ];
 
+/// The database representation of the final [AppointmentState]s, as a comma separated list to be used in queries.
+fn final_states_db_data() -> String {
+    AppointmentState::ALL
+        .iter()
+        .filter(|state| state.is_final())
+        .map(|state| state.to_db_data().to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 /// Component in charge of interacting with the underlying database.
 ///
 /// Currently works for `SQLite`. `PostgreSQL` should also be added in the future.
@@ -87,15 +150,184 @@ impl DBM {
         let connection = Connection::open(db_path)?;
         connection.execute("PRAGMA foreign_keys=1;", [])?;
         let mut dbm = Self { connection };
+        dbm.migrate_tracker_status()?;
+        dbm.migrate_granted_slots()?;
+        dbm.create_tables(Vec::from_iter(TABLES))?;
+        dbm.backfill_appointment_states()?;
+        dbm.migrate_appointments_fk()?;
+        dbm.create_indices()?;
+
+        Ok(dbm)
+    }
+
+    /// Creates a new [DBM] instance backed by an in-memory database. Data is lost once the instance is dropped.
+    pub fn in_memory() -> Result<Self, SqliteError> {
+        let connection = Connection::open_in_memory()?;
+        connection.execute("PRAGMA foreign_keys=1;", [])?;
+        let mut dbm = Self { connection };
         dbm.create_tables(Vec::from_iter(TABLES))?;
+        dbm.create_indices()?;
 
         Ok(dbm)
     }
 
+    /// Creates the indices over the per-user data (check [INDICES]), if they don't exist yet.
+    ///
+    /// Must be called once the tables (and their migrations) are in place.
+    fn create_indices(&mut self) -> Result<(), SqliteError> {
+        self.create_tables(Vec::from_iter(INDICES))
+    }
+
+    /// Adds the `status` column to the `trackers` table of databases created by versions of the tower that did not have it.
+    ///
+    /// Those versions only stored whether the tracker was confirmed, so that's all the status is built from.
+    fn migrate_tracker_status(&self) -> Result<(), SqliteError> {
+        let table_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='trackers'",
+            [],
+            |row| row.get(0),
+        )?;
+        let column_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('trackers') WHERE name='status'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if table_exists && !column_exists {
+            self.connection.execute(
+                "ALTER TABLE trackers ADD COLUMN status INT NOT NULL DEFAULT 0",
+                [],
+            )?;
+            let n = self.connection.execute(
+                "UPDATE trackers SET status=(?1) WHERE confirmed",
+                [ConfirmationStatus::DB_CONFIRMED],
+            )?;
+            log::info!(
+                "Tracker status column added ({} confirmed trackers migrated)",
+                n
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Adds the `granted_slots` column to the `users` table of databases created by versions of the tower that did not
+    /// have it.
+    ///
+    /// Those versions did not keep track of the slots granted to each user, so they are assumed to be the ones available
+    /// plus the ones taken by the user's appointments at the time of migrating.
+    fn migrate_granted_slots(&self) -> Result<(), SqliteError> {
+        let table_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='users'",
+            [],
+            |row| row.get(0),
+        )?;
+        let column_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('users') WHERE name='granted_slots'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if table_exists && !column_exists {
+            self.connection.execute(
+                "ALTER TABLE users ADD COLUMN granted_slots INT NOT NULL DEFAULT 0",
+                [],
+            )?;
+            let used_slots = self.load_used_slots();
+            for (user_id, user_info) in self.load_all_users() {
+                let used = used_slots.get(&user_id).cloned().unwrap_or_default();
+                self.connection.execute(
+                    "UPDATE users SET granted_slots=(?1) WHERE user_id=(?2)",
+                    params![user_info.available_slots as u64 + used, user_id.to_vec()],
+                )?;
+            }
+            log::info!("Granted slots column added to the users table");
+        }
+
+        Ok(())
+    }
+
+    /// Sets the state of the appointments stored by versions of the tower that did not persist it.
+    ///
+    /// Appointments with a tracker are triggered, the rest are being watched.
+    fn backfill_appointment_states(&self) -> Result<(), SqliteError> {
+        let n = self.connection.execute(
+            "INSERT OR IGNORE INTO appointment_states (UUID, user_id, state, height)
+                SELECT a.UUID, a.user_id, CASE WHEN t.UUID IS NULL THEN (?1) ELSE (?2) END, a.start_block
+                FROM appointments as a LEFT JOIN trackers as t ON a.UUID=t.UUID",
+            params![
+                AppointmentState::Watched.to_db_data(),
+                AppointmentState::Triggered.to_db_data()
+            ],
+        )?;
+        if n > 0 {
+            log::info!("Appointment states backfilled for {} appointments", n);
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds the `appointments` table of databases created by versions of the tower where appointments did not
+    /// reference their state.
+    ///
+    /// SQLite cannot add constraints to an existing table, so the data is moved to a new one. This must run once
+    /// every appointment has a state (see [Self::backfill_appointment_states]).
+    fn migrate_appointments_fk(&mut self) -> Result<(), SqliteError> {
+        let sql: String = self.connection.query_row(
+            "SELECT sql FROM sqlite_master WHERE type='table' AND name='appointments'",
+            [],
+            |row| row.get(0),
+        )?;
+        if sql.contains("REFERENCES appointment_states") {
+            return Ok(());
+        }
+
+        // Foreign keys cannot be toggled within a transaction. Dropping the old table with them on would delete the
+        // trackers (and review entries) in cascade. They are turned back on no matter how the rebuild goes
+        self.connection.execute("PRAGMA foreign_keys=0;", [])?;
+        let rebuilt = self.rebuild_appointments_table();
+        self.connection.execute("PRAGMA foreign_keys=1;", [])?;
+        let n = rebuilt?;
+        log::info!(
+            "Appointments table migrated to reference their state ({} appointments)",
+            n
+        );
+
+        Ok(())
+    }
+
+    /// Moves the appointments to a new table built from the current schema (check [TABLES]) and swaps it for the old one.
+    ///
+    /// Everything happens within a single transaction, so the table is left untouched if anything fails. Returns the
+    /// number of moved appointments.
+    fn rebuild_appointments_table(&mut self) -> Result<usize, SqliteError> {
+        let tx = self.connection.transaction()?;
+        tx.execute(
+            &TABLES[1].replace(
+                "CREATE TABLE IF NOT EXISTS appointments (",
+                "CREATE TABLE appointments_new (",
+            ),
+            [],
+        )?;
+        let n = tx.execute(
+            "INSERT INTO appointments_new SELECT * FROM appointments",
+            [],
+        )?;
+        tx.execute("DROP TABLE appointments", [])?;
+        tx.execute("ALTER TABLE appointments_new RENAME TO appointments", [])?;
+        tx.commit()?;
+
+        Ok(n)
+    }
+
     /// Stores a user ([UserInfo]) into the database.
+    ///
+    /// The slots granted to the user (check [UserInfo::granted_slots]) are stored alongside, so they can be checked against on
+    /// startup (check [DBM::remove_orphan_data]).
     pub(crate) fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error> {
+        let _stage = telemetry::stage_span("db_write").entered();
         let query =
-        "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry) VALUES (?1, ?2, ?3, ?4)";
+        "INSERT INTO users (user_id, available_slots, subscription_start, subscription_expiry, granted_slots) VALUES (?1, ?2, ?3, ?4, ?5)";
 
         match self.store_data(
             query,
@@ -104,6 +336,7 @@ impl DBM {
                 user_info.available_slots,
                 user_info.subscription_start,
                 user_info.subscription_expiry,
+                user_info.granted_slots(),
             ],
         ) {
             Ok(x) => {
@@ -119,14 +352,16 @@ impl DBM {
 
     /// Updates an existing user ([UserInfo]) in the database.
     pub(crate) fn update_user(&self, user_id: UserId, user_info: &UserInfo) {
+        let _stage = telemetry::stage_span("db_write").entered();
         let query =
-        "UPDATE users SET available_slots=(?1), subscription_start=(?2), subscription_expiry=(?3) WHERE user_id=(?4)";
+        "UPDATE users SET available_slots=(?1), subscription_start=(?2), subscription_expiry=(?3), granted_slots=(?4) WHERE user_id=(?5)";
         match self.update_data(
             query,
             params![
                 user_info.available_slots,
                 user_info.subscription_start,
                 user_info.subscription_expiry,
+                user_info.granted_slots(),
                 user_id.to_vec(),
             ],
         ) {
@@ -193,8 +428,14 @@ impl DBM {
     }
 
     /// Removes some users from the database in batch.
+    ///
+    /// The users' appointments, trackers and non-final appointment states are deleted in cascade within the same
+    /// transaction. Final states are kept until pruned (check [DBM::prune_appointment_states]), so the appointments
+    /// of removed users can still be accounted for. Cascading goes through the `user_id` indices, so the cost is
+    /// proportional to the data of the removed users.
     pub(crate) fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize {
         let limit = self.connection.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER) as usize;
+        let final_states = final_states_db_data();
         let tx = self.connection.transaction().unwrap();
         let iter = users
             .iter()
@@ -202,9 +443,17 @@ impl DBM {
             .collect::<Vec<Vec<u8>>>();
 
         for chunk in iter.chunks(limit) {
-            let query = "DELETE FROM users WHERE user_id IN ".to_owned();
             let placeholders = format!("(?{})", (", ?").repeat(chunk.len() - 1));
+            let query = format!(
+                "DELETE FROM appointment_states WHERE state NOT IN ({}) AND user_id IN {}",
+                final_states, placeholders
+            );
+            match tx.execute(&query, params_from_iter(chunk)) {
+                Ok(_) => log::debug!("Appointment states deletion added to db transaction"),
+                Err(e) => log::error!("Couldn't add deletion query to transaction. Error: {:?}", e),
+            }
 
+            let query = "DELETE FROM users WHERE user_id IN ".to_owned();
             match tx.execute(
                 &format!("{}{}", query, placeholders),
                 params_from_iter(chunk),
@@ -223,11 +472,28 @@ impl DBM {
     }
 
     /// Stores an [Appointment] into the database.
+    ///
+    /// Appointments reference their state, so appointments with no state yet are stored as [AppointmentState::Accepted]
+    /// (within the same transaction).
     pub(crate) fn store_appointment(
         &self,
         uuid: UUID,
         appointment: &ExtendedAppointment,
     ) -> Result<(), Error> {
+        let _stage = telemetry::stage_span("db_write").entered();
+        let tx = self
+            .connection
+            .unchecked_transaction()
+            .map_err(Error::Unknown)?;
+        self.store_data(
+            "INSERT OR IGNORE INTO appointment_states (UUID, user_id, state, height) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                uuid.to_vec(),
+                appointment.user_id.to_vec(),
+                AppointmentState::Accepted.to_db_data(),
+                appointment.start_block
+            ],
+        )?;
         let query = "INSERT INTO appointments (UUID, locator, encrypted_blob, to_self_delay, user_signature, start_block, user_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
         match self.store_data(
             query,
@@ -242,6 +508,7 @@ impl DBM {
             ],
         ) {
             Ok(x) => {
+                tx.commit().map_err(Error::Unknown)?;
                 log::debug!("Appointment successfully stored: {}", uuid);
                 Ok(x)
             }
@@ -254,6 +521,7 @@ impl DBM {
 
     /// Updates an existing [Appointment] in the database.
     pub(crate) fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment) {
+        let _stage = telemetry::stage_span("db_write").entered();
         // DISCUSS: Check what fields we'd like to make updatable. e_blob and signature are the obvious, to_self_delay and start_block may not be necessary (or even risky)
         let query =
             "UPDATE appointments SET encrypted_blob=(?1), to_self_delay=(?2), user_signature=(?3), start_block=(?4) WHERE UUID=(?5)";
@@ -355,28 +623,29 @@ impl DBM {
         appointments
     }
 
-    /// Removes an [Appointment] from the database.
-    pub(crate) fn remove_appointment(&self, uuid: UUID) {
-        let query = "DELETE FROM appointments WHERE UUID=(?)";
-        match self.remove_data(query, params![uuid.to_vec()]) {
-            Ok(_) => {
-                log::debug!("Appointment successfully removed: {}", uuid);
-            }
-            Err(_) => {
-                log::error!("Appointment not found, data cannot be removed: {}", uuid);
-            }
-        }
-    }
-
     /// Removes some appointments from the database in batch and updates the associated users giving back
-    /// the freed appointment slots
+    /// the freed appointment slots.
+    ///
+    /// The appointments are moved to their final [AppointmentState] within the same transaction (as long as the
+    /// transition is valid).
     pub(crate) fn batch_remove_appointments(
         &mut self,
         appointments: &HashSet<UUID>,
         updated_users: &HashMap<UserId, UserInfo>,
+        state: AppointmentState,
+        height: u32,
     ) -> usize {
         let limit = self.connection.limit(Limit::SQLITE_LIMIT_VARIABLE_NUMBER) as usize;
+        let valid_uuids = self.filter_valid_transitions(appointments, state);
         let tx = self.connection.transaction().unwrap();
+        for uuid in valid_uuids.iter() {
+            let query = "UPDATE appointment_states SET state=(?1), height=(?2) WHERE UUID=(?3)";
+            match tx.execute(query, params![state.to_db_data(), height, uuid.to_vec()]) {
+                Ok(_) => log::debug!("Appointment state update added to db transaction"),
+                Err(e) => log::error!("Couldn't add update query to transaction. Error: {:?}", e),
+            }
+        }
+
         let iter = appointments
             .iter()
             .map(|uuid| uuid.to_vec())
@@ -425,16 +694,175 @@ impl DBM {
         .map_err(|_| Error::NotFound)
     }
 
+    /// Loads the [AppointmentState] of a given appointment from the database.
+    ///
+    /// Appointments with no state are considered [AppointmentState::Received].
+    pub(crate) fn load_appointment_state(&self, uuid: UUID) -> AppointmentState {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT state FROM appointment_states WHERE UUID=(?)")
+            .unwrap();
+
+        stmt.query_row([uuid.to_vec()], |row| row.get::<_, u8>(0))
+            .ok()
+            .and_then(AppointmentState::from_db_data)
+            .unwrap_or(AppointmentState::Received)
+    }
+
+    /// Moves an appointment to a new [AppointmentState], storing it if it has no state yet.
+    ///
+    /// The transition is only persisted if valid. Returns whether the state has been updated.
+    pub(crate) fn update_appointment_state(
+        &self,
+        uuid: UUID,
+        user_id: UserId,
+        state: AppointmentState,
+        height: u32,
+    ) -> bool {
+        let _stage = telemetry::stage_span("db_write").entered();
+        let current = self.load_appointment_state(uuid);
+        if !current.can_transition_to(state) {
+            log::error!(
+                "Invalid appointment state transition ({} -> {}): {}",
+                current,
+                state,
+                uuid
+            );
+            return false;
+        }
+
+        // Appointments reference their state, so the row cannot be replaced (that would delete them in cascade)
+        let query =
+            "INSERT INTO appointment_states (UUID, user_id, state, height) VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(UUID) DO UPDATE SET state=excluded.state, height=excluded.height";
+        match self.store_data(
+            query,
+            params![uuid.to_vec(), user_id.to_vec(), state.to_db_data(), height],
+        ) {
+            Ok(_) => {
+                log::debug!(
+                    "Appointment state updated ({} -> {}): {}",
+                    current,
+                    state,
+                    uuid
+                );
+                true
+            }
+            Err(e) => {
+                log::error!(
+                    "Couldn't update appointment state: {}. Error: {:?}",
+                    uuid,
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Filters out the appointments that cannot transition to a given [AppointmentState], logging them.
+    fn filter_valid_transitions(
+        &self,
+        uuids: &HashSet<UUID>,
+        state: AppointmentState,
+    ) -> Vec<UUID> {
+        uuids
+            .iter()
+            .filter(|uuid| {
+                let current = self.load_appointment_state(**uuid);
+                if current.can_transition_to(state) {
+                    true
+                } else {
+                    log::error!(
+                        "Invalid appointment state transition ({} -> {}): {}",
+                        current,
+                        state,
+                        uuid
+                    );
+                    false
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Moves a batch of already stored appointments to a new [AppointmentState].
+    ///
+    /// Only valid transitions are persisted, invalid ones are logged and ignored.
+    pub(crate) fn batch_update_appointment_states(
+        &mut self,
+        uuids: &HashSet<UUID>,
+        state: AppointmentState,
+        height: u32,
+    ) {
+        let valid_uuids = self.filter_valid_transitions(uuids, state);
+        if valid_uuids.is_empty() {
+            return;
+        }
+
+        let tx = self.connection.transaction().unwrap();
+        for uuid in valid_uuids.iter() {
+            let query = "UPDATE appointment_states SET state=(?1), height=(?2) WHERE UUID=(?3)";
+            match tx.execute(query, params![state.to_db_data(), height, uuid.to_vec()]) {
+                Ok(_) => log::debug!("Appointment state update added to db transaction"),
+                Err(e) => log::error!("Couldn't add update query to transaction. Error: {:?}", e),
+            }
+        }
+
+        match tx.commit() {
+            Ok(_) => log::debug!("Appointment states successfully updated ({})", state),
+            Err(e) => log::error!("Couldn't update appointment states. Error: {:?}", e),
+        }
+    }
+
+    /// Loads the number of appointments in each [AppointmentState].
+    pub(crate) fn load_appointment_state_counts(&self) -> HashMap<AppointmentState, usize> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT state, COUNT(*) FROM appointment_states GROUP BY state")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut counts = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let state: u8 = row.get(0).unwrap();
+            let count: i64 = row.get(1).unwrap();
+            if let Some(state) = AppointmentState::from_db_data(state) {
+                counts.insert(state, count as usize);
+            }
+        }
+
+        counts
+    }
+
+    /// Removes the states of the appointments that reached a final [AppointmentState] at or before a given height.
+    ///
+    /// Any appointment data left behind is removed in cascade. Returns the number of removed states.
+    pub(crate) fn prune_appointment_states(&self, height: u32) -> usize {
+        let query = format!(
+            "DELETE FROM appointment_states WHERE height<=(?) AND state IN ({})",
+            final_states_db_data()
+        );
+
+        match self.connection.execute(&query, [height]) {
+            Ok(n) => n,
+            Err(e) => {
+                log::error!("Couldn't prune appointment states. Error: {:?}", e);
+                0
+            }
+        }
+    }
+
     /// Stores a [TransactionTracker] into the database.
     pub(crate) fn store_tracker(
         &self,
         uuid: UUID,
         tracker: &TransactionTracker,
     ) -> Result<(), Error> {
-        let (height, confirmed) = tracker.status.to_db_data().ok_or(Error::MissingField)?;
+        let _stage = telemetry::stage_span("db_write").entered();
+        let (height, status) = tracker.status.to_db_data().ok_or(Error::MissingField)?;
 
         let query =
-            "INSERT INTO trackers (UUID, dispute_tx, penalty_tx, height, confirmed) VALUES (?1, ?2, ?3, ?4, ?5)";
+            "INSERT INTO trackers (UUID, dispute_tx, penalty_tx, height, confirmed, status) VALUES (?1, ?2, ?3, ?4, ?5, ?6)";
         match self.store_data(
             query,
             params![
@@ -442,7 +870,8 @@ impl DBM {
                 consensus::serialize(&tracker.dispute_tx),
                 consensus::serialize(&tracker.penalty_tx),
                 height,
-                confirmed,
+                matches!(tracker.status, ConfirmationStatus::ConfirmedIn(_)),
+                status,
             ],
         ) {
             Ok(x) => {
@@ -456,12 +885,42 @@ impl DBM {
         }
     }
 
+    /// Updates the confirmation status of an existing [TransactionTracker] in the database.
+    pub(crate) fn update_tracker_status(&self, uuid: UUID, status: &ConfirmationStatus) {
+        let _stage = telemetry::stage_span("db_write").entered();
+        let (height, db_status) = match status.to_db_data() {
+            Some(data) => data,
+            None => {
+                log::error!("Tracker status cannot be stored: {}", uuid);
+                return;
+            }
+        };
+
+        let query = "UPDATE trackers SET height=(?1), confirmed=(?2), status=(?3) WHERE UUID=(?4)";
+        match self.update_data(
+            query,
+            params![
+                height,
+                matches!(status, ConfirmationStatus::ConfirmedIn(_)),
+                db_status,
+                uuid.to_vec()
+            ],
+        ) {
+            Ok(_) => {
+                log::debug!("Tracker successfully updated: {}", uuid);
+            }
+            Err(_) => {
+                log::error!("Tracker not found, data cannot be updated: {}", uuid);
+            }
+        }
+    }
+
     /// Loads a [TransactionTracker] from the database.
     pub(crate) fn load_tracker(&self, uuid: UUID) -> Result<TransactionTracker, Error> {
         let key = uuid.to_vec();
         let mut stmt = self
             .connection.prepare(
-                "SELECT t.dispute_tx, t.penalty_tx, t.height, t.confirmed, a.user_id
+                "SELECT t.dispute_tx, t.penalty_tx, t.height, t.status, a.user_id
                     FROM trackers as t INNER JOIN appointments as a ON t.UUID=a.UUID WHERE t.UUID=(?)"
             )
             .unwrap();
@@ -470,7 +929,7 @@ impl DBM {
             let raw_dispute_tx: Vec<u8> = row.get(0).unwrap();
             let raw_penalty_tx: Vec<u8> = row.get(1).unwrap();
             let height: u32 = row.get(2).unwrap();
-            let confirmed: bool = row.get(3).unwrap();
+            let status: u8 = row.get(3).unwrap();
             let raw_userid: Vec<u8> = row.get(4).unwrap();
 
             let dispute_tx = consensus::deserialize(&raw_dispute_tx).unwrap();
@@ -480,7 +939,7 @@ impl DBM {
             Ok(TransactionTracker {
                 dispute_tx,
                 penalty_tx,
-                status: ConfirmationStatus::from_db_data(height, confirmed),
+                status: ConfirmationStatus::from_db_data(height, status),
                 user_id,
             })
         })
@@ -495,7 +954,7 @@ impl DBM {
     ) -> HashMap<UUID, TransactionTracker> {
         let mut trackers = HashMap::new();
 
-        let mut sql = "SELECT t.UUID, t.dispute_tx, t.penalty_tx, t.height, t.confirmed, a.user_id
+        let mut sql = "SELECT t.UUID, t.dispute_tx, t.penalty_tx, t.height, t.status, a.user_id
             FROM trackers as t INNER JOIN appointments as a ON t.UUID=a.UUID"
             .to_string();
         // If a locator was passed, filter based on it.
@@ -518,7 +977,7 @@ impl DBM {
             let raw_penalty_tx: Vec<u8> = row.get(2).unwrap();
             let penalty_tx = consensus::deserialize(&raw_penalty_tx).unwrap();
             let height: u32 = row.get(3).unwrap();
-            let confirmed: bool = row.get(4).unwrap();
+            let status: u8 = row.get(4).unwrap();
             let raw_userid: Vec<u8> = row.get(5).unwrap();
             let user_id = UserId::from_slice(&raw_userid).unwrap();
 
@@ -527,7 +986,7 @@ impl DBM {
                 TransactionTracker {
                     dispute_tx,
                     penalty_tx,
-                    status: ConfirmationStatus::from_db_data(height, confirmed),
+                    status: ConfirmationStatus::from_db_data(height, status),
                     user_id,
                 },
             );
@@ -536,79 +995,439 @@ impl DBM {
         trackers
     }
 
-    /// Stores the last known block into the database.
-    pub(crate) fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error> {
-        let query = "INSERT OR REPLACE INTO last_known_block (id, block_hash) VALUES (0, ?)";
-        self.store_data(query, params![block_hash.to_vec()])
+    /// Checks the consistency between the user and appointment data stored in the database, removing orphan data (if any).
+    ///
+    /// [UserInfo] appointments are built from the appointments table, so this boils down to finding appointments whose
+    /// user cannot be found, and trackers whose appointment cannot be found. Orphan data can only be the result of the
+    /// database being modified while foreign keys were disabled (e.g. manually).
+    ///
+    /// Once orphan data is gone, the users' `available_slots` are checked against the slots they have been granted
+    /// (check [DBM::repair_available_slots]).
+    ///
+    /// Returns the number of orphan appointments and trackers that have been removed (trackers of orphan appointments
+    /// included), and the number of users whose slots have been repaired.
+    pub fn remove_orphan_data(&mut self) -> (usize, usize, usize) {
+        let orphan_appointments = self.load_uuids(
+            "SELECT a.UUID FROM appointments as a LEFT JOIN users as u ON a.user_id=u.user_id WHERE u.user_id IS NULL",
+        );
+        // Trackers of orphan appointments are orphan too
+        let orphan_trackers = self.load_uuids(
+            "SELECT t.UUID FROM trackers as t LEFT JOIN appointments as a ON t.UUID=a.UUID LEFT JOIN users as u ON a.user_id=u.user_id WHERE u.user_id IS NULL",
+        );
+
+        for uuid in orphan_appointments.iter() {
+            log::warn!(
+                "Appointment belongs to an unknown user. Removing it: {}",
+                uuid
+            );
+        }
+        for uuid in orphan_trackers.iter() {
+            log::warn!(
+                "Tracker belongs to an unknown (or orphan) appointment. Removing it: {}",
+                uuid
+            );
+        }
+
+        if !orphan_appointments.is_empty() || !orphan_trackers.is_empty() {
+            let tx = self.connection.transaction().unwrap();
+            for query in [
+                "DELETE FROM trackers WHERE UUID IN (SELECT t.UUID FROM trackers as t LEFT JOIN appointments as a ON t.UUID=a.UUID LEFT JOIN users as u ON a.user_id=u.user_id WHERE u.user_id IS NULL)",
+                "DELETE FROM appointments WHERE UUID IN (SELECT a.UUID FROM appointments as a LEFT JOIN users as u ON a.user_id=u.user_id WHERE u.user_id IS NULL)",
+            ] {
+                if let Err(e) = tx.execute(query, []) {
+                    log::error!("Couldn't add deletion query to transaction. Error: {:?}", e);
+                }
+            }
+
+            match tx.commit() {
+                Ok(_) => log::debug!("Orphan data successfully deleted"),
+                Err(e) => log::error!("Couldn't delete orphan data. Error: {:?}", e),
+            }
+        }
+
+        (
+            orphan_appointments.len(),
+            orphan_trackers.len(),
+            self.repair_available_slots(),
+        )
     }
 
-    /// Loads the last known block from the database.
-    pub fn load_last_known_block(&self) -> Result<BlockHash, Error> {
+    /// Loads the slots taken by the appointments of each user.
+    fn load_used_slots(&self) -> HashMap<UserId, u64> {
+        let mut used_slots: HashMap<UserId, u64> = HashMap::new();
         let mut stmt = self
             .connection
-            .prepare("SELECT block_hash FROM last_known_block WHERE id=0")
+            .prepare("SELECT user_id, length(encrypted_blob) FROM appointments")
             .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_userid: Vec<u8> = row.get(0).unwrap();
+            let blob_len: usize = row.get(1).unwrap();
+            *used_slots
+                .entry(UserId::from_slice(&raw_userid).unwrap())
+                .or_default() +=
+                compute_appointment_slots(blob_len, ENCRYPTED_BLOB_MAX_SIZE) as u64;
+        }
 
-        stmt.query_row([], |row| {
-            let raw_hash: Vec<u8> = row.get(0).unwrap();
-            Ok(BlockHash::from_slice(&raw_hash).unwrap())
-        })
-        .map_err(|_| Error::NotFound)
+        used_slots
     }
 
-    /// Stores the tower secret key into the database.
+    /// Checks the users' `available_slots` against the slots they have been granted.
     ///
-    /// When a new key is generated, old keys are not overwritten but are not retrievable from the API either.
-    pub fn store_tower_key(&self, sk: &SecretKey) -> Result<(), Error> {
-        let query = "INSERT INTO keys (key) VALUES (?)";
-        self.store_data(query, params![sk.display_secret().to_string()])
-    }
-
-    /// Loads the last known tower secret key from the database.
+    /// Slots are only moved between the available ones and the ones taken by the user's appointments, so both must add
+    /// up to the granted slots. Anything else means appointments have been added or removed without the user slots being
+    /// updated (e.g. the tower crashed in between, or the database has been modified by hand), in which case the
+    /// available slots are set back to the granted ones minus the ones taken.
     ///
-    /// Loads the key with higher id from the database. Old keys are not overwritten just in case a recovery is needed,
-    /// but they are not accessible from the API either.
-    pub fn load_tower_key(&self) -> Result<SecretKey, Error> {
-        let mut stmt = self
-            .connection
-            .prepare(
-                "SELECT key FROM keys WHERE id = (SELECT seq FROM sqlite_sequence WHERE name=(?))",
-            )
-            .unwrap();
+    /// Returns the number of users that have been repaired.
+    fn repair_available_slots(&mut self) -> usize {
+        let used_slots = self.load_used_slots();
+        let mut granted: HashMap<UserId, (u32, u64)> = HashMap::new();
+        {
+            let mut stmt = self
+                .connection
+                .prepare("SELECT user_id, available_slots, granted_slots FROM users")
+                .unwrap();
+            let mut rows = stmt.query([]).unwrap();
+            while let Ok(Some(row)) = rows.next() {
+                let raw_userid: Vec<u8> = row.get(0).unwrap();
+                granted.insert(
+                    UserId::from_slice(&raw_userid).unwrap(),
+                    (row.get(1).unwrap(), row.get(2).unwrap()),
+                );
+            }
+        }
 
-        stmt.query_row(["keys"], |row| {
-            let sk: String = row.get(0).unwrap();
-            Ok(SecretKey::from_str(&sk).unwrap())
-        })
-        .map_err(|_| Error::NotFound)
-    }
-}
+        let mut repairs = Vec::new();
+        for (user_id, (available, granted)) in granted {
+            let used = used_slots.get(&user_id).cloned().unwrap_or_default();
+            if available as u64 + used == granted {
+                continue;
+            }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::iter::FromIterator;
+            let available_slots =
+                std::cmp::min(granted.saturating_sub(used), u32::MAX as u64) as u32;
+            log::warn!(
+                "User slots are inconsistent with their appointments. Repairing them ({} -> {}): {}",
+                available,
+                available_slots,
+                user_id
+            );
+            repairs.push((user_id, available_slots));
+        }
 
-    use teos_common::cryptography::{get_random_bytes, get_random_keypair};
-    use teos_common::test_utils::get_random_user_id;
+        if !repairs.is_empty() {
+            let tx = self.connection.transaction().unwrap();
+            for (user_id, available_slots) in repairs.iter() {
+                let query = "UPDATE users SET available_slots=(?1) WHERE user_id=(?2)";
+                if let Err(e) = tx.execute(query, params![available_slots, user_id.to_vec()]) {
+                    log::error!("Couldn't add update query to transaction. Error: {:?}", e);
+                }
+            }
 
-    use crate::test_utils::{
-        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid,
-        get_random_tracker, get_random_tx, AVAILABLE_SLOTS, SUBSCRIPTION_EXPIRY,
-        SUBSCRIPTION_START,
-    };
+            match tx.commit() {
+                Ok(_) => log::debug!("User slots successfully repaired"),
+                Err(e) => log::error!("Couldn't repair user slots. Error: {:?}", e),
+            }
+        }
 
-    impl DBM {
-        pub(crate) fn in_memory() -> Result<Self, SqliteError> {
-            let connection = Connection::open_in_memory()?;
-            connection.execute("PRAGMA foreign_keys=1;", [])?;
-            let mut dbm = Self { connection };
-            dbm.create_tables(Vec::from_iter(TABLES))?;
+        repairs.len()
+    }
+
+    /// Loads a set of [UUID]s given a query returning them.
+    fn load_uuids(&self, query: &str) -> HashSet<UUID> {
+        let mut stmt = self.connection.prepare(query).unwrap();
+        let mut rows = stmt.query([]).unwrap();
 
-            Ok(dbm)
+        let mut uuids = HashSet::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            uuids.insert(UUID::from_slice(&raw_uuid[0..20]).unwrap());
         }
 
-        pub(crate) fn load_user(&self, user_id: UserId) -> Result<UserInfo, Error> {
+        uuids
+    }
+
+    /// Adds a tracker to the review queue, alongside the height at which it was flagged.
+    ///
+    /// Entries are deleted in cascade alongside their trackers.
+    pub(crate) fn store_review_entry(&self, uuid: UUID, height: u32) -> Result<(), Error> {
+        let query = "INSERT INTO review_queue (UUID, height) VALUES (?1, ?2)";
+        self.store_data(query, params![uuid.to_vec(), height])
+    }
+
+    /// Removes a tracker from the review queue (e.g. if its penalty made it to the chain after all).
+    pub(crate) fn remove_review_entry(&self, uuid: UUID) {
+        let query = "DELETE FROM review_queue WHERE UUID=(?)";
+        match self.remove_data(query, params![uuid.to_vec()]) {
+            Ok(_) => log::debug!("Review entry successfully removed: {}", uuid),
+            Err(_) => log::error!("Review entry not found, data cannot be removed: {}", uuid),
+        }
+    }
+
+    /// Loads the review queue from the database, that is, the trackers flagged for review and the height they were
+    /// flagged at.
+    pub(crate) fn load_review_queue(&self) -> HashMap<UUID, u32> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT UUID, height FROM review_queue")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut review_queue = HashMap::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            review_queue.insert(
+                UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                row.get(1).unwrap(),
+            );
+        }
+
+        review_queue
+    }
+
+    /// Flags a tracker as having a penalty that does not clear the mempool min fee.
+    ///
+    /// Flags are deleted in cascade alongside their trackers.
+    pub(crate) fn store_low_fee_tracker(&self, uuid: UUID) -> Result<(), Error> {
+        let query = "INSERT INTO low_fee_trackers (UUID) VALUES (?1)";
+        self.store_data(query, params![uuid.to_vec()])
+    }
+
+    /// Removes the low fee flag of a tracker (e.g. if its penalty cleared the mempool min fee after all).
+    pub(crate) fn remove_low_fee_tracker(&self, uuid: UUID) {
+        let query = "DELETE FROM low_fee_trackers WHERE UUID=(?)";
+        match self.remove_data(query, params![uuid.to_vec()]) {
+            Ok(_) => log::debug!("Low fee flag successfully removed: {}", uuid),
+            Err(_) => log::error!("Low fee flag not found, data cannot be removed: {}", uuid),
+        }
+    }
+
+    /// Loads the trackers flagged as having a penalty that does not clear the mempool min fee.
+    pub(crate) fn load_low_fee_trackers(&self) -> HashSet<UUID> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT UUID FROM low_fee_trackers")
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut uuids = HashSet::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            uuids.insert(UUID::from_slice(&raw_uuid[0..20]).unwrap());
+        }
+
+        uuids
+    }
+
+    /// Stores a receipt that is waiting for its batch to be signed.
+    ///
+    /// Receipts are kept after their batch is closed (until pruned), so batches can be rebuilt if the tower restarts.
+    pub(crate) fn store_batch_receipt(
+        &self,
+        uuid: UUID,
+        receipt: &AppointmentReceipt,
+    ) -> Result<(), Error> {
+        let query = "INSERT OR REPLACE INTO batch_receipts (UUID, user_signature, start_block) VALUES (?1, ?2, ?3)";
+        self.store_data(
+            query,
+            params![
+                uuid.to_vec(),
+                receipt.user_signature(),
+                receipt.start_block()
+            ],
+        )
+    }
+
+    /// Loads all the batched receipts from the database, sorted by the height they were issued at.
+    pub(crate) fn load_batch_receipts(&self) -> Vec<(UUID, AppointmentReceipt)> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT UUID, user_signature, start_block FROM batch_receipts ORDER BY start_block",
+            )
+            .unwrap();
+        let mut rows = stmt.query([]).unwrap();
+
+        let mut receipts = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_uuid: Vec<u8> = row.get(0).unwrap();
+            receipts.push((
+                UUID::from_slice(&raw_uuid[0..20]).unwrap(),
+                AppointmentReceipt::new(row.get(1).unwrap(), row.get(2).unwrap()),
+            ));
+        }
+
+        receipts
+    }
+
+    /// Deletes the batched receipts issued at, or before, a given height. Returns the number of receipts deleted.
+    pub(crate) fn prune_batch_receipts(&self, height: u32) -> usize {
+        match self.connection.execute(
+            "DELETE FROM batch_receipts WHERE start_block<=(?)",
+            [height],
+        ) {
+            Ok(n) => n,
+            Err(e) => {
+                log::error!("Couldn't prune batched receipts. Error: {:?}", e);
+                0
+            }
+        }
+    }
+
+    /// Adds some responded breaches to the breach log, identified by their penalty [Txid] and confirmation height.
+    ///
+    /// Penalties already in the log are ignored, so a penalty shared by several appointments is only logged once.
+    pub(crate) fn store_breach_log_entries(&mut self, entries: &[(Txid, u32)]) {
+        let tx = self.connection.transaction().unwrap();
+        for (txid, height) in entries.iter() {
+            let query = "INSERT OR IGNORE INTO breach_log (penalty_txid, height) VALUES (?1, ?2)";
+            match tx.execute(query, params![txid.to_vec(), height]) {
+                Ok(_) => log::debug!("Breach log entry added to db transaction"),
+                Err(e) => log::error!(
+                    "Couldn't add breach log entry to transaction. Error: {:?}",
+                    e
+                ),
+            }
+        }
+
+        match tx.commit() {
+            Ok(_) => log::debug!("Breach log entries successfully stored"),
+            Err(e) => log::error!("Couldn't store breach log entries. Error: {:?}", e),
+        }
+    }
+
+    /// Loads the breach log entries within a given block range (both ends included), sorted by height.
+    pub(crate) fn load_breach_log(&self, from_height: u32, to_height: u32) -> Vec<(Txid, u32)> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT penalty_txid, height FROM breach_log WHERE height BETWEEN (?1) AND (?2) ORDER BY height, penalty_txid",
+            )
+            .unwrap();
+        let mut rows = stmt.query([from_height, to_height]).unwrap();
+
+        let mut entries = Vec::new();
+        while let Ok(Some(row)) = rows.next() {
+            let raw_txid: Vec<u8> = row.get(0).unwrap();
+            entries.push((Txid::from_slice(&raw_txid).unwrap(), row.get(1).unwrap()));
+        }
+
+        entries
+    }
+
+    /// Stores the last known block into the database.
+    pub(crate) fn store_last_known_block(&self, block_hash: &BlockHash) -> Result<(), Error> {
+        let query = "INSERT OR REPLACE INTO last_known_block (id, block_hash) VALUES (0, ?)";
+        self.store_data(query, params![block_hash.to_vec()])
+    }
+
+    /// Loads the last known block from the database.
+    pub fn load_last_known_block(&self) -> Result<BlockHash, Error> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT block_hash FROM last_known_block WHERE id=0")
+            .unwrap();
+
+        stmt.query_row([], |row| {
+            let raw_hash: Vec<u8> = row.get(0).unwrap();
+            Ok(BlockHash::from_slice(&raw_hash).unwrap())
+        })
+        .map_err(|_| Error::NotFound)
+    }
+
+    /// Stores the tower secret key into the database.
+    ///
+    /// When a new key is generated, old keys are not overwritten but are not retrievable from the API either.
+    pub fn store_tower_key(&self, sk: &SecretKey) -> Result<(), Error> {
+        let query = "INSERT INTO keys (key) VALUES (?)";
+        self.store_data(query, params![sk.display_secret().to_string()])
+    }
+
+    /// Loads the last known tower secret key from the database.
+    ///
+    /// Loads the key with higher id from the database. Old keys are not overwritten just in case a recovery is needed,
+    /// but they are not accessible from the API either.
+    pub fn load_tower_key(&self) -> Result<SecretKey, Error> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT key FROM keys WHERE id = (SELECT seq FROM sqlite_sequence WHERE name=(?))",
+            )
+            .unwrap();
+
+        stmt.query_row(["keys"], |row| {
+            let sk: String = row.get(0).unwrap();
+            Ok(SecretKey::from_str(&sk).unwrap())
+        })
+        .map_err(|_| Error::NotFound)
+    }
+
+    /// Stores the height at which the tower rotated into its current key (the last stored one).
+    ///
+    /// The key the tower rotated out of can then be loaded using [load_previous_tower_key](Self::load_previous_tower_key).
+    pub fn store_key_rotation(&self, height: u32) -> Result<(), Error> {
+        let query = "INSERT INTO key_rotations (key_id, height) VALUES ((SELECT seq FROM sqlite_sequence WHERE name='keys'), ?)";
+        self.store_data(query, params![height])
+    }
+
+    /// Loads the key the tower rotated out of, alongside the height at which the rotation happened.
+    ///
+    /// Returns [Error::NotFound] if the current key does not come from a rotation (or there is no previous key).
+    pub fn load_previous_tower_key(&self) -> Result<(SecretKey, u32), Error> {
+        let mut stmt = self
+            .connection
+            .prepare(
+                "SELECT keys.key, key_rotations.height FROM key_rotations
+                    JOIN keys ON keys.id = (SELECT MAX(id) FROM keys WHERE id < key_rotations.key_id)
+                    WHERE key_rotations.key_id = (SELECT seq FROM sqlite_sequence WHERE name='keys')",
+            )
+            .unwrap();
+
+        stmt.query_row([], |row| {
+            let sk: String = row.get(0).unwrap();
+            Ok((SecretKey::from_str(&sk).unwrap(), row.get(1).unwrap()))
+        })
+        .map_err(|_| Error::NotFound)
+    }
+
+    /// Stores the network the database belongs to.
+    ///
+    /// This is only expected to be set once, when the database is created, so it can be checked on every restart.
+    pub fn store_network(&self, network: &str) -> Result<(), Error> {
+        let query = "INSERT INTO network (id, name) VALUES (0, ?)";
+        self.store_data(query, params![network])
+    }
+
+    /// Loads the network the database belongs to.
+    pub fn load_network(&self) -> Result<String, Error> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT name FROM network WHERE id=0")
+            .unwrap();
+
+        stmt.query_row([], |row| row.get(0))
+            .map_err(|_| Error::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::FromIterator;
+    use tempdir::TempDir;
+
+    use teos_common::cryptography::{get_random_bytes, get_random_keypair};
+    use teos_common::test_utils::get_random_user_id;
+
+    use crate::test_utils::{
+        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid,
+        get_random_tracker, get_random_tx, AVAILABLE_SLOTS, SUBSCRIPTION_EXPIRY,
+        SUBSCRIPTION_START,
+    };
+
+    impl DBM {
+        pub(crate) fn load_user(&self, user_id: UserId) -> Result<UserInfo, Error> {
             let key = user_id.to_vec();
             let mut stmt = self
                 .connection
@@ -640,6 +1459,37 @@ mod tests {
         let connection = Connection::open_in_memory().unwrap();
         let mut dbm = DBM { connection };
         dbm.create_tables(Vec::from_iter(TABLES)).unwrap();
+        dbm.create_indices().unwrap();
+    }
+
+    impl DBM {
+        fn query_plan(&self, query: &str) -> String {
+            let mut stmt = self
+                .connection
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", query))
+                .unwrap();
+            let details = stmt
+                .query_map([get_random_user_id().to_vec()], |row| row.get(3))
+                .unwrap()
+                .collect::<Result<Vec<String>, _>>()
+                .unwrap();
+            details.join("\n")
+        }
+    }
+
+    #[test]
+    fn test_user_data_indices() {
+        // Queries (and cascades) over the user data must not scan the whole table
+        let dbm = DBM::in_memory().unwrap();
+        assert!(dbm
+            .query_plan("SELECT UUID FROM appointments WHERE user_id=(?)")
+            .contains("USING COVERING INDEX appointments_user_id"));
+        assert!(dbm
+            .query_plan("SELECT UUID FROM appointment_states WHERE user_id=(?)")
+            .contains("USING COVERING INDEX appointment_states_user_id"));
+        assert!(dbm
+            .query_plan("SELECT UUID, encrypted_blob FROM appointments WHERE user_id=(?)")
+            .contains("USING INDEX appointments_user_id"));
     }
 
     #[test]
@@ -1024,7 +1874,12 @@ mod tests {
 
             // Check that the db transaction had i queries on it
             assert_eq!(
-                dbm.batch_remove_appointments(&to_be_deleted, &updated_users),
+                dbm.batch_remove_appointments(
+                    &to_be_deleted,
+                    &updated_users,
+                    AppointmentState::Rejected,
+                    i as u32
+                ),
                 i as usize
             );
             // Check appointment data was deleted and users properly updated
@@ -1058,6 +1913,8 @@ mod tests {
         dbm.batch_remove_appointments(
             &HashSet::from_iter(vec![uuid]),
             &HashMap::from_iter([(appointment.user_id, info.clone())]),
+            AppointmentState::Rejected,
+            21,
         );
         assert!(matches!(dbm.load_appointment(uuid), Err(Error::NotFound)));
 
@@ -1071,6 +1928,8 @@ mod tests {
         dbm.batch_remove_appointments(
             &HashSet::from_iter(vec![uuid]),
             &HashMap::from_iter([(appointment.user_id, info)]),
+            AppointmentState::Rejected,
+            21,
         );
         assert!(matches!(dbm.load_appointment(uuid), Err(Error::NotFound)));
         assert!(matches!(dbm.load_tracker(uuid), Err(Error::NotFound)));
@@ -1082,7 +1941,12 @@ mod tests {
         let appointments = (0..10).map(|_| generate_uuid()).collect();
 
         // Test it does not fail even if the user does not exist (it will log though)
-        dbm.batch_remove_appointments(&appointments, &HashMap::new());
+        dbm.batch_remove_appointments(
+            &appointments,
+            &HashMap::new(),
+            AppointmentState::Rejected,
+            21,
+        );
     }
     #[test]
     fn test_load_locator() {
@@ -1125,10 +1989,181 @@ mod tests {
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
         dbm.store_appointment(uuid, &appointment).unwrap();
 
-        // The confirmation status doesn't really matter here, it can be any of {ConfirmedIn, InMempoolSince}.
+        // Any of the stored statuses {ConfirmedIn, InMempoolSince, DelayedUntil} is loaded back as is.
         let tracker = get_random_tracker(user_id, ConfirmationStatus::ConfirmedIn(21));
         assert!(matches!(dbm.store_tracker(uuid, &tracker), Ok { .. }));
         assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
+
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        let tracker = get_random_tracker(user_id, ConfirmationStatus::DelayedUntil(42));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+        assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
+    }
+
+    #[test]
+    fn test_migrate_tracker_status() {
+        let tmp_path = TempDir::new("migrate_tracker_status").unwrap();
+        let db_path = tmp_path.path().join("teos_db.sql3");
+        let user_id = get_random_user_id();
+        let (confirmed_uuid, confirmed_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+        let (mempool_uuid, mempool_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+
+        {
+            // Trackers stored by versions of the tower with no status column
+            let dbm = DBM::new(db_path.clone()).unwrap();
+            dbm.connection
+                .execute("ALTER TABLE trackers DROP COLUMN status", [])
+                .unwrap();
+            let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+            dbm.store_user(user_id, &user).unwrap();
+            for (uuid, appointment, confirmed) in [
+                (confirmed_uuid, &confirmed_appointment, true),
+                (mempool_uuid, &mempool_appointment, false),
+            ] {
+                dbm.store_appointment(uuid, appointment).unwrap();
+                let tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42));
+                dbm.connection
+                    .execute(
+                        "INSERT INTO trackers (UUID, dispute_tx, penalty_tx, height, confirmed) VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            uuid.to_vec(),
+                            consensus::serialize(&tracker.dispute_tx),
+                            consensus::serialize(&tracker.penalty_tx),
+                            42,
+                            confirmed
+                        ],
+                    )
+                    .unwrap();
+            }
+        }
+
+        // The status is built out of whether the trackers were confirmed
+        let dbm = DBM::new(db_path).unwrap();
+        assert_eq!(
+            dbm.load_tracker(confirmed_uuid).unwrap().status,
+            ConfirmationStatus::ConfirmedIn(42)
+        );
+        assert_eq!(
+            dbm.load_tracker(mempool_uuid).unwrap().status,
+            ConfirmationStatus::InMempoolSince(42)
+        );
+    }
+
+    #[test]
+    fn test_update_tracker_status() {
+        let dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+
+        let mut tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+
+        // Only the status is updated
+        tracker.status = ConfirmationStatus::InMempoolSince(50);
+        dbm.update_tracker_status(uuid, &tracker.status);
+        assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
+
+        // Statuses that cannot be stored are ignored
+        dbm.update_tracker_status(uuid, &ConfirmationStatus::ReorgedOut);
+        assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
+    }
+
+    #[test]
+    fn test_store_load_review_queue() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        // Only trackers can be flagged for review
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        assert!(matches!(
+            dbm.store_review_entry(uuid, 42),
+            Err(Error::MissingForeignKey)
+        ));
+
+        let mut review_queue = HashMap::new();
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        let tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(21));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+        dbm.store_review_entry(uuid, 42).unwrap();
+        review_queue.insert(uuid, 42);
+
+        let (another_uuid, another_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(another_uuid, &another_appointment)
+            .unwrap();
+        dbm.store_tracker(another_uuid, &tracker).unwrap();
+        dbm.store_review_entry(another_uuid, 43).unwrap();
+        review_queue.insert(another_uuid, 43);
+        assert_eq!(dbm.load_review_queue(), review_queue);
+
+        // Entries can be removed on their own or alongside their trackers
+        dbm.remove_review_entry(uuid);
+        review_queue.remove(&uuid);
+        assert_eq!(dbm.load_review_queue(), review_queue);
+
+        dbm.batch_remove_appointments(
+            &HashSet::from_iter([another_uuid]),
+            &HashMap::new(),
+            AppointmentState::Rejected,
+            42,
+        );
+        assert!(dbm.load_review_queue().is_empty());
+    }
+
+    #[test]
+    fn test_store_load_low_fee_trackers() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        // Only trackers can be flagged
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        assert!(matches!(
+            dbm.store_low_fee_tracker(uuid),
+            Err(Error::MissingForeignKey)
+        ));
+
+        let mut low_fee_trackers = HashSet::new();
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        let tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(21));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+        dbm.store_low_fee_tracker(uuid).unwrap();
+        low_fee_trackers.insert(uuid);
+
+        let (another_uuid, another_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(another_uuid, &another_appointment)
+            .unwrap();
+        dbm.store_tracker(another_uuid, &tracker).unwrap();
+        dbm.store_low_fee_tracker(another_uuid).unwrap();
+        low_fee_trackers.insert(another_uuid);
+        assert_eq!(dbm.load_low_fee_trackers(), low_fee_trackers);
+
+        // Flags can be removed on their own or alongside their trackers
+        dbm.remove_low_fee_tracker(uuid);
+        low_fee_trackers.remove(&uuid);
+        assert_eq!(dbm.load_low_fee_trackers(), low_fee_trackers);
+
+        dbm.batch_remove_appointments(
+            &HashSet::from_iter([another_uuid]),
+            &HashMap::new(),
+            AppointmentState::Rejected,
+            42,
+        );
+        assert!(dbm.load_low_fee_trackers().is_empty());
     }
 
     #[test]
@@ -1239,6 +2274,441 @@ mod tests {
         assert_eq!(dbm.load_trackers(Some(locator)), trackers);
     }
 
+    #[test]
+    fn test_remove_orphan_data() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        // Consistent data is left untouched. The user has been granted AVAILABLE_SLOTS + 1 slots, one of which is taken
+        // by the appointment
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        let user = UserInfo::with_appointments(
+            AVAILABLE_SLOTS,
+            SUBSCRIPTION_START,
+            SUBSCRIPTION_EXPIRY,
+            HashMap::from([(uuid, 1)]),
+        );
+        dbm.store_user(user_id, &user).unwrap();
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        let tracker = get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42));
+        dbm.store_tracker(uuid, &tracker).unwrap();
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 0));
+
+        // Orphan data can only be stored with foreign keys disabled
+        dbm.connection
+            .execute("PRAGMA foreign_keys=0;", [])
+            .unwrap();
+        let orphan_user_id = get_random_user_id();
+        let (orphan_appointment_uuid, orphan_appointment) =
+            generate_dummy_appointment_with_user(orphan_user_id, None);
+        dbm.store_appointment(orphan_appointment_uuid, &orphan_appointment)
+            .unwrap();
+        // Trackers of orphan appointments are orphan too
+        dbm.store_tracker(
+            orphan_appointment_uuid,
+            &get_random_tracker(orphan_user_id, ConfirmationStatus::InMempoolSince(42)),
+        )
+        .unwrap();
+        let orphan_tracker_uuid = generate_uuid();
+        dbm.store_tracker(
+            orphan_tracker_uuid,
+            &get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42)),
+        )
+        .unwrap();
+        dbm.connection
+            .execute("PRAGMA foreign_keys=1;", [])
+            .unwrap();
+
+        assert_eq!(dbm.remove_orphan_data(), (1, 2, 0));
+        assert!(matches!(
+            dbm.load_appointment(orphan_appointment_uuid),
+            Err(Error::NotFound)
+        ));
+        assert_eq!(
+            dbm.load_uuids("SELECT UUID FROM trackers"),
+            HashSet::from_iter([uuid])
+        );
+        assert_eq!(dbm.load_appointment(uuid).unwrap(), appointment);
+        assert_eq!(dbm.load_tracker(uuid).unwrap(), tracker);
+
+        // Nothing is left to be removed
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 0));
+
+        // Appointments removed without freeing the user slots are given back to the user
+        dbm.connection
+            .execute("DELETE FROM appointments WHERE UUID=(?)", [uuid.to_vec()])
+            .unwrap();
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 1));
+        assert_eq!(
+            dbm.load_user(user_id).unwrap().available_slots,
+            AVAILABLE_SLOTS + 1
+        );
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 0));
+
+        // Users whose slots add up are left untouched no matter how many slots they have been granted
+        let other_user_id = get_random_user_id();
+        let other_user = UserInfo::new(
+            AVAILABLE_SLOTS * 3 + 7,
+            SUBSCRIPTION_START,
+            SUBSCRIPTION_EXPIRY,
+        );
+        dbm.store_user(other_user_id, &other_user).unwrap();
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 0));
+        assert_eq!(
+            dbm.load_user(other_user_id).unwrap().available_slots,
+            AVAILABLE_SLOTS * 3 + 7
+        );
+    }
+
+    #[test]
+    fn test_migrate_granted_slots() {
+        let tmp_path = TempDir::new("migrate_granted_slots").unwrap();
+        let db_path = tmp_path.path().join("teos_db.sql3");
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+
+        {
+            // Users stored by versions of the tower with no granted slots column
+            let dbm = DBM::new(db_path.clone()).unwrap();
+            let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+            dbm.store_user(user_id, &user).unwrap();
+            dbm.store_appointment(uuid, &appointment).unwrap();
+            dbm.connection
+                .execute("ALTER TABLE users DROP COLUMN granted_slots", [])
+                .unwrap();
+        }
+
+        // The granted slots are backfilled out of the available slots and the ones taken by the appointments
+        let mut dbm = DBM::new(db_path).unwrap();
+        let granted: u64 = dbm
+            .connection
+            .query_row(
+                "SELECT granted_slots FROM users WHERE user_id=(?)",
+                [user_id.to_vec()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(granted, AVAILABLE_SLOTS as u64 + 1);
+        assert_eq!(dbm.remove_orphan_data(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_update_load_appointment_state() {
+        let dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        // Appointments with no state are received
+        let uuid = generate_uuid();
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Received);
+
+        // Valid transitions are persisted
+        assert!(dbm.update_appointment_state(uuid, user_id, AppointmentState::Accepted, 42));
+        assert!(dbm.update_appointment_state(uuid, user_id, AppointmentState::Watched, 42));
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Watched);
+
+        // Invalid ones are not
+        assert!(!dbm.update_appointment_state(uuid, user_id, AppointmentState::Completed, 43));
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Watched);
+
+        // States belong to users, so they cannot be stored for unknown users
+        assert!(!dbm.update_appointment_state(
+            generate_uuid(),
+            get_random_user_id(),
+            AppointmentState::Accepted,
+            42
+        ));
+    }
+
+    #[test]
+    fn test_batch_update_appointment_states() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        let mut watched = HashSet::new();
+        for _ in 0..5 {
+            let uuid = generate_uuid();
+            dbm.update_appointment_state(uuid, user_id, AppointmentState::Accepted, 42);
+            dbm.update_appointment_state(uuid, user_id, AppointmentState::Watched, 42);
+            watched.insert(uuid);
+        }
+        let accepted = generate_uuid();
+        dbm.update_appointment_state(accepted, user_id, AppointmentState::Accepted, 42);
+
+        // Only the appointments that can transition are updated
+        let mut uuids = watched.clone();
+        uuids.insert(accepted);
+        dbm.batch_update_appointment_states(&uuids, AppointmentState::Outdated, 50);
+
+        for uuid in watched.iter() {
+            assert_eq!(
+                dbm.load_appointment_state(*uuid),
+                AppointmentState::Outdated
+            );
+        }
+        assert_eq!(
+            dbm.load_appointment_state(accepted),
+            AppointmentState::Accepted
+        );
+        assert_eq!(
+            dbm.load_appointment_state_counts(),
+            HashMap::from_iter([
+                (AppointmentState::Outdated, 5),
+                (AppointmentState::Accepted, 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_appointment_states_cascade() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+        let uuid = generate_uuid();
+        dbm.update_appointment_state(uuid, user_id, AppointmentState::Accepted, 42);
+        let rejected_uuid = generate_uuid();
+        dbm.update_appointment_state(rejected_uuid, user_id, AppointmentState::Rejected, 42);
+
+        // Non-final states are deleted alongside their user, whereas final ones are kept until pruned
+        dbm.batch_remove_users(&HashSet::from_iter([user_id]));
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Received);
+        assert_eq!(
+            dbm.load_appointment_state(rejected_uuid),
+            AppointmentState::Rejected
+        );
+        assert_eq!(
+            dbm.load_appointment_state_counts(),
+            HashMap::from_iter([(AppointmentState::Rejected, 1)])
+        );
+
+        assert_eq!(dbm.prune_appointment_states(42), 1);
+        assert!(dbm.load_appointment_state_counts().is_empty());
+    }
+
+    #[test]
+    fn test_migrate_appointments_fk_restores_foreign_keys() {
+        let mut dbm = DBM::in_memory().unwrap();
+        dbm.connection
+            .execute_batch(
+                "PRAGMA foreign_keys=0;
+                CREATE TABLE appointments_old AS SELECT * FROM appointments;
+                DROP TABLE appointments;
+                ALTER TABLE appointments_old RENAME TO appointments;
+                CREATE TABLE appointments_new (UUID INT PRIMARY KEY);
+                PRAGMA foreign_keys=1;",
+            )
+            .unwrap();
+
+        // The rebuild fails (the new table cannot be created), but foreign keys are turned back on anyway
+        assert!(dbm.migrate_appointments_fk().is_err());
+        let foreign_keys: bool = dbm
+            .connection
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+    }
+
+    #[test]
+    fn test_backfill_appointment_states() {
+        let tmp_path = TempDir::new("backfill_appointment_states").unwrap();
+        let db_path = tmp_path.path().join("teos_db.sql3");
+        let user_id = get_random_user_id();
+        let (watched_uuid, watched_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+        let (triggered_uuid, triggered_appointment) =
+            generate_dummy_appointment_with_user(user_id, None);
+
+        {
+            // Appointments stored by versions of the tower with no appointment states
+            let dbm = DBM::new(db_path.clone()).unwrap();
+            let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+            dbm.store_user(user_id, &user).unwrap();
+            dbm.store_appointment(watched_uuid, &watched_appointment)
+                .unwrap();
+            dbm.store_appointment(triggered_uuid, &triggered_appointment)
+                .unwrap();
+            dbm.store_tracker(
+                triggered_uuid,
+                &get_random_tracker(user_id, ConfirmationStatus::InMempoolSince(42)),
+            )
+            .unwrap();
+
+            dbm.connection
+                .execute_batch(
+                    "PRAGMA foreign_keys=0;
+                    DELETE FROM appointment_states;
+                    CREATE TABLE appointments_old (
+                        UUID INT PRIMARY KEY,
+                        locator INT NOT NULL,
+                        encrypted_blob BLOB NOT NULL,
+                        to_self_delay INT NOT NULL,
+                        user_signature BLOB NOT NULL,
+                        start_block INT NOT NULL,
+                        user_id INT NOT NULL,
+                        FOREIGN KEY(user_id)
+                            REFERENCES users(user_id)
+                            ON DELETE CASCADE
+                    );
+                    INSERT INTO appointments_old SELECT * FROM appointments;
+                    DROP TABLE appointments;
+                    ALTER TABLE appointments_old RENAME TO appointments;
+                    PRAGMA foreign_keys=1;",
+                )
+                .unwrap();
+        }
+
+        // Appointments with no state are backfilled depending on whether they have a tracker
+        let mut dbm = DBM::new(db_path).unwrap();
+        assert_eq!(
+            dbm.load_appointment_state(watched_uuid),
+            AppointmentState::Watched
+        );
+        assert_eq!(
+            dbm.load_appointment_state(triggered_uuid),
+            AppointmentState::Triggered
+        );
+
+        // The appointments (and their trackers) are kept when the table is rebuilt to reference their state
+        assert_eq!(
+            dbm.load_appointment(watched_uuid).unwrap(),
+            watched_appointment
+        );
+        assert_eq!(
+            dbm.load_appointment(triggered_uuid).unwrap(),
+            triggered_appointment
+        );
+        assert!(dbm.load_tracker(triggered_uuid).is_ok());
+
+        // Existing states are left untouched
+        dbm.update_appointment_state(watched_uuid, user_id, AppointmentState::Rejected, 50);
+        dbm.backfill_appointment_states().unwrap();
+        assert_eq!(
+            dbm.load_appointment_state(watched_uuid),
+            AppointmentState::Rejected
+        );
+
+        // Migrating an already migrated database does nothing
+        dbm.migrate_appointments_fk().unwrap();
+        assert_eq!(
+            dbm.load_appointment(triggered_uuid).unwrap(),
+            triggered_appointment
+        );
+    }
+
+    #[test]
+    fn test_store_appointment_state() {
+        let dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        // Appointments with no state are stored as accepted
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Accepted);
+
+        // Existing states are kept, and updating them does not remove the appointment
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.update_appointment_state(uuid, user_id, AppointmentState::Accepted, 42);
+        dbm.update_appointment_state(uuid, user_id, AppointmentState::Watched, 42);
+        dbm.store_appointment(uuid, &appointment).unwrap();
+        assert_eq!(dbm.load_appointment_state(uuid), AppointmentState::Watched);
+        dbm.update_appointment_state(uuid, user_id, AppointmentState::Triggered, 43);
+        assert_eq!(dbm.load_appointment(uuid).unwrap(), appointment);
+    }
+
+    #[test]
+    fn test_prune_appointment_states() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let user_id = get_random_user_id();
+        let user = UserInfo::new(AVAILABLE_SLOTS, SUBSCRIPTION_START, SUBSCRIPTION_EXPIRY);
+        dbm.store_user(user_id, &user).unwrap();
+
+        let (rejected, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(rejected, &appointment).unwrap();
+        dbm.batch_remove_appointments(
+            &HashSet::from_iter([rejected]),
+            &HashMap::new(),
+            AppointmentState::Rejected,
+            42,
+        );
+        let (accepted, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        dbm.store_appointment(accepted, &appointment).unwrap();
+
+        // Final states are only pruned once they are deep enough
+        assert_eq!(dbm.prune_appointment_states(41), 0);
+        assert_eq!(
+            dbm.load_appointment_state(rejected),
+            AppointmentState::Rejected
+        );
+
+        // Non-final states are never pruned
+        assert_eq!(dbm.prune_appointment_states(u32::MAX), 1);
+        assert_eq!(
+            dbm.load_appointment_state(rejected),
+            AppointmentState::Received
+        );
+        assert_eq!(
+            dbm.load_appointment_state(accepted),
+            AppointmentState::Accepted
+        );
+        assert_eq!(dbm.load_appointment(accepted).unwrap(), appointment);
+    }
+
+    #[test]
+    fn test_store_load_batch_receipts() {
+        let dbm = DBM::in_memory().unwrap();
+        assert!(dbm.load_batch_receipts().is_empty());
+
+        let mut receipts = Vec::new();
+        for i in 0..10 {
+            let receipt = AppointmentReceipt::new(format!("user_signature_{}", i), 100 + i);
+            let uuid = generate_uuid();
+            dbm.store_batch_receipt(uuid, &receipt).unwrap();
+            receipts.push((uuid, receipt));
+        }
+        assert_eq!(dbm.load_batch_receipts(), receipts);
+
+        // Receipts of the same appointment issued at different heights are kept apart
+        let update = AppointmentReceipt::new("updated".to_owned(), 110);
+        dbm.store_batch_receipt(receipts[0].0, &update).unwrap();
+        receipts.push((receipts[0].0, update));
+        assert_eq!(dbm.load_batch_receipts(), receipts);
+
+        // Receipts are pruned by height, both ends included
+        assert_eq!(dbm.prune_batch_receipts(104), 5);
+        assert_eq!(dbm.load_batch_receipts(), receipts[5..].to_vec());
+    }
+
+    #[test]
+    fn test_store_load_breach_log() {
+        let mut dbm = DBM::in_memory().unwrap();
+        assert!(dbm.load_breach_log(0, u32::MAX).is_empty());
+
+        let entries = (0..10)
+            .map(|i| (get_random_tx().txid(), 100 + i))
+            .collect::<Vec<_>>();
+        dbm.store_breach_log_entries(&entries);
+
+        // Entries are loaded by height range, both ends included
+        assert_eq!(dbm.load_breach_log(0, u32::MAX), entries);
+        assert_eq!(dbm.load_breach_log(102, 105), entries[2..6].to_vec());
+        assert!(dbm.load_breach_log(200, 300).is_empty());
+
+        // Penalties are only logged once
+        dbm.store_breach_log_entries(&[(entries[0].0, 150)]);
+        assert_eq!(dbm.load_breach_log(0, u32::MAX), entries);
+    }
+
     #[test]
     fn test_store_load_last_known_block() {
         let dbm = DBM::in_memory().unwrap();
@@ -1271,4 +2741,65 @@ mod tests {
             assert_eq!(dbm.load_tower_key().unwrap(), sk);
         }
     }
+
+    #[test]
+    fn test_store_load_key_rotation() {
+        let dbm = DBM::in_memory().unwrap();
+
+        // No key, nothing to rotate from
+        assert!(matches!(
+            dbm.load_previous_tower_key(),
+            Err(Error::NotFound)
+        ));
+        assert!(dbm.store_key_rotation(10).is_err());
+
+        // A single key has no previous key
+        let first_sk = get_random_keypair().0;
+        dbm.store_tower_key(&first_sk).unwrap();
+        assert!(matches!(
+            dbm.load_previous_tower_key(),
+            Err(Error::NotFound)
+        ));
+
+        // Rotating the key makes the first one the previous key
+        let second_sk = get_random_keypair().0;
+        dbm.store_tower_key(&second_sk).unwrap();
+        assert!(matches!(
+            dbm.load_previous_tower_key(),
+            Err(Error::NotFound)
+        ));
+        dbm.store_key_rotation(10).unwrap();
+        assert_eq!(dbm.load_previous_tower_key().unwrap(), (first_sk, 10));
+
+        // A rotation can only be recorded once per key
+        assert!(matches!(
+            dbm.store_key_rotation(11),
+            Err(Error::AlreadyExists)
+        ));
+
+        // A key stored without recording the rotation has no previous key
+        dbm.store_tower_key(&get_random_keypair().0).unwrap();
+        assert!(matches!(
+            dbm.load_previous_tower_key(),
+            Err(Error::NotFound)
+        ));
+        dbm.store_key_rotation(20).unwrap();
+        assert_eq!(dbm.load_previous_tower_key().unwrap(), (second_sk, 20));
+    }
+
+    #[test]
+    fn test_store_load_network() {
+        let dbm = DBM::in_memory().unwrap();
+
+        assert!(matches!(dbm.load_network(), Err(Error::NotFound)));
+        dbm.store_network("regtest").unwrap();
+        assert_eq!(dbm.load_network().unwrap(), "regtest");
+
+        // The network cannot be overwritten
+        assert!(matches!(
+            dbm.store_network("main"),
+            Err(Error::AlreadyExists)
+        ));
+        assert_eq!(dbm.load_network().unwrap(), "regtest");
+    }
 }
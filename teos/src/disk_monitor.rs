@@ -0,0 +1,147 @@
+//! Logic related to the DiskMonitor, the component in charge of making sure the tower does not run out of disk space.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::timeout;
+use triggered::Listener;
+
+/// How often (in seconds) the free space of the data dir volume is checked.
+pub const CHECK_INTERVAL: u64 = 60;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+/// Component in charge of monitoring the free space of the volume the tower data lives in.
+///
+/// Writing to a full disk may corrupt the database, so once the free space drops below the configured threshold
+/// the `low_disk_space` flag is raised, signaling the interfaces to stop accepting new data. Watching and responding
+/// are not affected.
+pub struct DiskMonitor {
+    /// The path to monitor (the tower data dir).
+    path: PathBuf,
+    /// The minimum free space (in bytes) before new data is rejected.
+    min_free_space: u64,
+    /// A flag that indicates whether the tower is running low on disk space.
+    low_disk_space: Arc<AtomicBool>,
+    /// A signal indicating the tower is shuting down.
+    shutdown_signal: Listener,
+}
+
+impl DiskMonitor {
+    /// Creates a new [DiskMonitor] instance.
+    pub fn new(
+        path: PathBuf,
+        min_free_space_mb: u64,
+        low_disk_space: Arc<AtomicBool>,
+        shutdown_signal: Listener,
+    ) -> Self {
+        Self {
+            path,
+            min_free_space: min_free_space_mb.saturating_mul(BYTES_PER_MB),
+            low_disk_space,
+            shutdown_signal,
+        }
+    }
+
+    /// Checks the available space of the monitored volume and updates the `low_disk_space` flag accordingly.
+    ///
+    /// If the available space cannot be queried the flag is left untouched.
+    pub fn check_free_space(&self) {
+        match fs2::available_space(&self.path) {
+            Ok(available) => self.update_state(available),
+            Err(e) => log::error!("Cannot check the free space of {:?}: {}", self.path, e),
+        }
+    }
+
+    /// Updates the `low_disk_space` flag given the available space (in bytes), alerting the operator on changes.
+    fn update_state(&self, available: u64) {
+        let low = available < self.min_free_space;
+        let was_low = self.low_disk_space.swap(low, Ordering::SeqCst);
+
+        if low && !was_low {
+            log::error!(
+                "Running low on disk space ({} MB available, {} MB required). New users and appointments will be rejected until space is freed",
+                available / BYTES_PER_MB,
+                self.min_free_space / BYTES_PER_MB
+            );
+        } else if !low && was_low {
+            log::info!(
+                "Disk space recovered ({} MB available). Accepting new users and appointments again",
+                available / BYTES_PER_MB
+            );
+        }
+    }
+
+    /// Monitors the free space of the data dir volume every [CHECK_INTERVAL] seconds until shutdown.
+    pub async fn monitor_disk(&self) {
+        loop {
+            self.check_free_space();
+            if timeout(
+                Duration::from_secs(CHECK_INTERVAL),
+                self.shutdown_signal.clone(),
+            )
+            .await
+            .is_ok()
+            {
+                log::debug!("Received shutting down signal. Shutting down");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    fn init_disk_monitor(path: PathBuf, min_free_space_mb: u64) -> DiskMonitor {
+        let (_, shutdown_signal) = triggered::trigger();
+        DiskMonitor::new(
+            path,
+            min_free_space_mb,
+            Arc::new(AtomicBool::new(false)),
+            shutdown_signal,
+        )
+    }
+
+    #[test]
+    fn test_update_state() {
+        let disk_monitor = init_disk_monitor(PathBuf::new(), 10);
+
+        // Enough space
+        disk_monitor.update_state(10 * BYTES_PER_MB);
+        assert!(!disk_monitor.low_disk_space.load(Ordering::SeqCst));
+
+        // Below the threshold
+        disk_monitor.update_state(10 * BYTES_PER_MB - 1);
+        assert!(disk_monitor.low_disk_space.load(Ordering::SeqCst));
+
+        // And back
+        disk_monitor.update_state(20 * BYTES_PER_MB);
+        assert!(!disk_monitor.low_disk_space.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        let tmp_path = TempDir::new("disk_monitor").unwrap();
+
+        // No volume has this much space
+        let disk_monitor = init_disk_monitor(tmp_path.path().to_path_buf(), u64::MAX);
+        disk_monitor.check_free_space();
+        assert!(disk_monitor.low_disk_space.load(Ordering::SeqCst));
+
+        // Every volume has at least this much
+        let disk_monitor = init_disk_monitor(tmp_path.path().to_path_buf(), 0);
+        disk_monitor.check_free_space();
+        assert!(!disk_monitor.low_disk_space.load(Ordering::SeqCst));
+
+        // Non-existing paths leave the state untouched
+        let disk_monitor = init_disk_monitor(tmp_path.path().join("nope"), u64::MAX);
+        disk_monitor.check_free_space();
+        assert!(!disk_monitor.low_disk_space.load(Ordering::SeqCst));
+    }
+}
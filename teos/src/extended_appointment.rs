@@ -11,8 +11,8 @@ use teos_common::UserId;
 
 /// Unique identifier used to identify appointments.
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
-pub(crate) struct UUID([u8; 20]);
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct UUID([u8; 20]);
 
 impl UUID {
     /// Creates a new [UUID].
@@ -43,13 +43,104 @@ impl std::fmt::Display for UUID {
     }
 }
 
+/// The lifecycle of an appointment within the tower.
+///
+/// Appointments are [Received](AppointmentState::Received) by the tower and [Accepted](AppointmentState::Accepted) once
+/// they pass all the checks (subscription, policies and slots). Accepted appointments are then either
+/// [Watched](AppointmentState::Watched) or, if their trigger has already been seen, [Triggered](AppointmentState::Triggered)
+/// straightaway. Watched appointments are triggered once their dispute is seen on chain. Appointments end up being:
+/// - [Completed](AppointmentState::Completed) if their penalty is irrevocably resolved
+/// - [Rejected](AppointmentState::Rejected) if their data is invalid or their penalty is rejected (or abandoned)
+/// - [Outdated](AppointmentState::Outdated) if the user subscription expires before they are completed
+///
+/// Watched appointments can be updated by the user, moving them back to accepted. Appointments in a final state
+/// can be accepted again if the user sends them anew.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppointmentState {
+    Received,
+    Accepted,
+    Watched,
+    Triggered,
+    Completed,
+    Rejected,
+    Outdated,
+}
+
+impl AppointmentState {
+    /// Every [AppointmentState], in lifecycle order.
+    pub const ALL: [AppointmentState; 7] = [
+        AppointmentState::Received,
+        AppointmentState::Accepted,
+        AppointmentState::Watched,
+        AppointmentState::Triggered,
+        AppointmentState::Completed,
+        AppointmentState::Rejected,
+        AppointmentState::Outdated,
+    ];
+
+    /// Whether the appointment can be moved from this state to `next`.
+    pub fn can_transition_to(&self, next: AppointmentState) -> bool {
+        use AppointmentState::*;
+
+        match self {
+            Received => matches!(next, Accepted | Rejected),
+            Accepted => matches!(next, Watched | Triggered | Rejected),
+            Watched => matches!(next, Accepted | Triggered | Rejected | Outdated),
+            Triggered => matches!(next, Completed | Rejected | Outdated),
+            Completed | Rejected | Outdated => next == Accepted,
+        }
+    }
+
+    /// Whether this is a final state, that is, the tower is not doing anything else with the appointment.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            AppointmentState::Completed | AppointmentState::Rejected | AppointmentState::Outdated
+        )
+    }
+
+    /// Builds an [AppointmentState] from data loaded from the database.
+    pub fn from_db_data(state: u8) -> Option<Self> {
+        match state {
+            0 => Some(AppointmentState::Received),
+            1 => Some(AppointmentState::Accepted),
+            2 => Some(AppointmentState::Watched),
+            3 => Some(AppointmentState::Triggered),
+            4 => Some(AppointmentState::Completed),
+            5 => Some(AppointmentState::Rejected),
+            6 => Some(AppointmentState::Outdated),
+            _ => None,
+        }
+    }
+
+    /// Converts an [AppointmentState] into its database representation.
+    pub fn to_db_data(self) -> u8 {
+        self as u8
+    }
+}
+
+impl std::fmt::Display for AppointmentState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            AppointmentState::Received => "received",
+            AppointmentState::Accepted => "accepted",
+            AppointmentState::Watched => "watched",
+            AppointmentState::Triggered => "triggered",
+            AppointmentState::Completed => "completed",
+            AppointmentState::Rejected => "rejected",
+            AppointmentState::Outdated => "outdated",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 /// An extended version of the appointment hold by the tower.
 ///
 /// The [Appointment] is extended in terms of data, that is, it provides further information only relevant to the tower.
 /// Notice [ExtendedAppointment]s are not kept in memory but persisted on disk. The [Watcher](crate::watcher::Watcher)
 /// keeps [AppointmentSummary] instead.
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub(crate) struct ExtendedAppointment {
+pub struct ExtendedAppointment {
     /// The underlying appointment extended by [ExtendedAppointment].
     pub inner: Appointment,
     /// The user this [Appointment] belongs to.
@@ -65,7 +156,7 @@ pub(crate) struct ExtendedAppointment {
 /// Contains the minimal amount of data the [Watcher](crate::watcher::Watcher) needs to keep in memory in order to
 /// watch for breaches.
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub(crate) struct AppointmentSummary {
+pub struct AppointmentSummary {
     /// The [Appointment] locator.
     pub locator: Locator,
     /// The user this [Appointment] belongs to.
@@ -120,6 +211,48 @@ mod tests {
     use teos_common::cryptography::get_random_bytes;
     use teos_common::test_utils::get_random_user_id;
 
+    #[test]
+    fn test_appointment_state_transitions() {
+        use AppointmentState::*;
+
+        // Happy paths
+        for path in [
+            vec![Received, Accepted, Watched, Triggered, Completed],
+            vec![Received, Accepted, Triggered, Completed],
+            vec![Received, Accepted, Watched, Accepted, Watched, Outdated],
+            vec![Received, Accepted, Watched, Triggered, Rejected, Accepted],
+        ] {
+            for states in path.windows(2) {
+                assert!(states[0].can_transition_to(states[1]));
+            }
+        }
+
+        // Some invalid transitions
+        assert!(!Received.can_transition_to(Watched));
+        assert!(!Watched.can_transition_to(Completed));
+        assert!(!Triggered.can_transition_to(Watched));
+        assert!(!Completed.can_transition_to(Triggered));
+        assert!(!Outdated.can_transition_to(Outdated));
+
+        // Only the last three states are final
+        assert_eq!(
+            [Received, Accepted, Watched, Triggered, Completed, Rejected, Outdated]
+                .iter()
+                .filter(|s| s.is_final())
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_appointment_state_db_data() {
+        for i in 0..7 {
+            let state = AppointmentState::from_db_data(i).unwrap();
+            assert_eq!(state.to_db_data(), i);
+        }
+        assert_eq!(AppointmentState::from_db_data(7), None);
+    }
+
     #[test]
     fn test_get_summary() {
         let locator = Locator::from_slice(&get_random_bytes(16)).unwrap();
@@ -1,10 +1,16 @@
 //! Logic related to the Gatekeeper, the component in charge of managing access to the tower resources.
 
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::iter::FromIterator;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use tokio::sync::broadcast;
+
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
 use lightning::chain;
 
 use teos_common::appointment::compute_appointment_slots;
@@ -15,18 +21,20 @@ use teos_common::UserId;
 
 use crate::dbm::DBM;
 use crate::extended_appointment::{ExtendedAppointment, UUID};
+use crate::storage::Storage;
+use crate::telemetry;
 
 /// Data regarding a user subscription with the tower.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct UserInfo {
+pub struct UserInfo {
     /// Number of appointment slots available for a given user.
-    pub(crate) available_slots: u32,
+    pub available_slots: u32,
     /// Block height where the user subscription starts.
-    pub(crate) subscription_start: u32,
+    pub subscription_start: u32,
     /// Block height where the user subscription expires.
-    pub(crate) subscription_expiry: u32,
+    pub subscription_expiry: u32,
     /// Map of appointment ids and the how many slots they take from the subscription.
-    pub(crate) appointments: HashMap<UUID, u32>,
+    pub appointments: HashMap<UUID, u32>,
 }
 
 impl UserInfo {
@@ -40,6 +48,16 @@ impl UserInfo {
         }
     }
 
+    /// Gets the slots granted to the user, that is, the available ones plus the ones taken by their appointments.
+    pub fn granted_slots(&self) -> u64 {
+        self.available_slots as u64
+            + self
+                .appointments
+                .values()
+                .map(|slots| *slots as u64)
+                .sum::<u64>()
+    }
+
     /// Creates a new [UserInfo] instance with some associated appointments.
     pub fn with_appointments(
         available_slots: u32,
@@ -56,19 +74,231 @@ impl UserInfo {
     }
 }
 
+/// Reminder sent to users whose subscription is about to expire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenewalReminder {
+    /// The user whose subscription is about to expire.
+    pub user_id: UserId,
+    /// Block height where the user subscription expires.
+    pub subscription_expiry: u32,
+}
+
+/// Price of a subscription with the tower, in millisatoshis.
+///
+/// A subscription costs `slots * price_per_slot_msat + duration * price_per_block_msat`. Subscriptions are free if
+/// both prices are set to zero. Prices are only enforced if `payments_required` is set, otherwise they are just
+/// advertised.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubscriptionPricing {
+    /// Price of each appointment slot.
+    pub price_per_slot_msat: u64,
+    /// Price of each block the subscription is valid for.
+    pub price_per_block_msat: u64,
+    /// Whether registrations must pay the subscription price.
+    pub payments_required: bool,
+}
+
+impl SubscriptionPricing {
+    /// Creates a new [SubscriptionPricing] instance.
+    pub fn new(
+        price_per_slot_msat: u64,
+        price_per_block_msat: u64,
+        payments_required: bool,
+    ) -> Self {
+        SubscriptionPricing {
+            price_per_slot_msat,
+            price_per_block_msat,
+            payments_required,
+        }
+    }
+
+    /// Returns whether subscriptions are free of charge.
+    pub fn is_free(&self) -> bool {
+        self.price_per_slot_msat == 0 && self.price_per_block_msat == 0
+    }
+
+    /// Computes the price of a subscription with the given number of slots and duration (in blocks).
+    ///
+    /// Returns `None` if the price does not fit in a [u64].
+    pub fn price(&self, slots: u32, duration: u32) -> Option<u64> {
+        (slots as u64)
+            .checked_mul(self.price_per_slot_msat)?
+            .checked_add((duration as u64).checked_mul(self.price_per_block_msat)?)
+    }
+}
+
+/// Invoice issued by the tower to pay for a registration.
+///
+/// Registrations are only granted once the preimage of the payment hash is presented, which the payer only learns once
+/// the invoice is settled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistrationInvoice {
+    /// Hash of the payment preimage.
+    pub payment_hash: sha256::Hash,
+    /// Amount to be paid (i.e. the subscription price).
+    pub amount_msat: u64,
+}
+
+/// Registration invoice waiting to be paid and redeemed.
+#[derive(Debug)]
+struct PendingInvoice {
+    /// The user the invoice was issued to.
+    user_id: UserId,
+    /// The payment preimage, released to the payer once the invoice is settled.
+    preimage: Vec<u8>,
+    /// When the invoice was issued.
+    issued_at: Instant,
+}
+
+/// Packs the reasons why issuing a registration invoice may fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvoiceFailure {
+    /// The tower does not require payments, so there is nothing to pay for.
+    PaymentsNotRequired,
+    /// The subscription price does not fit in a [u64].
+    PriceOverflow,
+    /// The tower already has [MAX_PENDING_INVOICES] pending invoices.
+    TooManyInvoices,
+}
+
+/// Packs the reasons why the payment of a registration may not be accepted.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaymentFailure {
+    /// No payment preimage was provided.
+    MissingPreimage,
+    /// The preimage does not match any pending invoice issued to the user (it may have expired or been redeemed).
+    UnknownInvoice,
+}
+
+/// Maximum number of entries held by the authentication cache.
+const AUTH_CACHE_MAX_SIZE: usize = 10_000;
+
+/// Size of the authentication challenges, in bytes. Challenges are built as `nonce | issued_at | tag`.
+const CHALLENGE_LEN: usize = 32;
+
+/// Size of the random nonce of the authentication challenges, in bytes.
+const CHALLENGE_NONCE_LEN: usize = 8;
+
+/// Size of the tag of the authentication challenges (a truncated HMAC), in bytes.
+const CHALLENGE_TAG_LEN: usize = 16;
+
+/// Time an authentication challenge can be used for after being issued.
+const CHALLENGE_TTL: Duration = Duration::from_secs(300);
+
+/// Size of the registration invoices payment preimages, in bytes.
+const PREIMAGE_LEN: usize = 32;
+
+/// Time a registration invoice can be paid and redeemed for after being issued.
+const INVOICE_TTL: Duration = Duration::from_secs(3600);
+
+/// Maximum number of pending registration invoices. New invoices are refused while the limit is reached.
+const MAX_PENDING_INVOICES: usize = 10_000;
+
+/// Short-lived cache of the user ids recovered from (message, signature) pairs.
+///
+/// Recovering the public key out of a signature is CPU-heavy, and clients tend to repeat the same requests (e.g. when
+/// polling the state of an appointment), so the recovered keys are kept around for a while. Recovery is deterministic,
+/// so a cache hit is as good as running it again.
+#[derive(Debug)]
+struct AuthCache {
+    ttl: Duration,
+    entries: HashMap<(sha256::Hash, String), (UserId, Instant)>,
+}
+
+impl AuthCache {
+    fn new(ttl: Duration) -> Self {
+        AuthCache {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&self, message: sha256::Hash, signature: &str) -> Option<UserId> {
+        self.entries
+            .get(&(message, signature.to_owned()))
+            .filter(|(_, added_at)| added_at.elapsed() < self.ttl)
+            .map(|(user_id, _)| *user_id)
+    }
+
+    fn insert(&mut self, message: sha256::Hash, signature: &str, user_id: UserId) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        if self.entries.len() >= AUTH_CACHE_MAX_SIZE {
+            let ttl = self.ttl;
+            self.entries
+                .retain(|_, (_, added_at)| added_at.elapsed() < ttl);
+            if self.entries.len() >= AUTH_CACHE_MAX_SIZE {
+                self.entries.clear();
+            }
+        }
+        self.entries
+            .insert((message, signature.to_owned()), (user_id, Instant::now()));
+    }
+}
+
 /// Error raised if the user cannot be authenticated.
 #[derive(Debug, PartialEq)]
-pub(crate) struct AuthenticationFailure<'a>(&'a str);
+pub struct AuthenticationFailure<'a>(&'a str);
+
+/// Packs the reasons why issuing an authentication challenge may fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChallengeFailure {
+    /// The user is not registered with the tower.
+    UserNotFound,
+}
+
+/// Key the authentication challenges are tagged with. Not persisted, so challenges do not survive restarts.
+struct ChallengeKey(Vec<u8>);
+
+impl fmt::Debug for ChallengeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChallengeKey(..)")
+    }
+}
+
+/// Gets the current time in seconds since epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
 
 /// Error raised if the user subscription has not enough slots to fit a new appointment.
 #[derive(Debug, PartialEq)]
 pub(crate) struct NotEnoughSlots;
 
-/// Error raised if the user subscription slots limit has been reached.
-///
-/// This is currently set to [u32::MAX].
+/// Packs the reasons why registering a user (or renewing its subscription) may fail.
 #[derive(Debug, PartialEq)]
-pub(crate) struct MaxSlotsReached;
+pub enum RegistrationFailure {
+    /// The user subscription slots limit has been reached. This is currently set to [u32::MAX].
+    MaxSlotsReached,
+    /// The tower is not accepting new users, given the registered users limit has been reached.
+    MaxUsersReached,
+}
+
+/// Settings a [Gatekeeper] is created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GatekeeperSettings {
+    /// Number of slots new subscriptions get by default.
+    pub subscription_slots: u32,
+    /// Expiry time new subscription get by default, in blocks (starting from the block the subscription is requested).
+    pub subscription_duration: u32,
+    /// Grace period given to renew subscriptions, in blocks.
+    pub expiry_delta: u32,
+    /// Number of blocks before the subscription expiry from which users are reminded to renew. Zero disables reminders.
+    pub renewal_window: u32,
+    /// Maximum number of users the tower accepts. Zero means no limit.
+    pub max_users: u32,
+    /// Price of new subscriptions.
+    pub pricing: SubscriptionPricing,
+    /// For how long user authentications are cached. Zero disables the cache.
+    pub auth_cache_ttl: Duration,
+    /// Whether requests that support authentication challenges must include one.
+    pub require_auth_challenges: bool,
+}
 
 /// Component in charge of managing access to the tower resources.
 ///
@@ -78,7 +308,7 @@ pub(crate) struct MaxSlotsReached;
 /// This is the only component in the system that has some knowledge regarding users, all other components do query the
 /// [Gatekeeper] for such information.
 #[derive(Debug)]
-pub struct Gatekeeper {
+pub struct Gatekeeper<S = DBM> {
     /// last known block header by the [Gatekeeper].
     last_known_block_height: AtomicU32,
     /// Number of slots new subscriptions get by default.
@@ -87,28 +317,55 @@ pub struct Gatekeeper {
     subscription_duration: u32,
     /// Grace period given to renew subscriptions, in blocks.
     expiry_delta: u32,
+    /// Number of blocks before the subscription expiry from which users are reminded to renew. Zero disables reminders.
+    renewal_window: u32,
+    /// Maximum number of users the tower accepts. Zero means no limit.
+    max_users: u32,
+    /// Price of new subscriptions.
+    pricing: SubscriptionPricing,
+    /// Channel used to push [RenewalReminder]s to whoever is listening.
+    renewal_reminders: broadcast::Sender<RenewalReminder>,
     /// Map of users registered within the tower.
     registered_users: Mutex<HashMap<UserId, UserInfo>>,
-    /// A [DBM] (database manager) instance. Used to persist appointment data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// Cache of recently authenticated (message, signature) pairs.
+    auth_cache: Mutex<AuthCache>,
+    /// Whether requests that support authentication challenges must include one.
+    require_auth_challenges: bool,
+    /// Key the authentication challenges are tagged with, so they do not need to be stored until they are used.
+    challenge_key: ChallengeKey,
+    /// Authentication challenges that have already been used (alongside when they were issued), so they cannot be
+    /// replayed. Kept until they expire.
+    used_challenges: Mutex<HashMap<Vec<u8>, u64>>,
+    /// Registration invoices waiting to be redeemed, by payment hash.
+    invoices: Mutex<HashMap<sha256::Hash, PendingInvoice>>,
+    /// The [Storage] backend (a [DBM] by default). Used to persist user data.
+    dbm: Arc<Mutex<S>>,
 }
 
-impl Gatekeeper {
+impl<S: Storage> Gatekeeper<S> {
     /// Creates a new [Gatekeeper] instance.
     pub fn new(
         last_known_block_height: u32,
-        subscription_slots: u32,
-        subscription_duration: u32,
-        expiry_delta: u32,
-        dbm: Arc<Mutex<DBM>>,
+        settings: GatekeeperSettings,
+        dbm: Arc<Mutex<S>>,
     ) -> Self {
         let registered_users = dbm.lock().unwrap().load_all_users();
+        let (renewal_reminders, _) = broadcast::channel(128);
         Gatekeeper {
             last_known_block_height: AtomicU32::new(last_known_block_height),
-            subscription_slots,
-            subscription_duration,
-            expiry_delta,
+            subscription_slots: settings.subscription_slots,
+            subscription_duration: settings.subscription_duration,
+            expiry_delta: settings.expiry_delta,
+            renewal_window: settings.renewal_window,
+            max_users: settings.max_users,
+            pricing: settings.pricing,
+            renewal_reminders,
             registered_users: Mutex::new(registered_users),
+            auth_cache: Mutex::new(AuthCache::new(settings.auth_cache_ttl)),
+            require_auth_challenges: settings.require_auth_challenges,
+            challenge_key: ChallengeKey(cryptography::get_random_bytes(32)),
+            used_challenges: Mutex::new(HashMap::new()),
+            invoices: Mutex::new(HashMap::new()),
             dbm,
         }
     }
@@ -119,12 +376,12 @@ impl Gatekeeper {
     }
 
     /// Ges the number of users currently registered to the tower.
-    pub(crate) fn get_registered_users_count(&self) -> usize {
+    pub fn get_registered_users_count(&self) -> usize {
         self.registered_users.lock().unwrap().len()
     }
 
     /// Gets the list of all registered user ids.
-    pub(crate) fn get_user_ids(&self) -> Vec<UserId> {
+    pub fn get_user_ids(&self) -> Vec<UserId> {
         self.registered_users
             .lock()
             .unwrap()
@@ -134,36 +391,259 @@ impl Gatekeeper {
     }
 
     /// Gets the data held by the tower about a given user.
-    pub(crate) fn get_user_info(&self, user_id: UserId) -> Option<UserInfo> {
+    pub fn get_user_info(&self, user_id: UserId) -> Option<UserInfo> {
         self.registered_users.lock().unwrap().get(&user_id).cloned()
     }
 
+    /// Gets the slots granted to a given user (check [UserInfo::granted_slots]).
+    pub(crate) fn get_granted_slots(&self, user_id: UserId) -> Option<u64> {
+        self.registered_users
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .map(|user_info| user_info.granted_slots())
+    }
+
+    /// Gets the price of new subscriptions.
+    pub fn get_pricing(&self) -> SubscriptionPricing {
+        self.pricing
+    }
+
+    /// Gets the number of slots and the duration (in blocks) each registration is granted.
+    pub fn get_subscription_terms(&self) -> (u32, u32) {
+        (self.subscription_slots, self.subscription_duration)
+    }
+
+    /// Issues an invoice for the registration of a given user.
+    ///
+    /// Subscriptions are priced based on the slots and duration new subscriptions get by default. Invoices can be
+    /// redeemed (check [Gatekeeper::check_payment]) for [INVOICE_TTL] after being issued. Invoices are not bound to
+    /// a requester, so at most [MAX_PENDING_INVOICES] are kept around (expired ones are dropped first).
+    pub fn issue_invoice(&self, user_id: UserId) -> Result<RegistrationInvoice, InvoiceFailure> {
+        if !self.pricing.payments_required {
+            return Err(InvoiceFailure::PaymentsNotRequired);
+        }
+        let amount_msat = self
+            .pricing
+            .price(self.subscription_slots, self.subscription_duration)
+            .ok_or(InvoiceFailure::PriceOverflow)?;
+
+        let mut invoices = self.invoices.lock().unwrap();
+        if invoices.len() >= MAX_PENDING_INVOICES {
+            invoices.retain(|_, invoice| invoice.issued_at.elapsed() < INVOICE_TTL);
+            if invoices.len() >= MAX_PENDING_INVOICES {
+                return Err(InvoiceFailure::TooManyInvoices);
+            }
+        }
+
+        let preimage = cryptography::get_random_bytes(PREIMAGE_LEN);
+        let payment_hash = sha256::Hash::hash(&preimage);
+        invoices.insert(
+            payment_hash,
+            PendingInvoice {
+                user_id,
+                preimage,
+                issued_at: Instant::now(),
+            },
+        );
+
+        Ok(RegistrationInvoice {
+            payment_hash,
+            amount_msat,
+        })
+    }
+
+    /// Gets the preimage of a pending registration invoice, so it can be handed to the Lightning node settling it.
+    pub fn get_invoice_preimage(&self, payment_hash: &sha256::Hash) -> Option<Vec<u8>> {
+        self.invoices
+            .lock()
+            .unwrap()
+            .get(payment_hash)
+            .filter(|invoice| invoice.issued_at.elapsed() < INVOICE_TTL)
+            .map(|invoice| invoice.preimage.clone())
+    }
+
+    /// Checks that a registration has been paid for, given the preimage of an invoice issued to the user (check
+    /// [Gatekeeper::issue_invoice]).
+    ///
+    /// The invoice is redeemed if the check succeeds, so each payment can only be used once. Registrations need no
+    /// payment if payments are not required.
+    pub fn check_payment(&self, user_id: UserId, preimage: &[u8]) -> Result<(), PaymentFailure> {
+        if !self.pricing.payments_required {
+            return Ok(());
+        }
+        if preimage.is_empty() {
+            return Err(PaymentFailure::MissingPreimage);
+        }
+
+        let mut invoices = self.invoices.lock().unwrap();
+        let payment_hash = sha256::Hash::hash(preimage);
+        let valid = invoices.get(&payment_hash).map_or(false, |invoice| {
+            invoice.user_id == user_id && invoice.issued_at.elapsed() < INVOICE_TTL
+        });
+        if valid {
+            invoices.remove(&payment_hash);
+            Ok(())
+        } else {
+            Err(PaymentFailure::UnknownInvoice)
+        }
+    }
+
     /// Authenticates a user.
     ///
     /// User authentication is performed using ECRecover against fixed messages (one for each command).
     /// Notice all interaction with the tower should be guarded by this.
-    pub(crate) fn authenticate_user(
+    ///
+    /// Recovered user ids are cached for a short while, so repeated requests do not need to go through ECRecover again.
+    /// Only ids belonging to registered users are cached, and whether the user is still registered is checked every time.
+    pub fn authenticate_user(
         &self,
         message: &[u8],
         signature: &str,
     ) -> Result<UserId, AuthenticationFailure> {
+        let _stage = telemetry::stage_span("auth").entered();
+        let message_hash = sha256::Hash::hash(message);
+        let cached = self.auth_cache.lock().unwrap().get(message_hash, signature);
+        let user_id = match cached {
+            Some(user_id) => user_id,
+            None => UserId(
+                cryptography::recover_pk(message, signature)
+                    .map_err(|_| AuthenticationFailure("Wrong message or signature."))?,
+            ),
+        };
+
+        if !self.registered_users.lock().unwrap().contains_key(&user_id) {
+            return Err(AuthenticationFailure("User not found."));
+        }
+
+        // Caching unregistered ids would let anyone fill the cache with junk
+        if cached.is_none() {
+            self.auth_cache
+                .lock()
+                .unwrap()
+                .insert(message_hash, signature, user_id);
+        }
+
+        Ok(user_id)
+    }
+
+    /// Authenticates a user, optionally using a challenge previously issued by [Gatekeeper::issue_challenge].
+    ///
+    /// If a challenge is provided, the signature must cover both the message and the challenge (`message | challenge`),
+    /// and the challenge is consumed, so the request cannot be replayed. Requests with no challenge are authenticated as
+    /// in [Gatekeeper::authenticate_user], unless challenges are required.
+    pub fn authenticate_user_with_challenge(
+        &self,
+        message: &[u8],
+        signature: &str,
+        challenge: Option<&[u8]>,
+    ) -> Result<UserId, AuthenticationFailure> {
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None if self.require_auth_challenges => {
+                return Err(AuthenticationFailure("A challenge is required."))
+            }
+            None => return self.authenticate_user(message, signature),
+        };
+
+        // Challenged messages are never repeated, so there's no point in caching them
         let user_id = UserId(
-            cryptography::recover_pk(message, signature)
+            cryptography::recover_pk(&[message, challenge].concat(), signature)
                 .map_err(|_| AuthenticationFailure("Wrong message or signature."))?,
         );
+        if !self.registered_users.lock().unwrap().contains_key(&user_id) {
+            return Err(AuthenticationFailure("User not found."));
+        }
 
-        if self.registered_users.lock().unwrap().contains_key(&user_id) {
-            Ok(user_id)
-        } else {
-            Err(AuthenticationFailure("User not found."))
+        // Challenges are bound to the user they were issued to and can only be used once, before they expire
+        let now = now();
+        let issued_at = self
+            .check_challenge(user_id, challenge)
+            .filter(|issued_at| now.saturating_sub(*issued_at) < CHALLENGE_TTL.as_secs())
+            .ok_or(AuthenticationFailure("Invalid or expired challenge."))?;
+        let mut used_challenges = self.used_challenges.lock().unwrap();
+        used_challenges
+            .retain(|_, issued_at| now.saturating_sub(*issued_at) < CHALLENGE_TTL.as_secs());
+        if used_challenges
+            .insert(challenge.to_vec(), issued_at)
+            .is_some()
+        {
+            return Err(AuthenticationFailure("Invalid or expired challenge."));
         }
+
+        Ok(user_id)
+    }
+
+    /// Issues a one-time authentication challenge for a given user.
+    ///
+    /// Challenges are only issued to registered users, and expire after [CHALLENGE_TTL]. Challenges can be requested by
+    /// anyone, so they are not stored when issued (otherwise a third party could exhaust the challenges of a user, or
+    /// the tower memory, by requesting them on their behalf). Instead, they are tagged with a key only known by the
+    /// tower, and bound to the user they are issued to, so only they can use them.
+    pub fn issue_challenge(&self, user_id: UserId) -> Result<Vec<u8>, ChallengeFailure> {
+        if !self.registered_users.lock().unwrap().contains_key(&user_id) {
+            return Err(ChallengeFailure::UserNotFound);
+        }
+
+        Ok(self.build_challenge(user_id, now()))
+    }
+
+    /// Builds an authentication challenge for a given user, issued at a given time (in seconds since epoch).
+    fn build_challenge(&self, user_id: UserId, issued_at: u64) -> Vec<u8> {
+        let mut challenge = cryptography::get_random_bytes(CHALLENGE_NONCE_LEN);
+        challenge.extend_from_slice(&issued_at.to_be_bytes());
+        let tag = self.challenge_tag(user_id, &challenge);
+        challenge.extend_from_slice(&tag);
+
+        challenge
+    }
+
+    /// Computes the tag of an authentication challenge (`nonce | issued_at`) issued to a given user.
+    fn challenge_tag(&self, user_id: UserId, data: &[u8]) -> Vec<u8> {
+        let mut engine = HmacEngine::<sha256::Hash>::new(&self.challenge_key.0);
+        engine.input(&user_id.to_vec());
+        engine.input(data);
+        Hmac::<sha256::Hash>::from_engine(engine).into_inner()[..CHALLENGE_TAG_LEN].to_vec()
+    }
+
+    /// Checks an authentication challenge was issued by the tower to a given user. Returns when it was issued if so.
+    fn check_challenge(&self, user_id: UserId, challenge: &[u8]) -> Option<u64> {
+        if challenge.len() != CHALLENGE_LEN {
+            return None;
+        }
+        let (data, tag) = challenge.split_at(CHALLENGE_LEN - CHALLENGE_TAG_LEN);
+        if self.challenge_tag(user_id, data) != tag {
+            return None;
+        }
+
+        let mut issued_at = [0; 8];
+        issued_at.copy_from_slice(&data[CHALLENGE_NONCE_LEN..]);
+        Some(u64::from_be_bytes(issued_at))
+    }
+
+    /// Gets the grace period given to users to renew their subscriptions, in blocks.
+    pub fn get_expiry_delta(&self) -> u32 {
+        self.expiry_delta
+    }
+
+    /// Gets the maximum number of users accepted by the tower. Zero means no limit.
+    pub fn get_max_users(&self) -> u32 {
+        self.max_users
+    }
+
+    /// Returns whether the tower is accepting new users (i.e. the registered users limit has not been reached).
+    pub fn is_accepting_registrations(&self) -> bool {
+        self.max_users == 0 || self.get_registered_users_count() < self.max_users as usize
     }
 
     /// Adds a new user to the tower (or updates its subscription if already registered).
-    pub(crate) fn add_update_user(
+    ///
+    /// New users are only accepted as long as the registered users limit has not been reached. Users that are already
+    /// registered can always renew their subscription.
+    pub fn add_update_user(
         &self,
         user_id: UserId,
-    ) -> Result<RegistrationReceipt, MaxSlotsReached> {
+    ) -> Result<RegistrationReceipt, RegistrationFailure> {
         let block_count = self.last_known_block_height.load(Ordering::Acquire);
 
         // TODO: For now, new calls to `add_update_user` add subscription_slots to the current count and reset the expiry time
@@ -174,7 +654,7 @@ impl Gatekeeper {
                 user_info.available_slots = user_info
                     .available_slots
                     .checked_add(self.subscription_slots)
-                    .ok_or(MaxSlotsReached)?;
+                    .ok_or(RegistrationFailure::MaxSlotsReached)?;
                 user_info.subscription_expiry = user_info
                     .subscription_expiry
                     .checked_add(self.subscription_duration)
@@ -185,6 +665,14 @@ impl Gatekeeper {
             }
             // New user
             None => {
+                if self.max_users != 0 && registered_users.len() >= self.max_users as usize {
+                    log::info!(
+                        "Registered users limit reached, rejecting new user: {}",
+                        user_id
+                    );
+                    return Err(RegistrationFailure::MaxUsersReached);
+                }
+
                 let user_info = UserInfo::new(
                     self.subscription_slots,
                     block_count,
@@ -216,6 +704,7 @@ impl Gatekeeper {
         uuid: UUID,
         appointment: &ExtendedAppointment,
     ) -> Result<u32, NotEnoughSlots> {
+        let _stage = telemetry::stage_span("slot_check").entered();
         // For updates, the difference between the existing appointment size and the update is computed.
         let mut registered_users = self.registered_users.lock().unwrap();
         let user_info = registered_users.get_mut(&user_id).unwrap();
@@ -239,8 +728,49 @@ impl Gatekeeper {
         }
     }
 
+    /// Adds a batch of appointments to a given user (or updates them if already present in the system).
+    ///
+    /// Slot accounting is atomic: either there are enough slots for the whole batch, and all appointments are added, or
+    /// none is.
+    pub(crate) fn add_update_appointments(
+        &self,
+        user_id: UserId,
+        appointments: &[(UUID, &ExtendedAppointment)],
+    ) -> Result<u32, NotEnoughSlots> {
+        let _stage = telemetry::stage_span("slot_check").entered();
+        let mut registered_users = self.registered_users.lock().unwrap();
+        let user_info = registered_users.get_mut(&user_id).unwrap();
+
+        // Appointments may be repeated within the batch, so the latest version of each one is the one that counts.
+        let mut batch = HashMap::new();
+        let mut diff = 0;
+        for (uuid, appointment) in appointments.iter() {
+            let used_slots = batch
+                .get(uuid)
+                .or_else(|| user_info.appointments.get(uuid))
+                .map_or(0, |x| *x);
+            let required_slots = compute_appointment_slots(
+                appointment.encrypted_blob().len(),
+                ENCRYPTED_BLOB_MAX_SIZE,
+            );
+            diff += required_slots as i64 - used_slots as i64;
+            batch.insert(*uuid, required_slots);
+        }
+
+        if diff <= user_info.available_slots as i64 {
+            user_info.appointments.extend(batch);
+            user_info.available_slots = (user_info.available_slots as i64 - diff) as u32;
+
+            self.dbm.lock().unwrap().update_user(user_id, user_info);
+
+            Ok(user_info.available_slots)
+        } else {
+            Err(NotEnoughSlots)
+        }
+    }
+
     /// Checks whether a subscription has expired.
-    pub(crate) fn has_subscription_expired(
+    pub fn has_subscription_expired(
         &self,
         user_id: UserId,
     ) -> Result<(bool, u32), AuthenticationFailure<'_>> {
@@ -256,6 +786,39 @@ impl Gatekeeper {
         )
     }
 
+    /// Checks whether a subscription expiring at `subscription_expiry` is due for renewal. That is, whether it has not
+    /// expired yet but it will in less than [renewal_window](Self::renewal_window) blocks.
+    pub fn is_renewal_due(&self, subscription_expiry: u32) -> bool {
+        let block_height = self.last_known_block_height.load(Ordering::Acquire);
+        self.renewal_window > 0
+            && subscription_expiry > block_height
+            && subscription_expiry - block_height <= self.renewal_window
+    }
+
+    /// Subscribes to the [RenewalReminder]s sent by the [Gatekeeper].
+    ///
+    /// A reminder is sent, for every user, the moment their subscription enters the renewal window.
+    pub fn subscribe_renewal_reminders(&self) -> broadcast::Receiver<RenewalReminder> {
+        self.renewal_reminders.subscribe()
+    }
+
+    /// Gets the users whose subscription enters the renewal window at the given height.
+    pub(crate) fn get_renewal_due_users(&self, block_height: u32) -> HashMap<UserId, u32> {
+        // No subscription can enter the window if its start is past the highest possible expiry
+        let window_start = match block_height.checked_add(self.renewal_window) {
+            Some(window_start) if self.renewal_window > 0 => window_start,
+            _ => return HashMap::new(),
+        };
+
+        self.registered_users
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, info)| info.subscription_expiry == window_start)
+            .map(|(id, info)| (*id, info.subscription_expiry))
+            .collect()
+    }
+
     /// Gets a map of outdated users. Outdated users are those whose subscription has expired and the renewal grace period
     /// has already passed ([expiry_delta](Self::expiry_delta)).
     pub(crate) fn get_outdated_users(&self, block_height: u32) -> HashMap<UserId, HashSet<UUID>> {
@@ -311,7 +874,7 @@ impl Gatekeeper {
     }
 }
 
-impl chain::Listen for Gatekeeper {
+impl<S: Storage> chain::Listen for Gatekeeper<S> {
     /// Handles the monitoring process by the [Gatekeeper].
     ///
     /// This is mainly used to keep track of time and expire / outdate subscriptions when needed.
@@ -333,6 +896,15 @@ impl chain::Listen for Gatekeeper {
             self.dbm.lock().unwrap().batch_remove_users(&outdated_users);
         }
 
+        // Remind users whose subscription is about to expire. Sending fails if no one is listening, which is fine.
+        for (user_id, subscription_expiry) in self.get_renewal_due_users(height) {
+            log::debug!("Subscription renewal due for {}", user_id);
+            let _ = self.renewal_reminders.send(RenewalReminder {
+                user_id,
+                subscription_expiry,
+            });
+        }
+
         // Update last known block height
         self.last_known_block_height
             .store(height, Ordering::Release);
@@ -352,7 +924,8 @@ mod tests {
     use super::*;
 
     use crate::test_utils::{
-        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid, Blockchain,
+        generate_dummy_appointment, generate_dummy_appointment_with_user, generate_uuid,
+        get_gatekeeper_settings, Blockchain,
     };
     use lightning::chain::Listen;
     use teos_common::cryptography::{get_random_bytes, get_random_keypair};
@@ -362,6 +935,7 @@ mod tests {
     const SLOTS: u32 = 21;
     const DURATION: u32 = 500;
     const EXPIRY_DELTA: u32 = 42;
+    const RENEWAL_WINDOW: u32 = 10;
     const START_HEIGHT: usize = 100;
 
     impl PartialEq for Gatekeeper {
@@ -401,7 +975,7 @@ mod tests {
 
     fn init_gatekeeper(chain: &Blockchain) -> Gatekeeper {
         let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
-        Gatekeeper::new(chain.get_block_count(), SLOTS, DURATION, EXPIRY_DELTA, dbm)
+        Gatekeeper::new(chain.get_block_count(), get_gatekeeper_settings(), dbm)
     }
 
     #[test]
@@ -412,9 +986,7 @@ mod tests {
 
         let gatekeeper = Gatekeeper::new(
             chain.get_block_count(),
-            SLOTS,
-            DURATION,
-            EXPIRY_DELTA,
+            get_gatekeeper_settings(),
             dbm.clone(),
         );
         assert!(gatekeeper.is_fresh());
@@ -439,12 +1011,190 @@ mod tests {
         }
 
         // Create a new GK reusing the same DB and check that the data is loaded
-        let another_gk =
-            Gatekeeper::new(chain.get_block_count(), SLOTS, DURATION, EXPIRY_DELTA, dbm);
+        let another_gk = Gatekeeper::new(chain.get_block_count(), get_gatekeeper_settings(), dbm);
         assert!(!another_gk.is_fresh());
         assert_eq!(gatekeeper, another_gk);
     }
 
+    #[test]
+    fn test_subscription_pricing() {
+        let pricing = SubscriptionPricing::default();
+        assert!(pricing.is_free());
+        assert_eq!(pricing.price(SLOTS, DURATION), Some(0));
+
+        let pricing = SubscriptionPricing::new(2, 3, false);
+        assert!(!pricing.is_free());
+        assert_eq!(
+            pricing.price(SLOTS, DURATION),
+            Some(2 * SLOTS as u64 + 3 * DURATION as u64)
+        );
+
+        // Prices that do not fit in a u64 cannot be computed
+        let pricing = SubscriptionPricing::new(u64::MAX, 1, false);
+        assert_eq!(pricing.price(2, 0), None);
+        assert_eq!(pricing.price(1, 1), None);
+    }
+
+    #[test]
+    fn test_issue_invoice() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let pricing = SubscriptionPricing::new(2, 3, true);
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            GatekeeperSettings {
+                pricing,
+                ..get_gatekeeper_settings()
+            },
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        );
+        assert_eq!(gatekeeper.get_pricing(), pricing);
+
+        // Invoices are issued for the subscription price, and the tower knows their preimage
+        let invoice = gatekeeper.issue_invoice(get_random_user_id()).unwrap();
+        assert_eq!(invoice.amount_msat, pricing.price(SLOTS, DURATION).unwrap());
+        let preimage = gatekeeper
+            .get_invoice_preimage(&invoice.payment_hash)
+            .unwrap();
+        assert_eq!(sha256::Hash::hash(&preimage), invoice.payment_hash);
+
+        // Every invoice has its own payment hash
+        assert_ne!(
+            gatekeeper
+                .issue_invoice(get_random_user_id())
+                .unwrap()
+                .payment_hash,
+            invoice.payment_hash
+        );
+
+        // Invoices are not issued if payments are not required, or if the price cannot be computed
+        for (pricing, failure) in [
+            (
+                SubscriptionPricing::new(2, 3, false),
+                InvoiceFailure::PaymentsNotRequired,
+            ),
+            (
+                SubscriptionPricing::new(u64::MAX, 3, true),
+                InvoiceFailure::PriceOverflow,
+            ),
+        ] {
+            let gatekeeper = Gatekeeper::new(
+                chain.get_block_count(),
+                GatekeeperSettings {
+                    pricing,
+                    ..get_gatekeeper_settings()
+                },
+                Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+            );
+            assert_eq!(gatekeeper.issue_invoice(get_random_user_id()), Err(failure));
+        }
+    }
+
+    #[test]
+    fn test_issue_invoice_limit() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            GatekeeperSettings {
+                pricing: SubscriptionPricing::new(2, 3, true),
+                ..get_gatekeeper_settings()
+            },
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        );
+
+        for _ in 0..MAX_PENDING_INVOICES {
+            gatekeeper.issue_invoice(get_random_user_id()).unwrap();
+        }
+        assert_eq!(
+            gatekeeper.issue_invoice(get_random_user_id()),
+            Err(InvoiceFailure::TooManyInvoices)
+        );
+
+        // Expired invoices are dropped to make room for new ones
+        for invoice in gatekeeper.invoices.lock().unwrap().values_mut() {
+            invoice.issued_at -= INVOICE_TTL;
+        }
+        gatekeeper.issue_invoice(get_random_user_id()).unwrap();
+        assert_eq!(gatekeeper.invoices.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_check_payment() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let pricing = SubscriptionPricing::new(2, 3, true);
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            GatekeeperSettings {
+                pricing,
+                ..get_gatekeeper_settings()
+            },
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        );
+        let user_id = get_random_user_id();
+        let invoice = gatekeeper.issue_invoice(user_id).unwrap();
+        let preimage = gatekeeper
+            .get_invoice_preimage(&invoice.payment_hash)
+            .unwrap();
+
+        // Registrations with no proof of payment, or with a preimage not matching any invoice, are rejected
+        assert_eq!(
+            gatekeeper.check_payment(user_id, &[]),
+            Err(PaymentFailure::MissingPreimage)
+        );
+        assert_eq!(
+            gatekeeper.check_payment(user_id, &get_random_bytes(PREIMAGE_LEN)),
+            Err(PaymentFailure::UnknownInvoice)
+        );
+        // The payment hash is not a valid proof either
+        assert_eq!(
+            gatekeeper.check_payment(user_id, &invoice.payment_hash[..]),
+            Err(PaymentFailure::UnknownInvoice)
+        );
+
+        // Invoices can only be redeemed by the user they were issued to
+        assert_eq!(
+            gatekeeper.check_payment(get_random_user_id(), &preimage),
+            Err(PaymentFailure::UnknownInvoice)
+        );
+
+        // The preimage is accepted, but only once
+        assert_eq!(gatekeeper.check_payment(user_id, &preimage), Ok(()));
+        assert_eq!(
+            gatekeeper.check_payment(user_id, &preimage),
+            Err(PaymentFailure::UnknownInvoice)
+        );
+        assert!(gatekeeper
+            .get_invoice_preimage(&invoice.payment_hash)
+            .is_none());
+
+        // Expired invoices cannot be redeemed
+        let invoice = gatekeeper.issue_invoice(user_id).unwrap();
+        let preimage = gatekeeper
+            .get_invoice_preimage(&invoice.payment_hash)
+            .unwrap();
+        gatekeeper
+            .invoices
+            .lock()
+            .unwrap()
+            .get_mut(&invoice.payment_hash)
+            .unwrap()
+            .issued_at -= INVOICE_TTL;
+        assert_eq!(
+            gatekeeper.check_payment(user_id, &preimage),
+            Err(PaymentFailure::UnknownInvoice)
+        );
+
+        // No payment is needed if payments are not required
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            GatekeeperSettings {
+                pricing: SubscriptionPricing::new(2, 3, false),
+                ..get_gatekeeper_settings()
+            },
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        );
+        assert_eq!(gatekeeper.check_payment(user_id, &[]), Ok(()));
+    }
+
     #[test]
     fn test_authenticate_user() {
         let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
@@ -476,13 +1226,209 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_authenticate_user_cache() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        gatekeeper.add_update_user(user_id).unwrap();
+
+        // Successful authentications are cached
+        let message = "message".as_bytes();
+        let signature = cryptography::sign(message, &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Ok(user_id)
+        );
+        assert_eq!(
+            gatekeeper
+                .auth_cache
+                .lock()
+                .unwrap()
+                .get(sha256::Hash::hash(message), &signature),
+            Some(user_id)
+        );
+
+        // A cached signature is not valid for a different message (it recovers a different, unregistered, key)
+        assert_eq!(
+            gatekeeper.authenticate_user("another message".as_bytes(), &signature),
+            Err(AuthenticationFailure("User not found."))
+        );
+
+        // Unregistered users are not cached
+        let (other_sk, _) = get_random_keypair();
+        let other_signature = cryptography::sign(message, &other_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &other_signature),
+            Err(AuthenticationFailure("User not found."))
+        );
+        assert_eq!(
+            gatekeeper
+                .auth_cache
+                .lock()
+                .unwrap()
+                .get(sha256::Hash::hash(message), &other_signature),
+            None
+        );
+
+        // Cache hits still require the user to be registered
+        gatekeeper.registered_users.lock().unwrap().remove(&user_id);
+        assert_eq!(
+            gatekeeper.authenticate_user(message, &signature),
+            Err(AuthenticationFailure("User not found."))
+        );
+
+        // A zero TTL disables the cache
+        let mut cache = AuthCache::new(Duration::ZERO);
+        cache.insert(sha256::Hash::hash(message), &signature, user_id);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_issue_challenge() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+
+        // Challenges are only issued to registered users
+        let user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.issue_challenge(user_id),
+            Err(ChallengeFailure::UserNotFound)
+        );
+
+        gatekeeper.add_update_user(user_id).unwrap();
+        let challenge = gatekeeper.issue_challenge(user_id).unwrap();
+        assert_eq!(challenge.len(), CHALLENGE_LEN);
+        assert_ne!(gatekeeper.issue_challenge(user_id).unwrap(), challenge);
+
+        // Challenges are bound to the user they are issued to
+        assert!(gatekeeper.check_challenge(user_id, &challenge).is_some());
+        assert!(gatekeeper
+            .check_challenge(get_random_user_id(), &challenge)
+            .is_none());
+    }
+
+    #[test]
+    fn test_issue_challenge_third_party() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        gatekeeper.add_update_user(user_id).unwrap();
+
+        // Anyone can request challenges on behalf of a user, but that does not prevent the user from getting (and using)
+        // their own, nor piles up data in the tower
+        for _ in 0..1000 {
+            gatekeeper.issue_challenge(user_id).unwrap();
+        }
+        let message = "message".as_bytes();
+        let challenge = gatekeeper.issue_challenge(user_id).unwrap();
+        let signature = cryptography::sign(&[message, &challenge].concat(), &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(message, &signature, Some(&challenge)),
+            Ok(user_id)
+        );
+        assert_eq!(gatekeeper.used_challenges.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_authenticate_user_with_challenge() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        gatekeeper.add_update_user(user_id).unwrap();
+
+        let message = "message".as_bytes();
+        let challenge = gatekeeper.issue_challenge(user_id).unwrap();
+
+        // The signature must cover the challenge
+        let signature = cryptography::sign(message, &user_sk).unwrap();
+        assert!(gatekeeper
+            .authenticate_user_with_challenge(message, &signature, Some(&challenge))
+            .is_err());
+
+        // A challenge not issued by the tower is rejected
+        let fake_challenge = get_random_bytes(CHALLENGE_LEN);
+        let fake_signature =
+            cryptography::sign(&[message, &fake_challenge].concat(), &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(
+                message,
+                &fake_signature,
+                Some(&fake_challenge)
+            ),
+            Err(AuthenticationFailure("Invalid or expired challenge."))
+        );
+
+        // A valid challenge can only be used once
+        let signature = cryptography::sign(&[message, &challenge].concat(), &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(message, &signature, Some(&challenge)),
+            Ok(user_id)
+        );
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(message, &signature, Some(&challenge)),
+            Err(AuthenticationFailure("Invalid or expired challenge."))
+        );
+
+        // Challenges issued to another user cannot be used
+        let (another_sk, another_pk) = get_random_keypair();
+        let another_id = UserId(another_pk);
+        gatekeeper.add_update_user(another_id).unwrap();
+        let challenge = gatekeeper.issue_challenge(user_id).unwrap();
+        let another_signature =
+            cryptography::sign(&[message, &challenge].concat(), &another_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(
+                message,
+                &another_signature,
+                Some(&challenge)
+            ),
+            Err(AuthenticationFailure("Invalid or expired challenge."))
+        );
+
+        // Nor expired ones
+        let expired_challenge =
+            gatekeeper.build_challenge(user_id, now() - CHALLENGE_TTL.as_secs());
+        let signature =
+            cryptography::sign(&[message, &expired_challenge].concat(), &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(
+                message,
+                &signature,
+                Some(&expired_challenge)
+            ),
+            Err(AuthenticationFailure("Invalid or expired challenge."))
+        );
+
+        // Requests with no challenge are accepted unless challenges are required
+        let signature = cryptography::sign(message, &user_sk).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(message, &signature, None),
+            Ok(user_id)
+        );
+
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let gatekeeper = Gatekeeper::new(
+            START_HEIGHT as u32,
+            GatekeeperSettings {
+                require_auth_challenges: true,
+                ..get_gatekeeper_settings()
+            },
+            dbm,
+        );
+        gatekeeper.add_update_user(user_id).unwrap();
+        assert_eq!(
+            gatekeeper.authenticate_user_with_challenge(message, &signature, None),
+            Err(AuthenticationFailure("A challenge is required."))
+        );
+    }
+
     #[test]
     fn test_add_update_user() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
         let gatekeeper = init_gatekeeper(&chain);
 
         // add_update_user adds a user to the system if it is not still registered, otherwise it add slots to the user subscription
-        // and refreshes the subscription expiry. Slots are added up to u32:MAX, further call will return an MaxSlotsReached error.
+        // and refreshes the subscription expiry. Slots are added up to u32:MAX, further call will return a MaxSlotsReached error.
 
         // Let's start by adding new user
         let user_id = get_random_user_id();
@@ -531,7 +1477,7 @@ mod tests {
 
         assert!(matches!(
             gatekeeper.add_update_user(user_id),
-            Err(MaxSlotsReached)
+            Err(RegistrationFailure::MaxSlotsReached)
         ));
 
         // Data in the database remains untouched
@@ -545,6 +1491,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_add_update_user_max_users() {
+        let chain = Blockchain::default().with_height(START_HEIGHT);
+        let gatekeeper = Gatekeeper::new(
+            chain.get_block_count(),
+            GatekeeperSettings {
+                max_users: 2,
+                ..get_gatekeeper_settings()
+            },
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        );
+
+        // New users are accepted until the limit is reached
+        let user_id = get_random_user_id();
+        assert!(gatekeeper.is_accepting_registrations());
+        gatekeeper.add_update_user(user_id).unwrap();
+        assert!(gatekeeper.is_accepting_registrations());
+        gatekeeper.add_update_user(get_random_user_id()).unwrap();
+        assert!(!gatekeeper.is_accepting_registrations());
+
+        let new_user_id = get_random_user_id();
+        assert_eq!(
+            gatekeeper.add_update_user(new_user_id),
+            Err(RegistrationFailure::MaxUsersReached)
+        );
+        assert!(gatekeeper.get_user_info(new_user_id).is_none());
+        assert!(matches!(
+            gatekeeper.dbm.lock().unwrap().load_user(new_user_id),
+            Err(DBError::NotFound)
+        ));
+
+        // Registered users can still renew their subscription
+        let receipt = gatekeeper.add_update_user(user_id).unwrap();
+        assert_eq!(receipt.available_slots(), SLOTS * 2);
+    }
+
     #[test]
     fn test_add_update_appointment() {
         let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
@@ -643,6 +1625,70 @@ mod tests {
         assert_eq!(loaded_user.available_slots, updated_slot_count);
     }
 
+    #[test]
+    fn test_add_update_appointments() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let user_id = get_random_user_id();
+        gatekeeper.add_update_user(user_id).unwrap();
+
+        // A batch consumes the slots of all its appointments at once
+        let appointments: Vec<_> = (0..5)
+            .map(|_| generate_dummy_appointment_with_user(user_id, None))
+            .collect();
+        let batch: Vec<_> = appointments.iter().map(|(u, a)| (*u, a)).collect();
+        let available_slots = gatekeeper.add_update_appointments(user_id, &batch).unwrap();
+        assert_eq!(available_slots, SLOTS - 5);
+        for (uuid, _) in appointments.iter() {
+            assert!(gatekeeper.registered_users.lock().unwrap()[&user_id]
+                .appointments
+                .contains_key(uuid));
+        }
+        assert_eq!(
+            gatekeeper
+                .dbm
+                .lock()
+                .unwrap()
+                .load_user(user_id)
+                .unwrap()
+                .available_slots,
+            available_slots
+        );
+
+        // Updates (both within the batch and of already added appointments) only account for the slot difference
+        let (uuid, appointment) = &appointments[0];
+        let mut bigger_appointment = appointment.clone();
+        bigger_appointment.inner.encrypted_blob = get_random_bytes(ENCRYPTED_BLOB_MAX_SIZE + 1);
+        assert_eq!(
+            gatekeeper.add_update_appointments(
+                user_id,
+                &[(*uuid, &bigger_appointment), (*uuid, &bigger_appointment)]
+            ),
+            Ok(available_slots - 1)
+        );
+
+        // If there are not enough slots for the whole batch, none of the appointments is added
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = 1;
+        let new_appointments: Vec<_> = (0..2)
+            .map(|_| generate_dummy_appointment_with_user(user_id, None))
+            .collect();
+        let batch: Vec<_> = new_appointments.iter().map(|(u, a)| (*u, a)).collect();
+        assert!(matches!(
+            gatekeeper.add_update_appointments(user_id, &batch),
+            Err(NotEnoughSlots)
+        ));
+        let user_info = gatekeeper.registered_users.lock().unwrap()[&user_id].clone();
+        assert_eq!(user_info.available_slots, 1);
+        for (uuid, _) in new_appointments.iter() {
+            assert!(!user_info.appointments.contains_key(uuid));
+        }
+    }
+
     #[test]
     fn test_has_subscription_expired() {
         let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
@@ -676,6 +1722,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_is_renewal_due() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let height = START_HEIGHT as u32;
+
+        // Subscriptions are due for renewal if they expire within the next RENEWAL_WINDOW blocks
+        assert!(!gatekeeper.is_renewal_due(height + RENEWAL_WINDOW + 1));
+        assert!(gatekeeper.is_renewal_due(height + RENEWAL_WINDOW));
+        assert!(gatekeeper.is_renewal_due(height + 1));
+
+        // Expired subscriptions are not due for renewal, they are already expired
+        assert!(!gatekeeper.is_renewal_due(height));
+
+        // A zero renewal window disables reminders
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let gatekeeper = Gatekeeper::new(
+            height,
+            GatekeeperSettings {
+                renewal_window: 0,
+                ..get_gatekeeper_settings()
+            },
+            dbm,
+        );
+        assert!(!gatekeeper.is_renewal_due(height + 1));
+    }
+
+    #[test]
+    fn test_get_renewal_due_users() {
+        let gatekeeper = init_gatekeeper(&Blockchain::default().with_height(START_HEIGHT));
+        let height = START_HEIGHT as u32;
+
+        // Only the users whose subscription enters the renewal window at the given height are returned
+        let user_id = get_random_user_id();
+        let receipt = gatekeeper.add_update_user(user_id).unwrap();
+        let expiry = receipt.subscription_expiry();
+
+        assert!(gatekeeper.get_renewal_due_users(height).is_empty());
+        assert_eq!(
+            gatekeeper.get_renewal_due_users(expiry - RENEWAL_WINDOW),
+            HashMap::from([(user_id, expiry)])
+        );
+        assert!(gatekeeper
+            .get_renewal_due_users(expiry - RENEWAL_WINDOW + 1)
+            .is_empty());
+
+        // Heights whose window would start past the highest possible expiry have no due users
+        assert!(gatekeeper.get_renewal_due_users(u32::MAX).is_empty());
+    }
+
     #[test]
     fn test_get_outdated_users() {
         let start_height = START_HEIGHT as u32 + EXPIRY_DELTA;
@@ -839,9 +1934,31 @@ mod tests {
             gatekeeper.add_outdated_user(*user_id, chain.tip().height + 1, None)
         }
 
+        // Also add a user whose subscription enters the renewal window in the next block
+        let user4_id = get_random_user_id();
+        gatekeeper.add_update_user(user4_id).unwrap();
+        gatekeeper
+            .registered_users
+            .lock()
+            .unwrap()
+            .get_mut(&user4_id)
+            .unwrap()
+            .subscription_expiry = chain.tip().height + 1 + RENEWAL_WINDOW;
+        let mut reminders = gatekeeper.subscribe_renewal_reminders();
+
         // Connect a new block. Outdated users are deleted
         gatekeeper.block_connected(&chain.generate(None), chain.get_block_count());
 
+        // The user about to expire should have been reminded (only once)
+        assert_eq!(
+            reminders.try_recv().unwrap(),
+            RenewalReminder {
+                user_id: user4_id,
+                subscription_expiry: chain.get_block_count() + RENEWAL_WINDOW
+            }
+        );
+        assert!(reminders.try_recv().is_err());
+
         // Check that users have been removed from registered_users and the database
         for user_id in &[user1_id, user2_id, user3_id] {
             assert!(!gatekeeper
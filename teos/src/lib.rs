@@ -3,7 +3,8 @@
 //! A watchtower implementation written in Rust.
 
 // FIXME: This is a temporary fix. See https://github.com/tokio-rs/prost/issues/661
-#[allow(clippy::derive_partial_eq_without_eq)]
+// Streaming RPCs (e.g. subscribe_renewal_reminders) make tonic generate associated types named after the RPC.
+#[allow(clippy::derive_partial_eq_without_eq, non_camel_case_types)]
 pub mod protos {
     tonic::include_proto!("teos.v2");
 }
@@ -11,17 +12,25 @@ pub mod api;
 pub mod bitcoin_cli;
 pub mod carrier;
 pub mod chain_monitor;
+pub mod chain_source;
 pub mod cli_config;
 pub mod config;
 pub mod dbm;
+pub mod disk_monitor;
 #[doc(hidden)]
 mod errors;
-mod extended_appointment;
+pub mod extended_appointment;
 pub mod gatekeeper;
+pub mod metrics;
+pub mod policy;
+mod receipt_batcher;
 pub mod responder;
-#[doc(hidden)]
-mod rpc_errors;
+pub mod rpc_errors;
+pub mod storage;
+pub mod sync_monitor;
+pub mod telemetry;
 pub mod tls;
+pub mod tower;
 mod tx_index;
 pub mod watcher;
 
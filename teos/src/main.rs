@@ -1,9 +1,7 @@
-use log::LevelFilter;
-use simple_logger::SimpleLogger;
 use std::fs;
 use std::io::ErrorKind;
-use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Condvar, Mutex};
 use structopt::StructOpt;
 use tokio::task;
@@ -13,51 +11,29 @@ use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
 use bitcoincore_rpc::{Auth, Client};
 use lightning_block_sync::init::validate_best_block_header;
-use lightning_block_sync::poll::{
-    ChainPoller, Poll, Validate, ValidatedBlock, ValidatedBlockHeader,
-};
+use lightning_block_sync::poll::{ChainPoller, Validate};
 use lightning_block_sync::{BlockSource, SpvClient, UnboundedCache};
 
 use teos::api::internal::InternalAPI;
 use teos::api::{http, tor::TorAPI};
 use teos::bitcoin_cli::BitcoindClient;
 use teos::carrier::Carrier;
-use teos::chain_monitor::ChainMonitor;
+use teos::chain_monitor::{ChainMonitor, PollingStrategy};
+use teos::chain_source::{BitcoindChainSource, BlockFeed};
 use teos::config::{self, Config, Opt};
 use teos::dbm::DBM;
-use teos::gatekeeper::Gatekeeper;
+use teos::disk_monitor::DiskMonitor;
+use teos::metrics::{self, LatencyStats};
 use teos::protos as msgs;
 use teos::protos::private_tower_services_server::PrivateTowerServicesServer;
 use teos::protos::public_tower_services_server::PublicTowerServicesServer;
-use teos::responder::Responder;
-use teos::tls::tls_init;
-use teos::watcher::Watcher;
+use teos::sync_monitor::{SyncMonitor, SyncPolicy, SyncStatus};
+use teos::telemetry;
+use teos::tls::{self, tls_init};
+use teos::tower::{get_last_n_blocks, TowerBuilder};
+use teos::watcher::PreviousKey;
 
-use teos_common::constants::IRREVOCABLY_RESOLVED;
 use teos_common::cryptography::get_random_keypair;
-use teos_common::TowerId;
-
-async fn get_last_n_blocks<B, T>(
-    poller: &mut ChainPoller<B, T>,
-    mut last_known_block: ValidatedBlockHeader,
-    n: usize,
-) -> Vec<ValidatedBlock>
-where
-    B: DerefMut<Target = T> + Sized + Send + Sync,
-    T: BlockSource,
-{
-    let mut last_n_blocks = Vec::with_capacity(n);
-    for _ in 0..n {
-        let block = poller.fetch_block(&last_known_block).await.unwrap();
-        last_known_block = poller
-            .look_up_previous_header(&last_known_block)
-            .await
-            .unwrap();
-        last_n_blocks.push(block);
-    }
-
-    last_n_blocks
-}
 
 fn create_new_tower_keypair(db: &DBM) -> (SecretKey, PublicKey) {
     let (sk, pk) = get_random_keypair();
@@ -85,23 +61,18 @@ async fn main() {
         std::process::exit(1);
     });
 
-    // Set log level
-    SimpleLogger::new()
-        .with_level(if conf.deps_debug {
-            LevelFilter::Debug
-        } else {
-            LevelFilter::Warn
-        })
-        .with_module_level(
-            "teos",
-            if conf.debug {
-                LevelFilter::Debug
-            } else {
-                LevelFilter::Info
-            },
-        )
-        .init()
-        .unwrap();
+    // Set log level (and span exporting, if enabled)
+    let latency_stats = Arc::new(LatencyStats::new());
+    telemetry::init(
+        conf.debug,
+        conf.deps_debug,
+        (!conf.otlp_endpoint.is_empty()).then(|| conf.otlp_endpoint.as_str()),
+        latency_stats.clone(),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Cannot set up logging: {}", e);
+        std::process::exit(1);
+    });
 
     if is_default {
         log::info!("Loading default configuration")
@@ -109,7 +80,8 @@ async fn main() {
         log::info!("Loading configuration from file")
     }
 
-    // Create network dir
+    // Create network dir. Everything network specific (database, tower keys, tls and tor keys) lives in here, so
+    // instances for different networks can share the same data dir.
     let path_network = path.join(conf.btc_network.clone());
     fs::create_dir_all(&path_network).unwrap_or_else(|e| {
         eprintln!("Cannot create network dir: {:?}", e);
@@ -119,12 +91,46 @@ async fn main() {
         DBM::new(path_network.join("teos_db.sql3")).unwrap(),
     ));
 
+    // Make sure the database was not created for a different network (e.g. if it was manually moved around)
+    {
+        let locked_db = dbm.lock().unwrap();
+        match locked_db.load_network() {
+            Ok(network) => {
+                if network != conf.btc_network {
+                    log::error!(
+                        "The database at {:?} belongs to a different network (expected: {}, found: {})",
+                        path_network,
+                        conf.btc_network,
+                        network
+                    );
+                    std::process::exit(1);
+                }
+            }
+            Err(_) => locked_db.store_network(&conf.btc_network).unwrap(),
+        }
+    }
+
+    // Check the user and appointment data is consistent before loading it. This may not be the case if the database
+    // has been modified by hand.
+    let (orphan_appointments, orphan_trackers, repaired_users) =
+        dbm.lock().unwrap().remove_orphan_data();
+    if orphan_appointments + orphan_trackers + repaired_users > 0 {
+        log::warn!(
+            "Inconsistent data found in the database. Removed {} orphan appointments and {} orphan trackers, and repaired the slots of {} users",
+            orphan_appointments,
+            orphan_trackers,
+            repaired_users
+        );
+    }
+
     // Load tower secret key or create a fresh one if none is found. If overwrite key is set, create a new
-    // key straightaway
+    // key straightaway. Overwriting an existing key counts as a key rotation
+    let mut key_rotated = false;
     let (tower_sk, tower_pk) = {
         let locked_db = dbm.lock().unwrap();
         if conf.overwrite_key {
             log::info!("Overwriting tower keys");
+            key_rotated = locked_db.load_tower_key().is_ok();
             create_new_tower_keypair(&locked_db)
         } else {
             match locked_db.load_tower_key() {
@@ -145,6 +151,7 @@ async fn main() {
         &conf.btc_rpc_user,
         &conf.btc_rpc_password,
         &conf.btc_network,
+        conf.btc_rest,
     )
     .await
     {
@@ -169,42 +176,62 @@ async fn main() {
     } else {
         ""
     };
-    let rpc = Arc::new(
-        Client::new(
-            &format!("{}{}:{}", schema, conf.btc_rpc_connect, conf.btc_rpc_port),
-            Auth::UserPass(conf.btc_rpc_user.clone(), conf.btc_rpc_password.clone()),
-        )
-        .unwrap(),
-    );
-    let mut derefed = bitcoin_cli.deref();
+    let rpc = Client::new(
+        &format!("{}{}:{}", schema, conf.btc_rpc_connect, conf.btc_rpc_port),
+        Auth::UserPass(conf.btc_rpc_user.clone(), conf.btc_rpc_password.clone()),
+    )
+    .unwrap();
+    // Blocks (ChainMonitor), transactions (Carrier) and sync state (SyncMonitor) are handled through the same chain source.
+    let chain_source = Arc::new(BitcoindChainSource::new(bitcoin_cli, rpc));
+    let mut block_feed = BlockFeed::new(chain_source.clone());
     // Load last known block from DB if found. Poll it from Bitcoind otherwise.
     let last_known_block = dbm.lock().unwrap().load_last_known_block();
     let tip = if let Ok(block_hash) = last_known_block {
-        derefed
+        block_feed
             .get_header(&block_hash, None)
             .await
             .unwrap()
             .validate(block_hash)
             .unwrap()
     } else {
-        validate_best_block_header(&mut derefed).await.unwrap()
+        validate_best_block_header(&mut block_feed).await.unwrap()
     };
 
-    // DISCUSS: This is not really required (and only triggered in regtest). This is only in place so the caches can be
-    // populated with enough blocks mainly because the size of the cache is based on the amount of blocks passed when initializing.
-    // However, we could add an additional parameter to specify the size of the cache, and initialize with however may blocks we
-    // could pull from the backend. Adding this functionality just for regtest seemed unnecessary though, hence the check.
-    if tip.height < IRREVOCABLY_RESOLVED {
+    // The caches are populated on bootstrap, so the size of each cache is based on the amount of blocks passed when
+    // initializing. Make sure the chain is long enough to fill the biggest one (this is mainly triggered in regtest).
+    let builder = TowerBuilder::from_config(&conf);
+    let required_blocks = builder.required_blocks();
+    if tip.height < required_blocks {
         log::error!(
             "Not enough blocks to start teosd (required: {}). Mine at least {} more",
-            IRREVOCABLY_RESOLVED,
-            IRREVOCABLY_RESOLVED - tip.height
+            required_blocks,
+            required_blocks - tip.height
         );
         std::process::exit(1);
     }
 
     log::info!("Last known block: {}", tip.header.block_hash());
 
+    // Rotations are recorded at the current height, so the previous key keeps co-signing receipts until the overlap
+    // window closes (also across restarts)
+    let builder = {
+        let locked_db = dbm.lock().unwrap();
+        if key_rotated {
+            locked_db.store_key_rotation(tip.height).unwrap();
+        }
+        match locked_db.load_previous_tower_key() {
+            Ok((previous_sk, rotated_at))
+                if tip.height < rotated_at.saturating_add(conf.key_rotation_overlap) =>
+            {
+                builder.previous_key(PreviousKey::new(
+                    previous_sk,
+                    rotated_at.saturating_add(conf.key_rotation_overlap),
+                ))
+            }
+            _ => builder,
+        }
+    };
+
     // This is how chain poller names bitcoin networks.
     let btc_network = match conf.btc_network.as_str() {
         "main" => "bitcoin",
@@ -212,37 +239,48 @@ async fn main() {
         any => any,
     };
 
-    let mut poller = ChainPoller::new(&mut derefed, Network::from_str(btc_network).unwrap());
-    let last_n_blocks = get_last_n_blocks(&mut poller, tip, IRREVOCABLY_RESOLVED as usize).await;
+    let mut poller = ChainPoller::new(&mut block_feed, Network::from_str(btc_network).unwrap());
+    let last_n_blocks = get_last_n_blocks(&mut poller, tip, required_blocks as usize)
+        .await
+        .unwrap_or_else(|e| {
+            // This may happen if the backend cannot serve old enough blocks (e.g. a pruned node)
+            log::error!(
+                "Cannot fetch the last {} blocks from bitcoind (is locator_cache_size too big for a pruned node?). Error: {:?}",
+                required_blocks,
+                e
+            );
+            std::process::exit(1);
+        });
 
     // Build components
-    let gatekeeper = Arc::new(Gatekeeper::new(
-        tip.height,
-        conf.subscription_slots,
-        conf.subscription_duration,
-        conf.expiry_delta,
-        dbm.clone(),
-    ));
-
-    let carrier = Carrier::new(rpc, bitcoind_reachable.clone(), tip.height);
-    let responder = Arc::new(Responder::new(
-        &last_n_blocks,
-        tip.height,
-        carrier,
-        gatekeeper.clone(),
-        dbm.clone(),
-    ));
-    let watcher = Arc::new(Watcher::new(
-        gatekeeper.clone(),
-        responder.clone(),
-        &last_n_blocks[0..6],
+    if conf.require_payments {
+        log::info!(
+            "Payments required. Registrations must pay {} msat per slot and {} msat per block",
+            conf.subscription_price_per_slot_msat,
+            conf.subscription_price_per_block_msat
+        );
+    }
+    if conf.dry_run {
+        log::warn!("Running in dry-run mode. Penalty transactions will NOT be broadcast");
+    }
+    if conf.breach_log {
+        log::info!("Breach log enabled. Responded breaches will be publicly available");
+    }
+    let carrier = Carrier::new(
+        chain_source.clone(),
+        bitcoind_reachable.clone(),
         tip.height,
-        tower_sk,
-        TowerId(tower_pk),
-        dbm.clone(),
-    ));
-
-    if watcher.is_fresh() & responder.is_fresh() & gatekeeper.is_fresh() {
+        conf.dry_run,
+        conf.esplora_broadcast_urls.clone(),
+    );
+    let tower = builder
+        .build(dbm.clone(), carrier, &last_n_blocks, tip.height, tower_sk)
+        .unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1);
+        });
+
+    if tower.is_fresh() {
         log::info!("Fresh bootstrap");
     } else {
         log::info!("Bootstrapping from backed up data");
@@ -253,17 +291,19 @@ async fn main() {
     let shutdown_signal_http = shutdown_signal_rpc_api.clone();
     let shutdown_signal_cm = shutdown_signal_rpc_api.clone();
     let shutdown_signal_tor = shutdown_signal_rpc_api.clone();
+    let shutdown_signal_disk = shutdown_signal_rpc_api.clone();
+    let shutdown_signal_sync = shutdown_signal_rpc_api.clone();
+    let shutdown_signal_metrics = shutdown_signal_rpc_api.clone();
 
-    // The ordering here actually matters. Listeners are called by order, and we want the gatekeeper to be called
-    // last, so both the Watcher and the Responder can query the necessary data from it during data deletion.
-    let listener = &(watcher.clone(), &(responder, gatekeeper));
+    // The listener takes care of calling the components in the order they expect (check TowerListener).
+    let listener = &tower.listener();
     let cache = &mut UnboundedCache::new();
     let spv_client = SpvClient::new(tip, poller, cache, listener);
     let mut chain_monitor = ChainMonitor::new(
         spv_client,
         tip,
         dbm,
-        conf.polling_delta,
+        PollingStrategy::from_config(&conf),
         shutdown_signal_cm,
         bitcoind_reachable.clone(),
     )
@@ -273,27 +313,53 @@ async fn main() {
     chain_monitor.poll_best_tip().await;
     log::info!("Bootstrap completed. Turning on interfaces");
 
-    // Build interfaces
-    let http_api_addr = format!("{}:{}", conf.api_bind, conf.api_port)
-        .parse()
-        .unwrap();
-    let mut addresses = vec![msgs::NetworkAddress::from_ipv4(
-        conf.api_bind.clone(),
-        conf.api_port,
-    )];
+    // Check there's enough disk space before accepting any data, and keep monitoring it once the tower is up.
+    let low_disk_space = Arc::new(AtomicBool::new(false));
+    let disk_monitor = (conf.min_free_disk_space_mb > 0).then(|| {
+        let disk_monitor = DiskMonitor::new(
+            path_network.clone(),
+            conf.min_free_disk_space_mb,
+            low_disk_space.clone(),
+            shutdown_signal_disk,
+        );
+        disk_monitor.check_free_space();
+        disk_monitor
+    });
+
+    // Check how far behind the best known chain we are (e.g. if bitcoind is still in IBD) and apply the sync policy accordingly.
+    let sync_policy = SyncPolicy::from_config(&conf);
+    if let SyncPolicy::Reject(max_blocks_behind) = sync_policy {
+        log::info!(
+            "New users and appointments will be rejected while more than {} blocks behind the best known header",
+            max_blocks_behind
+        );
+    }
+    let sync_status = Arc::new(Mutex::new(SyncStatus::new(sync_policy)));
+    let sync_monitor = SyncMonitor::new(chain_source, sync_status.clone(), shutdown_signal_sync);
+    sync_monitor.check_sync().await;
+
+    // Build interfaces. Bind addresses have already been checked when verifying the config.
+    let http_api_addrs = conf.api_addresses().unwrap();
+    let mut addresses: Vec<msgs::NetworkAddress> = http_api_addrs
+        .iter()
+        .map(|addr| msgs::NetworkAddress::from_socket_addr(*addr))
+        .collect();
 
     // Create Tor endpoint if required
     let tor_api = if conf.tor_support {
+        // Tor support requires the API to be enabled, so there is at least one address to forward to. The onion address
+        // is advertised with the port of the address it forwards to.
+        let forwarded_addr = http_api_addrs[0];
         let tor_api = TorAPI::new(
-            http_api_addr,
+            forwarded_addr,
             conf.onion_hidden_service_port,
             conf.tor_control_port,
-            path_network,
+            path_network.clone(),
         )
         .await;
         addresses.push(msgs::NetworkAddress::from_torv3(
             tor_api.get_onion_address(),
-            conf.api_port,
+            forwarded_addr.port(),
         ));
 
         Some(tor_api)
@@ -302,16 +368,18 @@ async fn main() {
     };
 
     let rpc_api = Arc::new(InternalAPI::new(
-        watcher,
+        tower.watcher.clone(),
         addresses,
         bitcoind_reachable.clone(),
+        low_disk_space,
+        sync_status,
+        latency_stats.clone(),
         shutdown_trigger,
+        conf.operator_key(),
     ));
     let internal_rpc_api = rpc_api.clone();
 
-    let rpc_api_addr = format!("{}:{}", conf.rpc_bind, conf.rpc_port)
-        .parse()
-        .unwrap();
+    let rpc_api_addrs = conf.rpc_addresses().unwrap();
     let internal_rpc_api_addr = format!("{}:{}", conf.internal_api_bind, conf.internal_api_port)
         .parse()
         .unwrap();
@@ -320,9 +388,22 @@ async fn main() {
         conf.internal_api_bind, conf.internal_api_port
     );
 
-    // Generate mtls certificates to data directory so the admin can securely connect
-    // to the server to perform administrative tasks.
-    let (identity, ca_cert) = tls_init(&path).unwrap_or_else(|e| {
+    // Generate mtls certificates to the network directory so the admin can securely connect
+    // to the server to perform administrative tasks. Certificates generated by older versions (straight into the
+    // data directory) are moved to the first network started, so existing clients are still trusted.
+    match tls::migrate_legacy_identities(&path, &path_network) {
+        Ok(true) => log::info!(
+            "Moved the tls certificates from {:?} to {:?}",
+            path,
+            path_network
+        ),
+        Ok(false) => (),
+        Err(e) => {
+            eprintln!("Couldn't migrate tls certificates: {:?}", e);
+            std::process::exit(1);
+        }
+    }
+    let (identity, ca_cert) = tls_init(&path_network).unwrap_or_else(|e| {
         eprintln!("Couldn't generate tls certificates: {:?}", e);
         std::process::exit(1);
     });
@@ -332,15 +413,48 @@ async fn main() {
         .client_ca_root(Certificate::from_pem(ca_cert));
 
     // Start tasks
-    let private_api_task = task::spawn(async move {
-        Server::builder()
-            .tls_config(tls)
-            .expect("couldn't configure tls")
-            .add_service(PrivateTowerServicesServer::new(rpc_api))
-            .serve_with_shutdown(rpc_api_addr, shutdown_signal_rpc_api)
-            .await
-            .unwrap();
-    });
+    let private_api_tasks: Vec<_> = rpc_api_addrs
+        .into_iter()
+        .map(|rpc_api_addr| {
+            let tls = tls.clone();
+            let rpc_api = rpc_api.clone();
+            let shutdown_signal_rpc_api = shutdown_signal_rpc_api.clone();
+            task::spawn(async move {
+                Server::builder()
+                    .tls_config(tls)
+                    .expect("couldn't configure tls")
+                    .add_service(PrivateTowerServicesServer::new(rpc_api))
+                    .serve_with_shutdown(rpc_api_addr, shutdown_signal_rpc_api)
+                    .await
+                    .unwrap();
+            })
+        })
+        .collect();
+    if private_api_tasks.is_empty() {
+        log::info!("RPC server disabled");
+    }
+
+    // Users may also reach the public services straight through gRPC, with the exact same handlers the HTTP API uses.
+    let public_grpc_api_tasks: Vec<_> = conf
+        .grpc_api_addresses()
+        .unwrap()
+        .into_iter()
+        .map(|grpc_api_addr| {
+            log::info!("Serving public gRPC API at {}", grpc_api_addr);
+            let rpc_api = rpc_api.clone();
+            let shutdown_signal_grpc_api = shutdown_signal_rpc_api.clone();
+            task::spawn(async move {
+                Server::builder()
+                    .add_service(PublicTowerServicesServer::with_interceptor(
+                        rpc_api,
+                        telemetry::drop_client_addr,
+                    ))
+                    .serve_with_shutdown(grpc_api_addr, shutdown_signal_grpc_api)
+                    .await
+                    .unwrap();
+            })
+        })
+        .collect();
 
     let public_api_task = task::spawn(async move {
         Server::builder()
@@ -350,14 +464,25 @@ async fn main() {
             .unwrap();
     });
 
-    let (http_service_ready, ready_signal_http) = triggered::trigger();
-    let http_api_task = task::spawn(http::serve(
-        http_api_addr,
-        internal_rpc_api_uri,
-        http_service_ready,
-        shutdown_signal_http,
-    ));
-    ready_signal_http.await;
+    let http_api_task = if http_api_addrs.is_empty() {
+        log::info!("API disabled");
+        None
+    } else {
+        let (http_service_ready, ready_signal_http) = triggered::trigger();
+        let http_api_task = task::spawn(http::serve(
+            http_api_addrs,
+            internal_rpc_api_uri,
+            http::cors(
+                &conf.api_cors_origins,
+                &conf.api_cors_methods,
+                &conf.api_cors_headers,
+            ),
+            http_service_ready,
+            shutdown_signal_http,
+        ));
+        ready_signal_http.await;
+        Some(http_api_task)
+    };
 
     // Add Tor Onion Service for public API
     let mut tor_task = Option::None;
@@ -378,16 +503,56 @@ async fn main() {
         ready_signal_tor.await
     }
 
+    // The metrics bind addresses have already been checked when verifying the config.
+    let metrics_tasks: Vec<_> = conf
+        .metrics_addresses()
+        .unwrap()
+        .into_iter()
+        .map(|metrics_addr| {
+            log::info!("Serving metrics at {}", metrics_addr);
+            task::spawn(metrics::serve(
+                metrics_addr,
+                latency_stats.clone(),
+                tower.responder.clone(),
+                shutdown_signal_metrics.clone(),
+            ))
+        })
+        .collect();
+
+    let disk_monitor_task = match disk_monitor {
+        Some(disk_monitor) => Some(task::spawn(
+            async move { disk_monitor.monitor_disk().await },
+        )),
+        None => {
+            log::info!("Disk space monitoring disabled");
+            None
+        }
+    };
+
     log::info!("Tower ready");
-    chain_monitor.monitor_chain().await;
+    tokio::join!(chain_monitor.monitor_chain(), sync_monitor.monitor_sync());
 
     // Wait until shutdown
-    http_api_task.await.unwrap();
-    private_api_task.await.unwrap();
+    if let Some(http_api_task) = http_api_task {
+        http_api_task.await.unwrap();
+    }
+    for private_api_task in private_api_tasks {
+        private_api_task.await.unwrap();
+    }
+    for public_grpc_api_task in public_grpc_api_tasks {
+        public_grpc_api_task.await.unwrap();
+    }
     public_api_task.await.unwrap();
     if let Some(tor_task) = tor_task {
         tor_task.await.unwrap();
     }
+    if let Some(disk_monitor_task) = disk_monitor_task {
+        disk_monitor_task.await.unwrap();
+    }
+    for metrics_task in metrics_tasks {
+        metrics_task.await.unwrap();
+    }
 
     log::info!("Shutting down tower");
+    telemetry::shutdown();
 }
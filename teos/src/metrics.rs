@@ -0,0 +1,390 @@
+//! Logic related to the tower metrics: response-time (latency) histograms per endpoint and per internal stage.
+//!
+//! Latencies are measured from the tracing spans: every request span (named `request`, with an `api` and a `method`
+//! field) and stage span (named `stage`, with a `name` field, check [stage_span](crate::telemetry::stage_span)) is timed from creation
+//! until it is closed. Stats are served to the operator via the `get_latency_stats` RPC and, optionally, a metrics
+//! endpoint using the Prometheus text format. The metrics endpoint also exports some gauges about the tower state, such
+//! as the number of penalties below the mempool min fee.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use triggered::Listener;
+use warp::Filter;
+
+use crate::responder::Responder;
+use crate::storage::Storage;
+
+/// Upper bounds (in seconds) of the latency histogram buckets.
+pub const BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Label used for internal stages.
+pub const STAGE: &str = "stage";
+
+/// A latency histogram with fixed buckets (check [BUCKETS]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Number of observations per bucket. The last one holds the ones exceeding the biggest bucket bound.
+    buckets: [u64; BUCKETS.len() + 1],
+    /// Number of observations.
+    count: u64,
+    /// Sum of all the observations.
+    sum: Duration,
+    /// The biggest observation.
+    max: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS.len() + 1],
+            count: 0,
+            sum: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl Histogram {
+    /// Records a new observation.
+    pub fn observe(&mut self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// Gets the number of observations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Gets the mean of the observations.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.sum.as_nanos() / self.count as u128) as u64)
+        }
+    }
+
+    /// Gets the biggest observation.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Estimates the given quantile (between 0 and 1) as the upper bound of the bucket it falls into.
+    ///
+    /// The estimation is capped by the biggest observation, so it is never above the real value by more than a bucket.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = (q * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank.max(1) {
+                return match BUCKETS.get(i) {
+                    Some(bound) => Duration::from_secs_f64(*bound).min(self.max),
+                    None => self.max,
+                };
+            }
+        }
+        self.max
+    }
+}
+
+/// Latency histograms of the tower, indexed by kind (`http`, `public`, `private` or [STAGE]) and name (the endpoint
+/// method or stage name).
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    histograms: Mutex<BTreeMap<(String, String), Histogram>>,
+}
+
+impl LatencyStats {
+    /// Creates a new (empty) [LatencyStats] instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a latency for the given kind and name.
+    pub fn record(&self, kind: &str, name: &str, latency: Duration) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry((kind.to_owned(), name.to_owned()))
+            .or_default()
+            .observe(latency);
+    }
+
+    /// Gets a copy of all the histograms, sorted by kind and name.
+    pub fn snapshot(&self) -> Vec<((String, String), Histogram)> {
+        self.histograms
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(k, h)| (k.clone(), h.clone()))
+            .collect()
+    }
+
+    /// Renders the histograms using the Prometheus text format.
+    pub fn render(&self) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+        for (metric, help, stages) in [
+            (
+                "teos_request_duration_seconds",
+                "Time spent serving requests, per API and method",
+                false,
+            ),
+            (
+                "teos_stage_duration_seconds",
+                "Time spent in internal stages while serving requests",
+                true,
+            ),
+        ] {
+            writeln!(out, "# HELP {} {}", metric, help).unwrap();
+            writeln!(out, "# TYPE {} histogram", metric).unwrap();
+            for ((kind, name), histogram) in
+                snapshot.iter().filter(|((k, _), _)| (k == STAGE) == stages)
+            {
+                let labels = if stages {
+                    format!("stage=\"{}\"", name)
+                } else {
+                    format!("api=\"{}\",method=\"{}\"", kind, name)
+                };
+                let mut cumulative = 0;
+                for (bound, n) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+                    cumulative += n;
+                    writeln!(
+                        out,
+                        "{}_bucket{{{},le=\"{}\"}} {}",
+                        metric, labels, bound, cumulative
+                    )
+                    .unwrap();
+                }
+                writeln!(
+                    out,
+                    "{}_bucket{{{},le=\"+Inf\"}} {}",
+                    metric, labels, histogram.count
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "{}_sum{{{}}} {}",
+                    metric,
+                    labels,
+                    histogram.sum.as_secs_f64()
+                )
+                .unwrap();
+                writeln!(out, "{}_count{{{}}} {}", metric, labels, histogram.count).unwrap();
+            }
+        }
+        out
+    }
+}
+
+/// Renders a gauge using the Prometheus text format.
+pub fn render_gauge(metric: &str, help: &str, value: usize) -> String {
+    format!(
+        "# HELP {0} {1}\n# TYPE {0} gauge\n{0} {2}\n",
+        metric, help, value
+    )
+}
+
+/// Timing data attached to the measured spans.
+struct Timing {
+    kind: String,
+    name: String,
+    start: Instant,
+}
+
+/// Collects the fields used to label the measured spans.
+#[derive(Default)]
+struct LabelVisitor {
+    api: Option<String>,
+    name: Option<String>,
+}
+
+impl Visit for LabelVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "api" => self.api = Some(value.to_owned()),
+            "method" | "name" => self.name = Some(value.to_owned()),
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value))
+    }
+}
+
+/// Tracing layer measuring how long request and stage spans live, and recording it into a [LatencyStats] instance.
+pub struct LatencyLayer {
+    stats: Arc<LatencyStats>,
+}
+
+impl LatencyLayer {
+    /// Creates a new [LatencyLayer] instance.
+    pub fn new(stats: Arc<LatencyStats>) -> Self {
+        Self { stats }
+    }
+}
+
+impl<S> Layer<S> for LatencyLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span_name = attrs.metadata().name();
+        if span_name != "request" && span_name != STAGE {
+            return;
+        }
+
+        let mut visitor = LabelVisitor::default();
+        attrs.record(&mut visitor);
+        let kind = if span_name == STAGE {
+            Some(STAGE.to_owned())
+        } else {
+            visitor.api
+        };
+
+        if let (Some(kind), Some(name), Some(span)) = (kind, visitor.name, ctx.span(id)) {
+            span.extensions_mut().insert(Timing {
+                kind,
+                name,
+                start: Instant::now(),
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(timing) = span.extensions().get::<Timing>() {
+                self.stats
+                    .record(&timing.kind, &timing.name, timing.start.elapsed());
+            }
+        }
+    }
+}
+
+/// Serves the latency stats and the [Responder] gauges (Prometheus text format) at `/metrics` until the shutdown signal
+/// is received.
+pub async fn serve<S: Storage + 'static>(
+    metrics_bind: SocketAddr,
+    stats: Arc<LatencyStats>,
+    responder: Arc<Responder<S>>,
+    shutdown_signal: Listener,
+) {
+    let route = warp::get()
+        .and(warp::path("metrics"))
+        .and(warp::path::end())
+        .map(move || {
+            let mut out = stats.render();
+            out.push_str(&render_gauge(
+                "teos_penalties_below_min_fee",
+                "Penalties whose feerate did not clear the mempool min fee the last time they were broadcast",
+                responder.get_low_fee_trackers_count(),
+            ));
+            out
+        });
+    let (_, server) = warp::serve(route).bind_with_graceful_shutdown(metrics_bind, shutdown_signal);
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+
+        for ms in [1, 1, 2, 3, 40] {
+            histogram.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Duration::from_micros(9400));
+        assert_eq!(histogram.max(), Duration::from_millis(40));
+
+        // Quantiles are estimated by the bucket bounds
+        assert_eq!(histogram.quantile(0.3), Duration::from_millis(1));
+        assert_eq!(histogram.quantile(0.5), Duration::from_secs_f64(0.0025));
+        // But never go above the biggest observation
+        assert_eq!(histogram.quantile(0.99), Duration::from_millis(40));
+
+        // Observations over the biggest bound are still accounted for
+        histogram.observe(Duration::from_secs(10));
+        assert_eq!(histogram.quantile(1.0), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_render() {
+        let stats = LatencyStats::new();
+        stats.record("public", "register", Duration::from_millis(3));
+        stats.record(STAGE, "auth", Duration::from_millis(1));
+
+        let rendered = stats.render();
+        assert!(rendered.contains(
+            "teos_request_duration_seconds_bucket{api=\"public\",method=\"register\",le=\"0.005\"} 1"
+        ));
+        assert!(rendered.contains(
+            "teos_request_duration_seconds_bucket{api=\"public\",method=\"register\",le=\"0.0025\"} 0"
+        ));
+        assert!(rendered
+            .contains("teos_request_duration_seconds_count{api=\"public\",method=\"register\"} 1"));
+        assert!(
+            rendered.contains("teos_stage_duration_seconds_bucket{stage=\"auth\",le=\"+Inf\"} 1")
+        );
+        assert!(!rendered.contains("teos_stage_duration_seconds_count{stage=\"register\"}"));
+    }
+
+    #[test]
+    fn test_render_gauge() {
+        assert_eq!(
+            render_gauge("teos_penalties_below_min_fee", "Some help", 3),
+            "# HELP teos_penalties_below_min_fee Some help\n# TYPE teos_penalties_below_min_fee gauge\nteos_penalties_below_min_fee 3\n"
+        );
+    }
+
+    #[test]
+    fn test_latency_layer() {
+        let stats = Arc::new(LatencyStats::new());
+        let subscriber = tracing_subscriber::registry().with(LatencyLayer::new(stats.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", api = "public", method = "register").in_scope(|| {
+                tracing::info_span!("stage", name = "auth").in_scope(|| ());
+            });
+            // Spans are only measured if labeled
+            tracing::info_span!("request", method = "register").in_scope(|| ());
+            tracing::info_span!("appointment").in_scope(|| ());
+        });
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, ("public".to_owned(), "register".to_owned()));
+        assert_eq!(snapshot[0].1.count(), 1);
+        assert_eq!(snapshot[1].0, (STAGE.to_owned(), "auth".to_owned()));
+        assert_eq!(snapshot[1].1.count(), 1);
+    }
+}
@@ -0,0 +1,273 @@
+//! Logic related to appointment acceptance policies. Policies are checked by the [Watcher](crate::watcher::Watcher)
+//! before accepting any appointment.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+use teos_common::appointment::Appointment;
+use teos_common::UserId;
+
+use crate::config::Config;
+
+/// Reason given by an [AcceptancePolicy] when rejecting an appointment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation(pub String);
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The user an appointment comes from, as seen by the [AcceptancePolicy]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyUser {
+    /// The id of the user.
+    pub user_id: UserId,
+    /// The slots the user has been granted, i.e. the size of the subscription they paid for.
+    pub granted_slots: u64,
+}
+
+/// A check run over every appointment sent to the tower. Appointments are only accepted if all the policies agree.
+pub trait AcceptancePolicy: Send + Sync + fmt::Debug {
+    /// Checks whether a given appointment, sent by the given user, can be accepted by the tower.
+    fn check(&self, user: &PolicyUser, appointment: &Appointment) -> Result<(), PolicyViolation>;
+}
+
+/// Rejects appointments whose encrypted blob is smaller than the given size (in bytes).
+#[derive(Debug)]
+pub struct MinBlobSize(pub usize);
+
+impl AcceptancePolicy for MinBlobSize {
+    fn check(&self, _: &PolicyUser, appointment: &Appointment) -> Result<(), PolicyViolation> {
+        if appointment.encrypted_blob.len() < self.0 {
+            Err(PolicyViolation(format!(
+                "encrypted_blob is too small (min: {}, received: {})",
+                self.0,
+                appointment.encrypted_blob.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rejects appointments whose encrypted blob is bigger than the given size (in bytes).
+#[derive(Debug)]
+pub struct MaxBlobSize(pub usize);
+
+impl AcceptancePolicy for MaxBlobSize {
+    fn check(&self, _: &PolicyUser, appointment: &Appointment) -> Result<(), PolicyViolation> {
+        if appointment.encrypted_blob.len() > self.0 {
+            Err(PolicyViolation(format!(
+                "encrypted_blob is too big (max: {}, received: {})",
+                self.0,
+                appointment.encrypted_blob.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rejects appointments coming from any of the given users.
+#[derive(Debug)]
+pub struct UserDenyList(pub HashSet<UserId>);
+
+impl AcceptancePolicy for UserDenyList {
+    fn check(&self, user: &PolicyUser, _: &Appointment) -> Result<(), PolicyViolation> {
+        if self.0.contains(&user.user_id) {
+            Err(PolicyViolation(
+                "user is not allowed to add appointments".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Payment tier required to send large appointments. Appointments whose encrypted blob is bigger than `blob_size` (in
+/// bytes) are only accepted from users that have been granted at least `min_slots` slots.
+#[derive(Debug)]
+pub struct LargeBlobTier {
+    pub blob_size: usize,
+    pub min_slots: u64,
+}
+
+impl AcceptancePolicy for LargeBlobTier {
+    fn check(&self, user: &PolicyUser, appointment: &Appointment) -> Result<(), PolicyViolation> {
+        if appointment.encrypted_blob.len() > self.blob_size && user.granted_slots < self.min_slots
+        {
+            Err(PolicyViolation(format!(
+                "encrypted_blobs bigger than {} bytes require a subscription of at least {} slots (current: {})",
+                self.blob_size, self.min_slots, user.granted_slots
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Set of [AcceptancePolicy]s enforced by the tower.
+///
+/// The built-in policies are created from the tower [Config]. Custom builds can add their own policies
+/// if the `custom-policies` feature is enabled.
+#[derive(Debug, Default)]
+pub struct PolicySet {
+    policies: Vec<Box<dyn AcceptancePolicy>>,
+}
+
+impl PolicySet {
+    /// Creates a new [PolicySet] instance holding the built-in policies enabled in the given [Config].
+    pub fn from_config(config: &Config) -> Self {
+        let mut policies: Vec<Box<dyn AcceptancePolicy>> = Vec::new();
+
+        if config.min_blob_size > 0 {
+            policies.push(Box::new(MinBlobSize(config.min_blob_size)));
+        }
+        if config.max_blob_size > 0 {
+            policies.push(Box::new(MaxBlobSize(config.max_blob_size)));
+        }
+        if !config.denied_users.is_empty() {
+            policies.push(Box::new(UserDenyList(
+                config
+                    .denied_users
+                    .iter()
+                    .filter_map(|user_id| UserId::from_str(user_id).ok())
+                    .collect(),
+            )));
+        }
+        if config.large_blob_size > 0 {
+            policies.push(Box::new(LargeBlobTier {
+                blob_size: config.large_blob_size,
+                min_slots: config.large_blob_min_slots as u64,
+            }));
+        }
+
+        PolicySet { policies }
+    }
+
+    /// Adds a custom policy to the set.
+    #[cfg(feature = "custom-policies")]
+    pub fn add_policy(&mut self, policy: Box<dyn AcceptancePolicy>) {
+        self.policies.push(policy)
+    }
+
+    /// Returns the number of policies in the set.
+    pub fn len(&self) -> usize {
+        self.policies.len()
+    }
+
+    /// Returns whether the set has no policies.
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Checks an appointment against all the policies in the set. The first violation found (if any) is returned.
+    pub(crate) fn check(
+        &self,
+        user: &PolicyUser,
+        appointment: &Appointment,
+    ) -> Result<(), PolicyViolation> {
+        for policy in self.policies.iter() {
+            policy.check(user, appointment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::generate_dummy_appointment;
+    use teos_common::test_utils::get_random_user_id;
+
+    fn get_random_user(granted_slots: u64) -> PolicyUser {
+        PolicyUser {
+            user_id: get_random_user_id(),
+            granted_slots,
+        }
+    }
+
+    #[test]
+    fn test_min_blob_size() {
+        let user = get_random_user(1);
+        let appointment = generate_dummy_appointment(None).inner;
+        let blob_size = appointment.encrypted_blob.len();
+
+        assert!(MinBlobSize(blob_size).check(&user, &appointment).is_ok());
+        assert!(MinBlobSize(blob_size + 1)
+            .check(&user, &appointment)
+            .is_err());
+    }
+
+    #[test]
+    fn test_max_blob_size() {
+        let user = get_random_user(1);
+        let appointment = generate_dummy_appointment(None).inner;
+        let blob_size = appointment.encrypted_blob.len();
+
+        assert!(MaxBlobSize(blob_size).check(&user, &appointment).is_ok());
+        assert!(MaxBlobSize(blob_size - 1)
+            .check(&user, &appointment)
+            .is_err());
+    }
+
+    #[test]
+    fn test_user_deny_list() {
+        let user = get_random_user(1);
+        let denied_user = get_random_user(1);
+        let appointment = generate_dummy_appointment(None).inner;
+        let policy = UserDenyList(HashSet::from([denied_user.user_id]));
+
+        assert!(policy.check(&user, &appointment).is_ok());
+        assert!(policy.check(&denied_user, &appointment).is_err());
+    }
+
+    #[test]
+    fn test_large_blob_tier() {
+        let appointment = generate_dummy_appointment(None).inner;
+        let blob_size = appointment.encrypted_blob.len();
+        let policy = LargeBlobTier {
+            blob_size: blob_size - 1,
+            min_slots: 100,
+        };
+
+        // Large blobs are only accepted from users with a big enough subscription
+        assert!(policy.check(&get_random_user(99), &appointment).is_err());
+        assert!(policy.check(&get_random_user(100), &appointment).is_ok());
+
+        // Blobs up to the size limit are accepted regardless of the subscription
+        let policy = LargeBlobTier {
+            blob_size,
+            min_slots: 100,
+        };
+        assert!(policy.check(&get_random_user(1), &appointment).is_ok());
+    }
+
+    #[test]
+    fn test_policy_set_from_config() {
+        // No policies are enabled by default
+        assert!(PolicySet::from_config(&Config::default()).is_empty());
+
+        let denied_user = get_random_user(1);
+        let config = Config {
+            min_blob_size: 1,
+            max_blob_size: 10,
+            denied_users: vec![denied_user.user_id.to_string()],
+            large_blob_size: 5,
+            large_blob_min_slots: 100,
+            ..Default::default()
+        };
+        let policies = PolicySet::from_config(&config);
+        assert_eq!(policies.len(), 4);
+
+        // The first failing policy is reported
+        let appointment = generate_dummy_appointment(None).inner;
+        assert!(
+            matches!(policies.check(&denied_user, &appointment), Err(PolicyViolation(e)) if e.contains("too big"))
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Logic related to the ReceiptBatcher, the component in charge of batching appointment receipts so the tower
+//! only needs to sign once per block.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bitcoin::secp256k1::SecretKey;
+
+use teos_common::cryptography;
+use teos_common::merkle::MerkleTree;
+use teos_common::receipts::{batch_root_to_vec, AppointmentReceipt, BatchedAppointmentReceipt};
+
+use crate::extended_appointment::UUID;
+
+/// Number of blocks batched receipts are kept for after their batch is closed.
+pub const BATCH_RETENTION: u32 = 144;
+
+/// Component in charge of batching [AppointmentReceipt]s into Merkle trees, one per block.
+///
+/// Receipts are collected while the tip of the chain does not change and the batch is closed (the tree is built and
+/// its root signed) once a new block is connected. From that point on, users can query the [BatchedAppointmentReceipt]
+/// of their appointments for [BATCH_RETENTION] blocks.
+#[derive(Debug, Default)]
+pub(crate) struct ReceiptBatcher {
+    /// Receipts waiting for their batch to be closed, grouped by the height they were issued at.
+    pending: BTreeMap<u32, HashMap<UUID, AppointmentReceipt>>,
+    /// Receipts whose batch has already been closed.
+    batched: HashMap<UUID, BatchedAppointmentReceipt>,
+}
+
+impl ReceiptBatcher {
+    /// Creates a new, empty, [ReceiptBatcher] instance.
+    pub fn new() -> Self {
+        ReceiptBatcher::default()
+    }
+
+    /// Adds a receipt to the batch of the height it was issued at.
+    ///
+    /// If the appointment is updated within the same batch, only the last receipt is kept.
+    pub fn add_receipt(&mut self, uuid: UUID, receipt: AppointmentReceipt) {
+        self.pending
+            .entry(receipt.start_block())
+            .or_insert_with(HashMap::new)
+            .insert(uuid, receipt);
+    }
+
+    /// Closes all the batches issued at a height lower than `height`, signing their roots using `sk`.
+    ///
+    /// Batched receipts older than [BATCH_RETENTION] blocks are dropped.
+    pub fn close_batches(&mut self, height: u32, sk: &SecretKey) {
+        let open_batches = self.pending.split_off(&height);
+        for (batch_height, batch) in std::mem::replace(&mut self.pending, open_batches) {
+            // Sorting so the tree layout does not depend on the map iteration order
+            let mut batch = batch.into_iter().collect::<Vec<_>>();
+            batch.sort_by_key(|(uuid, _)| *uuid);
+
+            let tree = MerkleTree::new(
+                &batch
+                    .iter()
+                    .map(|(_, receipt)| receipt.to_vec())
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+            // TODO: Check if there's any case where this can actually fail. Don't unwrap if so.
+            let root_signature = cryptography::sign(&batch_root_to_vec(&tree.root()), sk).unwrap();
+
+            log::debug!(
+                "Receipt batch closed (height: {}, receipts: {}, root: {})",
+                batch_height,
+                tree.len(),
+                tree.root()
+            );
+
+            for (i, (uuid, receipt)) in batch.into_iter().enumerate() {
+                self.batched.insert(
+                    uuid,
+                    BatchedAppointmentReceipt::new(
+                        receipt,
+                        tree.get_proof(i).unwrap(),
+                        root_signature.clone(),
+                    ),
+                );
+            }
+        }
+
+        self.batched
+            .retain(|_, r| r.receipt().start_block() + BATCH_RETENTION >= height);
+    }
+
+    /// Gets the [BatchedAppointmentReceipt] of a given appointment, if its batch has been closed.
+    pub fn get_batched_receipt(&self, uuid: UUID) -> Option<BatchedAppointmentReceipt> {
+        self.batched.get(&uuid).cloned()
+    }
+
+    /// Checks whether the receipt of a given appointment is waiting for its batch to be closed.
+    pub fn is_pending(&self, uuid: UUID) -> bool {
+        self.pending.values().any(|batch| batch.contains_key(&uuid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teos_common::cryptography::get_random_keypair;
+    use teos_common::TowerId;
+
+    use crate::test_utils::generate_uuid;
+
+    #[test]
+    fn test_close_batches() {
+        let (sk, pk) = get_random_keypair();
+        let tower_id = TowerId(pk);
+        let mut batcher = ReceiptBatcher::new();
+
+        // Add some receipts to two different heights
+        let mut uuids = Vec::new();
+        for i in 0..10 {
+            let uuid = generate_uuid();
+            batcher.add_receipt(
+                uuid,
+                AppointmentReceipt::new(format!("user_signature_{}", i), 100 + i % 2),
+            );
+            uuids.push(uuid);
+        }
+
+        // Closing the batches up to 101 only covers the receipts issued at 100
+        batcher.close_batches(101, &sk);
+        for (i, uuid) in uuids.iter().enumerate() {
+            if i % 2 == 0 {
+                let batched = batcher.get_batched_receipt(*uuid).unwrap();
+                assert!(batched.verify(&tower_id));
+                assert!(!batcher.is_pending(*uuid));
+            } else {
+                assert!(batcher.get_batched_receipt(*uuid).is_none());
+                assert!(batcher.is_pending(*uuid));
+            }
+        }
+
+        // Closing the next one covers the rest
+        batcher.close_batches(102, &sk);
+        for uuid in uuids.iter() {
+            assert!(batcher
+                .get_batched_receipt(*uuid)
+                .unwrap()
+                .verify(&tower_id));
+            assert!(!batcher.is_pending(*uuid));
+        }
+
+        // Batches are dropped once they are old enough
+        batcher.close_batches(101 + BATCH_RETENTION, &sk);
+        for (i, uuid) in uuids.iter().enumerate() {
+            assert_eq!(batcher.get_batched_receipt(*uuid).is_some(), i % 2 == 1);
+        }
+        batcher.close_batches(102 + BATCH_RETENTION, &sk);
+        assert!(batcher.batched.is_empty());
+    }
+
+    #[test]
+    fn test_add_receipt_update() {
+        let (sk, _) = get_random_keypair();
+        let mut batcher = ReceiptBatcher::new();
+
+        // Updates within the same batch replace the old receipt
+        let uuid = generate_uuid();
+        batcher.add_receipt(uuid, AppointmentReceipt::new("old".to_owned(), 100));
+        batcher.add_receipt(uuid, AppointmentReceipt::new("new".to_owned(), 100));
+        batcher.close_batches(101, &sk);
+
+        let batched = batcher.get_batched_receipt(uuid).unwrap();
+        assert_eq!(batched.receipt().user_signature(), "new");
+        assert_eq!(batched.proof().siblings().len(), 0);
+    }
+}
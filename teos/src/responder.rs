@@ -13,10 +13,12 @@ use teos_common::constants;
 use teos_common::protos as common_msgs;
 use teos_common::UserId;
 
-use crate::carrier::Carrier;
+use crate::carrier::{Carrier, MempoolFeerates, RejectionReason};
 use crate::dbm::DBM;
-use crate::extended_appointment::UUID;
+use crate::extended_appointment::{AppointmentState, UUID};
 use crate::gatekeeper::{Gatekeeper, UserInfo};
+use crate::storage::Storage;
+use crate::telemetry;
 use crate::tx_index::TxIndex;
 use crate::watcher::Breach;
 
@@ -28,8 +30,16 @@ const CONFIRMATIONS_BEFORE_RETRY: u8 = 6;
 pub enum ConfirmationStatus {
     ConfirmedIn(u32),
     InMempoolSince(u32),
+    /// The penalty broadcast is being held back (see [Responder::get_broadcast_delay]) until the given height.
+    DelayedUntil(u32),
+    /// The penalty was rejected at the given height for a [non-final](RejectionReason::is_final) reason, so it is retried
+    /// every block.
+    RetryingSince(u32, RejectionReason),
+    /// The penalty was not sent given the tower runs in dry-run mode. It went through the whole pipeline at the given
+    /// height, but it is never rebroadcast, given it never made it to the network.
+    Simulated(u32),
     IrrevocablyResolved,
-    Rejected(i32),
+    Rejected(RejectionReason),
     ReorgedOut,
 }
 
@@ -37,29 +47,80 @@ pub enum ConfirmationStatus {
 enum DeletionReason {
     Outdated,
     Rejected,
+    ReviewExpired,
     Completed,
+    Abandoned,
+    Acknowledged,
+}
+
+impl DeletionReason {
+    /// The [AppointmentState] the appointment ends up in when its tracker is deleted for this reason.
+    fn final_state(&self) -> AppointmentState {
+        match self {
+            DeletionReason::Outdated => AppointmentState::Outdated,
+            DeletionReason::Completed | DeletionReason::Acknowledged => AppointmentState::Completed,
+            DeletionReason::Rejected
+            | DeletionReason::ReviewExpired
+            | DeletionReason::Abandoned => AppointmentState::Rejected,
+        }
+    }
+}
+
+/// Packs the reasons why a manual (operator triggered) action over a tracker may fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrackerActionFailure {
+    NotFound,
+    AlreadyConfirmed,
+    Rejected,
+    NotUnderReview,
 }
 
 impl ConfirmationStatus {
+    /// Database code of [ConfirmationStatus::InMempoolSince].
+    pub(crate) const DB_IN_MEMPOOL: u8 = 0;
+    /// Database code of [ConfirmationStatus::ConfirmedIn].
+    pub(crate) const DB_CONFIRMED: u8 = 1;
+    /// Database code of [ConfirmationStatus::DelayedUntil].
+    pub(crate) const DB_DELAYED: u8 = 2;
+    /// Database code of [ConfirmationStatus::RetryingSince], for penalties rejected for paying too low a fee.
+    pub(crate) const DB_RETRYING_LOW_FEE: u8 = 3;
+    /// Database code of [ConfirmationStatus::RetryingSince], for penalties conflicting with a transaction in mempool.
+    pub(crate) const DB_RETRYING_MEMPOOL_CONFLICT: u8 = 4;
+    /// Database code of [ConfirmationStatus::Simulated].
+    pub(crate) const DB_SIMULATED: u8 = 5;
+
     /// Builds a [ConfirmationStatus] from data loaded from the database.
-    /// Only trackers that are confirmed or accepted to mempool are stored.
-    pub fn from_db_data(height: u32, confirmed: bool) -> Self {
-        if confirmed {
-            ConfirmationStatus::ConfirmedIn(height)
-        } else {
-            ConfirmationStatus::InMempoolSince(height)
+    /// Only trackers that are confirmed, accepted to mempool, delayed, being retried or simulated are stored.
+    pub fn from_db_data(height: u32, status: u8) -> Self {
+        match status {
+            ConfirmationStatus::DB_CONFIRMED => ConfirmationStatus::ConfirmedIn(height),
+            ConfirmationStatus::DB_DELAYED => ConfirmationStatus::DelayedUntil(height),
+            ConfirmationStatus::DB_RETRYING_LOW_FEE => {
+                ConfirmationStatus::RetryingSince(height, RejectionReason::LowFee)
+            }
+            ConfirmationStatus::DB_RETRYING_MEMPOOL_CONFLICT => {
+                ConfirmationStatus::RetryingSince(height, RejectionReason::MempoolConflict)
+            }
+            ConfirmationStatus::DB_SIMULATED => ConfirmationStatus::Simulated(height),
+            _ => ConfirmationStatus::InMempoolSince(height),
         }
     }
 
     /// Converts a confirmation status into a tuple ready to be stored in the database.
-    /// Only trackers that are confirmed or accepted to mempool are stored.
-    pub fn to_db_data(&self) -> Option<(u32, bool)> {
-        if let ConfirmationStatus::ConfirmedIn(h) = self {
-            Some((*h, true))
-        } else if let ConfirmationStatus::InMempoolSince(h) = self {
-            Some((*h, false))
-        } else {
-            None
+    /// Only trackers that are confirmed, accepted to mempool, delayed, being retried or simulated are stored.
+    pub fn to_db_data(&self) -> Option<(u32, u8)> {
+        match self {
+            ConfirmationStatus::ConfirmedIn(h) => Some((*h, ConfirmationStatus::DB_CONFIRMED)),
+            ConfirmationStatus::InMempoolSince(h) => Some((*h, ConfirmationStatus::DB_IN_MEMPOOL)),
+            ConfirmationStatus::DelayedUntil(h) => Some((*h, ConfirmationStatus::DB_DELAYED)),
+            ConfirmationStatus::RetryingSince(h, RejectionReason::LowFee) => {
+                Some((*h, ConfirmationStatus::DB_RETRYING_LOW_FEE))
+            }
+            ConfirmationStatus::RetryingSince(h, RejectionReason::MempoolConflict) => {
+                Some((*h, ConfirmationStatus::DB_RETRYING_MEMPOOL_CONFLICT))
+            }
+            ConfirmationStatus::Simulated(h) => Some((*h, ConfirmationStatus::DB_SIMULATED)),
+            _ => None,
         }
     }
 
@@ -72,9 +133,32 @@ impl ConfirmationStatus {
     }
 }
 
+/// Computes the feerate (in sat/kvB) of a penalty transaction given the dispute transaction it spends from.
+///
+/// Returns [None] if the penalty spends from any other transaction, given the value of those inputs is unknown.
+fn penalty_feerate(penalty_tx: &Transaction, dispute_tx: &Transaction) -> Option<u64> {
+    let dispute_txid = dispute_tx.txid();
+    let mut input_value: u64 = 0;
+    for txin in penalty_tx.input.iter() {
+        if txin.previous_output.txid != dispute_txid {
+            return None;
+        }
+        input_value += dispute_tx
+            .output
+            .get(txin.previous_output.vout as usize)?
+            .value;
+    }
+
+    let output_value = penalty_tx.output.iter().map(|txout| txout.value).sum();
+    let fee = input_value.checked_sub(output_value)?;
+    let vsize = (penalty_tx.weight() as u64 + 3) / 4;
+
+    Some(fee * 1000 / vsize)
+}
+
 /// Minimal data required in memory to keep track of transaction trackers.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct TrackerSummary {
+pub struct TrackerSummary {
     /// Identifier of the user who arranged the appointment.
     user_id: UserId,
     /// Transaction id the [Responder] is keeping track of.
@@ -87,7 +171,7 @@ pub(crate) struct TrackerSummary {
 ///
 /// It is analogous to [ExtendedAppointment](crate::extended_appointment::ExtendedAppointment) for the [`Watcher`](crate::watcher::Watcher).
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct TransactionTracker {
+pub struct TransactionTracker {
     /// Matches the corresponding [Breach] `dispute_tx` field.
     pub dispute_tx: Transaction,
     /// Matches the corresponding [Breach] penalty_tx field.
@@ -129,13 +213,25 @@ impl From<TransactionTracker> for common_msgs::Tracker {
     }
 }
 
+/// Settings a [Responder] is created with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResponderSettings {
+    /// Whether the penalties of completed trackers are added to the public breach log.
+    pub breach_log: bool,
+    /// Number of blocks to wait after a breach is detected before broadcasting its penalty.
+    pub broadcast_delay: u32,
+    /// Number of blocks trackers are kept in the review queue before being garbage collected (zero means until the
+    /// operator acknowledges them).
+    pub review_timeout: u32,
+}
+
 /// Component in charge of keeping track of triggered appointments.
 ///
 /// The [Responder] receives data from the [Watcher](crate::watcher::Watcher) in form of a [Breach].
 /// From there, a [TransactionTracker] is created and the penalty transaction is sent to the network via the [Carrier].
 /// The [Transaction] is then monitored to make sure it makes it to a block and it gets [irrevocably resolved](https://github.com/lightning/bolts/blob/master/05-onchain.md#general-nomenclature).
 #[derive(Debug)]
-pub struct Responder {
+pub struct Responder<S = DBM> {
     /// A map holding a summary of every tracker ([TransactionTracker]) hold by the [Responder], identified by [UUID].
     /// The identifiers match those used by the [Watcher](crate::watcher::Watcher).
     trackers: Mutex<HashMap<UUID, TrackerSummary>>,
@@ -145,20 +241,34 @@ pub struct Responder {
     tx_index: Mutex<TxIndex<Txid, BlockHash>>,
     /// A [Carrier] instance. Data is sent to the `bitcoind` through it.
     carrier: Mutex<Carrier>,
+    /// Trackers whose penalty feerate was below the mempool min fee the last time it was broadcast. Persisted, so the
+    /// flags survive restarts.
+    low_fee_trackers: Mutex<HashSet<UUID>>,
     /// A [Gatekeeper] instance. Data regarding users is requested to it.
-    gatekeeper: Arc<Gatekeeper>,
-    /// A [DBM] (database manager) instance. Used to persist tracker data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    gatekeeper: Arc<Gatekeeper<S>>,
+    /// Whether the penalties of completed trackers are added to the public breach log.
+    breach_log: bool,
+    /// Number of blocks to wait after a breach is detected before broadcasting its penalty.
+    broadcast_delay: u32,
+    /// Trackers whose penalty can never confirm (its inputs were spent by a confirmed transaction), alongside the height
+    /// they were flagged at. They are kept for the operator to review, and still rebroadcast in case the spend is reorged.
+    review_queue: Mutex<HashMap<UUID, u32>>,
+    /// Number of blocks trackers are kept in the review queue before being garbage collected (zero means until the
+    /// operator acknowledges them).
+    review_timeout: u32,
+    /// The [Storage] backend (a [DBM] by default). Used to persist tracker data.
+    dbm: Arc<Mutex<S>>,
 }
 
-impl Responder {
+impl<S: Storage> Responder<S> {
     /// Creates a new [Responder] instance.
     pub fn new(
         last_n_blocs: &[ValidatedBlock],
         last_known_block_height: u32,
         carrier: Carrier,
-        gatekeeper: Arc<Gatekeeper>,
-        dbm: Arc<Mutex<DBM>>,
+        gatekeeper: Arc<Gatekeeper<S>>,
+        settings: ResponderSettings,
+        dbm: Arc<Mutex<S>>,
     ) -> Self {
         let mut trackers = HashMap::new();
         let mut tx_tracker_map: HashMap<Txid, HashSet<UUID>> = HashMap::new();
@@ -173,13 +283,21 @@ impl Responder {
             }
         }
 
+        let review_queue = dbm.lock().unwrap().load_review_queue();
+        let low_fee_trackers = dbm.lock().unwrap().load_low_fee_trackers();
+
         Responder {
             carrier: Mutex::new(carrier),
+            low_fee_trackers: Mutex::new(low_fee_trackers),
             trackers: Mutex::new(trackers),
             tx_tracker_map: Mutex::new(tx_tracker_map),
             tx_index: Mutex::new(TxIndex::new(last_n_blocs, last_known_block_height)),
             dbm,
             gatekeeper,
+            breach_log: settings.breach_log,
+            broadcast_delay: settings.broadcast_delay,
+            review_queue: Mutex::new(review_queue),
+            review_timeout: settings.review_timeout,
         }
     }
 
@@ -189,14 +307,158 @@ impl Responder {
     }
 
     /// Gets the total number of trackers in the responder.
-    pub(crate) fn get_trackers_count(&self) -> usize {
+    pub fn get_trackers_count(&self) -> usize {
         self.trackers.lock().unwrap().len()
     }
 
+    /// Returns whether the penalties of completed trackers are added to the public breach log.
+    pub fn is_breach_log_enabled(&self) -> bool {
+        self.breach_log
+    }
+
+    /// Gets the number of blocks the [Responder] waits after a breach is detected before broadcasting its penalty.
+    pub fn get_broadcast_delay(&self) -> u32 {
+        self.broadcast_delay
+    }
+
+    /// Gets the number of blocks trackers are kept in the review queue before being garbage collected.
+    pub fn get_review_timeout(&self) -> u32 {
+        self.review_timeout
+    }
+
+    /// Gets the number of trackers in the review queue.
+    pub fn get_trackers_under_review_count(&self) -> usize {
+        self.review_queue.lock().unwrap().len()
+    }
+
+    /// Gets the trackers in the review queue, alongside the height they were flagged at.
+    ///
+    /// The [TransactionTracker]s are queried to the [Storage].
+    pub fn get_review_queue(&self) -> Vec<(UUID, TransactionTracker, u32)> {
+        let review_queue = self.review_queue.lock().unwrap().clone();
+        let dbm = self.dbm.lock().unwrap();
+        review_queue
+            .into_iter()
+            .filter_map(|(uuid, height)| {
+                dbm.load_tracker(uuid)
+                    .ok()
+                    .map(|tracker| (uuid, tracker, height))
+            })
+            .collect()
+    }
+
+    /// Gets the number of trackers whose penalty did not clear the mempool min fee the last time it was broadcast.
+    ///
+    /// This covers both penalties accepted with a feerate below the min fee and penalties rejected for paying too low a fee.
+    pub fn get_low_fee_trackers_count(&self) -> usize {
+        self.low_fee_trackers.lock().unwrap().len()
+    }
+
+    /// Checks the feerate of a penalty transaction against the current mempool feerates.
+    ///
+    /// Returns whether the penalty does not clear the mempool min fee (in which case it is unlikely to make it to the
+    /// chain without manual intervention), or [None] if its feerate cannot be computed.
+    fn check_penalty_feerate(
+        &self,
+        penalty_tx: &Transaction,
+        dispute_tx: &Transaction,
+        feerates: &MempoolFeerates,
+    ) -> Option<bool> {
+        let feerate = match penalty_feerate(penalty_tx, dispute_tx) {
+            Some(feerate) => feerate,
+            None => {
+                log::debug!(
+                    "Cannot compute the feerate of penalty transaction: {}",
+                    penalty_tx.txid()
+                );
+                return None;
+            }
+        };
+
+        log::info!(
+            "Penalty transaction feerate: {} sat/kvB (mempool min fee: {} sat/kvB, estimated fee: {:?} sat/kvB): {}",
+            feerate,
+            feerates.min_fee,
+            feerates.estimated_fee,
+            penalty_tx.txid()
+        );
+
+        if feerate < feerates.min_fee {
+            log::warn!(
+                "Penalty transaction feerate is below the mempool min fee. Manual intervention may be needed: {}",
+                penalty_tx.txid()
+            );
+            Some(true)
+        } else {
+            if feerates.estimated_fee.map_or(false, |fee| feerate < fee) {
+                log::warn!(
+                    "Penalty transaction feerate is below the estimated fee, it may take a while to confirm: {}",
+                    penalty_tx.txid()
+                );
+            }
+            Some(false)
+        }
+    }
+
+    /// Flags (or unflags) a tracker as having a penalty that does not clear the mempool min fee.
+    ///
+    /// Takes the already locked [Storage], so it can be used while persisting the rest of the tracker data. The tracker
+    /// must be stored already.
+    fn set_low_fee(&self, dbm: &S, uuid: UUID, low_fee: bool) {
+        let mut low_fee_trackers = self.low_fee_trackers.lock().unwrap();
+        if low_fee {
+            if low_fee_trackers.insert(uuid) {
+                if let Err(e) = dbm.store_low_fee_tracker(uuid) {
+                    log::error!("Cannot flag tracker as low fee: {} (error: {:?})", uuid, e);
+                }
+            }
+        } else if low_fee_trackers.remove(&uuid) {
+            dbm.remove_low_fee_tracker(uuid);
+        }
+    }
+
+    /// Turns a [non-final](RejectionReason::is_final) rejection of the penalty of a given tracker into
+    /// [RetryingSince](ConfirmationStatus::RetryingSince), so the tracker is kept around and the penalty retried.
+    ///
+    /// Trackers whose penalty was rejected for paying too low a fee are flagged by the caller once stored (check
+    /// [Self::set_low_fee]). Penalties conflicting with a transaction in mempool (e.g. one broadcast by the cheater) are
+    /// retried until the conflicting transaction is either evicted or confirmed.
+    fn retry_if_not_final(
+        &self,
+        uuid: UUID,
+        status: ConfirmationStatus,
+        height: u32,
+    ) -> ConfirmationStatus {
+        match status {
+            ConfirmationStatus::Rejected(reason) if !reason.is_final() => {
+                if reason == RejectionReason::LowFee {
+                    log::warn!(
+                        "Penalty transaction rejected for paying too low a fee. Manual intervention may be needed: {}",
+                        uuid
+                    );
+                } else if reason == RejectionReason::MempoolConflict {
+                    log::warn!(
+                        "Penalty transaction conflicts with a transaction in mempool. Retrying: {}",
+                        uuid
+                    );
+                }
+                ConfirmationStatus::RetryingSince(height, reason)
+            }
+            status => status,
+        }
+    }
+
     /// Data entry point for the [Responder]. Handles a [Breach] provided by the [Watcher](crate::watcher::Watcher).
     ///
     /// Breaches can either be added to the [Responder] in the form of a [TransactionTracker] if the [penalty transaction](Breach::penalty_tx)
     /// is accepted by the `bitcoind` or rejected otherwise.
+    ///
+    /// Penalties rejected for a [non-final](RejectionReason::is_final) reason are added as
+    /// [RetryingSince](ConfirmationStatus::RetryingSince), and retried every block.
+    ///
+    /// If a broadcast delay is set, the penalty is not sent straightaway. Instead, the tracker is added as
+    /// [DelayedUntil](ConfirmationStatus::DelayedUntil) and the penalty is broadcast once the delay has elapsed, giving the
+    /// cheated party a chance to respond first.
     pub(crate) fn handle_breach(
         &self,
         uuid: UUID,
@@ -209,21 +471,65 @@ impl Responder {
             return tracker.status;
         }
 
-        let mut carrier = self.carrier.lock().unwrap();
-        let tx_index = self.tx_index.lock().unwrap();
-
-        // Check whether the transaction is in mempool or part of our internal txindex. Send it to our node otherwise.
-        let status = if carrier.in_mempool(&breach.penalty_tx.txid()) {
-            // If it's in mempool we assume it was just included
-            ConfirmationStatus::InMempoolSince(carrier.block_height())
-        } else if let Some(block_hash) = tx_index.get(&breach.penalty_tx.txid()) {
-            ConfirmationStatus::ConfirmedIn(tx_index.get_height(block_hash).unwrap() as u32)
-        } else {
-            carrier.send_transaction(&breach.penalty_tx)
+        // The carrier and the txindex are released before touching the storage
+        let (status, low_fee) = {
+            let mut carrier = self.carrier.lock().unwrap();
+            let tx_index = self.tx_index.lock().unwrap();
+
+            // Check whether the transaction is in mempool or part of our internal txindex. Send it to our node otherwise.
+            if carrier.in_mempool(&breach.penalty_tx.txid()) {
+                // If it's in mempool we assume it was just included
+                (
+                    ConfirmationStatus::InMempoolSince(carrier.block_height()),
+                    None,
+                )
+            } else if let Some(block_hash) = tx_index.get(&breach.penalty_tx.txid()) {
+                (
+                    ConfirmationStatus::ConfirmedIn(tx_index.get_height(block_hash).unwrap() as u32),
+                    None,
+                )
+            } else if self.broadcast_delay > 0 {
+                let broadcast_height = carrier.block_height().saturating_add(self.broadcast_delay);
+                log::info!(
+                    "Delaying penalty broadcast until height {}: {}",
+                    broadcast_height,
+                    breach.penalty_tx.txid()
+                );
+                (ConfirmationStatus::DelayedUntil(broadcast_height), None)
+            } else {
+                let status = carrier.send_transaction(&breach.penalty_tx);
+                let low_fee = if status.accepted() {
+                    carrier.get_mempool_feerates().and_then(|feerates| {
+                        self.check_penalty_feerate(
+                            &breach.penalty_tx,
+                            &breach.dispute_tx,
+                            &feerates,
+                        )
+                    })
+                } else {
+                    None
+                };
+                let status = self.retry_if_not_final(uuid, status, carrier.block_height());
+                if let ConfirmationStatus::RetryingSince(_, RejectionReason::LowFee) = status {
+                    (status, Some(true))
+                } else {
+                    (status, low_fee)
+                }
+            }
         };
 
-        if status.accepted() {
+        if status.accepted()
+            || matches!(
+                status,
+                ConfirmationStatus::DelayedUntil(_)
+                    | ConfirmationStatus::RetryingSince(..)
+                    | ConfirmationStatus::Simulated(_)
+            )
+        {
             self.add_tracker(uuid, breach, user_id, status);
+            if let Some(low_fee) = low_fee {
+                self.set_low_fee(&self.dbm.lock().unwrap(), uuid, low_fee);
+            }
         }
 
         status
@@ -258,16 +564,19 @@ impl Responder {
             tx_tracker_map.insert(tracker.penalty_tx.txid(), HashSet::from_iter(vec![uuid]));
         }
 
-        self.dbm
-            .lock()
-            .unwrap()
-            .store_tracker(uuid, &tracker)
-            .unwrap();
+        let dbm = self.dbm.lock().unwrap();
+        dbm.store_tracker(uuid, &tracker).unwrap();
+        dbm.update_appointment_state(
+            uuid,
+            user_id,
+            AppointmentState::Triggered,
+            status.to_db_data().map_or(0, |(height, _)| height),
+        );
         log::info!("New tracker added (uuid={}).", uuid);
     }
 
     /// Checks whether a given tracker can be found in the [Responder].
-    pub(crate) fn has_tracker(&self, uuid: UUID) -> bool {
+    pub fn has_tracker(&self, uuid: UUID) -> bool {
         // has_tracker should return true as long as the given tracker is hold by the Responder.
         // If the tracker is partially kept, the function will log and the return will be false.
         // This may point out that some partial data deletion is happening, which must be fixed.
@@ -294,8 +603,8 @@ impl Responder {
 
     /// Gets a tracker from the [Responder] if found. [None] otherwise.
     ///
-    /// The [TransactionTracker] is queried to the [DBM].
-    pub(crate) fn get_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
+    /// The [TransactionTracker] is queried to the [Storage].
+    pub fn get_tracker(&self, uuid: UUID) -> Option<TransactionTracker> {
         if self.trackers.lock().unwrap().contains_key(&uuid) {
             self.dbm.lock().unwrap().load_tracker(uuid).ok()
         } else {
@@ -303,6 +612,195 @@ impl Responder {
         }
     }
 
+    /// Gets the endpoints that accepted the penalty of a given tracker the last time it was sent (check
+    /// [Carrier::get_accepted_by]), sorted by name. Empty if the penalty has not been sent recently.
+    pub(crate) fn get_accepted_by(&self, uuid: UUID) -> Vec<String> {
+        let penalty_txid = match self.trackers.lock().unwrap().get(&uuid) {
+            Some(tracker) => tracker.penalty_txid,
+            None => return Vec::new(),
+        };
+        let mut accepted_by = self
+            .carrier
+            .lock()
+            .unwrap()
+            .get_accepted_by(&penalty_txid)
+            .map(|endpoints| endpoints.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        accepted_by.sort();
+
+        accepted_by
+    }
+
+    /// Rebroadcasts the penalty of a given tracker right away, without waiting for it to reach [CONFIRMATIONS_BEFORE_RETRY].
+    ///
+    /// Trackers whose penalty has already been confirmed are not rebroadcast. The new status is persisted if the penalty
+    /// is accepted (by [Self::rebroadcast]). Rejected trackers are kept around (unlike during the periodic rebroadcast), so the operator can decide
+    /// what to do with them.
+    pub fn rebroadcast_tracker(
+        &self,
+        uuid: UUID,
+    ) -> Result<ConfirmationStatus, TrackerActionFailure> {
+        let status = self
+            .trackers
+            .lock()
+            .unwrap()
+            .get(&uuid)
+            .map(|tracker| tracker.status)
+            .ok_or(TrackerActionFailure::NotFound)?;
+
+        if let ConfirmationStatus::ConfirmedIn(_) = status {
+            return Err(TrackerActionFailure::AlreadyConfirmed);
+        }
+
+        let tracker = self
+            .dbm
+            .lock()
+            .unwrap()
+            .load_tracker(uuid)
+            .map_err(|_| TrackerActionFailure::NotFound)?;
+        let dispute_tx = if let ConfirmationStatus::ReorgedOut = status {
+            Some(tracker.dispute_tx)
+        } else {
+            None
+        };
+
+        log::warn!(target: telemetry::AUDIT_TARGET, "Operator requested the rebroadcast of tracker: {}", uuid);
+        let (accepted, rejected) = self.rebroadcast(HashMap::from_iter([(
+            uuid,
+            (tracker.penalty_tx, dispute_tx),
+        )]));
+
+        match accepted.get(&uuid) {
+            Some(status) => {
+                self.remove_from_review(&HashSet::from_iter([uuid]));
+                log::warn!(target: telemetry::AUDIT_TARGET, "Tracker rebroadcast (status: {:?}): {}", status, uuid);
+                Ok(*status)
+            }
+            None => {
+                log::warn!(target: telemetry::AUDIT_TARGET, "Tracker rebroadcast rejected: {}", uuid);
+                if rejected.get(&uuid) == Some(&RejectionReason::InputsSpent)
+                    && self.penalty_inputs_spent_on_chain(uuid)
+                {
+                    let height = self.carrier.lock().unwrap().block_height();
+                    self.flag_for_review(&HashSet::from_iter([uuid]), height);
+                }
+                Err(TrackerActionFailure::Rejected)
+            }
+        }
+    }
+
+    /// Abandons a given tracker, stopping any further response for it.
+    ///
+    /// This is meant for trackers that the operator has confirmed to be invalid or superseded. The tracker is deleted
+    /// from memory and the database (alongside its appointment), and the user slots are freed accordingly.
+    pub fn abandon_tracker(&self, uuid: UUID, reason: &str) -> Result<(), TrackerActionFailure> {
+        let user_id = self
+            .trackers
+            .lock()
+            .unwrap()
+            .get(&uuid)
+            .map(|tracker| tracker.user_id)
+            .ok_or(TrackerActionFailure::NotFound)?;
+
+        log::warn!(target: telemetry::AUDIT_TARGET, "Operator abandoned tracker (reason: {}): {}", reason, uuid);
+        self.delete_trackers(
+            &HashSet::from_iter([uuid]),
+            &self
+                .gatekeeper
+                .delete_appointments_from_memory(&HashMap::from_iter([(uuid, user_id)])),
+            DeletionReason::Abandoned,
+        );
+
+        Ok(())
+    }
+
+    /// Acknowledges a given tracker in the review queue, so it is garbage collected straightaway.
+    ///
+    /// This is meant for the operator to close trackers whose penalty can never confirm once they have been looked into.
+    pub fn acknowledge_tracker(&self, uuid: UUID) -> Result<(), TrackerActionFailure> {
+        let user_id = self
+            .trackers
+            .lock()
+            .unwrap()
+            .get(&uuid)
+            .map(|tracker| tracker.user_id)
+            .ok_or(TrackerActionFailure::NotFound)?;
+        if !self.review_queue.lock().unwrap().contains_key(&uuid) {
+            return Err(TrackerActionFailure::NotUnderReview);
+        }
+
+        log::warn!(target: telemetry::AUDIT_TARGET, "Operator acknowledged tracker under review: {}", uuid);
+        self.delete_trackers(
+            &HashSet::from_iter([uuid]),
+            &self
+                .gatekeeper
+                .delete_appointments_from_memory(&HashMap::from_iter([(uuid, user_id)])),
+            DeletionReason::Acknowledged,
+        );
+
+        Ok(())
+    }
+
+    /// Adds the given trackers to the review queue, so the operator can look into them. Trackers already in the queue
+    /// keep the height they were first flagged at.
+    pub(crate) fn flag_for_review(&self, uuids: &HashSet<UUID>, height: u32) {
+        let mut review_queue = self.review_queue.lock().unwrap();
+        let dbm = self.dbm.lock().unwrap();
+        for uuid in uuids.iter() {
+            if review_queue.contains_key(uuid) {
+                continue;
+            }
+            log::warn!(
+                "Penalty transaction can never confirm (its inputs were spent by a confirmed transaction). Tracker flagged for review: {}",
+                uuid
+            );
+            review_queue.insert(*uuid, height);
+            if let Err(e) = dbm.store_review_entry(*uuid, height) {
+                log::error!("Couldn't store review entry: {}. Error: {:?}", uuid, e);
+            }
+        }
+    }
+
+    /// Removes the given trackers from the review queue (e.g. if their penalty has been accepted or confirmed after all).
+    fn remove_from_review(&self, uuids: &HashSet<UUID>) {
+        let mut review_queue = self.review_queue.lock().unwrap();
+        for uuid in uuids.iter() {
+            if review_queue.remove(uuid).is_some() {
+                log::info!("Tracker removed from the review queue: {}", uuid);
+                self.dbm.lock().unwrap().remove_review_entry(*uuid);
+            }
+        }
+    }
+
+    /// Checks whether the penalty of a given tracker can never confirm, that is, whether its inputs were spent by a
+    /// confirmed transaction.
+    fn penalty_inputs_spent_on_chain(&self, uuid: UUID) -> bool {
+        let penalty_tx = match self.dbm.lock().unwrap().load_tracker(uuid) {
+            Ok(tracker) => tracker.penalty_tx,
+            Err(_) => return false,
+        };
+        self.carrier
+            .lock()
+            .unwrap()
+            .inputs_spent_on_chain(&penalty_tx)
+    }
+
+    /// Gets the trackers that have been in the review queue for [review_timeout](Self::get_review_timeout) blocks,
+    /// so they can be garbage collected.
+    fn get_expired_reviews(&self, height: u32) -> HashSet<UUID> {
+        if self.review_timeout == 0 {
+            return HashSet::new();
+        }
+
+        self.review_queue
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, flagged_at)| height >= flagged_at.saturating_add(self.review_timeout))
+            .map(|(uuid, _)| *uuid)
+            .collect()
+    }
+
     /// Checks the confirmation count for the [TransactionTracker]s.
     ///
     /// For unconfirmed transactions, it checks whether they have been confirmed or keep missing confirmations.
@@ -310,6 +808,7 @@ impl Responder {
     /// Returns the set of completed trackers.
     fn check_confirmations(&self, txids: &[Txid], current_height: u32) -> HashSet<UUID> {
         let mut completed_trackers = HashSet::new();
+        let mut confirmed_trackers = HashSet::new();
 
         for (uuid, tracker) in self.trackers.lock().unwrap().iter_mut() {
             if let ConfirmationStatus::ConfirmedIn(h) = tracker.status {
@@ -323,6 +822,7 @@ impl Responder {
             } else if txids.contains(&tracker.penalty_txid) {
                 // First confirmation was received
                 tracker.status = ConfirmationStatus::ConfirmedIn(current_height);
+                confirmed_trackers.insert(*uuid);
             } else if let ConfirmationStatus::InMempoolSince(h) = tracker.status {
                 // Log all transactions that have missed confirmations
                 log::info!(
@@ -333,15 +833,21 @@ impl Responder {
             }
         }
 
+        // Trackers under review whose penalty confirmed after all do not need reviewing anymore
+        self.remove_from_review(&confirmed_trackers);
+
         completed_trackers
     }
 
     /// Gets a map of transactions that need to be rebroadcast. A [Transaction] is flagged to be rebroadcast
     /// if its missed confirmation count has reached the threshold ([CONFIRMATIONS_BEFORE_RETRY]) or if they have been
     /// reorged out of the chain. If the transaction has been reorged out, the commitment transaction is also returned.
+    /// Delayed transactions whose broadcast height has been reached are also returned, so they are sent for the first time,
+    /// alongside those being retried after a non-final rejection. Trackers in the review queue are rebroadcast too, given
+    /// the transaction spending their inputs may be reorged out. Simulated transactions (dry-run mode) are never returned.
     ///
     /// Given the [Responder] only keeps around the minimal data to track transactions, the [TransactionTracker]s
-    /// are queried to the [DBM].
+    /// are queried to the [Storage].
     fn get_txs_to_rebroadcast(
         &self,
         height: u32,
@@ -359,6 +865,14 @@ impl Responder {
             } else if let ConfirmationStatus::ReorgedOut = t.status {
                 tracker = dbm.load_tracker(*uuid).unwrap();
                 tx_to_rebroadcast.insert(*uuid, (tracker.penalty_tx, Some(tracker.dispute_tx)));
+            } else if let ConfirmationStatus::DelayedUntil(h) = t.status {
+                if height >= h {
+                    tracker = dbm.load_tracker(*uuid).unwrap();
+                    tx_to_rebroadcast.insert(*uuid, (tracker.penalty_tx, None));
+                }
+            } else if let ConfirmationStatus::RetryingSince(..) = t.status {
+                tracker = dbm.load_tracker(*uuid).unwrap();
+                tx_to_rebroadcast.insert(*uuid, (tracker.penalty_tx, None));
             }
         }
 
@@ -377,7 +891,11 @@ impl Responder {
             .get_outdated_appointments(block_height)
             .intersection(&trackers.keys().cloned().collect())
         {
-            if let ConfirmationStatus::InMempoolSince(_) = trackers[uuid].status {
+            if let ConfirmationStatus::InMempoolSince(_)
+            | ConfirmationStatus::DelayedUntil(_)
+            | ConfirmationStatus::RetryingSince(..)
+            | ConfirmationStatus::Simulated(_) = trackers[uuid].status
+            {
                 outdated_trackers.insert(*uuid);
             }
         }
@@ -385,7 +903,8 @@ impl Responder {
         outdated_trackers
     }
 
-    /// Rebroadcasts a list of penalty transactions that have missed too many confirmations (or that have been reorged out).
+    /// Rebroadcasts a list of penalty transactions that have missed too many confirmations (or that have been reorged out),
+    /// alongside those whose broadcast delay has elapsed and those being retried.
     ///
     /// This covers both the case where a transaction is not getting confirmations (most likely due to low fess, and needs to be bumped),
     /// and the case where the transaction has been reorged out of the chain. For the former, there's no much to be done at the moment (until anchors),
@@ -395,19 +914,42 @@ impl Responder {
     /// after a reorg, but bitcoind will already be at the new tip. If the transaction is accepted, we won't do anything else until passed the new tip,
     /// otherwise, we could potentially try to rebroadcast again while processing the upcoming reorged blocks (if the tx hits [CONFIRMATIONS_BEFORE_RETRY]).
     ///
-    /// Returns a tuple with two maps, one containing the trackers that where successfully rebroadcast and another one containing the ones that were rejected.
+    /// Returns a tuple with two maps, one containing the trackers that where successfully rebroadcast and another one containing the ones that were rejected
+    /// (alongside the reason why). Trackers whose penalty is rejected for a non-final reason are in neither, they are kept and retried.
     fn rebroadcast(
         &self,
         txs: HashMap<UUID, (Transaction, Option<Transaction>)>,
-    ) -> (HashMap<UUID, ConfirmationStatus>, HashSet<UUID>) {
+    ) -> (
+        HashMap<UUID, ConfirmationStatus>,
+        HashMap<UUID, RejectionReason>,
+    ) {
         let mut accepted = HashMap::new();
-        let mut rejected = HashSet::new();
+        let mut rejected = HashMap::new();
+        // Status updates to be persisted once the in-memory locks are released
+        let mut updated = Vec::new();
 
         let mut trackers = self.trackers.lock().unwrap();
         let mut carrier = self.carrier.lock().unwrap();
         let tx_index = self.tx_index.lock().unwrap();
 
+        // Feerates are only fetched once per batch
+        let feerates = if txs.is_empty() {
+            None
+        } else {
+            carrier.get_mempool_feerates()
+        };
+
         for (uuid, (penalty_tx, dispute_tx)) in txs.into_iter() {
+            let _span = telemetry::appointment_span(uuid).entered();
+            // The tracker may have gone (e.g. abandoned by the operator) since the transactions were collected
+            let tracker_status = match trackers.get(&uuid) {
+                Some(tracker) => tracker.status,
+                None => {
+                    log::info!("Tracker is gone, skipping rebroadcast");
+                    continue;
+                }
+            };
+            let mut dispute_rejected = false;
             let status = if let Some(dispute_tx) = dispute_tx {
                 // The tracker was reorged out, and the dispute may potentially not be in the chain (or mempool) anymore.
                 if tx_index.contains_key(&dispute_tx.txid())
@@ -427,12 +969,20 @@ impl Responder {
                         dispute_tx.txid(),
                         e
                     );
+                        dispute_rejected = true;
                         status
                     } else {
                         // The dispute was accepted, so we can rebroadcast the penalty.
                         carrier.send_transaction(&penalty_tx)
                     }
                 }
+            } else if let ConfirmationStatus::DelayedUntil(_) = tracker_status {
+                // The broadcast delay has elapsed, so the penalty is sent for the first time.
+                log::info!(
+                    "Broadcast delay elapsed, sending penalty transaction: {}",
+                    penalty_tx.txid()
+                );
+                carrier.send_transaction(&penalty_tx)
             } else {
                 // The tracker has simply reached CONFIRMATIONS_BEFORE_RETRY missed confirmations.
                 log::warn!(
@@ -442,20 +992,78 @@ impl Responder {
                 carrier.send_transaction(&penalty_tx)
             };
 
-            if let ConfirmationStatus::Rejected(_) = status {
-                rejected.insert(uuid);
+            // Retrying only makes sense for the penalty, a rejected dispute is handled as a final rejection
+            let status = if dispute_rejected {
+                status
+            } else {
+                self.retry_if_not_final(uuid, status, carrier.block_height())
+            };
+
+            if let ConfirmationStatus::Rejected(reason) = status {
+                rejected.insert(uuid, reason);
+            } else if let ConfirmationStatus::RetryingSince(..) = status {
+                if let Some(tracker) = trackers.get_mut(&uuid) {
+                    tracker.status = status;
+                }
+                updated.push((uuid, status));
             } else {
                 // Update the tracker if it gets accepted. This will also update the height (since when we are counting the tracker
                 // to have been in mempool), so it resets the wait period instead of trying to rebroadcast every block.
                 // DISCUSS: We may want to find another approach in the future for the InMempoool transactions.
-                trackers.get_mut(&uuid).unwrap().status = status;
+                if let Some(tracker) = trackers.get_mut(&uuid) {
+                    tracker.status = status;
+                }
                 accepted.insert(uuid, status);
+                updated.push((uuid, status));
+            }
+        }
+
+        // The storage is only reached once the in-memory data is released
+        drop(tx_index);
+        drop(carrier);
+        drop(trackers);
+
+        let dbm = self.dbm.lock().unwrap();
+        for (uuid, status) in updated {
+            dbm.update_tracker_status(uuid, &status);
+
+            let low_fee = match status {
+                ConfirmationStatus::RetryingSince(_, RejectionReason::LowFee) => Some(true),
+                ConfirmationStatus::RetryingSince(..) => None,
+                _ => feerates.as_ref().and_then(|feerates| {
+                    let tracker = dbm.load_tracker(uuid).ok()?;
+                    self.check_penalty_feerate(&tracker.penalty_tx, &tracker.dispute_tx, feerates)
+                }),
+            };
+            if let Some(low_fee) = low_fee {
+                self.set_low_fee(&dbm, uuid, low_fee);
             }
         }
 
         (accepted, rejected)
     }
 
+    /// Adds the penalties of the given (completed) trackers to the breach log, alongside their confirmation height.
+    ///
+    /// Does nothing if the breach log is disabled.
+    fn log_responded_breaches(&self, uuids: &HashSet<UUID>) {
+        if !self.breach_log || uuids.is_empty() {
+            return;
+        }
+
+        let entries = {
+            let trackers = self.trackers.lock().unwrap();
+            uuids
+                .iter()
+                .filter_map(|uuid| match trackers.get(uuid)?.status {
+                    ConfirmationStatus::ConfirmedIn(h) => Some((trackers[uuid].penalty_txid, h)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        self.dbm.lock().unwrap().store_breach_log_entries(&entries);
+    }
+
     // DISCUSS: Check comment regarding callbacks in watcher.rs
 
     /// Deletes trackers from memory.
@@ -464,11 +1072,18 @@ impl Responder {
     fn delete_trackers_from_memory(&self, uuids: &HashSet<UUID>, reason: DeletionReason) {
         let mut trackers = self.trackers.lock().unwrap();
         let mut tx_tracker_map = self.tx_tracker_map.lock().unwrap();
+        let mut low_fee_trackers = self.low_fee_trackers.lock().unwrap();
+        let mut review_queue = self.review_queue.lock().unwrap();
         for uuid in uuids.iter() {
+            low_fee_trackers.remove(uuid);
+            review_queue.remove(uuid);
             match reason {
                 DeletionReason::Completed => log::info!("Appointment completed. Penalty transaction was irrevocably confirmed: {}", uuid),
                 DeletionReason::Outdated => log::info!("Appointment couldn't be completed. Expiry reached but penalty didn't make it to the chain: {}", uuid),
                 DeletionReason::Rejected => log::info!("Appointment couldn't be completed. Either the dispute or the penalty txs where rejected during rebroadcast: {}", uuid),
+                DeletionReason::ReviewExpired => log::info!("Appointment couldn't be completed. The penalty inputs were spent on chain and the tracker was never reviewed: {}", uuid),
+                DeletionReason::Abandoned => log::info!("Appointment couldn't be completed. Tracker was abandoned by the operator: {}", uuid),
+                DeletionReason::Acknowledged => log::info!("Appointment closed. The penalty could never confirm and the operator acknowledged it: {}", uuid),
             }
 
             match trackers.remove(uuid) {
@@ -504,17 +1119,19 @@ impl Responder {
         reason: DeletionReason,
     ) {
         if !uuids.is_empty() {
+            let state = reason.final_state();
             self.delete_trackers_from_memory(uuids, reason);
+            let height = self.carrier.lock().unwrap().block_height();
             self.dbm
                 .lock()
                 .unwrap()
-                .batch_remove_appointments(uuids, updated_users);
+                .batch_remove_appointments(uuids, updated_users, state, height);
         }
     }
 }
 
 /// Listen implementation by the [Responder]. Handles monitoring and reorgs.
-impl chain::Listen for Responder {
+impl<S: Storage> chain::Listen for Responder<S> {
     /// Handles the monitoring process by the [Responder].
     ///
     /// Watching is performed in a per-block basis. A [TransactionTracker] is tracked until:
@@ -546,6 +1163,7 @@ impl chain::Listen for Responder {
                 &txdata.iter().map(|(_, tx)| tx.txid()).collect::<Vec<_>>(),
                 height,
             );
+            self.log_responded_breaches(&completed_trackers);
             let trackers_to_delete_gk = completed_trackers
                 .iter()
                 .map(|uuid| (*uuid, self.trackers.lock().unwrap()[uuid].user_id))
@@ -559,25 +1177,53 @@ impl chain::Listen for Responder {
             );
 
             // Also delete trackers from outdated users (from memory only, the db deletion is handled by the Gatekeeper)
-            self.delete_trackers_from_memory(
-                &self.get_outdated_trackers(height),
-                DeletionReason::Outdated,
+            let outdated_trackers = self.get_outdated_trackers(height);
+            self.dbm.lock().unwrap().batch_update_appointment_states(
+                &outdated_trackers,
+                AppointmentState::Outdated,
+                height,
             );
+            self.delete_trackers_from_memory(&outdated_trackers, DeletionReason::Outdated);
 
             // Rebroadcast those transactions that need to
-            let (_, rejected_trackers) = self.rebroadcast(self.get_txs_to_rebroadcast(height));
-            // Delete trackers rejected during rebroadcast
-            let trackers_to_delete_gk = rejected_trackers
-                .iter()
-                .map(|uuid| (*uuid, self.trackers.lock().unwrap()[uuid].user_id))
-                .collect();
-            self.delete_trackers(
-                &rejected_trackers,
-                &self
-                    .gatekeeper
-                    .delete_appointments_from_memory(&trackers_to_delete_gk),
-                DeletionReason::Rejected,
-            );
+            let (accepted, rejected) = self.rebroadcast(self.get_txs_to_rebroadcast(height));
+            // Trackers under review whose penalty is accepted after all (e.g. the spend was reorged) need no review anymore
+            self.remove_from_review(&accepted.keys().cloned().collect());
+            // Delete trackers rejected during rebroadcast. If the penalty inputs were spent by a confirmed transaction the
+            // penalty can never confirm, but whether the dispute was resolved elsewhere is for the operator to tell, so the
+            // tracker is flagged for review instead. Trackers whose inputs are only missing (e.g. not confirmed yet) are kept
+            // and retried.
+            let mut unresolvable_trackers = HashSet::new();
+            let mut rejected_trackers = HashSet::new();
+            for (uuid, reason) in rejected.into_iter() {
+                if reason == RejectionReason::InputsSpent {
+                    if self.penalty_inputs_spent_on_chain(uuid) {
+                        unresolvable_trackers.insert(uuid);
+                    }
+                } else {
+                    rejected_trackers.insert(uuid);
+                }
+            }
+            self.flag_for_review(&unresolvable_trackers, height);
+
+            // Trackers that have been under review for too long are garbage collected
+            let expired_reviews = self.get_expired_reviews(height);
+            for (trackers, reason) in [
+                (expired_reviews, DeletionReason::ReviewExpired),
+                (rejected_trackers, DeletionReason::Rejected),
+            ] {
+                let trackers_to_delete_gk = trackers
+                    .iter()
+                    .map(|uuid| (*uuid, self.trackers.lock().unwrap()[uuid].user_id))
+                    .collect();
+                self.delete_trackers(
+                    &trackers,
+                    &self
+                        .gatekeeper
+                        .delete_appointments_from_memory(&trackers_to_delete_gk),
+                    reason,
+                );
+            }
 
             // Remove all receipts created in this block
             self.carrier.lock().unwrap().clear_receipts();
@@ -613,24 +1259,42 @@ mod tests {
 
     use std::sync::{Arc, Mutex};
 
+    use crate::carrier::BITCOIND_ENDPOINT;
     use crate::dbm::DBM;
     use crate::gatekeeper::UserInfo;
     use crate::rpc_errors;
     use crate::test_utils::{
-        create_carrier, generate_dummy_appointment_with_user, generate_uuid, get_last_n_blocks,
-        get_random_breach, get_random_tracker, get_random_tx, store_appointment_and_fks_to_db,
-        BitcoindStopper, Blockchain, MockedServerQuery, AVAILABLE_SLOTS, DURATION, EXPIRY_DELTA,
-        SLOTS, START_HEIGHT, SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
+        create_carrier, generate_dummy_appointment_with_user, generate_uuid,
+        get_gatekeeper_settings, get_last_n_blocks, get_random_breach, get_random_tracker,
+        get_random_tx, store_appointment_and_fks_to_db, BitcoindStopper, Blockchain,
+        MockedServerQuery, AVAILABLE_SLOTS, REVIEW_TIMEOUT, START_HEIGHT, SUBSCRIPTION_EXPIRY,
+        SUBSCRIPTION_START,
     };
 
     use teos_common::constants::IRREVOCABLY_RESOLVED;
     use teos_common::dbm::Error as DBError;
     use teos_common::test_utils::get_random_user_id;
 
+    use bitcoin::OutPoint;
+
+    /// Creates a breach where the penalty spends from the dispute paying the given fee.
+    fn get_breach_with_fee(fee: u64) -> Breach {
+        let mut dispute_tx = get_random_tx();
+        dispute_tx.output[0].value = 1_000_000;
+
+        let mut penalty_tx = get_random_tx();
+        penalty_tx.input[0].previous_output = OutPoint::new(dispute_tx.txid(), 0);
+        penalty_tx.output[0].value = 1_000_000 - fee;
+
+        Breach::new(dispute_tx, penalty_tx)
+    }
+
     impl PartialEq for Responder {
         fn eq(&self, other: &Self) -> bool {
             *self.trackers.lock().unwrap() == *other.trackers.lock().unwrap()
                 && *self.tx_tracker_map.lock().unwrap() == *other.tx_tracker_map.lock().unwrap()
+                && *self.review_queue.lock().unwrap() == *other.review_queue.lock().unwrap()
+                && *self.low_fee_trackers.lock().unwrap() == *other.low_fee_trackers.lock().unwrap()
         }
     }
     impl Eq for Responder {}
@@ -672,12 +1336,15 @@ mod tests {
                 tracker.user_id,
                 Some(&tracker.dispute_tx.txid()),
             );
-            store_appointment_and_fks_to_db(&self.dbm.lock().unwrap(), uuid, &appointment);
-            self.dbm
-                .lock()
-                .unwrap()
-                .store_tracker(uuid, tracker)
-                .unwrap();
+            let dbm = self.dbm.lock().unwrap();
+            store_appointment_and_fks_to_db(&dbm, uuid, &appointment);
+            dbm.store_tracker(uuid, tracker).unwrap();
+            dbm.update_appointment_state(
+                uuid,
+                tracker.user_id,
+                AppointmentState::Triggered,
+                tracker.status.to_db_data().map_or(0, |(height, _)| height),
+            );
         }
     }
 
@@ -697,7 +1364,17 @@ mod tests {
 
         let (carrier, bitcoind_stopper) = create_carrier(query, chain.tip().height);
         (
-            Responder::new(&last_n_blocks, chain.tip().height, carrier, gatekeeper, dbm),
+            Responder::new(
+                &last_n_blocks,
+                chain.tip().height,
+                carrier,
+                gatekeeper,
+                ResponderSettings {
+                    review_timeout: REVIEW_TIMEOUT,
+                    ..Default::default()
+                },
+                dbm,
+            ),
             bitcoind_stopper,
         )
     }
@@ -709,9 +1386,7 @@ mod tests {
     ) -> (Responder, BitcoindStopper) {
         let gk = Gatekeeper::new(
             chain.get_block_count(),
-            SLOTS,
-            DURATION,
-            EXPIRY_DELTA,
+            get_gatekeeper_settings(),
             dbm.clone(),
         );
         create_responder(chain, Arc::new(gk), dbm, mocked_query).await
@@ -726,40 +1401,61 @@ mod tests {
     #[test]
     fn test_confirmation_status_from_db_data() {
         // These are pretty simple tests. The db can only store trackers with a confirmation status
-        // that's either ConfirmedIn or InMempoolSince (Rejected and Reorged are never passed to store).
+        // that's either ConfirmedIn, InMempoolSince or DelayedUntil (Rejected and Reorged are never passed to store).
         let h = 21;
-        let statuses = [true, false];
 
-        for status in statuses {
-            if status {
-                assert_eq!(
-                    ConfirmationStatus::from_db_data(h, status),
-                    ConfirmationStatus::ConfirmedIn(h)
-                );
-            } else {
-                assert_eq!(
-                    ConfirmationStatus::from_db_data(h, status),
-                    ConfirmationStatus::InMempoolSince(h)
-                );
-            }
-        }
+        assert_eq!(
+            ConfirmationStatus::from_db_data(h, ConfirmationStatus::DB_CONFIRMED),
+            ConfirmationStatus::ConfirmedIn(h)
+        );
+        assert_eq!(
+            ConfirmationStatus::from_db_data(h, ConfirmationStatus::DB_IN_MEMPOOL),
+            ConfirmationStatus::InMempoolSince(h)
+        );
+        assert_eq!(
+            ConfirmationStatus::from_db_data(h, ConfirmationStatus::DB_DELAYED),
+            ConfirmationStatus::DelayedUntil(h)
+        );
+        assert_eq!(
+            ConfirmationStatus::from_db_data(h, ConfirmationStatus::DB_RETRYING_MEMPOOL_CONFLICT),
+            ConfirmationStatus::RetryingSince(h, RejectionReason::MempoolConflict)
+        );
+        assert_eq!(
+            ConfirmationStatus::from_db_data(h, ConfirmationStatus::DB_SIMULATED),
+            ConfirmationStatus::Simulated(h)
+        );
     }
 
     #[test]
     fn test_confirmation_status_to_db_data() {
-        // Analogous to the previous test, this will only construct ConfirmedIn and InMempolSince statuses.
+        // Analogous to the previous test, this will only construct ConfirmedIn, InMempoolSince and DelayedUntil statuses.
         // The None case has to be threaten though.
         let h = 21;
 
         assert_eq!(
             ConfirmationStatus::ConfirmedIn(h).to_db_data(),
-            Some((h, true))
+            Some((h, ConfirmationStatus::DB_CONFIRMED))
         );
         assert_eq!(
             ConfirmationStatus::InMempoolSince(h).to_db_data(),
-            Some((h, false))
+            Some((h, ConfirmationStatus::DB_IN_MEMPOOL))
+        );
+        assert_eq!(
+            ConfirmationStatus::DelayedUntil(h).to_db_data(),
+            Some((h, ConfirmationStatus::DB_DELAYED))
+        );
+        assert_eq!(
+            ConfirmationStatus::RetryingSince(h, RejectionReason::MempoolConflict).to_db_data(),
+            Some((h, ConfirmationStatus::DB_RETRYING_MEMPOOL_CONFLICT))
+        );
+        assert_eq!(
+            ConfirmationStatus::Simulated(h).to_db_data(),
+            Some((h, ConfirmationStatus::DB_SIMULATED))
+        );
+        assert_eq!(
+            ConfirmationStatus::Rejected(RejectionReason::Other(0)).to_db_data(),
+            None
         );
-        assert_eq!(ConfirmationStatus::Rejected(0).to_db_data(), None);
         assert_eq!(ConfirmationStatus::ReorgedOut.to_db_data(), None);
     }
 
@@ -788,6 +1484,16 @@ mod tests {
                 ConfirmationStatus::ConfirmedIn(i)
             };
             responder.add_tracker(uuid, breach.clone(), user_id, s);
+
+            // The review queue is also loaded
+            if i % 3 == 0 {
+                responder.flag_for_review(&HashSet::from_iter([uuid]), i);
+            }
+
+            // And so are the low fee flags
+            if i % 4 == 0 {
+                responder.set_low_fee(&responder.dbm.lock().unwrap(), uuid, true);
+            }
         }
 
         // Create a new Responder reusing the same DB and check that the data is loaded
@@ -845,73 +1551,279 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_handle_breach_accepted_in_mempool() {
+    async fn test_handle_breach_delayed() {
+        let start_height = START_HEIGHT as u32;
+        let broadcast_delay = 3;
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let (mut responder, _s) =
+            init_responder_with_chain_and_dbm(MockedServerQuery::Regular, &mut chain, dbm.clone())
+                .await;
+        responder.broadcast_delay = broadcast_delay;
+
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+
+        // The tracker is added, but the penalty is held back until the delay elapses
+        let breach = get_random_breach();
+        let delayed_status = ConfirmationStatus::DelayedUntil(start_height + broadcast_delay);
+        assert_eq!(
+            responder.handle_breach(uuid, breach.clone(), user_id),
+            delayed_status
+        );
+        assert_eq!(
+            responder.trackers.lock().unwrap()[&uuid].status,
+            delayed_status
+        );
+        assert!(responder
+            .get_txs_to_rebroadcast(start_height + broadcast_delay - 1)
+            .is_empty());
+
+        // Delayed trackers are recovered as such after a restart
+        let (restarted_responder, _s2) = create_responder(
+            &mut chain,
+            responder.gatekeeper.clone(),
+            dbm,
+            MockedServerQuery::Regular,
+        )
+        .await;
+        assert_eq!(
+            restarted_responder.trackers.lock().unwrap()[&uuid].status,
+            delayed_status
+        );
+
+        // Once the delay elapses, the penalty is broadcast
+        let txs = responder.get_txs_to_rebroadcast(start_height + broadcast_delay);
+        assert_eq!(txs, HashMap::from_iter([(uuid, (breach.penalty_tx, None))]));
+        let (accepted, rejected) = responder.rebroadcast(txs);
+        assert!(rejected.is_empty());
+        assert!(accepted[&uuid].accepted());
+        assert!(responder.trackers.lock().unwrap()[&uuid].status.accepted());
+
+        // And the tracker is not delayed anymore after a restart
+        assert_eq!(
+            responder
+                .dbm
+                .lock()
+                .unwrap()
+                .load_tracker(uuid)
+                .unwrap()
+                .status,
+            accepted[&uuid]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_breach_accepted_in_mempool() {
+        let start_height = START_HEIGHT as u32;
+        let (responder, _s) = init_responder(MockedServerQuery::InMempoool).await;
+
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+
+        let breach = get_random_breach();
+        let penalty_txid = breach.penalty_tx.txid();
+
+        assert_eq!(
+            responder.handle_breach(uuid, breach, user_id),
+            ConfirmationStatus::InMempoolSince(start_height)
+        );
+        assert!(responder.trackers.lock().unwrap().contains_key(&uuid));
+        assert_eq!(
+            responder.trackers.lock().unwrap()[&uuid].status,
+            ConfirmationStatus::InMempoolSince(start_height)
+        );
+        assert!(responder
+            .tx_tracker_map
+            .lock()
+            .unwrap()
+            .contains_key(&penalty_txid));
+    }
+
+    #[tokio::test]
+    async fn test_handle_breach_accepted_in_txindex() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        let user_id = get_random_user_id();
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+
+        let breach = get_random_breach();
+        let penalty_txid = breach.penalty_tx.txid();
+
+        // Add the tx to our txindex
+        let target_block_hash = *responder.tx_index.lock().unwrap().blocks().get(2).unwrap();
+        responder
+            .tx_index
+            .lock()
+            .unwrap()
+            .index_mut()
+            .insert(penalty_txid, target_block_hash);
+        let target_height = responder
+            .tx_index
+            .lock()
+            .unwrap()
+            .get_height(&target_block_hash)
+            .unwrap() as u32;
+
+        assert_eq!(
+            responder.handle_breach(uuid, breach, user_id),
+            ConfirmationStatus::ConfirmedIn(target_height)
+        );
+        assert!(responder.trackers.lock().unwrap().contains_key(&uuid));
+        assert_eq!(
+            responder.trackers.lock().unwrap()[&uuid].status,
+            ConfirmationStatus::ConfirmedIn(target_height)
+        );
+        assert!(responder
+            .tx_tracker_map
+            .lock()
+            .unwrap()
+            .contains_key(&penalty_txid));
+    }
+
+    #[test]
+    fn test_penalty_feerate() {
+        let breach = get_breach_with_fee(1000);
+        let vsize = (breach.penalty_tx.weight() as u64 + 3) / 4;
+        assert_eq!(
+            penalty_feerate(&breach.penalty_tx, &breach.dispute_tx),
+            Some(1000 * 1000 / vsize)
+        );
+
+        // The feerate cannot be computed if the penalty does not spend (only) from the dispute
+        let random_breach = get_random_breach();
+        assert_eq!(
+            penalty_feerate(&random_breach.penalty_tx, &random_breach.dispute_tx),
+            None
+        );
+        let mut penalty_tx = breach.penalty_tx.clone();
+        penalty_tx.input.push(get_random_tx().input[0].clone());
+        assert_eq!(penalty_feerate(&penalty_tx, &breach.dispute_tx), None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_breach_low_fee() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+        let user_id = get_random_user_id();
+
+        // A penalty paying a tiny fee is flagged, since it does not clear the mempool min fee
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
+        assert!(responder
+            .handle_breach(uuid, get_breach_with_fee(10), user_id)
+            .accepted());
+        assert!(responder.low_fee_trackers.lock().unwrap().contains(&uuid));
+
+        // A penalty paying enough fees is not
+        let user2_id = get_random_user_id();
+        let (uuid2, appointment) = generate_dummy_appointment_with_user(user2_id, None);
+        store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid2, &appointment);
+        assert!(responder
+            .handle_breach(uuid2, get_breach_with_fee(5000), user2_id)
+            .accepted());
+        assert!(!responder.low_fee_trackers.lock().unwrap().contains(&uuid2));
+        assert_eq!(responder.get_low_fee_trackers_count(), 1);
+
+        // Flags are persisted
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_low_fee_trackers(),
+            HashSet::from_iter([uuid])
+        );
+
+        // The flag is cleared once the tracker is gone
+        responder
+            .delete_trackers_from_memory(&HashSet::from_iter([uuid]), DeletionReason::Outdated);
+        assert_eq!(responder.get_low_fee_trackers_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_breach_rejected_low_fee() {
         let start_height = START_HEIGHT as u32;
-        let (responder, _s) = init_responder(MockedServerQuery::InMempoool).await;
+        let (responder, _s) = init_responder(MockedServerQuery::ErrorWithMessage(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+            "mempool min fee not met, 100 < 2000",
+        ))
+        .await;
 
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
         store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
 
+        // Penalties rejected for paying too low a fee are kept (and flagged) so they can be retried
         let breach = get_random_breach();
-        let penalty_txid = breach.penalty_tx.txid();
-
+        let retrying_status =
+            ConfirmationStatus::RetryingSince(start_height, RejectionReason::LowFee);
         assert_eq!(
-            responder.handle_breach(uuid, breach, user_id),
-            ConfirmationStatus::InMempoolSince(start_height)
+            responder.handle_breach(uuid, breach.clone(), user_id),
+            retrying_status
         );
-        assert!(responder.trackers.lock().unwrap().contains_key(&uuid));
         assert_eq!(
             responder.trackers.lock().unwrap()[&uuid].status,
-            ConfirmationStatus::InMempoolSince(start_height)
+            retrying_status
         );
-        assert!(responder
-            .tx_tracker_map
-            .lock()
-            .unwrap()
-            .contains_key(&penalty_txid));
+        assert_eq!(
+            responder
+                .dbm
+                .lock()
+                .unwrap()
+                .load_tracker(uuid)
+                .unwrap()
+                .status,
+            retrying_status
+        );
+        assert_eq!(responder.get_low_fee_trackers_count(), 1);
+
+        // They are retried every block, and kept as long as the rejection is not final
+        let txs = responder.get_txs_to_rebroadcast(start_height + 1);
+        assert_eq!(txs, HashMap::from_iter([(uuid, (breach.penalty_tx, None))]));
+        let (accepted, rejected) = responder.rebroadcast(txs);
+        assert!(accepted.is_empty() && rejected.is_empty());
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(responder.get_low_fee_trackers_count(), 1);
     }
 
     #[tokio::test]
-    async fn test_handle_breach_accepted_in_txindex() {
-        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+    async fn test_handle_breach_mempool_conflict() {
+        let start_height = START_HEIGHT as u32;
+        let (responder, _s) = init_responder(MockedServerQuery::ErrorWithMessage(
+            rpc_errors::RPC_VERIFY_REJECTED as i64,
+            "txn-mempool-conflict",
+        ))
+        .await;
 
         let user_id = get_random_user_id();
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
         store_appointment_and_fks_to_db(&responder.dbm.lock().unwrap(), uuid, &appointment);
 
+        // Penalties conflicting with a transaction in mempool are kept and retried, the inputs are not spent yet
         let breach = get_random_breach();
-        let penalty_txid = breach.penalty_tx.txid();
-
-        // Add the tx to our txindex
-        let target_block_hash = *responder.tx_index.lock().unwrap().blocks().get(2).unwrap();
-        responder
-            .tx_index
-            .lock()
-            .unwrap()
-            .index_mut()
-            .insert(penalty_txid, target_block_hash);
-        let target_height = responder
-            .tx_index
-            .lock()
-            .unwrap()
-            .get_height(&target_block_hash)
-            .unwrap() as u32;
-
+        let retrying_status =
+            ConfirmationStatus::RetryingSince(start_height, RejectionReason::MempoolConflict);
         assert_eq!(
-            responder.handle_breach(uuid, breach, user_id),
-            ConfirmationStatus::ConfirmedIn(target_height)
+            responder.handle_breach(uuid, breach.clone(), user_id),
+            retrying_status
         );
-        assert!(responder.trackers.lock().unwrap().contains_key(&uuid));
         assert_eq!(
-            responder.trackers.lock().unwrap()[&uuid].status,
-            ConfirmationStatus::ConfirmedIn(target_height)
+            responder
+                .dbm
+                .lock()
+                .unwrap()
+                .load_tracker(uuid)
+                .unwrap()
+                .status,
+            retrying_status
         );
-        assert!(responder
-            .tx_tracker_map
-            .lock()
-            .unwrap()
-            .contains_key(&penalty_txid));
+        assert_eq!(responder.get_low_fee_trackers_count(), 0);
+
+        let txs = responder.get_txs_to_rebroadcast(start_height + 1);
+        assert_eq!(txs, HashMap::from_iter([(uuid, (breach.penalty_tx, None))]));
+        let (accepted, rejected) = responder.rebroadcast(txs);
+        assert!(accepted.is_empty() && rejected.is_empty());
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(responder.get_trackers_under_review_count(), 0);
     }
 
     #[tokio::test]
@@ -928,7 +1840,7 @@ mod tests {
 
         assert_eq!(
             responder.handle_breach(uuid, breach, user_id),
-            ConfirmationStatus::Rejected(rpc_errors::RPC_VERIFY_ERROR)
+            ConfirmationStatus::Rejected(RejectionReason::Other(rpc_errors::RPC_VERIFY_ERROR))
         );
         assert!(!responder.trackers.lock().unwrap().contains_key(&uuid));
         assert!(!responder
@@ -1281,6 +2193,21 @@ mod tests {
             }
         }
 
+        // Simulated transactions (dry-run mode) never made it to the network, so they are never rebroadcast
+        let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
+        responder
+            .dbm
+            .lock()
+            .unwrap()
+            .store_appointment(uuid, &appointment)
+            .unwrap();
+        responder.add_tracker(
+            uuid,
+            get_random_breach(),
+            user_id,
+            ConfirmationStatus::Simulated(current_height - CONFIRMATIONS_BEFORE_RETRY as u32),
+        );
+
         assert_eq!(responder.get_txs_to_rebroadcast(current_height), txs);
     }
 
@@ -1528,8 +2455,230 @@ mod tests {
         // Check all are rejected
         let (accepted, rejected) =
             responder.rebroadcast(responder.get_txs_to_rebroadcast(current_height));
-        assert_eq!(rejected, need_rebroadcast);
+        assert_eq!(
+            rejected.keys().cloned().collect::<HashSet<UUID>>(),
+            need_rebroadcast
+        );
+        assert!(accepted.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_gone_tracker() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        // Trackers that are gone by the time their transactions are rebroadcast (e.g. abandoned) are skipped
+        let mut txs = HashMap::new();
+        txs.insert(generate_uuid(), (get_random_tx(), None));
+        let (accepted, rejected) = responder.rebroadcast(txs);
         assert!(accepted.is_empty());
+        assert!(rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_tracker() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        // Unknown trackers cannot be rebroadcast
+        assert_eq!(
+            responder.rebroadcast_tracker(generate_uuid()),
+            Err(TrackerActionFailure::NotFound)
+        );
+
+        // Neither can confirmed ones
+        let uuid = generate_uuid();
+        responder.add_random_tracker(uuid, ConfirmationStatus::ConfirmedIn(42));
+        assert_eq!(
+            responder.rebroadcast_tracker(uuid),
+            Err(TrackerActionFailure::AlreadyConfirmed)
+        );
+
+        // Trackers in mempool are rebroadcast right away, and their new status is persisted
+        let uuid = generate_uuid();
+        let mut tracker =
+            responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert!(responder.get_accepted_by(uuid).is_empty());
+        let status = responder.rebroadcast_tracker(uuid).unwrap();
+        let height = responder.carrier.lock().unwrap().block_height();
+        assert_eq!(status, ConfirmationStatus::InMempoolSince(height));
+        assert_eq!(
+            responder.get_accepted_by(uuid),
+            vec![BITCOIND_ENDPOINT.to_owned()]
+        );
+        assert_eq!(responder.trackers.lock().unwrap()[&uuid].status, status);
+        tracker.status = status;
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_tracker(uuid).unwrap(),
+            tracker
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_tracker_rejected() {
+        let (responder, _s) = init_responder(MockedServerQuery::Error(
+            rpc_errors::RPC_VERIFY_ERROR as i64,
+        ))
+        .await;
+
+        // Rejected trackers are not deleted, so the operator can decide what to do with them
+        let uuid = generate_uuid();
+        let tracker = responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert_eq!(
+            responder.rebroadcast_tracker(uuid),
+            Err(TrackerActionFailure::Rejected)
+        );
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_tracker(uuid).unwrap(),
+            tracker
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_tracker_inputs_spent() {
+        let (responder, _s) = init_responder(MockedServerQuery::InputsSpentOnChain).await;
+
+        // Trackers whose penalty can never confirm are flagged for review
+        let uuid = generate_uuid();
+        responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert_eq!(
+            responder.rebroadcast_tracker(uuid),
+            Err(TrackerActionFailure::Rejected)
+        );
+        assert!(responder.has_tracker(uuid));
+        let height = responder.carrier.lock().unwrap().block_height();
+        assert_eq!(
+            *responder.review_queue.lock().unwrap(),
+            HashMap::from_iter([(uuid, height)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rebroadcast_tracker_inputs_missing() {
+        let (responder, _s) = init_responder(MockedServerQuery::ErrorWithMessage(
+            rpc_errors::RPC_VERIFY_ERROR as i64,
+            "bad-txns-inputs-missingorspent",
+        ))
+        .await;
+
+        // Trackers whose penalty inputs are missing but not spent by a confirmed transaction are not flagged
+        let uuid = generate_uuid();
+        responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert_eq!(
+            responder.rebroadcast_tracker(uuid),
+            Err(TrackerActionFailure::Rejected)
+        );
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(responder.get_trackers_under_review_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_tracker() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        assert_eq!(
+            responder.acknowledge_tracker(generate_uuid()),
+            Err(TrackerActionFailure::NotFound)
+        );
+
+        // Only trackers under review can be acknowledged
+        let uuid = generate_uuid();
+        let tracker = responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert_eq!(
+            responder.acknowledge_tracker(uuid),
+            Err(TrackerActionFailure::NotUnderReview)
+        );
+
+        responder.flag_for_review(&HashSet::from_iter([uuid]), 42);
+        assert_eq!(
+            responder.get_review_queue(),
+            vec![(uuid, tracker.clone(), 42)]
+        );
+
+        // Acknowledged trackers are removed from memory and the database
+        assert_eq!(responder.acknowledge_tracker(uuid), Ok(()));
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Completed
+        );
+        assert!(!responder.has_tracker(uuid));
+        assert!(responder.get_review_queue().is_empty());
+        assert!(responder.dbm.lock().unwrap().load_review_queue().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_review_queue_confirmed() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        // Trackers under review whose penalty confirms after all are taken out of the queue
+        let uuid = generate_uuid();
+        let tracker = responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        responder.flag_for_review(&HashSet::from_iter([uuid]), 42);
+
+        responder.check_confirmations(&[tracker.penalty_tx.txid()], 43);
+        assert_eq!(responder.get_trackers_under_review_count(), 0);
+        assert!(responder.dbm.lock().unwrap().load_review_queue().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abandon_tracker() {
+        let (responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        assert_eq!(
+            responder.abandon_tracker(generate_uuid(), "reason"),
+            Err(TrackerActionFailure::NotFound)
+        );
+
+        // Abandoned trackers are removed from memory and the database
+        let uuid = generate_uuid();
+        let tracker = responder.add_random_tracker(uuid, ConfirmationStatus::InMempoolSince(42));
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Triggered
+        );
+        assert_eq!(responder.abandon_tracker(uuid, "superseded"), Ok(()));
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
+        assert!(!responder.trackers.lock().unwrap().contains_key(&uuid));
+        assert!(!responder
+            .tx_tracker_map
+            .lock()
+            .unwrap()
+            .contains_key(&tracker.penalty_tx.txid()));
+        assert!(matches!(
+            responder.dbm.lock().unwrap().load_tracker(uuid),
+            Err(DBError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_log_responded_breaches() {
+        let (mut responder, _s) = init_responder(MockedServerQuery::Regular).await;
+
+        let confirmed_uuid = generate_uuid();
+        let confirmed =
+            responder.add_random_tracker(confirmed_uuid, ConfirmationStatus::ConfirmedIn(42));
+        let unconfirmed_uuid = generate_uuid();
+        responder.add_random_tracker(unconfirmed_uuid, ConfirmationStatus::InMempoolSince(42));
+        let uuids = HashSet::from_iter([confirmed_uuid, unconfirmed_uuid]);
+
+        // Nothing is logged if the breach log is disabled
+        responder.log_responded_breaches(&uuids);
+        assert!(responder
+            .dbm
+            .lock()
+            .unwrap()
+            .load_breach_log(0, u32::MAX)
+            .is_empty());
+
+        // Otherwise, confirmed penalties are logged alongside their confirmation height
+        responder.breach_log = true;
+        responder.log_responded_breaches(&uuids);
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_breach_log(0, u32::MAX),
+            vec![(confirmed.penalty_tx.txid(), 42)]
+        );
     }
 
     #[tokio::test]
@@ -1970,6 +3119,104 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_filtered_block_connected_inputs_spent() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (responder, _s) = init_responder_with_chain_and_dbm(
+            MockedServerQuery::InputsSpentOnChain,
+            &mut chain,
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        )
+        .await;
+
+        // Trackers whose penalty inputs have been spent on chain are not rejected but flagged for review, given the
+        // penalty can never confirm but whether the dispute has been resolved elsewhere is for the operator to tell.
+        let mut target_block_height = chain.get_block_count() + 1;
+        let uuid = generate_uuid();
+        let tracker = responder.add_random_tracker(
+            uuid,
+            ConfirmationStatus::InMempoolSince(
+                target_block_height - CONFIRMATIONS_BEFORE_RETRY as u32,
+            ),
+        );
+
+        responder.block_connected(&chain.generate(None), target_block_height);
+
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(responder.get_trackers_under_review_count(), 1);
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_review_queue(),
+            HashMap::from_iter([(uuid, target_block_height)])
+        );
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Triggered
+        );
+
+        // Flagged trackers are still rebroadcast, in case the transaction spending their inputs is reorged out
+        assert!(responder
+            .get_txs_to_rebroadcast(target_block_height + CONFIRMATIONS_BEFORE_RETRY as u32)
+            .contains_key(&uuid));
+
+        // Once the review timeout is reached, the tracker is garbage collected
+        for _ in 0..REVIEW_TIMEOUT {
+            assert!(responder.has_tracker(uuid));
+            target_block_height += 1;
+            responder.block_connected(&chain.generate(None), target_block_height);
+        }
+
+        assert!(!responder.has_tracker(uuid));
+        assert_eq!(responder.get_trackers_under_review_count(), 0);
+        assert!(!responder
+            .tx_tracker_map
+            .lock()
+            .unwrap()
+            .contains_key(&tracker.penalty_tx.txid()));
+        assert!(responder.dbm.lock().unwrap().load_review_queue().is_empty());
+        // Nobody looked into it, so the appointment is not considered completed
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_filtered_block_connected_inputs_missing() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (responder, _s) = init_responder_with_chain_and_dbm(
+            MockedServerQuery::ErrorWithMessage(
+                rpc_errors::RPC_VERIFY_ERROR as i64,
+                "bad-txns-inputs-missingorspent",
+            ),
+            &mut chain,
+            Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+        )
+        .await;
+
+        // Trackers whose penalty inputs are missing, but not spent by a confirmed transaction, are neither rejected nor
+        // flagged for review. They are kept and retried.
+        let target_block_height = chain.get_block_count() + 1;
+        let uuid = generate_uuid();
+        responder.add_random_tracker(
+            uuid,
+            ConfirmationStatus::InMempoolSince(
+                target_block_height - CONFIRMATIONS_BEFORE_RETRY as u32,
+            ),
+        );
+
+        responder.block_connected(&chain.generate(None), target_block_height);
+
+        assert!(responder.has_tracker(uuid));
+        assert_eq!(responder.get_trackers_under_review_count(), 0);
+        assert_eq!(
+            responder.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Triggered
+        );
+        assert!(responder
+            .get_txs_to_rebroadcast(target_block_height + 1)
+            .contains_key(&uuid));
+    }
+
     #[tokio::test]
     async fn test_block_disconnected() {
         let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
@@ -1,3 +1,5 @@
+//! `bitcoind` RPC error codes. Also used by [ChainSource](crate::chain_source::ChainSource) implementations to report rejections.
+
 #![allow(dead_code)]
 // Ported from https://github.com/bitcoin/bitcoin/blob/0.18/src/rpc/protocol.h
 // TODO: Check if we can get rid of this whole module once `bitcoincore-rpc` is fully integrated.
@@ -0,0 +1,281 @@
+//! Logic related to the Storage, the interface through which the tower core components persist their data.
+//!
+//! The [Gatekeeper](crate::gatekeeper::Gatekeeper), the [Responder](crate::responder::Responder) and the
+//! [Watcher](crate::watcher::Watcher) share a single [Storage] instance. `teosd` uses the [DBM] (a SQLite database),
+//! but projects embedding the tower (check [tower](crate::tower)) can plug in their own storage backend instead.
+
+use std::collections::{HashMap, HashSet};
+
+use bitcoin::Txid;
+
+use teos_common::appointment::Locator;
+use teos_common::dbm::Error;
+use teos_common::receipts::AppointmentReceipt;
+use teos_common::UserId;
+
+use crate::dbm::DBM;
+use crate::extended_appointment::{AppointmentState, ExtendedAppointment, UUID};
+use crate::gatekeeper::UserInfo;
+use crate::responder::{ConfirmationStatus, TransactionTracker};
+
+/// A storage backend for the tower data.
+///
+/// Implementors are expected to behave like the [DBM] does. Namely, removing a user removes its appointments, and
+/// removing an appointment removes its tracker (alongside its review entry and low fee flag), and appointment states
+/// only move through valid transitions (check [AppointmentState::can_transition_to]).
+pub trait Storage: Send {
+    /// Stores a new user ([UserInfo]).
+    fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error>;
+
+    /// Updates an existing user ([UserInfo]).
+    fn update_user(&self, user_id: UserId, user_info: &UserInfo);
+
+    /// Loads all users, alongside the slots taken by each of their appointments.
+    fn load_all_users(&self) -> HashMap<UserId, UserInfo>;
+
+    /// Removes some users, alongside all their data but the final appointment states (which are pruned later on). Returns
+    /// the number of batches the removal has been split into.
+    fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize;
+
+    /// Stores a new [ExtendedAppointment]. Appointments with no state yet are stored as [AppointmentState::Accepted].
+    fn store_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment)
+        -> Result<(), Error>;
+
+    /// Updates an existing [ExtendedAppointment].
+    fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment);
+
+    /// Loads an [ExtendedAppointment].
+    fn load_appointment(&self, uuid: UUID) -> Result<ExtendedAppointment, Error>;
+
+    /// Loads the appointments matching a given locator, or all of them if no locator is given.
+    fn load_appointments(&self, locator: Option<Locator>) -> HashMap<UUID, ExtendedAppointment>;
+
+    /// Removes some appointments, moving them to their final [AppointmentState] and updating the users that held them.
+    /// Returns the number of batches the removal has been split into.
+    fn batch_remove_appointments(
+        &mut self,
+        appointments: &HashSet<UUID>,
+        updated_users: &HashMap<UserId, UserInfo>,
+        state: AppointmentState,
+        height: u32,
+    ) -> usize;
+
+    /// Loads the locator of a given appointment.
+    fn load_locator(&self, uuid: UUID) -> Result<Locator, Error>;
+
+    /// Loads the [AppointmentState] of a given appointment ([AppointmentState::Received] if it has no state).
+    fn load_appointment_state(&self, uuid: UUID) -> AppointmentState;
+
+    /// Moves an appointment to a new [AppointmentState], as long as the transition is valid. Returns whether the state
+    /// has been updated.
+    fn update_appointment_state(
+        &self,
+        uuid: UUID,
+        user_id: UserId,
+        state: AppointmentState,
+        height: u32,
+    ) -> bool;
+
+    /// Moves a batch of already stored appointments to a new [AppointmentState], skipping invalid transitions.
+    fn batch_update_appointment_states(
+        &mut self,
+        uuids: &HashSet<UUID>,
+        state: AppointmentState,
+        height: u32,
+    );
+
+    /// Loads the number of appointments in each [AppointmentState].
+    fn load_appointment_state_counts(&self) -> HashMap<AppointmentState, usize>;
+
+    /// Removes the states of the appointments that reached a final [AppointmentState] at or before a given height.
+    /// Returns the number of removed states.
+    fn prune_appointment_states(&self, height: u32) -> usize;
+
+    /// Stores a new [TransactionTracker].
+    fn store_tracker(&self, uuid: UUID, tracker: &TransactionTracker) -> Result<(), Error>;
+
+    /// Updates the confirmation status of an existing [TransactionTracker].
+    fn update_tracker_status(&self, uuid: UUID, status: &ConfirmationStatus);
+
+    /// Loads a [TransactionTracker].
+    fn load_tracker(&self, uuid: UUID) -> Result<TransactionTracker, Error>;
+
+    /// Loads the trackers matching a given locator, or all of them if no locator is given.
+    fn load_trackers(&self, locator: Option<Locator>) -> HashMap<UUID, TransactionTracker>;
+
+    /// Adds a tracker to the review queue, alongside the height at which it was flagged.
+    fn store_review_entry(&self, uuid: UUID, height: u32) -> Result<(), Error>;
+
+    /// Removes a tracker from the review queue.
+    fn remove_review_entry(&self, uuid: UUID);
+
+    /// Loads the review queue, that is, the trackers flagged for review and the height they were flagged at.
+    fn load_review_queue(&self) -> HashMap<UUID, u32>;
+
+    /// Flags a tracker as having a penalty that does not clear the mempool min fee.
+    fn store_low_fee_tracker(&self, uuid: UUID) -> Result<(), Error>;
+
+    /// Removes the low fee flag of a tracker.
+    fn remove_low_fee_tracker(&self, uuid: UUID);
+
+    /// Loads the trackers flagged as having a penalty that does not clear the mempool min fee.
+    fn load_low_fee_trackers(&self) -> HashSet<UUID>;
+
+    /// Stores a receipt that is waiting for its batch to be signed.
+    fn store_batch_receipt(&self, uuid: UUID, receipt: &AppointmentReceipt) -> Result<(), Error>;
+
+    /// Loads all the batched receipts, sorted by the height they were issued at.
+    fn load_batch_receipts(&self) -> Vec<(UUID, AppointmentReceipt)>;
+
+    /// Deletes the batched receipts issued at, or before, a given height. Returns the number of receipts deleted.
+    fn prune_batch_receipts(&self, height: u32) -> usize;
+
+    /// Adds some responded breaches to the breach log, ignoring the penalties that are already in it.
+    fn store_breach_log_entries(&mut self, entries: &[(Txid, u32)]);
+
+    /// Loads the breach log entries within a given block range (both ends included), sorted by height.
+    fn load_breach_log(&self, from_height: u32, to_height: u32) -> Vec<(Txid, u32)>;
+}
+
+impl Storage for DBM {
+    fn store_user(&self, user_id: UserId, user_info: &UserInfo) -> Result<(), Error> {
+        DBM::store_user(self, user_id, user_info)
+    }
+
+    fn update_user(&self, user_id: UserId, user_info: &UserInfo) {
+        DBM::update_user(self, user_id, user_info)
+    }
+
+    fn load_all_users(&self) -> HashMap<UserId, UserInfo> {
+        DBM::load_all_users(self)
+    }
+
+    fn batch_remove_users(&mut self, users: &HashSet<UserId>) -> usize {
+        DBM::batch_remove_users(self, users)
+    }
+
+    fn store_appointment(
+        &self,
+        uuid: UUID,
+        appointment: &ExtendedAppointment,
+    ) -> Result<(), Error> {
+        DBM::store_appointment(self, uuid, appointment)
+    }
+
+    fn update_appointment(&self, uuid: UUID, appointment: &ExtendedAppointment) {
+        DBM::update_appointment(self, uuid, appointment)
+    }
+
+    fn load_appointment(&self, uuid: UUID) -> Result<ExtendedAppointment, Error> {
+        DBM::load_appointment(self, uuid)
+    }
+
+    fn load_appointments(&self, locator: Option<Locator>) -> HashMap<UUID, ExtendedAppointment> {
+        DBM::load_appointments(self, locator)
+    }
+
+    fn batch_remove_appointments(
+        &mut self,
+        appointments: &HashSet<UUID>,
+        updated_users: &HashMap<UserId, UserInfo>,
+        state: AppointmentState,
+        height: u32,
+    ) -> usize {
+        DBM::batch_remove_appointments(self, appointments, updated_users, state, height)
+    }
+
+    fn load_locator(&self, uuid: UUID) -> Result<Locator, Error> {
+        DBM::load_locator(self, uuid)
+    }
+
+    fn load_appointment_state(&self, uuid: UUID) -> AppointmentState {
+        DBM::load_appointment_state(self, uuid)
+    }
+
+    fn update_appointment_state(
+        &self,
+        uuid: UUID,
+        user_id: UserId,
+        state: AppointmentState,
+        height: u32,
+    ) -> bool {
+        DBM::update_appointment_state(self, uuid, user_id, state, height)
+    }
+
+    fn batch_update_appointment_states(
+        &mut self,
+        uuids: &HashSet<UUID>,
+        state: AppointmentState,
+        height: u32,
+    ) {
+        DBM::batch_update_appointment_states(self, uuids, state, height)
+    }
+
+    fn load_appointment_state_counts(&self) -> HashMap<AppointmentState, usize> {
+        DBM::load_appointment_state_counts(self)
+    }
+
+    fn prune_appointment_states(&self, height: u32) -> usize {
+        DBM::prune_appointment_states(self, height)
+    }
+
+    fn store_tracker(&self, uuid: UUID, tracker: &TransactionTracker) -> Result<(), Error> {
+        DBM::store_tracker(self, uuid, tracker)
+    }
+
+    fn update_tracker_status(&self, uuid: UUID, status: &ConfirmationStatus) {
+        DBM::update_tracker_status(self, uuid, status)
+    }
+
+    fn load_tracker(&self, uuid: UUID) -> Result<TransactionTracker, Error> {
+        DBM::load_tracker(self, uuid)
+    }
+
+    fn load_trackers(&self, locator: Option<Locator>) -> HashMap<UUID, TransactionTracker> {
+        DBM::load_trackers(self, locator)
+    }
+
+    fn store_review_entry(&self, uuid: UUID, height: u32) -> Result<(), Error> {
+        DBM::store_review_entry(self, uuid, height)
+    }
+
+    fn remove_review_entry(&self, uuid: UUID) {
+        DBM::remove_review_entry(self, uuid)
+    }
+
+    fn load_review_queue(&self) -> HashMap<UUID, u32> {
+        DBM::load_review_queue(self)
+    }
+
+    fn store_low_fee_tracker(&self, uuid: UUID) -> Result<(), Error> {
+        DBM::store_low_fee_tracker(self, uuid)
+    }
+
+    fn remove_low_fee_tracker(&self, uuid: UUID) {
+        DBM::remove_low_fee_tracker(self, uuid)
+    }
+
+    fn load_low_fee_trackers(&self) -> HashSet<UUID> {
+        DBM::load_low_fee_trackers(self)
+    }
+
+    fn store_batch_receipt(&self, uuid: UUID, receipt: &AppointmentReceipt) -> Result<(), Error> {
+        DBM::store_batch_receipt(self, uuid, receipt)
+    }
+
+    fn load_batch_receipts(&self) -> Vec<(UUID, AppointmentReceipt)> {
+        DBM::load_batch_receipts(self)
+    }
+
+    fn prune_batch_receipts(&self, height: u32) -> usize {
+        DBM::prune_batch_receipts(self, height)
+    }
+
+    fn store_breach_log_entries(&mut self, entries: &[(Txid, u32)]) {
+        DBM::store_breach_log_entries(self, entries)
+    }
+
+    fn load_breach_log(&self, from_height: u32, to_height: u32) -> Vec<(Txid, u32)> {
+        DBM::load_breach_log(self, from_height, to_height)
+    }
+}
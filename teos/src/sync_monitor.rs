@@ -0,0 +1,229 @@
+//! Logic related to the SyncMonitor, the component in charge of tracking how far behind the best known chain the tower is.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::time::timeout;
+use triggered::Listener;
+
+use crate::chain_source::ChainSource;
+use crate::config::Config;
+
+/// How often (in seconds) bitcoind's sync state is checked.
+pub const CHECK_INTERVAL: u64 = 30;
+
+/// What the tower does with new users and appointments while it is syncing (e.g. if bitcoind is in its initial block download).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Accept them. Appointments are watched as the tower catches up, so breaches found in the blocks pending to be
+    /// processed are responded late.
+    Accept,
+    /// Reject them while the tower is more than the given number of blocks behind the best known header.
+    Reject(u32),
+}
+
+impl SyncPolicy {
+    /// Creates a new [SyncPolicy] from the sync options of the given [Config].
+    pub fn from_config(conf: &Config) -> Self {
+        match conf.sync_policy.as_str() {
+            "reject" => SyncPolicy::Reject(conf.max_blocks_behind),
+            // The config has already been verified at this point, so anything else is accept
+            _ => SyncPolicy::Accept,
+        }
+    }
+}
+
+/// Reasons why new data may not be accepted while the tower is syncing.
+#[derive(Debug, PartialEq)]
+pub struct SyncFailure {
+    /// How many blocks behind the best known header the tower is.
+    pub blocks_behind: u32,
+    /// Estimated time (in seconds) until the tower catches up, if it can be estimated.
+    pub eta: Option<u64>,
+}
+
+/// Sync state of the tower, shared between the [SyncMonitor] and the interfaces.
+#[derive(Debug)]
+pub struct SyncStatus {
+    /// The policy applied while syncing.
+    policy: SyncPolicy,
+    /// Height of the best header known by bitcoind.
+    best_header_height: u32,
+    /// The last bitcoind height seen and when it was seen. Used to estimate the sync pace.
+    last_sample: Option<(Instant, u32)>,
+    /// Estimated time (in seconds) bitcoind takes to process a block while syncing.
+    secs_per_block: Option<f64>,
+}
+
+impl SyncStatus {
+    /// Creates a new [SyncStatus] instance.
+    pub fn new(policy: SyncPolicy) -> Self {
+        Self {
+            policy,
+            best_header_height: 0,
+            last_sample: None,
+            secs_per_block: None,
+        }
+    }
+
+    /// Updates the status with bitcoind's height and best known header height, sampled at `now`.
+    pub fn update(&mut self, blocks: u32, headers: u32, now: Instant) {
+        self.best_header_height = headers;
+        if let Some((sampled_at, sampled_blocks)) = self.last_sample {
+            // The pace is only updated if there was progress. Otherwise the last estimation is kept
+            if blocks > sampled_blocks {
+                self.secs_per_block = Some(
+                    now.duration_since(sampled_at).as_secs_f64() / (blocks - sampled_blocks) as f64,
+                );
+            }
+        }
+        self.last_sample = Some((now, blocks));
+    }
+
+    /// Gets how many blocks behind the best known header a tower at the given height is.
+    pub fn blocks_behind(&self, height: u32) -> u32 {
+        self.best_header_height.saturating_sub(height)
+    }
+
+    /// Checks whether new data can be accepted by a tower at the given height according to the [SyncPolicy].
+    ///
+    /// Returns how many blocks behind the tower is if accepted, or a [SyncFailure] otherwise.
+    pub fn check(&self, height: u32) -> Result<u32, SyncFailure> {
+        let blocks_behind = self.blocks_behind(height);
+        match self.policy {
+            SyncPolicy::Reject(max_blocks_behind) if blocks_behind > max_blocks_behind => {
+                Err(SyncFailure {
+                    blocks_behind,
+                    eta: self
+                        .secs_per_block
+                        .map(|pace| (pace * blocks_behind as f64).ceil() as u64),
+                })
+            }
+            _ => Ok(blocks_behind),
+        }
+    }
+}
+
+/// Component in charge of keeping the [SyncStatus] up to date by periodically querying the [ChainSource].
+pub struct SyncMonitor {
+    /// A chain source to query the sync state from.
+    chain_source: Arc<dyn ChainSource>,
+    /// The sync status shared with the interfaces.
+    sync_status: Arc<Mutex<SyncStatus>>,
+    /// A signal indicating the tower is shuting down.
+    shutdown_signal: Listener,
+}
+
+impl SyncMonitor {
+    /// Creates a new [SyncMonitor] instance.
+    pub fn new(
+        chain_source: Arc<dyn ChainSource>,
+        sync_status: Arc<Mutex<SyncStatus>>,
+        shutdown_signal: Listener,
+    ) -> Self {
+        Self {
+            chain_source,
+            sync_status,
+            shutdown_signal,
+        }
+    }
+
+    /// Queries the sync state of the [ChainSource] and updates the [SyncStatus] accordingly.
+    ///
+    /// Errors are only logged, bitcoind reachability is handled by the [ChainMonitor](crate::chain_monitor::ChainMonitor).
+    pub async fn check_sync(&self) {
+        // ChainSource methods are blocking, so they are kept out of the async runtime threads
+        let chain_source = self.chain_source.clone();
+        match tokio::task::spawn_blocking(move || chain_source.get_sync_state()).await {
+            Ok(Ok((blocks, headers))) => {
+                if headers > blocks {
+                    log::debug!(
+                        "bitcoind is syncing ({} blocks behind the best known header)",
+                        headers - blocks
+                    );
+                }
+                self.sync_status
+                    .lock()
+                    .unwrap()
+                    .update(blocks, headers, Instant::now());
+            }
+            Ok(Err(e)) => log::debug!("Cannot get bitcoind's sync state: {}", e),
+            Err(e) => log::error!("Sync state check panicked: {}", e),
+        }
+    }
+
+    /// Monitors bitcoind's sync state every [CHECK_INTERVAL] seconds until shutdown.
+    pub async fn monitor_sync(&self) {
+        loop {
+            self.check_sync().await;
+            if timeout(
+                Duration::from_secs(CHECK_INTERVAL),
+                self.shutdown_signal.clone(),
+            )
+            .await
+            .is_ok()
+            {
+                log::debug!("Received shutting down signal. Shutting down");
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_policy_from_config() {
+        let mut conf = Config::default();
+        assert_eq!(SyncPolicy::from_config(&conf), SyncPolicy::Accept);
+
+        conf.sync_policy = "reject".to_owned();
+        conf.max_blocks_behind = 10;
+        assert_eq!(SyncPolicy::from_config(&conf), SyncPolicy::Reject(10));
+    }
+
+    #[test]
+    fn test_check_accept() {
+        let mut status = SyncStatus::new(SyncPolicy::Accept);
+        status.update(100, 1000, Instant::now());
+
+        // Data is accepted no matter how far behind the tower is, but the lag is reported
+        assert_eq!(status.check(100), Ok(900));
+        assert_eq!(status.check(1000), Ok(0));
+    }
+
+    #[test]
+    fn test_check_reject() {
+        let mut status = SyncStatus::new(SyncPolicy::Reject(6));
+        let start = Instant::now();
+        status.update(100, 1000, start);
+
+        // Within the allowed lag data is accepted
+        assert_eq!(status.check(994), Ok(6));
+
+        // Further behind it is rejected. The pace cannot be estimated with a single sample
+        assert_eq!(
+            status.check(100),
+            Err(SyncFailure {
+                blocks_behind: 900,
+                eta: None
+            })
+        );
+
+        // Once progress is seen the ETA can be estimated (100 blocks in 10 seconds)
+        status.update(200, 1000, start + Duration::from_secs(10));
+        assert_eq!(
+            status.check(200),
+            Err(SyncFailure {
+                blocks_behind: 800,
+                eta: Some(80)
+            })
+        );
+
+        // The estimation is kept if no progress is made
+        status.update(200, 1000, start + Duration::from_secs(20));
+        assert_eq!(status.check(200).unwrap_err().eta, Some(80));
+    }
+}
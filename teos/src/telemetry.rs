@@ -0,0 +1,219 @@
+//! Logic related to the tower telemetry: structured logging (tracing) and the (optional) export of spans via OTLP.
+//!
+//! Requests received by the public API are given a correlation id (`request_id`), which is forwarded from the HTTP API
+//! to the internal API so both ends can be matched. The address of the HTTP client is forwarded alongside it, so the
+//! internal API can tell clients apart (e.g. for rate limiting). Appointments are followed through the tower
+//! (Gatekeeper, Watcher and Responder) using their [UUID], so the full lifecycle of an appointment can be found by
+//! grepping it.
+//!
+//! Request spans, and the stages they go through (e.g. authentication or database writes), are also timed to build
+//! the tower latency stats (check [metrics](crate::metrics)).
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use tonic::metadata::MetadataValue;
+use tonic::{Request, Status};
+use tracing::level_filters::LevelFilter;
+use tracing::Span;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use teos_common::cryptography::get_random_bytes;
+
+use crate::extended_appointment::UUID;
+use crate::metrics::{LatencyLayer, LatencyStats};
+
+/// Metadata key used to forward the request id to the internal API.
+pub const REQUEST_ID_KEY: &str = "x-request-id";
+
+/// Metadata key used to forward the address of the HTTP client to the internal API.
+pub const CLIENT_ADDR_KEY: &str = "x-client-addr";
+
+/// Log target used for audit entries (actions manually triggered by the tower operator).
+pub const AUDIT_TARGET: &str = "teos::audit";
+
+/// Generates a new random request id.
+pub fn new_request_id() -> String {
+    hex::encode(get_random_bytes(8))
+}
+
+/// Gets the request id of a gRPC request. A new one is generated if the request does not carry any (e.g. if it was
+/// not received through the HTTP API).
+pub fn request_id<T>(request: &Request<T>) -> String {
+    request
+        .metadata()
+        .get(REQUEST_ID_KEY)
+        .and_then(|id| id.to_str().ok())
+        .map(|id| id.to_owned())
+        .unwrap_or_else(new_request_id)
+}
+
+/// Builds a gRPC request carrying the given request id.
+pub fn with_request_id<T>(message: T, request_id: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    if let Ok(id) = MetadataValue::from_str(request_id) {
+        request.metadata_mut().insert(REQUEST_ID_KEY, id);
+    }
+    request
+}
+
+/// Gets the address of the client that sent a gRPC request. Requests forwarded by the HTTP API carry the address of the
+/// HTTP client, while the rest come straight from the client. `None` if the address is unknown.
+pub fn client_addr<T>(request: &Request<T>) -> Option<IpAddr> {
+    request
+        .metadata()
+        .get(CLIENT_ADDR_KEY)
+        .and_then(|addr| addr.to_str().ok())
+        .and_then(|addr| addr.parse().ok())
+        .or_else(|| request.remote_addr().map(|addr| addr.ip()))
+}
+
+/// Tags a gRPC request with the address of the HTTP client it is forwarded on behalf of.
+pub fn set_client_addr<T>(request: &mut Request<T>, addr: Option<SocketAddr>) {
+    if let Some(addr) = addr {
+        if let Ok(addr) = MetadataValue::from_str(&addr.ip().to_string()) {
+            request.metadata_mut().insert(CLIENT_ADDR_KEY, addr);
+        }
+    }
+}
+
+/// Interceptor for the gRPC servers reachable by users. Drops any forwarded client address, so users cannot pose as
+/// someone else. Only requests forwarded by the HTTP API can carry one.
+pub fn drop_client_addr(mut request: Request<()>) -> Result<Request<()>, Status> {
+    request.metadata_mut().remove(CLIENT_ADDR_KEY);
+    Ok(request)
+}
+
+/// Builds the span used to follow an appointment through the tower.
+pub(crate) fn appointment_span(uuid: UUID) -> Span {
+    tracing::info_span!("appointment", %uuid)
+}
+
+/// Builds the span used to time an internal stage of a request (e.g. `auth` or `db_write`).
+pub(crate) fn stage_span(name: &'static str) -> Span {
+    tracing::info_span!("stage", name)
+}
+
+/// Sets up the global tracing subscriber.
+///
+/// Records emitted using `log` are also captured, so they carry the context of the span they are emitted in. If an
+/// `otlp_endpoint` is provided, spans are also exported to it (requires the `otlp` feature). Request latencies are
+/// recorded into `latency_stats`.
+pub fn init(
+    debug: bool,
+    deps_debug: bool,
+    otlp_endpoint: Option<&str>,
+    latency_stats: Arc<LatencyStats>,
+) -> Result<(), String> {
+    let filter = Targets::new()
+        .with_default(if deps_debug {
+            LevelFilter::DEBUG
+        } else {
+            LevelFilter::WARN
+        })
+        .with_target(
+            "teos",
+            if debug {
+                LevelFilter::DEBUG
+            } else {
+                LevelFilter::INFO
+            },
+        );
+
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(LatencyLayer::new(latency_stats));
+
+    #[cfg(feature = "otlp")]
+    {
+        let otlp_layer = match otlp_endpoint {
+            Some(endpoint) => Some(otlp::layer(endpoint)?),
+            None => None,
+        };
+        registry
+            .with(otlp_layer)
+            .try_init()
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        if otlp_endpoint.is_some() {
+            return Err("teosd was built without OTLP support (otlp feature)".to_owned());
+        }
+        registry.try_init().map_err(|e| e.to_string())
+    }
+}
+
+/// Flushes the pending spans (if any) before shutting down.
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry::sdk::{trace, Resource};
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Builds a layer exporting spans to the given OTLP (gRPC) endpoint.
+    pub(super) fn layer<S>(
+        endpoint: &str,
+    ) -> Result<OpenTelemetryLayer<S, opentelemetry::sdk::trace::Tracer>, String>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(
+                trace::config()
+                    .with_resource(Resource::new(vec![KeyValue::new("service.name", "teosd")])),
+            )
+            .install_batch(opentelemetry::runtime::Tokio)
+            .map_err(|e| format!("Cannot set up the OTLP exporter: {}", e))?;
+
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id() {
+        // Request ids are forwarded through the request metadata
+        let request = with_request_id((), "deadbeef");
+        assert_eq!(request_id(&request), "deadbeef");
+
+        // And generated if the request does not carry one
+        let request = Request::new(());
+        let id = request_id(&request);
+        assert_eq!(id.len(), 16);
+        assert_ne!(id, request_id(&request));
+    }
+
+    #[test]
+    fn test_client_addr() {
+        // Client addresses are forwarded through the request metadata
+        let mut request = Request::new(());
+        assert_eq!(client_addr(&request), None);
+        set_client_addr(&mut request, Some("1.2.3.4:9814".parse().unwrap()));
+        assert_eq!(client_addr(&request), Some("1.2.3.4".parse().unwrap()));
+
+        // But dropped if received by a server reachable by users
+        let request = drop_client_addr(request).unwrap();
+        assert_eq!(client_addr(&request), None);
+    }
+}
@@ -8,14 +8,18 @@
 */
 
 use rand::Rng;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use jsonrpc_http_server::jsonrpc_core::error::ErrorCode as JsonRpcErrorCode;
 use jsonrpc_http_server::jsonrpc_core::{Error as JsonRpcError, IoHandler, Params, Value};
 use jsonrpc_http_server::{CloseHandle, Server, ServerBuilder};
 
-use bitcoincore_rpc::{Auth, Client as BitcoindClient};
+use bitcoincore_rpc::{Auth, Client};
 
 use bitcoin::blockdata::block::{Block, BlockHeader};
 use bitcoin::blockdata::constants::genesis_block;
@@ -25,6 +29,7 @@ use bitcoin::hash_types::BlockHash;
 use bitcoin::hash_types::Txid;
 use bitcoin::hashes::Hash;
 use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::PublicKey;
 use bitcoin::util::hash::bitcoin_merkle_root;
 use bitcoin::util::uint::Uint256;
 use bitcoin::Witness;
@@ -41,24 +46,50 @@ use teos_common::test_utils::{generate_random_appointment, get_random_user_id, T
 use teos_common::UserId;
 
 use crate::api::internal::InternalAPI;
+use crate::bitcoin_cli::BitcoindClient;
 use crate::carrier::Carrier;
+use crate::chain_source::BitcoindChainSource;
 use crate::dbm::DBM;
-use crate::extended_appointment::{ExtendedAppointment, UUID};
-use crate::gatekeeper::{Gatekeeper, UserInfo};
+use crate::extended_appointment::{AppointmentState, ExtendedAppointment, UUID};
+use crate::gatekeeper::{Gatekeeper, GatekeeperSettings, SubscriptionPricing, UserInfo};
+use crate::metrics::LatencyStats;
 use crate::protos as msgs;
-use crate::responder::{ConfirmationStatus, Responder, TransactionTracker};
+use crate::responder::{ConfirmationStatus, Responder, ResponderSettings, TransactionTracker};
 use crate::rpc_errors;
-use crate::watcher::{Breach, Watcher};
+use crate::sync_monitor::{SyncPolicy, SyncStatus};
+use crate::watcher::{Breach, PreviousKey, Watcher, WatcherSettings};
 
 pub(crate) const SLOTS: u32 = 21;
 pub(crate) const DURATION: u32 = 500;
 pub(crate) const EXPIRY_DELTA: u32 = 42;
+pub(crate) const RENEWAL_WINDOW: u32 = 10;
+pub(crate) const REVIEW_TIMEOUT: u32 = 6;
 pub(crate) const START_HEIGHT: usize = 100;
+pub(crate) const AUTH_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Feerates (in sat/kvB) reported by the [BitcoindMock].
+pub(crate) const MEMPOOL_MIN_FEE: u64 = 1000;
+pub(crate) const ESTIMATED_FEE: u64 = 20000;
+/// Height of the best header reported by the [BitcoindMock] (its chain tip is at [START_HEIGHT]).
+pub(crate) const SYNC_HEADERS: u32 = START_HEIGHT as u32 + 10;
 
 pub(crate) const AVAILABLE_SLOTS: u32 = 21;
 pub(crate) const SUBSCRIPTION_START: u32 = START_HEIGHT as u32;
 pub(crate) const SUBSCRIPTION_EXPIRY: u32 = SUBSCRIPTION_START + 42;
 
+/// The [GatekeeperSettings] used by the [Gatekeeper]s created in tests, unless stated otherwise.
+pub(crate) fn get_gatekeeper_settings() -> GatekeeperSettings {
+    GatekeeperSettings {
+        subscription_slots: SLOTS,
+        subscription_duration: DURATION,
+        expiry_delta: EXPIRY_DELTA,
+        renewal_window: RENEWAL_WINDOW,
+        max_users: 0,
+        pricing: SubscriptionPricing::default(),
+        auth_cache_ttl: AUTH_CACHE_TTL,
+        require_auth_challenges: false,
+    }
+}
+
 #[derive(Clone, Default, Debug)]
 pub(crate) struct Blockchain {
     pub blocks: Vec<Block>,
@@ -352,6 +383,9 @@ pub(crate) fn store_appointment_and_fks_to_db(
     )
     .unwrap();
     dbm.store_appointment(uuid, appointment).unwrap();
+    for state in [AppointmentState::Accepted, AppointmentState::Watched] {
+        dbm.update_appointment_state(uuid, appointment.user_id, state, appointment.start_block);
+    }
 }
 
 pub(crate) async fn get_last_n_blocks(chain: &mut Blockchain, n: usize) -> Vec<ValidatedBlock> {
@@ -369,10 +403,25 @@ pub(crate) async fn get_last_n_blocks(chain: &mut Blockchain, n: usize) -> Vec<V
     last_n_blocks
 }
 
+/// Creates a [BitcoindChainSource] that sends / queries transactions to / from the given (mocked) bitcoind.
+///
+/// Blocks are not served, tests requiring a block source use a [Blockchain] instead. Still, the block client connects
+/// as soon as it is created, so it is pointed to the mock too.
+pub(crate) fn create_chain_source(server_url: &str) -> Arc<BitcoindChainSource> {
+    let addr = SocketAddr::from_str(server_url.trim_start_matches("http://")).unwrap();
+    Arc::new(BitcoindChainSource::new(
+        Arc::new(BitcoindClient::new_unchecked(&addr.ip().to_string(), addr.port()).unwrap()),
+        Client::new(server_url, Auth::None).unwrap(),
+    ))
+}
+
 pub(crate) enum MockedServerQuery {
     Regular,
     InMempoool,
     Error(i64),
+    ErrorWithMessage(i64, &'static str),
+    /// Rejects transactions for spending outputs that have been spent by a confirmed transaction.
+    InputsSpentOnChain,
 }
 
 pub(crate) fn create_carrier(query: MockedServerQuery, height: u32) -> (Carrier, BitcoindStopper) {
@@ -380,13 +429,23 @@ pub(crate) fn create_carrier(query: MockedServerQuery, height: u32) -> (Carrier,
         MockedServerQuery::Regular => BitcoindMock::new(MockOptions::default()),
         MockedServerQuery::InMempoool => BitcoindMock::new(MockOptions::in_mempool()),
         MockedServerQuery::Error(x) => BitcoindMock::new(MockOptions::with_error(x)),
+        MockedServerQuery::ErrorWithMessage(x, m) => {
+            BitcoindMock::new(MockOptions::with_error_message(x, m))
+        }
+        MockedServerQuery::InputsSpentOnChain => BitcoindMock::new(
+            MockOptions::with_error_message(
+                rpc_errors::RPC_VERIFY_ERROR as i64,
+                "bad-txns-inputs-missingorspent",
+            )
+            .spent_on_chain(),
+        ),
     };
-    let bitcoin_cli = Arc::new(BitcoindClient::new(bitcoind_mock.url(), Auth::None).unwrap());
+    let chain_source = create_chain_source(bitcoind_mock.url());
     let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
     start_server(bitcoind_mock.server);
 
     (
-        Carrier::new(bitcoin_cli, bitcoind_reachable, height),
+        Carrier::new(chain_source, bitcoind_reachable, height, false, Vec::new()),
         bitcoind_mock.stopper,
     )
 }
@@ -396,6 +455,7 @@ pub(crate) async fn create_responder(
     gatekeeper: Arc<Gatekeeper>,
     dbm: Arc<Mutex<DBM>>,
     server_url: &str,
+    breach_log: bool,
 ) -> Responder {
     let height = chain.tip().height;
     // For the local TxIndex logic to be sound, our index needs to have, at least, IRREVOCABLY_RESOLVED blocks
@@ -403,11 +463,22 @@ pub(crate) async fn create_responder(
 
     let last_n_blocks = get_last_n_blocks(chain, IRREVOCABLY_RESOLVED as usize).await;
 
-    let bitcoin_cli = Arc::new(BitcoindClient::new(server_url, Auth::None).unwrap());
+    let chain_source = create_chain_source(server_url);
     let bitcoind_reachable = Arc::new((Mutex::new(true), Condvar::new()));
-    let carrier = Carrier::new(bitcoin_cli, bitcoind_reachable, height);
-
-    Responder::new(&last_n_blocks, height, carrier, gatekeeper, dbm)
+    let carrier = Carrier::new(chain_source, bitcoind_reachable, height, false, Vec::new());
+
+    Responder::new(
+        &last_n_blocks,
+        height,
+        carrier,
+        gatekeeper,
+        ResponderSettings {
+            breach_log,
+            broadcast_delay: 0,
+            review_timeout: REVIEW_TIMEOUT,
+        },
+        dbm,
+    )
 }
 
 pub(crate) async fn create_watcher(
@@ -420,8 +491,7 @@ pub(crate) async fn create_watcher(
     let last_n_blocks = get_last_n_blocks(chain, 6).await;
 
     start_server(bitcoind_mock.server);
-    let (tower_sk, tower_pk) = get_random_keypair();
-    let tower_id = UserId(tower_pk);
+    let (tower_sk, _) = get_random_keypair();
     (
         Watcher::new(
             gatekeeper,
@@ -429,7 +499,7 @@ pub(crate) async fn create_watcher(
             &last_n_blocks,
             chain.get_block_count(),
             tower_sk,
-            tower_id,
+            WatcherSettings::default(),
             dbm,
         ),
         bitcoind_mock.stopper,
@@ -439,7 +509,15 @@ pub(crate) async fn create_watcher(
 pub(crate) struct ApiConfig {
     slots: u32,
     duration: u32,
+    max_users: u32,
+    pricing: SubscriptionPricing,
+    breach_log: bool,
     bitcoind_reachable: bool,
+    low_disk_space: bool,
+    sync_policy: SyncPolicy,
+    blocks_behind: u32,
+    operator_key: Option<PublicKey>,
+    previous_key: Option<PreviousKey>,
 }
 
 impl ApiConfig {
@@ -447,7 +525,15 @@ impl ApiConfig {
         Self {
             slots,
             duration,
+            max_users: 0,
+            pricing: SubscriptionPricing::default(),
+            breach_log: false,
             bitcoind_reachable: true,
+            low_disk_space: false,
+            sync_policy: SyncPolicy::Accept,
+            blocks_behind: 0,
+            operator_key: None,
+            previous_key: None,
         }
     }
 
@@ -455,6 +541,42 @@ impl ApiConfig {
         self.bitcoind_reachable = false;
         self.clone()
     }
+
+    pub fn low_disk_space(&mut self) -> Self {
+        self.low_disk_space = true;
+        self.clone()
+    }
+
+    pub fn syncing(&mut self, sync_policy: SyncPolicy, blocks_behind: u32) -> Self {
+        self.sync_policy = sync_policy;
+        self.blocks_behind = blocks_behind;
+        self.clone()
+    }
+
+    pub fn max_users(&mut self, max_users: u32) -> Self {
+        self.max_users = max_users;
+        self.clone()
+    }
+
+    pub fn pricing(&mut self, pricing: SubscriptionPricing) -> Self {
+        self.pricing = pricing;
+        self.clone()
+    }
+
+    pub fn breach_log(&mut self) -> Self {
+        self.breach_log = true;
+        self.clone()
+    }
+
+    pub fn operator_key(&mut self, operator_key: PublicKey) -> Self {
+        self.operator_key = Some(operator_key);
+        self.clone()
+    }
+
+    pub fn previous_key(&mut self, previous_key: PreviousKey) -> Self {
+        self.previous_key = Some(previous_key);
+        self.clone()
+    }
 }
 
 impl Default for ApiConfig {
@@ -462,7 +584,15 @@ impl Default for ApiConfig {
         Self {
             slots: SLOTS,
             duration: DURATION,
+            max_users: 0,
+            pricing: SubscriptionPricing::default(),
+            breach_log: false,
             bitcoind_reachable: true,
+            low_disk_space: false,
+            sync_policy: SyncPolicy::Accept,
+            blocks_behind: 0,
+            operator_key: None,
+            previous_key: None,
         }
     }
 }
@@ -476,14 +606,24 @@ pub(crate) async fn create_api_with_config(
     let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
     let gk = Arc::new(Gatekeeper::new(
         chain.get_block_count(),
-        api_config.slots,
-        api_config.duration,
-        EXPIRY_DELTA,
+        GatekeeperSettings {
+            subscription_slots: api_config.slots,
+            subscription_duration: api_config.duration,
+            max_users: api_config.max_users,
+            pricing: api_config.pricing,
+            ..get_gatekeeper_settings()
+        },
         dbm.clone(),
     ));
-    let responder =
-        create_responder(&mut chain, gk.clone(), dbm.clone(), bitcoind_mock.url()).await;
-    let (watcher, stopper) = create_watcher(
+    let responder = create_responder(
+        &mut chain,
+        gk.clone(),
+        dbm.clone(),
+        bitcoind_mock.url(),
+        api_config.breach_log,
+    )
+    .await;
+    let (mut watcher, stopper) = create_watcher(
         &mut chain,
         Arc::new(responder),
         gk.clone(),
@@ -491,15 +631,25 @@ pub(crate) async fn create_api_with_config(
         dbm.clone(),
     )
     .await;
+    if let Some(previous_key) = api_config.previous_key {
+        watcher.set_previous_key(previous_key);
+    }
 
     let bitcoind_reachable = Arc::new((Mutex::new(api_config.bitcoind_reachable), Condvar::new()));
+    let mut sync_status = SyncStatus::new(api_config.sync_policy);
+    let height = watcher.get_last_known_block_height();
+    sync_status.update(height, height + api_config.blocks_behind, Instant::now());
     let (shutdown_trigger, _) = triggered::trigger();
     (
         Arc::new(InternalAPI::new(
             Arc::new(watcher),
             vec![msgs::NetworkAddress::from_ipv4("address".to_string(), 21)],
             bitcoind_reachable,
+            Arc::new(AtomicBool::new(api_config.low_disk_space)),
+            Arc::new(Mutex::new(sync_status)),
+            Arc::new(LatencyStats::new()),
             shutdown_trigger,
+            api_config.operator_key,
         )),
         stopper,
     )
@@ -539,23 +689,39 @@ pub(crate) struct BitcoindMock {
 #[derive(Default)]
 pub(crate) struct MockOptions {
     error_code: Option<i64>,
+    error_message: Option<&'static str>,
     in_mempool: bool,
+    spent_on_chain: bool,
 }
 
 impl MockOptions {
     pub fn with_error(error_code: i64) -> Self {
         Self {
             error_code: Some(error_code),
-            in_mempool: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_error_message(error_code: i64, error_message: &'static str) -> Self {
+        Self {
+            error_code: Some(error_code),
+            error_message: Some(error_message),
+            ..Default::default()
         }
     }
 
     pub fn in_mempool() -> Self {
         Self {
-            error_code: None,
             in_mempool: true,
+            ..Default::default()
         }
     }
+
+    /// Reports every output as spent by a confirmed transaction, regardless of the error set for the rest of methods.
+    pub fn spent_on_chain(mut self) -> Self {
+        self.spent_on_chain = true;
+        self
+    }
 }
 
 impl BitcoindMock {
@@ -563,15 +729,26 @@ impl BitcoindMock {
         let mut io = IoHandler::default();
 
         if let Some(error) = options.error_code {
+            let message = options.error_message;
             io.add_sync_method("error", move |_params: Params| {
-                Err(JsonRpcError::new(JsonRpcErrorCode::ServerError(error)))
+                let mut e = JsonRpcError::new(JsonRpcErrorCode::ServerError(error));
+                if let Some(message) = message {
+                    e.message = message.to_owned();
+                }
+                Err(e)
             });
             io.add_alias("sendrawtransaction", "error");
             io.add_alias("getrawtransaction", "error");
+            io.add_alias("getmempoolinfo", "error");
+            io.add_alias("estimatesmartfee", "error");
+            io.add_alias("getblockchaininfo", "error");
         } else {
             BitcoindMock::add_sendrawtransaction(&mut io);
             BitcoindMock::add_getrawtransaction(&mut io, options.in_mempool);
+            BitcoindMock::add_fee_methods(&mut io);
+            BitcoindMock::add_getblockchaininfo(&mut io);
         }
+        BitcoindMock::add_gettxout(&mut io, options.spent_on_chain);
 
         let server = ServerBuilder::new(io)
             .threads(3)
@@ -614,6 +791,34 @@ impl BitcoindMock {
         })
     }
 
+    fn add_gettxout(io: &mut IoHandler, spent_on_chain: bool) {
+        if spent_on_chain {
+            // Spent outputs are not found, neither is the transaction that created them (given it is not in mempool)
+            io.add_method("gettxout", |_params: Params| async { Ok(Value::Null) });
+            BitcoindMock::add_getrawtransaction(io, false);
+        } else {
+            io.add_method("gettxout", |_params: Params| async {
+                Ok(serde_json::json!({"confirmations": 1, "value": 0.001, "coinbase": false}))
+            });
+        }
+    }
+
+    fn add_fee_methods(io: &mut IoHandler) {
+        io.add_method("getmempoolinfo", |_params: Params| async {
+            Ok(serde_json::json!({"loaded": true, "size": 0, "bytes": 0, "usage": 0,
+                "mempoolminfee": MEMPOOL_MIN_FEE as f64 / 100_000_000.0, "minrelaytxfee": 0.00001 }))
+        });
+        io.add_method("estimatesmartfee", |_params: Params| async {
+            Ok(serde_json::json!({"feerate": ESTIMATED_FEE as f64 / 100_000_000.0, "blocks": 6 }))
+        });
+    }
+
+    fn add_getblockchaininfo(io: &mut IoHandler) {
+        io.add_method("getblockchaininfo", |_params: Params| async {
+            Ok(serde_json::json!({"chain": "regtest", "blocks": START_HEIGHT, "headers": SYNC_HEADERS }))
+        });
+    }
+
     pub fn url(&self) -> &str {
         &self.url
     }
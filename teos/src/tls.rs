@@ -9,6 +9,16 @@ use rcgen::{Certificate, KeyPair, RcgenError};
 use std::convert::TryFrom;
 use std::path::Path;
 
+/// Files the mtls identities (certificates and keys) are stored in.
+const IDENTITY_FILES: [&str; 6] = [
+    "ca.pem",
+    "ca-key.pem",
+    "server.pem",
+    "server-key.pem",
+    "client.pem",
+    "client-key.pem",
+];
+
 /// Packs the reasons why generating mtls certificates may fail.
 #[derive(Debug)]
 pub enum GenCertificateFailure {
@@ -111,3 +121,71 @@ fn generate_or_load_identity(
     let certificate = std::fs::read(cert_path)?;
     Ok(Identity { certificate, key })
 }
+
+/// Moves the mtls identities generated by older versions of the tower, which lived straight in the data directory
+/// (shared by every network), to the given network directory, so already deployed clients are still trusted.
+///
+/// The identities are moved (not copied), so they are only carried over to the first network the tower is started on
+/// after upgrading. Any other network gets a fresh identity from [tls_init].
+///
+/// Nothing is moved if the network directory already holds any identity. Returns whether the identities were moved.
+pub fn migrate_legacy_identities(data_dir: &Path, network_dir: &Path) -> std::io::Result<bool> {
+    if IDENTITY_FILES.iter().any(|f| network_dir.join(f).exists())
+        || !IDENTITY_FILES.iter().all(|f| data_dir.join(f).exists())
+    {
+        return Ok(false);
+    }
+
+    for f in IDENTITY_FILES {
+        std::fs::rename(data_dir.join(f), network_dir.join(f))?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_migrate_legacy_identities() {
+        let data_dir = TempDir::new("tls_data_dir").unwrap();
+        let network_dir = data_dir.path().join("regtest");
+        std::fs::create_dir(&network_dir).unwrap();
+
+        // Nothing to migrate if the data dir holds no identities
+        assert!(!migrate_legacy_identities(data_dir.path(), &network_dir).unwrap());
+
+        // Legacy identities are moved over, and loaded from the network dir afterwards
+        let (_, legacy_ca) = tls_init(data_dir.path()).unwrap();
+        assert!(migrate_legacy_identities(data_dir.path(), &network_dir).unwrap());
+        for f in IDENTITY_FILES {
+            assert!(network_dir.join(f).exists());
+            assert!(!data_dir.path().join(f).exists());
+        }
+        assert_eq!(tls_init(&network_dir).unwrap().1, legacy_ca);
+
+        // Identities already in the network dir are never overwritten
+        assert!(!migrate_legacy_identities(data_dir.path(), &network_dir).unwrap());
+    }
+
+    #[test]
+    fn test_migrate_legacy_identities_multiple_networks() {
+        let data_dir = TempDir::new("tls_data_dir").unwrap();
+        let (_, legacy_ca) = tls_init(data_dir.path()).unwrap();
+
+        // Only the first network started after upgrading gets the legacy identity
+        let mut cas = Vec::new();
+        for network in ["bitcoin", "regtest"] {
+            let network_dir = data_dir.path().join(network);
+            std::fs::create_dir(&network_dir).unwrap();
+            migrate_legacy_identities(data_dir.path(), &network_dir).unwrap();
+            cas.push(tls_init(&network_dir).unwrap().1);
+        }
+
+        assert_eq!(cas[0], legacy_ca);
+        assert_ne!(cas[1], legacy_ca);
+        assert_ne!(cas[0], cas[1]);
+    }
+}
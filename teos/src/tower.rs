@@ -0,0 +1,362 @@
+//! Logic related to assembling a tower out of its core components, so it can be embedded into other projects.
+//!
+//! The [TowerBuilder] wires a [Gatekeeper], a [Responder] and a [Watcher] together on top of the storage (any
+//! [Storage], e.g. a [DBM]) and chain backend ([Carrier] plus the last few blocks of the chain) provided by the caller. The resulting [Tower] can be
+//! fed blocks through its [listener](Tower::listener) and served through an [InternalAPI](crate::api::internal::InternalAPI).
+//!
+//! Both the [Carrier] and the block feed can be built on top of the caller's own chain backend by implementing
+//! [ChainSource](crate::chain_source::ChainSource).
+
+use std::cmp::max;
+use std::ops::DerefMut;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitcoin::secp256k1::SecretKey;
+use lightning_block_sync::poll::{ChainPoller, Poll, ValidatedBlock, ValidatedBlockHeader};
+use lightning_block_sync::{BlockSource, BlockSourceError};
+
+use teos_common::constants::IRREVOCABLY_RESOLVED;
+use teos_common::TowerId;
+
+use crate::carrier::Carrier;
+use crate::config::Config;
+use crate::dbm::DBM;
+use crate::gatekeeper::{Gatekeeper, GatekeeperSettings, SubscriptionPricing};
+use crate::policy::PolicySet;
+use crate::responder::{Responder, ResponderSettings};
+use crate::storage::Storage;
+use crate::watcher::{PreviousKey, Watcher, WatcherSettings};
+
+/// Error raised if a [Tower] cannot be built.
+#[derive(Debug)]
+pub struct TowerBuildError(String);
+
+impl std::fmt::Display for TowerBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Tower build error: {}", self.0)
+    }
+}
+
+impl std::error::Error for TowerBuildError {}
+
+/// Chain listener that feeds blocks to the [Tower] components in the order they expect them.
+///
+/// The [Gatekeeper] goes last, so both the [Watcher] and the [Responder] can query the data of outdated users
+/// before it gets deleted.
+pub type TowerListener<S = DBM> = (
+    Arc<Watcher<S>>,
+    Arc<(Arc<Responder<S>>, Arc<Gatekeeper<S>>)>,
+);
+
+/// The core components of a tower, already wired together on top of a given [Storage].
+pub struct Tower<S = DBM> {
+    pub gatekeeper: Arc<Gatekeeper<S>>,
+    pub responder: Arc<Responder<S>>,
+    pub watcher: Arc<Watcher<S>>,
+}
+
+impl<S: Storage> Tower<S> {
+    /// Gets the tower identifier.
+    pub fn tower_id(&self) -> TowerId {
+        self.watcher.tower_id
+    }
+
+    /// Returns whether the tower has been created from scratch (fresh) or from backed-up data.
+    pub fn is_fresh(&self) -> bool {
+        self.watcher.is_fresh() & self.responder.is_fresh() & self.gatekeeper.is_fresh()
+    }
+
+    /// Gets a chain listener for the tower components. Check [TowerListener].
+    pub fn listener(&self) -> TowerListener<S> {
+        (
+            self.watcher.clone(),
+            Arc::new((self.responder.clone(), self.gatekeeper.clone())),
+        )
+    }
+}
+
+/// Builds a [Tower] out of its settings and the storage and chain backends provided by the caller.
+///
+/// Settings default to the ones of a default [Config], and can be either set one by one or loaded from a [Config].
+pub struct TowerBuilder {
+    gatekeeper: GatekeeperSettings,
+    responder: ResponderSettings,
+    watcher: WatcherSettings,
+    locator_cache_size: u32,
+}
+
+impl Default for TowerBuilder {
+    fn default() -> Self {
+        TowerBuilder::from_config(&Config::default())
+    }
+}
+
+impl TowerBuilder {
+    /// Creates a new [TowerBuilder] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [TowerBuilder] with the settings of the given [Config].
+    pub fn from_config(conf: &Config) -> Self {
+        TowerBuilder {
+            gatekeeper: GatekeeperSettings {
+                subscription_slots: conf.subscription_slots,
+                subscription_duration: conf.subscription_duration,
+                expiry_delta: conf.expiry_delta,
+                renewal_window: conf.renewal_window,
+                max_users: conf.max_users,
+                pricing: SubscriptionPricing::new(
+                    conf.subscription_price_per_slot_msat,
+                    conf.subscription_price_per_block_msat,
+                    conf.require_payments,
+                ),
+                auth_cache_ttl: Duration::from_secs(conf.auth_cache_ttl),
+                require_auth_challenges: conf.require_auth_challenges,
+            },
+            responder: ResponderSettings {
+                breach_log: conf.breach_log,
+                broadcast_delay: conf.broadcast_delay,
+                review_timeout: conf.review_timeout,
+            },
+            watcher: WatcherSettings {
+                previous_key: None,
+                policies: PolicySet::from_config(conf),
+                batch_receipts: conf.batch_receipts,
+            },
+            locator_cache_size: conf.locator_cache_size,
+        }
+    }
+
+    /// Sets the number of slots and the duration (in blocks) of each subscription.
+    pub fn subscription(mut self, slots: u32, duration: u32) -> Self {
+        self.gatekeeper.subscription_slots = slots;
+        self.gatekeeper.subscription_duration = duration;
+        self
+    }
+
+    /// Sets the grace period (in blocks) given to users to renew their subscriptions after they expire.
+    pub fn expiry_delta(mut self, expiry_delta: u32) -> Self {
+        self.gatekeeper.expiry_delta = expiry_delta;
+        self
+    }
+
+    /// Sets how many blocks before expiry users are reminded to renew their subscriptions.
+    pub fn renewal_window(mut self, renewal_window: u32) -> Self {
+        self.gatekeeper.renewal_window = renewal_window;
+        self
+    }
+
+    /// Sets the maximum number of registered users (zero means no limit).
+    pub fn max_users(mut self, max_users: u32) -> Self {
+        self.gatekeeper.max_users = max_users;
+        self
+    }
+
+    /// Sets the subscription pricing, and whether it is enforced.
+    pub fn pricing(mut self, pricing: SubscriptionPricing) -> Self {
+        self.gatekeeper.pricing = pricing;
+        self
+    }
+
+    /// Sets for how long user authentications are cached (zero disables the cache).
+    pub fn auth_cache_ttl(mut self, auth_cache_ttl: Duration) -> Self {
+        self.gatekeeper.auth_cache_ttl = auth_cache_ttl;
+        self
+    }
+
+    /// Sets whether the requests that support authentication challenges must include one.
+    pub fn require_auth_challenges(mut self, require_auth_challenges: bool) -> Self {
+        self.gatekeeper.require_auth_challenges = require_auth_challenges;
+        self
+    }
+
+    /// Sets the number of blocks kept by the [Watcher]'s locator cache.
+    pub fn locator_cache_size(mut self, locator_cache_size: u32) -> Self {
+        self.locator_cache_size = locator_cache_size;
+        self
+    }
+
+    /// Sets the policies every appointment must comply with to be accepted.
+    pub fn policies(mut self, policies: PolicySet) -> Self {
+        self.watcher.policies = policies;
+        self
+    }
+
+    /// Sets whether appointment receipts are signed in batches (one per block) instead of one by one.
+    pub fn batch_receipts(mut self, batch_receipts: bool) -> Self {
+        self.watcher.batch_receipts = batch_receipts;
+        self
+    }
+
+    /// Sets whether the tower publishes a signed log of the breaches it responds to.
+    pub fn breach_log(mut self, breach_log: bool) -> Self {
+        self.responder.breach_log = breach_log;
+        self
+    }
+
+    /// Sets the number of blocks to wait after a breach is detected before broadcasting its penalty.
+    pub fn broadcast_delay(mut self, broadcast_delay: u32) -> Self {
+        self.responder.broadcast_delay = broadcast_delay;
+        self
+    }
+
+    /// Sets the number of blocks trackers whose penalty can never confirm are kept for review before being garbage
+    /// collected (zero means until the operator acknowledges them).
+    pub fn review_timeout(mut self, review_timeout: u32) -> Self {
+        self.responder.review_timeout = review_timeout;
+        self
+    }
+
+    /// Sets the key the tower is rotating out of. Active subscriptions are re-attested under the new key when the
+    /// [Tower] is built, and receipts are co-signed by the previous key until its overlap window closes.
+    pub fn previous_key(mut self, previous_key: PreviousKey) -> Self {
+        self.watcher.previous_key = Some(previous_key);
+        self
+    }
+
+    /// Gets the number of blocks (previous to the tip, tip included) needed to build the tower.
+    pub fn required_blocks(&self) -> u32 {
+        max(IRREVOCABLY_RESOLVED, self.locator_cache_size)
+    }
+
+    /// Builds the [Tower].
+    ///
+    /// `last_n_blocks` are the last [required_blocks](Self::required_blocks) blocks of the chain, starting at the
+    /// tip (`tip_height`) and going backwards. The [Storage] is shared by all the components, and is also where data
+    /// from a previous run is loaded from.
+    pub fn build<S: Storage>(
+        self,
+        dbm: Arc<Mutex<S>>,
+        carrier: Carrier,
+        last_n_blocks: &[ValidatedBlock],
+        tip_height: u32,
+        tower_sk: SecretKey,
+    ) -> Result<Tower<S>, TowerBuildError> {
+        if self.locator_cache_size == 0 {
+            return Err(TowerBuildError(
+                "locator_cache_size must be at least 1".to_owned(),
+            ));
+        }
+        if last_n_blocks.len() < self.required_blocks() as usize {
+            return Err(TowerBuildError(format!(
+                "not enough blocks (required: {}, received: {})",
+                self.required_blocks(),
+                last_n_blocks.len()
+            )));
+        }
+
+        let gatekeeper = Arc::new(Gatekeeper::new(tip_height, self.gatekeeper, dbm.clone()));
+        let responder = Arc::new(Responder::new(
+            &last_n_blocks[0..IRREVOCABLY_RESOLVED as usize],
+            tip_height,
+            carrier,
+            gatekeeper.clone(),
+            self.responder,
+            dbm.clone(),
+        ));
+        let watcher = Arc::new(Watcher::new(
+            gatekeeper.clone(),
+            responder.clone(),
+            &last_n_blocks[0..self.locator_cache_size as usize],
+            tip_height,
+            tower_sk,
+            self.watcher,
+            dbm,
+        ));
+
+        if let Some(previous_key) = watcher.get_previous_key() {
+            log::info!(
+                "Re-attested {} subscriptions after rotating out of key {} (overlap window ends at height {})",
+                watcher.reattest_subscriptions(),
+                previous_key.tower_id(),
+                previous_key.overlap_end()
+            );
+        }
+
+        Ok(Tower {
+            gatekeeper,
+            responder,
+            watcher,
+        })
+    }
+}
+
+/// Fetches the last `n` blocks of the chain, starting at `last_known_block` and going backwards.
+///
+/// Check [TowerBuilder::build].
+pub async fn get_last_n_blocks<B, T>(
+    poller: &mut ChainPoller<B, T>,
+    mut last_known_block: ValidatedBlockHeader,
+    n: usize,
+) -> Result<Vec<ValidatedBlock>, BlockSourceError>
+where
+    B: DerefMut<Target = T> + Sized + Send + Sync,
+    T: BlockSource,
+{
+    let mut last_n_blocks = Vec::with_capacity(n);
+    for _ in 0..n {
+        let block = poller.fetch_block(&last_known_block).await?;
+        last_known_block = poller.look_up_previous_header(&last_known_block).await?;
+        last_n_blocks.push(block);
+    }
+
+    Ok(last_n_blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use teos_common::cryptography::get_random_keypair;
+
+    use crate::test_utils::{create_carrier, get_last_n_blocks, Blockchain, MockedServerQuery};
+
+    #[tokio::test]
+    async fn test_build() {
+        let mut chain = Blockchain::default().with_height(IRREVOCABLY_RESOLVED as usize * 2);
+        let tip_height = chain.tip().height;
+        let (tower_sk, tower_pk) = get_random_keypair();
+
+        let builder = TowerBuilder::new()
+            .expiry_delta(42)
+            .max_users(21)
+            .breach_log(true)
+            .broadcast_delay(3);
+        assert_eq!(builder.required_blocks(), IRREVOCABLY_RESOLVED);
+        let last_n_blocks = get_last_n_blocks(&mut chain, builder.required_blocks() as usize).await;
+
+        // Not enough blocks
+        let (carrier, _s) = create_carrier(MockedServerQuery::Regular, tip_height);
+        assert!(matches!(
+            TowerBuilder::new().build(
+                Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+                carrier,
+                &last_n_blocks[1..],
+                tip_height,
+                tower_sk,
+            ),
+            Err(TowerBuildError(e)) if e.contains("not enough blocks")
+        ));
+
+        // Enough blocks
+        let (carrier, _s) = create_carrier(MockedServerQuery::Regular, tip_height);
+        let tower = builder
+            .build(
+                Arc::new(Mutex::new(DBM::in_memory().unwrap())),
+                carrier,
+                &last_n_blocks,
+                tip_height,
+                tower_sk,
+            )
+            .unwrap();
+
+        assert_eq!(tower.tower_id(), TowerId(tower_pk));
+        assert!(tower.is_fresh());
+        assert_eq!(tower.gatekeeper.get_expiry_delta(), 42);
+        assert_eq!(tower.gatekeeper.get_max_users(), 21);
+        assert!(tower.responder.is_breach_log_enabled());
+        assert_eq!(tower.responder.get_broadcast_delay(), 3);
+    }
+}
@@ -148,6 +148,16 @@ where
         self.index.contains_key(k)
     }
 
+    /// Gets the number of blocks currently covered by the index.
+    pub fn depth(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Gets the maximum number of blocks the index can cover.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
     /// Checks if the index if full.
     pub fn is_full(&self) -> bool {
         self.blocks.len() > self.size
@@ -254,6 +264,7 @@ mod tests {
 
         let cache: TxIndex<Locator, Transaction> = TxIndex::new(&last_six_blocks, height);
         assert_eq!(blocks.len(), cache.size);
+        assert_eq!(cache.depth(), cache.size());
         for block in blocks.iter() {
             assert!(cache.blocks().contains(&block.block_hash()));
 
@@ -346,6 +357,9 @@ mod tests {
             assert!(!cache.contains_key(&Locator::new(tx.txid())));
         }
         assert!(!cache.tx_in_block.contains_key(&first_block.block_hash()));
+
+        // The depth of the cache never goes over its size
+        assert_eq!(cache.depth(), cache.size());
     }
 
     #[tokio::test]
@@ -5,32 +5,59 @@ use log;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::iter::FromIterator;
+use std::net::IpAddr;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
-use bitcoin::secp256k1::SecretKey;
+use tokio::sync::broadcast;
+
+use bitcoin::hashes::sha256;
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
 use bitcoin::{BlockHeader, Transaction};
 use lightning::chain;
 use lightning_block_sync::poll::ValidatedBlock;
 
 use teos_common::appointment::{Appointment, Locator};
+use teos_common::constants;
 use teos_common::cryptography;
-use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
+use teos_common::migration::MigrationConsent;
+use teos_common::receipts::{
+    AppointmentReceipt, BatchedAppointmentReceipt, BreachLog, RegistrationReceipt,
+};
 use teos_common::{TowerId, UserId};
 
 use crate::dbm::DBM;
-use crate::extended_appointment::{AppointmentSummary, ExtendedAppointment, UUID};
-use crate::gatekeeper::{Gatekeeper, MaxSlotsReached, UserInfo};
-use crate::responder::{ConfirmationStatus, Responder, TransactionTracker};
+use crate::extended_appointment::{
+    AppointmentState, AppointmentSummary, ExtendedAppointment, UUID,
+};
+use crate::gatekeeper::{
+    ChallengeFailure, Gatekeeper, InvoiceFailure, PaymentFailure, RegistrationFailure,
+    RegistrationInvoice, RenewalReminder, SubscriptionPricing, UserInfo,
+};
+use crate::policy::{PolicySet, PolicyUser, PolicyViolation};
+use crate::receipt_batcher::{ReceiptBatcher, BATCH_RETENTION};
+use crate::responder::{ConfirmationStatus, Responder, TrackerActionFailure, TransactionTracker};
+use crate::storage::Storage;
+use crate::telemetry;
 use crate::tx_index::TxIndex;
 
+/// Maximum number of blocks covered by a single page of the breach log.
+const BREACH_LOG_MAX_BLOCKS: u32 = 2016;
+/// Maximum number of breach log requests served per client and block. The log can only change once per block, so there
+/// is no point for anyone to query it more often than that.
+const MAX_BREACH_LOG_REQUESTS_PER_BLOCK: u32 = 10;
+/// Maximum number of clients whose breach log requests are tracked per block. New clients are refused once reached.
+const MAX_BREACH_LOG_CLIENTS_PER_BLOCK: usize = 10_000;
+/// Number of blocks the state of an appointment is kept for after reaching a final state (roughly a week).
+const FINAL_STATE_RETENTION: u32 = 1008;
+
 /// Structure holding data regarding a breach.
 ///
 /// Breaches are computed after spotting a [Locator] on chain and
 /// using the resulting dispute transaction id to decipher the encrypted blob of an ongoing [Appointment].
 /// Breaches are passed to the [Responder] once created.
 #[derive(Debug, Clone)]
-pub(crate) struct Breach {
+pub struct Breach {
     /// Transaction that triggered the breach.
     pub dispute_tx: Transaction,
     /// Transaction that will be used as a response to the breach.
@@ -47,41 +74,158 @@ impl Breach {
     }
 }
 
+/// An appointment accepted by the [Watcher].
+#[derive(Debug)]
+pub struct AcceptedAppointment {
+    /// The appointment receipt. Not signed if it is to be signed in a batch.
+    pub receipt: AppointmentReceipt,
+    /// Whether the dispute transaction was already on chain when the appointment was accepted.
+    pub dispute_on_chain: bool,
+}
+
+/// The result of adding an appointment to the [Watcher] (check [Watcher::add_appointment]).
+#[derive(Debug)]
+pub struct AddedAppointment {
+    /// The appointment receipt. Not signed if it is to be signed in a batch.
+    pub receipt: AppointmentReceipt,
+    /// The slots the user has available once the appointment has been added.
+    pub available_slots: u32,
+    /// The block height at which the user subscription expires.
+    pub subscription_expiry: u32,
+    /// Whether the dispute transaction was already on chain when the appointment was accepted.
+    pub dispute_on_chain: bool,
+}
+
+/// The result of adding a batch of appointments to the [Watcher] (check [Watcher::add_appointments]).
+#[derive(Debug)]
+pub struct AddedAppointments {
+    /// The result of each of the appointments, in the same order they were received.
+    pub results: Vec<(Locator, Result<AcceptedAppointment, AddAppointmentFailure>)>,
+    /// The slots the user has available once the batch has been added.
+    pub available_slots: u32,
+    /// The block height at which the user subscription expires.
+    pub subscription_expiry: u32,
+}
+
 /// Packs the reasons why trying to add an appointment may fail.
 // TODO: It may be nice to create richer errors so the API can return richer rejection
 #[derive(Debug)]
-pub(crate) enum AddAppointmentFailure {
+pub enum AddAppointmentFailure {
     AuthenticationFailure,
     NotEnoughSlots,
     SubscriptionExpired(u32),
     AlreadyTriggered,
+    RejectedByPolicy(PolicyViolation),
+    /// The locator was already found earlier in the same batch (check [Watcher::add_appointments]).
+    DuplicateLocator,
 }
 
 /// Packs the reasons why trying to query an appointment may fail.
 #[derive(Debug)]
-pub(crate) enum GetAppointmentFailure {
+pub enum GetAppointmentFailure {
     AuthenticationFailure,
     SubscriptionExpired(u32),
     NotFound,
 }
 
+/// Packs the reasons why trying to query a batched receipt may fail.
+#[derive(Debug)]
+pub enum GetBatchedReceiptFailure {
+    AuthenticationFailure,
+    SubscriptionExpired(u32),
+    BatchingDisabled,
+    Pending,
+    NotFound,
+}
+
+/// Packs the reasons why trying to query the breach log may fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GetBreachLogFailure {
+    Disabled,
+    RateLimited,
+    FutureHeight(u32),
+}
+
 /// Packs the reasons why trying to query a subscription info may fail.
 #[derive(Debug)]
-pub(crate) enum GetSubscriptionInfoFailure {
+pub enum GetSubscriptionInfoFailure {
     AuthenticationFailure,
     SubscriptionExpired(u32),
 }
 
+/// Packs the reasons why trying to export a user may fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExportUserFailure {
+    InvalidConsent,
+    ConsentExpired(u32),
+    NotFound,
+}
+
+/// Packs the reasons why trying to import a user may fail.
+#[derive(Debug, PartialEq)]
+pub enum ImportUserFailure {
+    WrongTower,
+    InvalidConsent,
+    ConsentExpired(u32),
+    AlreadyRegistered,
+    InvalidAppointmentSignature(Locator),
+    RegistrationFailure(RegistrationFailure),
+}
+
 /// Wraps the returning information regarding a queried appointment.
 ///
 /// Either an [Appointment] or a [TransactionTracker] can be
 /// returned depending on whether the appointment can be found in the [Watcher] or in the [Responder].
 #[derive(Debug)]
-pub(crate) enum AppointmentInfo {
+pub enum AppointmentInfo {
     Appointment(Appointment),
     Tracker(TransactionTracker),
 }
 
+/// The tower key being rotated out.
+///
+/// Receipts issued while the overlap window is open are signed by both the current and the previous key, so users
+/// can link both tower identities.
+#[derive(Debug, Clone)]
+pub struct PreviousKey {
+    /// The previous tower signing key.
+    signing_key: SecretKey,
+    /// The previous tower identifier.
+    tower_id: TowerId,
+    /// Height at which the overlap window closes. The previous key is not used from then on.
+    overlap_end: u32,
+}
+
+impl PreviousKey {
+    /// Creates a new [PreviousKey] instance.
+    pub fn new(signing_key: SecretKey, overlap_end: u32) -> Self {
+        PreviousKey {
+            signing_key,
+            tower_id: TowerId(PublicKey::from_secret_key(&Secp256k1::new(), &signing_key)),
+            overlap_end,
+        }
+    }
+
+    /// Gets the previous tower identifier.
+    pub fn tower_id(&self) -> TowerId {
+        self.tower_id
+    }
+
+    /// Gets the height at which the overlap window closes.
+    pub fn overlap_end(&self) -> u32 {
+        self.overlap_end
+    }
+}
+
+/// A subscription receipt re-issued under the current tower key after a key rotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReissuedReceipt {
+    /// The subscription terms, signed and attested by the current key.
+    pub receipt: RegistrationReceipt,
+    /// The signature of the same terms by the previous key.
+    pub previous_signature: String,
+}
+
 /// Reason why the appointment is deleted. Used for logging purposes.
 enum DeletionReason {
     Outdated,
@@ -89,6 +233,17 @@ enum DeletionReason {
     Accepted,
 }
 
+impl DeletionReason {
+    /// The [AppointmentState] the appointment ends up in when deleted for this reason.
+    fn state(&self) -> AppointmentState {
+        match self {
+            DeletionReason::Outdated => AppointmentState::Outdated,
+            DeletionReason::Invalid => AppointmentState::Rejected,
+            DeletionReason::Accepted => AppointmentState::Triggered,
+        }
+    }
+}
+
 /// Types of new appointments stored in the [Watcher].
 #[derive(Debug, PartialEq, Eq)]
 enum StoredAppointment {
@@ -105,9 +260,20 @@ enum TriggeredAppointment {
     Invalid,
 }
 
+/// Settings a [Watcher] is created with.
+#[derive(Debug, Default)]
+pub struct WatcherSettings {
+    /// The key the tower is rotating out of, if any.
+    pub previous_key: Option<PreviousKey>,
+    /// The set of policies every appointment must comply with to be accepted.
+    pub policies: PolicySet,
+    /// Whether receipts are signed in batches (one per block) instead of one by one.
+    pub batch_receipts: bool,
+}
+
 /// Component in charge of watching for triggers in the chain (aka channel breaches for lightning).
 #[derive(Debug)]
-pub struct Watcher {
+pub struct Watcher<S = DBM> {
     /// A map holding a summary of every appointment ([ExtendedAppointment]) hold by the [Watcher], identified by a [UUID].
     appointments: Mutex<HashMap<UUID, AppointmentSummary>>,
     /// A map between [Locator]s (user identifiers for [Appointment]s) and [UUID]s (tower identifiers).
@@ -115,29 +281,39 @@ pub struct Watcher {
     /// A cache of the [Locator]s computed for the transactions in the last few blocks.
     locator_cache: Mutex<TxIndex<Locator, Transaction>>,
     /// A [Responder] instance. Data will be passed to it once triggered (if valid).
-    responder: Arc<Responder>,
+    responder: Arc<Responder<S>>,
     /// A [Gatekeeper] instance. Data regarding users is requested to it.
-    gatekeeper: Arc<Gatekeeper>,
+    gatekeeper: Arc<Gatekeeper<S>>,
     /// The last known block height.
     last_known_block_height: AtomicU32,
     /// The tower signing key. Used to sign messages going to users.
     signing_key: SecretKey,
     /// The tower identifier.
     pub tower_id: TowerId,
-    /// A [DBM] (database manager) instance. Used to persist appointment data into disk.
-    dbm: Arc<Mutex<DBM>>,
+    /// The key the tower is rotating out of, if any.
+    previous_key: Option<PreviousKey>,
+    /// Subscription receipts re-issued under the current key after a key rotation. Only served during the overlap window.
+    reissued_receipts: Mutex<HashMap<UserId, ReissuedReceipt>>,
+    /// The set of policies every appointment must comply with to be accepted.
+    policies: PolicySet,
+    /// A [ReceiptBatcher] instance, only present if receipts are signed in batches instead of one by one.
+    receipt_batcher: Option<Mutex<ReceiptBatcher>>,
+    /// Number of breach log requests served to each client since the last block was connected.
+    breach_log_requests: Mutex<HashMap<Option<IpAddr>, u32>>,
+    /// The [Storage] backend (a [DBM] by default). Used to persist appointment data.
+    dbm: Arc<Mutex<S>>,
 }
 
-impl Watcher {
-    /// Creates a new [Watcher] instance.
+impl<S: Storage> Watcher<S> {
+    /// Creates a new [Watcher] instance. The tower identifier is derived from its `signing_key`.
     pub fn new(
-        gatekeeper: Arc<Gatekeeper>,
-        responder: Arc<Responder>,
+        gatekeeper: Arc<Gatekeeper<S>>,
+        responder: Arc<Responder<S>>,
         last_n_blocks: &[ValidatedBlock],
         last_known_block_height: u32,
         signing_key: SecretKey,
-        tower_id: TowerId,
-        dbm: Arc<Mutex<DBM>>,
+        settings: WatcherSettings,
+        dbm: Arc<Mutex<S>>,
     ) -> Self {
         let mut appointments = HashMap::new();
         let mut locator_uuid_map: HashMap<Locator, HashSet<UUID>> = HashMap::new();
@@ -151,6 +327,16 @@ impl Watcher {
             }
         }
 
+        // Batches are rebuilt from the stored receipts. The ones issued before the last known block are closed straightaway
+        let receipt_batcher = settings.batch_receipts.then(|| {
+            let mut batcher = ReceiptBatcher::new();
+            for (uuid, receipt) in dbm.lock().unwrap().load_batch_receipts() {
+                batcher.add_receipt(uuid, receipt);
+            }
+            batcher.close_batches(last_known_block_height, &signing_key);
+            Mutex::new(batcher)
+        });
+
         Watcher {
             appointments: Mutex::new(appointments),
             locator_uuid_map: Mutex::new(locator_uuid_map),
@@ -159,7 +345,12 @@ impl Watcher {
             gatekeeper,
             last_known_block_height: AtomicU32::new(last_known_block_height),
             signing_key,
-            tower_id,
+            tower_id: TowerId(PublicKey::from_secret_key(&Secp256k1::new(), &signing_key)),
+            previous_key: settings.previous_key,
+            reissued_receipts: Mutex::new(HashMap::new()),
+            policies: settings.policies,
+            receipt_batcher,
+            breach_log_requests: Mutex::new(HashMap::new()),
             dbm,
         }
     }
@@ -171,30 +362,120 @@ impl Watcher {
 
     /// Registers a new user within the [Watcher]. This request is passed to the [Gatekeeper], who is in
     /// charge of managing users.
-    pub(crate) fn register(&self, user_id: UserId) -> Result<RegistrationReceipt, MaxSlotsReached> {
+    ///
+    /// The returned receipt is signed and attested by the tower at the current block height.
+    pub fn register(&self, user_id: UserId) -> Result<RegistrationReceipt, RegistrationFailure> {
         let mut receipt = self.gatekeeper.add_update_user(user_id)?;
         receipt.sign(&self.signing_key);
+        receipt.attest(
+            &self.signing_key,
+            self.last_known_block_height.load(Ordering::Acquire),
+        );
+        // The new receipt supersedes any receipt re-issued on key rotation
+        self.reissued_receipts.lock().unwrap().remove(&user_id);
 
         Ok(receipt)
     }
 
+    /// Gets the key the tower is rotating out of, provided the overlap window is still open.
+    pub fn get_previous_key(&self) -> Option<&PreviousKey> {
+        self.previous_key
+            .as_ref()
+            .filter(|key| self.last_known_block_height.load(Ordering::Acquire) < key.overlap_end)
+    }
+
+    /// Signs a message with the key the tower is rotating out of, so receipts issued during the overlap window carry
+    /// both signatures.
+    ///
+    /// Returns [None] if the tower is not rotating its key (or the overlap window is closed).
+    pub fn sign_with_previous_key(&self, message: &[u8]) -> Option<String> {
+        self.get_previous_key()
+            .map(|key| cryptography::sign(message, &key.signing_key).unwrap())
+    }
+
+    /// Re-issues the subscription receipts of all the active users under the current key after a key rotation.
+    ///
+    /// Receipts are signed and attested at the current height by the current key, and signed by the previous key as
+    /// well, so users can move their trust from one tower identity to the other. They are served alongside the
+    /// subscription info until the overlap window closes. Returns the number of re-issued receipts.
+    pub fn reattest_subscriptions(&self) -> usize {
+        let previous_key = match self.get_previous_key() {
+            Some(key) => key,
+            None => return 0,
+        };
+        let height = self.last_known_block_height.load(Ordering::Acquire);
+
+        let mut reissued_receipts = HashMap::new();
+        for user_id in self.gatekeeper.get_user_ids() {
+            let user_info = match self.gatekeeper.get_user_info(user_id) {
+                Some(user_info) if user_info.subscription_expiry > height => user_info,
+                _ => continue,
+            };
+
+            let mut receipt = RegistrationReceipt::new(
+                user_id,
+                user_info.available_slots,
+                user_info.subscription_start,
+                user_info.subscription_expiry,
+            );
+            receipt.sign(&self.signing_key);
+            receipt.attest(&self.signing_key, height);
+            let previous_signature =
+                cryptography::sign(&receipt.to_vec(), &previous_key.signing_key).unwrap();
+
+            reissued_receipts.insert(
+                user_id,
+                ReissuedReceipt {
+                    receipt,
+                    previous_signature,
+                },
+            );
+        }
+
+        let n = reissued_receipts.len();
+        *self.reissued_receipts.lock().unwrap() = reissued_receipts;
+        n
+    }
+
     /// Adds a new [Appointment] to the tower.
     ///
     /// Appointments are only added provided:
     /// - The user is registered into the system
     /// - The user subscription has not expired
+    /// - The appointment complies with the tower acceptance policies
     /// - The user has enough available slots to fit the appointment
     /// - The appointment hasn't been responded to yet (data cannot be found in the [Responder])
     ///
     /// If an appointment is accepted, an [AppointmentSummary] will be added to the the watching pool and
     /// monitored by the [Watcher]. An [ExtendedAppointment] (constructed from the [Appointment]) will be persisted on disk.
     /// In case the locator for the given appointment can be found in the cache (meaning the appointment has been
-    /// triggered recently) the data will be passed to the [Responder] straightaway (modulo it being valid).
-    pub(crate) fn add_appointment(
+    /// triggered recently) the data will be passed to the [Responder] straightaway (modulo it being valid), instead of
+    /// being added to the watching pool. Whether this was the case is flagged alongside the receipt, so users can be told
+    /// the dispute is already on chain.
+    ///
+    /// If receipt batching is enabled and the user opted in (`batch_receipt`), the returned receipt is not signed. Its
+    /// signature will be available as a [BatchedAppointmentReceipt] once the next block is connected (check
+    /// [Watcher::get_batched_receipt]). Otherwise, the receipt is signed straightaway.
+    pub fn add_appointment(
+        &self,
+        appointment: Appointment,
+        user_signature: String,
+        batch_receipt: bool,
+    ) -> Result<AddedAppointment, AddAppointmentFailure> {
+        self.add_appointment_with_dispute(appointment, user_signature, batch_receipt, None)
+    }
+
+    /// Adds a new [Appointment] to the [Watcher] the same way [Watcher::add_appointment] does, but lets the caller
+    /// provide the transaction that triggered it (if any), instead of relying on the locator cache only.
+    ///
+    /// This is used to import appointments that had already been triggered in the tower they are imported from.
+    fn add_appointment_with_dispute(
         &self,
         appointment: Appointment,
         user_signature: String,
-    ) -> Result<(AppointmentReceipt, u32, u32), AddAppointmentFailure> {
+        batch_receipt: bool,
+        dispute_tx: Option<Transaction>,
+    ) -> Result<AddedAppointment, AddAppointmentFailure> {
         let user_id = self
             .gatekeeper
             .authenticate_user(&appointment.to_vec(), &user_signature)
@@ -207,6 +488,18 @@ impl Watcher {
             return Err(AddAppointmentFailure::SubscriptionExpired(expiry));
         }
 
+        let user = PolicyUser {
+            user_id,
+            granted_slots: self
+                .gatekeeper
+                .get_granted_slots(user_id)
+                .unwrap_or_default(),
+        };
+        self.policies.check(&user, &appointment).map_err(|e| {
+            log::info!("Appointment rejected by policy: {}", e);
+            AddAppointmentFailure::RejectedByPolicy(e)
+        })?;
+
         let extended_appointment = ExtendedAppointment::new(
             appointment,
             user_id,
@@ -215,6 +508,7 @@ impl Watcher {
         );
 
         let uuid = UUID::new(extended_appointment.locator(), user_id);
+        let _span = telemetry::appointment_span(uuid).entered();
 
         if self.responder.has_tracker(uuid) {
             log::info!("Tracker for {} already found in Responder", uuid);
@@ -225,24 +519,150 @@ impl Watcher {
             .gatekeeper
             .add_update_appointment(user_id, uuid, &extended_appointment)
             .map_err(|_| AddAppointmentFailure::NotEnoughSlots)?;
+        let accepted =
+            self.accept_appointment(uuid, extended_appointment, batch_receipt, dispute_tx);
+
+        Ok(AddedAppointment {
+            receipt: accepted.receipt,
+            available_slots,
+            subscription_expiry: expiry,
+            dispute_on_chain: accepted.dispute_on_chain,
+        })
+    }
+
+    /// Adds a batch of appointments (belonging to the given user) to the [Watcher].
+    ///
+    /// The user must be registered and have an active subscription, and there must be enough slots available to fit all
+    /// the valid appointments of the batch, otherwise the whole batch is rejected. Each appointment is then checked
+    /// individually (signature, policies, and whether it has already been triggered), and accepted the same way it would
+    /// be by [Watcher::add_appointment]. Only the first appointment for each locator is taken into account, later ones
+    /// within the same batch are rejected.
+    ///
+    /// Returns the result of each of the appointments (in the same order they were received), alongside the updated
+    /// available slots and subscription expiry.
+    pub fn add_appointments(
+        &self,
+        user_id: UserId,
+        appointments: Vec<(Appointment, String)>,
+        batch_receipts: bool,
+    ) -> Result<AddedAppointments, AddAppointmentFailure> {
+        let (has_subscription_expired, expiry) = self
+            .gatekeeper
+            .has_subscription_expired(user_id)
+            .map_err(|_| AddAppointmentFailure::AuthenticationFailure)?;
+
+        if has_subscription_expired {
+            return Err(AddAppointmentFailure::SubscriptionExpired(expiry));
+        }
+
+        let user = PolicyUser {
+            user_id,
+            granted_slots: self
+                .gatekeeper
+                .get_granted_slots(user_id)
+                .unwrap_or_default(),
+        };
+        let start_block = self.last_known_block_height.load(Ordering::Acquire);
+        let mut locators = HashSet::new();
+        let checked: Vec<_> = appointments
+            .into_iter()
+            .map(|(appointment, user_signature)| {
+                let locator = appointment.locator;
+                if !locators.insert(locator) {
+                    return (locator, Err(AddAppointmentFailure::DuplicateLocator));
+                }
+                if !cryptography::verify(&appointment.to_vec(), &user_signature, &user_id.0) {
+                    return (locator, Err(AddAppointmentFailure::AuthenticationFailure));
+                }
+                if let Err(e) = self.policies.check(&user, &appointment) {
+                    log::info!("Appointment rejected by policy: {}", e);
+                    return (locator, Err(AddAppointmentFailure::RejectedByPolicy(e)));
+                }
+
+                let uuid = UUID::new(locator, user_id);
+                if self.responder.has_tracker(uuid) {
+                    log::info!("Tracker for {} already found in Responder", uuid);
+                    return (locator, Err(AddAppointmentFailure::AlreadyTriggered));
+                }
+
+                let extended_appointment =
+                    ExtendedAppointment::new(appointment, user_id, user_signature, start_block);
+                (locator, Ok((uuid, extended_appointment)))
+            })
+            .collect();
+
+        let valid: Vec<_> = checked
+            .iter()
+            .filter_map(|(_, r)| r.as_ref().ok().map(|(uuid, a)| (*uuid, a)))
+            .collect();
+        let available_slots = self
+            .gatekeeper
+            .add_update_appointments(user_id, &valid)
+            .map_err(|_| AddAppointmentFailure::NotEnoughSlots)?;
+
+        let results = checked
+            .into_iter()
+            .map(|(locator, r)| {
+                (
+                    locator,
+                    r.map(|(uuid, extended_appointment)| {
+                        let _span = telemetry::appointment_span(uuid).entered();
+                        self.accept_appointment(uuid, extended_appointment, batch_receipts, None)
+                    }),
+                )
+            })
+            .collect();
+
+        Ok(AddedAppointments {
+            results,
+            available_slots,
+            subscription_expiry: expiry,
+        })
+    }
+
+    /// Stores an appointment that has already been accepted (slots have been filled for it) and builds its receipt.
+    ///
+    /// The appointment is handed to the [Responder] straightaway if it has already been triggered, either by the given
+    /// `dispute_tx` or by a transaction found in the locator cache.
+    ///
+    /// The receipt is added to the current batch if receipt batching is enabled and `batch_receipt` is set, otherwise it
+    /// is signed straightaway.
+    fn accept_appointment(
+        &self,
+        uuid: UUID,
+        extended_appointment: ExtendedAppointment,
+        batch_receipt: bool,
+        dispute_tx: Option<Transaction>,
+    ) -> AcceptedAppointment {
+        let user_id = extended_appointment.user_id;
+        self.dbm.lock().unwrap().update_appointment_state(
+            uuid,
+            user_id,
+            AppointmentState::Accepted,
+            extended_appointment.start_block,
+        );
 
         // FIXME: There's an edge case here if store_triggered_appointment is called and bitcoind is unreachable.
         // This will hang, the request will timeout but be accepted. However, the user will not be handed the receipt.
         // This could be fixed adding a thread to take care of storing while the main thread returns the receipt.
         // Not fixing this atm since working with threads that call self.method is surprisingly non-trivial.
-        match self
-            .locator_cache
-            .lock()
-            .unwrap()
-            .get(&extended_appointment.locator())
-        {
-            // Appointments that were triggered in blocks held in the cache
+        let dispute_tx = dispute_tx.or_else(|| {
+            self.locator_cache
+                .lock()
+                .unwrap()
+                .get(&extended_appointment.locator())
+                .cloned()
+        });
+        let dispute_on_chain = match dispute_tx {
+            // Appointments that were triggered in blocks held in the cache (or that are known to have been triggered)
             Some(dispute_tx) => {
-                self.store_triggered_appointment(uuid, &extended_appointment, user_id, dispute_tx);
+                self.store_triggered_appointment(uuid, &extended_appointment, user_id, &dispute_tx);
+                true
             }
             // Regular appointments that have not been triggered (or, at least, not recently)
             None => {
                 self.store_appointment(uuid, &extended_appointment);
+                false
             }
         };
 
@@ -250,9 +670,21 @@ impl Watcher {
             extended_appointment.user_signature,
             extended_appointment.start_block,
         );
-        receipt.sign(&self.signing_key);
+        match &self.receipt_batcher {
+            Some(batcher) if batch_receipt => {
+                // Receipts are persisted so they can still be signed if the tower restarts before the batch is closed
+                if let Err(e) = self.dbm.lock().unwrap().store_batch_receipt(uuid, &receipt) {
+                    log::error!("Couldn't store batch receipt: {}. Error: {:?}", uuid, e);
+                }
+                batcher.lock().unwrap().add_receipt(uuid, receipt.clone());
+            }
+            _ => receipt.sign(&self.signing_key),
+        }
 
-        Ok((receipt, available_slots, expiry))
+        AcceptedAppointment {
+            receipt,
+            dispute_on_chain,
+        }
     }
 
     /// Stores an appointment in the [Watcher] memory and into the database (or updates it if it already exists).
@@ -268,7 +700,7 @@ impl Watcher {
             .unwrap()
             .insert(uuid, appointment.get_summary());
         let mut locator_uuid_map = self.locator_uuid_map.lock().unwrap();
-        if let Entry::Vacant(e) = locator_uuid_map.entry(appointment.locator()) {
+        let stored = if let Entry::Vacant(e) = locator_uuid_map.entry(appointment.locator()) {
             // New appointment
             e.insert(HashSet::from_iter(vec![uuid]));
 
@@ -304,7 +736,16 @@ impl Watcher {
                     .update_appointment(uuid, appointment);
                 StoredAppointment::Update
             }
-        }
+        };
+
+        self.dbm.lock().unwrap().update_appointment_state(
+            uuid,
+            appointment.user_id,
+            AppointmentState::Watched,
+            appointment.start_block,
+        );
+
+        stored
     }
 
     /// Stores and already triggered appointment in the database and hands it to the [Responder].
@@ -341,7 +782,12 @@ impl Watcher {
                     // Keeping it for now.
                     log::warn!("Appointment bounced in the Responder. Reason: {:?}", reason);
 
-                    self.dbm.lock().unwrap().remove_appointment(uuid);
+                    self.dbm.lock().unwrap().batch_remove_appointments(
+                        &HashSet::from_iter([uuid]),
+                        &HashMap::new(),
+                        AppointmentState::Rejected,
+                        appointment.start_block,
+                    );
                     TriggeredAppointment::Rejected
                 } else {
                     log::info!("Appointment went straight to the Responder");
@@ -358,6 +804,12 @@ impl Watcher {
                     "The appointment contained invalid data {}",
                     appointment.locator()
                 );
+                self.dbm.lock().unwrap().update_appointment_state(
+                    uuid,
+                    user_id,
+                    AppointmentState::Rejected,
+                    appointment.start_block,
+                );
                 TriggeredAppointment::Invalid
             }
         }
@@ -370,16 +822,19 @@ impl Watcher {
     /// - The user subscription has not expired
     /// - The appointment belongs to the user
     /// - The appointment exists within the system (either in the [Watcher] or the [Responder])
-    pub(crate) fn get_appointment(
+    ///
+    /// The user subscription expiry is returned alongside the appointment data.
+    pub fn get_appointment(
         &self,
         locator: Locator,
         user_signature: &str,
-    ) -> Result<AppointmentInfo, GetAppointmentFailure> {
+        challenge: Option<&[u8]>,
+    ) -> Result<(AppointmentInfo, u32), GetAppointmentFailure> {
         let message = format!("get appointment {}", locator);
 
         let user_id = self
             .gatekeeper
-            .authenticate_user(message.as_bytes(), user_signature)
+            .authenticate_user_with_challenge(message.as_bytes(), user_signature, challenge)
             .map_err(|_| GetAppointmentFailure::AuthenticationFailure)?;
 
         let (has_subscription_expired, expiry) =
@@ -391,26 +846,121 @@ impl Watcher {
 
         let uuid = UUID::new(locator, user_id);
 
-        if self.appointments.lock().unwrap().contains_key(&uuid) {
-            Ok(AppointmentInfo::Appointment(
-                self.dbm
-                    .lock()
-                    .unwrap()
-                    .load_appointment(uuid)
-                    .unwrap()
-                    .inner,
-            ))
-        } else {
-            self.responder
+        // The appointment state tells where the data can be found (if anywhere)
+        let state = self.dbm.lock().unwrap().load_appointment_state(uuid);
+        let info = match state {
+            AppointmentState::Watched => self
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(uuid)
+                .ok()
+                .map(|appointment| AppointmentInfo::Appointment(appointment.inner)),
+            AppointmentState::Triggered => self
+                .responder
                 .get_tracker(uuid)
-                .map(AppointmentInfo::Tracker)
-                .ok_or_else(|| {
-                    log::info!("Cannot find {}", locator);
-                    GetAppointmentFailure::NotFound
-                })
+                .map(AppointmentInfo::Tracker),
+            _ => None,
+        };
+
+        info.map(|info| (info, expiry)).ok_or_else(|| {
+            log::info!("Cannot find {} (state: {})", locator, state);
+            GetAppointmentFailure::NotFound
+        })
+    }
+
+    /// Gets the [BatchedAppointmentReceipt] of an appointment identified by a given [Locator].
+    ///
+    /// Batched receipts are only available if receipt batching is enabled, and once the batch the receipt belongs
+    /// to has been closed. The same checks as for [Watcher::get_appointment] apply.
+    pub fn get_batched_receipt(
+        &self,
+        locator: Locator,
+        user_signature: &str,
+    ) -> Result<BatchedAppointmentReceipt, GetBatchedReceiptFailure> {
+        let message = format!("get batched receipt {}", locator);
+
+        let user_id = self
+            .gatekeeper
+            .authenticate_user(message.as_bytes(), user_signature)
+            .map_err(|_| GetBatchedReceiptFailure::AuthenticationFailure)?;
+
+        let (has_subscription_expired, expiry) =
+            self.gatekeeper.has_subscription_expired(user_id).unwrap();
+
+        if has_subscription_expired {
+            return Err(GetBatchedReceiptFailure::SubscriptionExpired(expiry));
+        }
+
+        let batcher = self
+            .receipt_batcher
+            .as_ref()
+            .ok_or(GetBatchedReceiptFailure::BatchingDisabled)?
+            .lock()
+            .unwrap();
+
+        // If an update is waiting to be batched, the previous batched receipt (if any) is already outdated
+        let uuid = UUID::new(locator, user_id);
+        if batcher.is_pending(uuid) {
+            Err(GetBatchedReceiptFailure::Pending)
+        } else {
+            batcher
+                .get_batched_receipt(uuid)
+                .ok_or(GetBatchedReceiptFailure::NotFound)
         }
     }
 
+    /// Gets a page of the tower [BreachLog], starting at `from_height`, on behalf of a given `client`.
+    ///
+    /// Breaches are logged once their penalty is irrevocably resolved, so only blocks at least
+    /// [IRREVOCABLY_RESOLVED](constants::IRREVOCABLY_RESOLVED) deep can be queried (pages covering more recent blocks
+    /// could still change). The page covers up to [BREACH_LOG_MAX_BLOCKS] blocks, or up to the last final block if
+    /// closer, and is signed by the tower. The log is public, so the number of requests served per client and block is
+    /// capped.
+    pub fn get_breach_log(
+        &self,
+        from_height: u32,
+        client: Option<IpAddr>,
+    ) -> Result<BreachLog, GetBreachLogFailure> {
+        if !self.responder.is_breach_log_enabled() {
+            return Err(GetBreachLogFailure::Disabled);
+        }
+
+        let final_height = self
+            .last_known_block_height
+            .load(Ordering::Acquire)
+            .saturating_sub(constants::IRREVOCABLY_RESOLVED);
+        if from_height > final_height {
+            return Err(GetBreachLogFailure::FutureHeight(final_height));
+        }
+
+        {
+            let mut requests = self.breach_log_requests.lock().unwrap();
+            if requests.len() >= MAX_BREACH_LOG_CLIENTS_PER_BLOCK && !requests.contains_key(&client)
+            {
+                return Err(GetBreachLogFailure::RateLimited);
+            }
+            let count = requests.entry(client).or_insert(0);
+            if *count >= MAX_BREACH_LOG_REQUESTS_PER_BLOCK {
+                return Err(GetBreachLogFailure::RateLimited);
+            }
+            *count += 1;
+        }
+
+        let to_height = final_height.min(from_height.saturating_add(BREACH_LOG_MAX_BLOCKS - 1));
+        let mut log = BreachLog::new(
+            from_height,
+            to_height,
+            self.dbm
+                .lock()
+                .unwrap()
+                .load_breach_log(from_height, to_height),
+        );
+        log.sign(&self.signing_key);
+
+        Ok(log)
+    }
+
     /// Gets a map of breaches provided a map between locators and transactions.
     ///
     /// The provided map if intersected with the map of all locators monitored by [Watcher] and the result
@@ -544,43 +1094,67 @@ impl Watcher {
     }
 
     /// Deletes appointments from memory and the database.
+    ///
+    /// The appointments are moved to the state matching the deletion reason at the given height.
     fn delete_appointments(
         &self,
         uuids: &HashSet<UUID>,
         updated_users: &HashMap<UserId, UserInfo>,
         reason: DeletionReason,
+        height: u32,
     ) {
         if !uuids.is_empty() {
+            let state = reason.state();
             self.delete_appointments_from_memory(uuids, reason);
             self.dbm
                 .lock()
                 .unwrap()
-                .batch_remove_appointments(uuids, updated_users);
+                .batch_remove_appointments(uuids, updated_users, state, height);
         }
     }
 
+    /// Gets the height of the last block processed by the [Watcher].
+    pub fn get_last_known_block_height(&self) -> u32 {
+        self.last_known_block_height.load(Ordering::Acquire)
+    }
+
     /// Ges the number of users currently registered with the tower.
-    pub(crate) fn get_registered_users_count(&self) -> usize {
+    pub fn get_registered_users_count(&self) -> usize {
         self.gatekeeper.get_registered_users_count()
     }
 
     /// Gets the total number of appointments stored in the [Watcher].
-    pub(crate) fn get_appointments_count(&self) -> usize {
+    pub fn get_appointments_count(&self) -> usize {
         self.appointments.lock().unwrap().len()
     }
 
+    /// Gets the number of blocks currently held by the [LocatorCache].
+    pub fn get_locator_cache_depth(&self) -> usize {
+        self.locator_cache.lock().unwrap().depth()
+    }
+
+    /// Gets the maximum number of blocks the [LocatorCache] can hold.
+    pub fn get_locator_cache_size(&self) -> usize {
+        self.locator_cache.lock().unwrap().size()
+    }
+
     /// Gets the total number of trackers in the [Responder].
-    pub(crate) fn get_trackers_count(&self) -> usize {
+    pub fn get_trackers_count(&self) -> usize {
         self.responder.get_trackers_count()
     }
 
+    /// Gets the number of trackers in the [Responder] whose penalty is below the mempool min fee.
+    pub fn get_low_fee_trackers_count(&self) -> usize {
+        self.responder.get_low_fee_trackers_count()
+    }
+
     /// Gets all the appointments stored in the [Watcher] (from the database).
-    pub(crate) fn get_all_watcher_appointments(&self) -> HashMap<UUID, ExtendedAppointment> {
+    pub fn get_all_watcher_appointments(&self) -> HashMap<UUID, ExtendedAppointment> {
         self.dbm.lock().unwrap().load_appointments(None)
     }
 
     /// Gets all the appointments matching a specific locator from the [Watcher] (from the database).
-    pub(crate) fn get_watcher_appointments_with_locator(
+    pub fn get_watcher_appointments_with_locator(
         &self,
         locator: Locator,
     ) -> HashMap<UUID, ExtendedAppointment> {
@@ -588,12 +1162,12 @@ impl Watcher {
     }
 
     /// Gets all the trackers stored in the [Responder] (from the database).
-    pub(crate) fn get_all_responder_trackers(&self) -> HashMap<UUID, TransactionTracker> {
+    pub fn get_all_responder_trackers(&self) -> HashMap<UUID, TransactionTracker> {
         self.dbm.lock().unwrap().load_trackers(None)
     }
 
     /// Gets all the trackers matching s specific locator from the [Responder] (from the database).
-    pub(crate) fn get_responder_trackers_with_locator(
+    pub fn get_responder_trackers_with_locator(
         &self,
         locator: Locator,
     ) -> HashMap<UUID, TransactionTracker> {
@@ -601,25 +1175,249 @@ impl Watcher {
     }
 
     /// Gets the list of all registered user ids.
-    pub(crate) fn get_user_ids(&self) -> Vec<UserId> {
+    pub fn get_user_ids(&self) -> Vec<UserId> {
         self.gatekeeper.get_user_ids()
     }
 
+    /// Gets the price of new subscriptions. Data is requested to the [Gatekeeper].
+    pub fn get_subscription_pricing(&self) -> SubscriptionPricing {
+        self.gatekeeper.get_pricing()
+    }
+
+    /// Gets the slots and duration (in blocks) each registration is granted. Data is requested to the [Gatekeeper].
+    pub fn get_subscription_terms(&self) -> (u32, u32) {
+        self.gatekeeper.get_subscription_terms()
+    }
+
+    /// Issues an invoice for the registration of a given user. Calls [Gatekeeper::issue_invoice].
+    pub fn get_registration_invoice(
+        &self,
+        user_id: UserId,
+    ) -> Result<RegistrationInvoice, InvoiceFailure> {
+        self.gatekeeper.issue_invoice(user_id)
+    }
+
+    /// Gets the preimage of a pending registration invoice. Calls [Gatekeeper::get_invoice_preimage].
+    pub fn get_invoice_preimage(&self, payment_hash: &sha256::Hash) -> Option<Vec<u8>> {
+        self.gatekeeper.get_invoice_preimage(payment_hash)
+    }
+
+    /// Checks a registration has been paid for given an invoice preimage. Calls [Gatekeeper::check_payment].
+    pub fn check_payment(&self, user_id: UserId, preimage: &[u8]) -> Result<(), PaymentFailure> {
+        self.gatekeeper.check_payment(user_id, preimage)
+    }
+
+    /// Gets the number of appointments in each [AppointmentState] (from the database).
+    pub fn get_appointment_state_counts(&self) -> HashMap<AppointmentState, usize> {
+        self.dbm.lock().unwrap().load_appointment_state_counts()
+    }
+
+    /// Gets the grace period given to users to renew their subscriptions, in blocks.
+    pub fn get_expiry_delta(&self) -> u32 {
+        self.gatekeeper.get_expiry_delta()
+    }
+
+    /// Gets the number of blocks the tower waits after a breach is detected before broadcasting its penalty.
+    pub fn get_broadcast_delay(&self) -> u32 {
+        self.responder.get_broadcast_delay()
+    }
+
+    /// Gets the maximum number of users accepted by the tower (zero means no limit).
+    pub fn get_max_users(&self) -> u32 {
+        self.gatekeeper.get_max_users()
+    }
+
+    /// Returns whether the tower is accepting new users.
+    pub fn is_accepting_registrations(&self) -> bool {
+        self.gatekeeper.is_accepting_registrations()
+    }
+
+    /// Rebroadcasts the penalty of a given tracker held by the [Responder] right away.
+    pub fn rebroadcast_tracker(
+        &self,
+        uuid: UUID,
+    ) -> Result<ConfirmationStatus, TrackerActionFailure> {
+        self.responder.rebroadcast_tracker(uuid)
+    }
+
+    /// Gets the endpoints that accepted the penalty of a given tracker. Calls [Responder::get_accepted_by].
+    pub fn get_penalty_accepted_by(&self, uuid: UUID) -> Vec<String> {
+        self.responder.get_accepted_by(uuid)
+    }
+
+    /// Abandons a given tracker held by the [Responder], so no further response is performed for it.
+    pub fn abandon_tracker(&self, uuid: UUID, reason: &str) -> Result<(), TrackerActionFailure> {
+        self.responder.abandon_tracker(uuid, reason)
+    }
+
+    /// Gets the trackers held by the [Responder] that are pending review (their penalty can never confirm), alongside
+    /// the height they were flagged at.
+    pub fn get_review_queue(&self) -> Vec<(UUID, TransactionTracker, u32)> {
+        self.responder.get_review_queue()
+    }
+
+    /// Gets the number of trackers held by the [Responder] that are pending review.
+    pub fn get_trackers_under_review_count(&self) -> usize {
+        self.responder.get_trackers_under_review_count()
+    }
+
+    /// Gets the number of blocks trackers are kept under review before being garbage collected.
+    pub fn get_review_timeout(&self) -> u32 {
+        self.responder.get_review_timeout()
+    }
+
+    /// Acknowledges a given tracker under review held by the [Responder], so it is garbage collected straightaway.
+    pub fn acknowledge_tracker(&self, uuid: UUID) -> Result<(), TrackerActionFailure> {
+        self.responder.acknowledge_tracker(uuid)
+    }
+
+    /// Exports the appointments held for a given user, so they can be imported by another tower.
+    ///
+    /// Data is only exported provided the user has consented to move it to the destination tower, and the consent has
+    /// not expired. Appointments are exported alongside their user signatures, so the destination tower can check they
+    /// were sent by the user. Appointments that have already been triggered (i.e. whose trackers are held by the
+    /// [Responder]) are exported alongside their dispute transaction, so the destination tower can keep responding to
+    /// the breach. Notice the data is not deleted from this tower, which keeps watching until the user subscription expires.
+    #[allow(clippy::type_complexity)]
+    pub fn export_user(
+        &self,
+        consent: &MigrationConsent,
+    ) -> Result<Vec<(Appointment, String, Option<Transaction>)>, ExportUserFailure> {
+        if !consent.verify() {
+            return Err(ExportUserFailure::InvalidConsent);
+        }
+        if consent.is_expired(self.last_known_block_height.load(Ordering::Acquire)) {
+            return Err(ExportUserFailure::ConsentExpired(consent.expiry()));
+        }
+
+        let user_id = consent.user_id();
+        let user_info = self
+            .gatekeeper
+            .get_user_info(user_id)
+            .ok_or(ExportUserFailure::NotFound)?;
+
+        let (watched, triggered): (Vec<UUID>, Vec<UUID>) = {
+            let appointments = self.appointments.lock().unwrap();
+            user_info
+                .appointments
+                .keys()
+                .cloned()
+                .partition(|uuid| appointments.contains_key(uuid))
+        };
+        let held: Vec<(UUID, Option<Transaction>)> = watched
+            .into_iter()
+            .map(|uuid| (uuid, None))
+            .chain(triggered.into_iter().filter_map(|uuid| {
+                self.responder
+                    .get_tracker(uuid)
+                    .map(|tracker| (uuid, Some(tracker.dispute_tx)))
+            }))
+            .collect();
+
+        let dbm = self.dbm.lock().unwrap();
+        let exported: Vec<(Appointment, String, Option<Transaction>)> = held
+            .into_iter()
+            .filter_map(|(uuid, dispute_tx)| {
+                dbm.load_appointment(uuid)
+                    .ok()
+                    .map(|appointment| (appointment.inner, appointment.user_signature, dispute_tx))
+            })
+            .collect();
+
+        log::warn!(target: telemetry::AUDIT_TARGET, "Operator exported {} appointments of user {} to tower {}", exported.len(), user_id, consent.tower_id());
+        Ok(exported)
+    }
+
+    /// Imports the appointments of a user exported by another tower (check [Watcher::export_user]).
+    ///
+    /// Data is only imported provided the user has consented to move it to this tower, the consent has not expired, and
+    /// all the appointments are signed by the user. Users can only be imported once, so users that are already
+    /// registered with the tower are rejected (otherwise every import would grant them a fresh subscription). The user
+    /// is given a fresh subscription and the appointments are then added as if they had been sent by the user (handing
+    /// the ones that have already been triggered to the [Responder]). Appointments that cannot be added (e.g. for not
+    /// complying with the tower policies, for not fitting in the subscription, or for not matching their dispute
+    /// transaction) are skipped.
+    ///
+    /// Returns the number of imported and skipped appointments, alongside the resulting user subscription.
+    pub fn import_user(
+        &self,
+        consent: &MigrationConsent,
+        appointments: Vec<(Appointment, String, Option<Transaction>)>,
+    ) -> Result<(usize, usize, UserInfo), ImportUserFailure> {
+        if consent.tower_id() != self.tower_id {
+            return Err(ImportUserFailure::WrongTower);
+        }
+        if !consent.verify() {
+            return Err(ImportUserFailure::InvalidConsent);
+        }
+        if consent.is_expired(self.last_known_block_height.load(Ordering::Acquire)) {
+            return Err(ImportUserFailure::ConsentExpired(consent.expiry()));
+        }
+
+        let user_id = consent.user_id();
+        if self.gatekeeper.get_user_info(user_id).is_some() {
+            return Err(ImportUserFailure::AlreadyRegistered);
+        }
+        for (appointment, user_signature, _) in appointments.iter() {
+            if !cryptography::verify(&appointment.to_vec(), user_signature, &user_id.0) {
+                return Err(ImportUserFailure::InvalidAppointmentSignature(
+                    appointment.locator,
+                ));
+            }
+        }
+
+        self.register(user_id)
+            .map_err(ImportUserFailure::RegistrationFailure)?;
+
+        let mut imported = 0;
+        let mut skipped = 0;
+        for (appointment, user_signature, dispute_tx) in appointments.into_iter() {
+            let locator = appointment.locator;
+            if let Some(tx) = &dispute_tx {
+                if Locator::new(tx.txid()) != locator {
+                    log::info!(
+                        "Cannot import appointment {}: the dispute transaction does not match",
+                        locator
+                    );
+                    skipped += 1;
+                    continue;
+                }
+            }
+
+            match self.add_appointment_with_dispute(appointment, user_signature, false, dispute_tx)
+            {
+                Ok(_) => imported += 1,
+                Err(e) => {
+                    log::info!("Cannot import appointment {}: {:?}", locator, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        log::warn!(target: telemetry::AUDIT_TARGET, "Operator imported {} appointments of user {} ({} skipped)", imported, user_id, skipped);
+        Ok((
+            imported,
+            skipped,
+            self.gatekeeper.get_user_info(user_id).unwrap(),
+        ))
+    }
+
     /// Gets the data held by the tower about a given user.
-    pub(crate) fn get_user_info(&self, user_id: UserId) -> Option<UserInfo> {
+    pub fn get_user_info(&self, user_id: UserId) -> Option<UserInfo> {
         self.gatekeeper.get_user_info(user_id)
     }
 
     /// Gets information about a user's subscription.
-    pub(crate) fn get_subscription_info(
+    pub fn get_subscription_info(
         &self,
         signature: &str,
-    ) -> Result<(UserInfo, Vec<Locator>), GetSubscriptionInfoFailure> {
+        challenge: Option<&[u8]>,
+    ) -> Result<(UserInfo, Vec<Locator>, Option<ReissuedReceipt>), GetSubscriptionInfoFailure> {
         let message = "get subscription info".to_string();
 
         let user_id = self
             .gatekeeper
-            .authenticate_user(message.as_bytes(), signature)
+            .authenticate_user_with_challenge(message.as_bytes(), signature, challenge)
             .map_err(|_| GetSubscriptionInfoFailure::AuthenticationFailure)?;
 
         let (has_subscription_expired, expiry) =
@@ -653,12 +1451,61 @@ impl Watcher {
             }
         }
 
-        Ok((subscription_info, locators))
+        let reissued_receipt = self.get_previous_key().and_then(|_| {
+            self.reissued_receipts
+                .lock()
+                .unwrap()
+                .get(&user_id)
+                .cloned()
+        });
+
+        Ok((subscription_info, locators, reissued_receipt))
+    }
+
+    /// Gets a one-time challenge a user can use to authenticate their next request.
+    ///
+    /// Fails if the user is not registered, or if they already have too many pending challenges.
+    pub fn get_auth_challenge(&self, user_id: UserId) -> Result<Vec<u8>, ChallengeFailure> {
+        self.gatekeeper.issue_challenge(user_id)
+    }
+
+    /// Checks whether a subscription expiring at `subscription_expiry` is due for renewal.
+    pub fn is_renewal_due(&self, subscription_expiry: u32) -> bool {
+        self.gatekeeper.is_renewal_due(subscription_expiry)
+    }
+
+    /// Subscribes a user to the subscription renewal reminders sent by the tower.
+    ///
+    /// Subscriptions can only be requested provided:
+    /// - The user is registered into the system
+    /// - The user subscription has not expired
+    ///
+    /// Returns the user id alongside the reminders channel, so reminders for other users can be filtered out.
+    pub fn subscribe_renewal_reminders(
+        &self,
+        signature: &str,
+        challenge: Option<&[u8]>,
+    ) -> Result<(UserId, broadcast::Receiver<RenewalReminder>), GetSubscriptionInfoFailure> {
+        let message = "subscribe renewal reminders".to_string();
+
+        let user_id = self
+            .gatekeeper
+            .authenticate_user_with_challenge(message.as_bytes(), signature, challenge)
+            .map_err(|_| GetSubscriptionInfoFailure::AuthenticationFailure)?;
+
+        let (has_subscription_expired, expiry) =
+            self.gatekeeper.has_subscription_expired(user_id).unwrap();
+
+        if has_subscription_expired {
+            return Err(GetSubscriptionInfoFailure::SubscriptionExpired(expiry));
+        }
+
+        Ok((user_id, self.gatekeeper.subscribe_renewal_reminders()))
     }
 }
 
 /// Listen implementation by the [Watcher]. Handles monitoring and reorgs.
-impl chain::Listen for Watcher {
+impl<S: Storage> chain::Listen for Watcher<S> {
     /// Handles the monitoring process by the [Watcher].
     ///
     /// Watching is performed in a per-block basis. Therefore, a breach is only considered (and detected) if seen
@@ -689,11 +1536,22 @@ impl chain::Listen for Watcher {
             .update(*header, &locator_tx_map);
 
         if !self.appointments.lock().unwrap().is_empty() {
-            // Start by removing outdated data so it is not taken into account from this point on
-            self.delete_appointments_from_memory(
-                &self.gatekeeper.get_outdated_appointments(height),
-                DeletionReason::Outdated,
+            // Start by removing outdated data so it is not taken into account from this point on.
+            // Outdated appointments held by the Responder are handled by it.
+            let outdated_appointments = {
+                let appointments = self.appointments.lock().unwrap();
+                self.gatekeeper
+                    .get_outdated_appointments(height)
+                    .into_iter()
+                    .filter(|uuid| appointments.contains_key(uuid))
+                    .collect()
+            };
+            self.dbm.lock().unwrap().batch_update_appointment_states(
+                &outdated_appointments,
+                AppointmentState::Outdated,
+                height,
             );
+            self.delete_appointments_from_memory(&outdated_appointments, DeletionReason::Outdated);
 
             // Filter out those breaches that do not yield a valid transaction
             let (valid_breaches, invalid_breaches) =
@@ -703,6 +1561,7 @@ impl chain::Listen for Watcher {
             let mut appointments_to_delete = HashSet::from_iter(invalid_breaches.into_keys());
             let mut delivered_appointments = HashSet::new();
             for (uuid, breach) in valid_breaches {
+                let _span = telemetry::appointment_span(uuid).entered();
                 log::info!(
                     "Notifying Responder and deleting appointment (uuid: {})",
                     uuid
@@ -734,6 +1593,7 @@ impl chain::Listen for Watcher {
                     .gatekeeper
                     .delete_appointments_from_memory(&appointments_to_delete_gatekeeper),
                 DeletionReason::Invalid,
+                height,
             );
 
             if self.appointments.lock().unwrap().is_empty() {
@@ -741,6 +1601,43 @@ impl chain::Listen for Watcher {
             }
         }
 
+        // Sign the receipts issued while the previous block was the tip
+        if let Some(batcher) = &self.receipt_batcher {
+            batcher
+                .lock()
+                .unwrap()
+                .close_batches(height, &self.signing_key);
+            if let Some(prune_height) = height.checked_sub(BATCH_RETENTION + 1) {
+                self.dbm.lock().unwrap().prune_batch_receipts(prune_height);
+            }
+        }
+
+        // Breach log requests are capped per block
+        self.breach_log_requests.lock().unwrap().clear();
+
+        // Final appointment states are only kept for a while
+        if let Some(prune_height) = height.checked_sub(FINAL_STATE_RETENTION) {
+            let n = self
+                .dbm
+                .lock()
+                .unwrap()
+                .prune_appointment_states(prune_height);
+            if n > 0 {
+                log::debug!("Pruned {} final appointment states", n);
+            }
+        }
+
+        // Drop the receipts re-issued on key rotation once the overlap window closes
+        if let Some(previous_key) = &self.previous_key {
+            if height == previous_key.overlap_end {
+                log::info!(
+                    "Key rotation overlap window closed. Receipts are no longer signed by {}",
+                    previous_key.tower_id
+                );
+                self.reissued_receipts.lock().unwrap().clear();
+            }
+        }
+
         // Update last known block
         self.last_known_block_height
             .store(height, Ordering::Release);
@@ -766,18 +1663,20 @@ mod tests {
     use std::ops::Deref;
     use std::sync::{Arc, Mutex};
 
+    use crate::config::Config;
     use crate::dbm::DBM;
     use crate::responder::ConfirmationStatus;
     use crate::rpc_errors;
     use crate::test_utils::{
         create_carrier, create_responder, create_watcher, generate_dummy_appointment,
-        generate_dummy_appointment_with_user, generate_uuid, get_random_breach, get_random_tx,
-        store_appointment_and_fks_to_db, BitcoindMock, BitcoindStopper, Blockchain, MockOptions,
-        MockedServerQuery, AVAILABLE_SLOTS, DURATION, EXPIRY_DELTA, SLOTS, START_HEIGHT,
-        SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
+        generate_dummy_appointment_with_user, generate_uuid, get_gatekeeper_settings,
+        get_last_n_blocks, get_random_breach, get_random_tx, store_appointment_and_fks_to_db,
+        BitcoindMock, BitcoindStopper, Blockchain, MockOptions, MockedServerQuery, AVAILABLE_SLOTS,
+        DURATION, EXPIRY_DELTA, SLOTS, START_HEIGHT, SUBSCRIPTION_EXPIRY, SUBSCRIPTION_START,
     };
     use teos_common::cryptography::{get_random_bytes, get_random_keypair};
     use teos_common::dbm::Error as DBError;
+    use teos_common::test_utils::get_random_user_id;
 
     use bitcoin::hash_types::Txid;
     use bitcoin::hashes::Hash;
@@ -796,6 +1695,14 @@ mod tests {
     impl Eq for Watcher {}
 
     impl Watcher {
+        pub(crate) fn get_dbm(&self) -> &Arc<Mutex<DBM>> {
+            &self.dbm
+        }
+
+        pub(crate) fn set_previous_key(&mut self, previous_key: PreviousKey) {
+            self.previous_key = Some(previous_key);
+        }
+
         pub(crate) fn add_dummy_tracker_to_responder(
             &self,
             uuid: UUID,
@@ -809,6 +1716,11 @@ mod tests {
             self.responder
                 .add_random_tracker(uuid, ConfirmationStatus::ConfirmedIn(100))
         }
+
+        pub(crate) fn flag_responder_tracker_for_review(&self, uuid: UUID, height: u32) {
+            self.responder
+                .flag_for_review(&HashSet::from_iter([uuid]), height)
+        }
     }
 
     async fn init_watcher(chain: &mut Blockchain) -> (Watcher, BitcoindStopper) {
@@ -824,12 +1736,11 @@ mod tests {
 
         let gk = Arc::new(Gatekeeper::new(
             chain.get_block_count(),
-            SLOTS,
-            DURATION,
-            EXPIRY_DELTA,
+            get_gatekeeper_settings(),
             dbm.clone(),
         ));
-        let responder = create_responder(chain, gk.clone(), dbm.clone(), bitcoind_mock.url()).await;
+        let responder =
+            create_responder(chain, gk.clone(), dbm.clone(), bitcoind_mock.url(), false).await;
         create_watcher(
             chain,
             Arc::new(responder),
@@ -875,7 +1786,7 @@ mod tests {
         for _ in 0..10 {
             let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
             watcher
-                .add_appointment(appointment.clone(), user_sig.clone())
+                .add_appointment(appointment.clone(), user_sig.clone(), false)
                 .unwrap();
         }
 
@@ -910,6 +1821,88 @@ mod tests {
             &receipt.signature().unwrap(),
             &tower_pk
         ));
+
+        // The receipt is also attested by the tower at the current height
+        assert!(receipt.verify_attestation(&watcher.tower_id));
+        assert_eq!(receipt.attestation().unwrap().height(), START_HEIGHT as u32);
+    }
+
+    #[tokio::test]
+    async fn test_reattest_subscriptions() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (mut watcher, _s) = init_watcher(&mut chain).await;
+
+        let active_users: Vec<UserId> = (0..2)
+            .map(|_| {
+                let user_id = get_random_user_id();
+                watcher.register(user_id).unwrap();
+                user_id
+            })
+            .collect();
+        let expired_user = get_random_user_id();
+        watcher.gatekeeper.add_outdated_user(
+            expired_user,
+            START_HEIGHT as u32 + EXPIRY_DELTA,
+            None,
+        );
+
+        // Nothing is re-issued (nor co-signed) if the tower is not rotating its key
+        assert_eq!(watcher.reattest_subscriptions(), 0);
+        assert_eq!(watcher.sign_with_previous_key(b"message"), None);
+
+        let (previous_sk, previous_pk) = get_random_keypair();
+        watcher.set_previous_key(PreviousKey::new(previous_sk, START_HEIGHT as u32 + 2));
+
+        // Only active subscriptions are re-issued
+        assert_eq!(watcher.reattest_subscriptions(), active_users.len());
+        let reissued_receipts = watcher.reissued_receipts.lock().unwrap().clone();
+        assert!(!reissued_receipts.contains_key(&expired_user));
+        for user_id in active_users.iter() {
+            let reissued = &reissued_receipts[user_id];
+            let user_info = watcher.gatekeeper.get_user_info(*user_id).unwrap();
+
+            assert_eq!(reissued.receipt.user_id(), *user_id);
+            assert_eq!(
+                reissued.receipt.available_slots(),
+                user_info.available_slots
+            );
+            assert_eq!(
+                reissued.receipt.subscription_expiry(),
+                user_info.subscription_expiry
+            );
+            // Signed and attested by the current key, and signed by the previous key too
+            assert!(reissued.receipt.verify(&watcher.tower_id));
+            assert!(reissued.receipt.verify_attestation(&watcher.tower_id));
+            assert_eq!(
+                reissued.receipt.attestation().unwrap().height(),
+                START_HEIGHT as u32
+            );
+            assert!(cryptography::verify(
+                &reissued.receipt.to_vec(),
+                &reissued.previous_signature,
+                &previous_pk
+            ));
+        }
+
+        // New receipts are co-signed by the previous key
+        let signature = watcher.sign_with_previous_key(b"message").unwrap();
+        assert!(cryptography::verify(b"message", &signature, &previous_pk));
+
+        // Renewing the subscription supersedes the re-issued receipt
+        watcher.register(active_users[0]).unwrap();
+        assert!(!watcher
+            .reissued_receipts
+            .lock()
+            .unwrap()
+            .contains_key(&active_users[0]));
+
+        // Once the overlap window closes, the previous key is not used anymore
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        assert!(watcher.sign_with_previous_key(b"message").is_some());
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        assert_eq!(watcher.sign_with_previous_key(b"message"), None);
+        assert!(watcher.reissued_receipts.lock().unwrap().is_empty());
+        assert_eq!(watcher.reattest_subscriptions(), 0);
     }
 
     #[tokio::test]
@@ -940,11 +1933,17 @@ mod tests {
         // Add the appointment for a new user (twice so we can check that updates work)
         for _ in 0..2 {
             let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
-            let (receipt, slots, expiry) = watcher
-                .add_appointment(appointment.clone(), user_sig.clone())
+            let AddedAppointment {
+                receipt,
+                available_slots: slots,
+                subscription_expiry: expiry,
+                dispute_on_chain,
+            } = watcher
+                .add_appointment(appointment.clone(), user_sig.clone(), false)
                 .unwrap();
 
             assert_appointment_added(slots, SLOTS - 1, expiry, receipt, &user_sig, tower_id);
+            assert!(!dispute_on_chain);
         }
 
         // Add the same appointment but for another user
@@ -953,8 +1952,13 @@ mod tests {
         watcher.register(user2_id).unwrap();
 
         let user2_sig = cryptography::sign(&appointment.to_vec(), &user2_sk).unwrap();
-        let (receipt, slots, expiry) = watcher
-            .add_appointment(appointment.clone(), user2_sig.clone())
+        let AddedAppointment {
+            receipt,
+            available_slots: slots,
+            subscription_expiry: expiry,
+            ..
+        } = watcher
+            .add_appointment(appointment.clone(), user2_sig.clone(), false)
             .unwrap();
 
         assert_appointment_added(slots, SLOTS - 1, expiry, receipt, &user2_sig, tower_id);
@@ -979,7 +1983,11 @@ mod tests {
         let signature =
             cryptography::sign(&triggered_appointment.inner.to_vec(), &user_sk).unwrap();
         watcher
-            .add_appointment(triggered_appointment.inner.clone(), signature.clone())
+            .add_appointment(
+                triggered_appointment.inner.clone(),
+                signature.clone(),
+                false,
+            )
             .unwrap();
 
         let breach = get_random_breach();
@@ -989,7 +1997,7 @@ mod tests {
             user_id,
             ConfirmationStatus::InMempoolSince(chain.get_block_count()),
         );
-        let receipt = watcher.add_appointment(triggered_appointment.inner, signature);
+        let receipt = watcher.add_appointment(triggered_appointment.inner, signature, false);
 
         assert!(matches!(
             receipt,
@@ -1001,13 +2009,20 @@ mod tests {
         let (uuid, appointment_in_cache) =
             generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
         let user_sig = cryptography::sign(&appointment_in_cache.inner.to_vec(), &user_sk).unwrap();
-        let (receipt, slots, expiry) = watcher
-            .add_appointment(appointment_in_cache.inner.clone(), user_sig.clone())
+        let AddedAppointment {
+            receipt,
+            available_slots: slots,
+            subscription_expiry: expiry,
+            dispute_on_chain,
+        } = watcher
+            .add_appointment(appointment_in_cache.inner.clone(), user_sig.clone(), false)
             .unwrap();
 
         // The appointment should have been accepted, slots should have been decreased, and data should have been deleted from
-        // the Watcher's memory. Moreover, a new tracker should be found in the Responder
+        // the Watcher's memory. Moreover, a new tracker should be found in the Responder and the user should be told the
+        // dispute is already on chain
         assert_appointment_added(slots, SLOTS - 3, expiry, receipt, &user_sig, tower_id);
+        assert!(dispute_on_chain);
         assert_eq!(watcher.appointments.lock().unwrap().len(), 3);
         assert!(!watcher
             .locator_uuid_map
@@ -1033,11 +2048,17 @@ mod tests {
             generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
         invalid_appointment.inner.encrypted_blob.reverse();
         let user_sig = cryptography::sign(&invalid_appointment.inner.to_vec(), &user_sk).unwrap();
-        let (receipt, slots, expiry) = watcher
-            .add_appointment(invalid_appointment.inner.clone(), user_sig.clone())
+        let AddedAppointment {
+            receipt,
+            available_slots: slots,
+            subscription_expiry: expiry,
+            dispute_on_chain,
+        } = watcher
+            .add_appointment(invalid_appointment.inner.clone(), user_sig.clone(), false)
             .unwrap();
 
         assert_appointment_added(slots, SLOTS - 4, expiry, receipt, &user_sig, tower_id);
+        assert!(dispute_on_chain);
         assert_eq!(watcher.appointments.lock().unwrap().len(), 3);
 
         // Data should not be in the database
@@ -1061,8 +2082,13 @@ mod tests {
         let dispute_tx = &tip_txs[tip_txs.len() - 2];
         let invalid_appointment = generate_dummy_appointment(Some(&dispute_tx.txid())).inner;
         let user_sig = cryptography::sign(&invalid_appointment.to_vec(), &user_sk).unwrap();
-        let (receipt, slots, expiry) = watcher
-            .add_appointment(invalid_appointment, user_sig.clone())
+        let AddedAppointment {
+            receipt,
+            available_slots: slots,
+            subscription_expiry: expiry,
+            ..
+        } = watcher
+            .add_appointment(invalid_appointment, user_sig.clone(), false)
             .unwrap();
 
         assert_appointment_added(slots, SLOTS - 4, expiry, receipt, &user_sig, tower_id);
@@ -1081,7 +2107,7 @@ mod tests {
         let user3_sig = String::from_utf8((0..65).collect()).unwrap();
 
         assert!(matches!(
-            watcher.add_appointment(appointment.clone(), user3_sig),
+            watcher.add_appointment(appointment.clone(), user3_sig, false),
             Err(AddAppointmentFailure::AuthenticationFailure)
         ));
         // Data should not be in the database
@@ -1106,7 +2132,7 @@ mod tests {
         let new_app_sig = cryptography::sign(&new_appointment.to_vec(), &user_sk).unwrap();
 
         assert!(matches!(
-            watcher.add_appointment(new_appointment, new_app_sig),
+            watcher.add_appointment(new_appointment, new_app_sig, false),
             Err(AddAppointmentFailure::NotEnoughSlots)
         ));
         // Data should not be in the database
@@ -1126,7 +2152,7 @@ mod tests {
             .subscription_expiry = START_HEIGHT as u32;
 
         assert!(matches!(
-            watcher.add_appointment(appointment, user2_sig),
+            watcher.add_appointment(appointment, user2_sig, false),
             Err(AddAppointmentFailure::SubscriptionExpired { .. })
         ));
         // Data should not be in the database
@@ -1136,6 +2162,288 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_add_appointment_rejected_by_policy() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (mut watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id).unwrap();
+
+        // Deny-list the user. Appointments coming from it should be rejected and no slots should be consumed
+        watcher.policies = PolicySet::from_config(&Config {
+            denied_users: vec![user_id.to_string()],
+            ..Default::default()
+        });
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.add_appointment(appointment, user_sig, false),
+            Err(AddAppointmentFailure::RejectedByPolicy(..))
+        ));
+        assert!(watcher.appointments.lock().unwrap().is_empty());
+        assert_eq!(
+            watcher.get_user_info(user_id).unwrap().available_slots,
+            SLOTS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_large_blob_tier() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (mut watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        watcher.register(user_id).unwrap();
+
+        // Any blob is large, and it takes two subscriptions to send them
+        watcher.policies = PolicySet::from_config(&Config {
+            large_blob_size: 1,
+            large_blob_min_slots: SLOTS * 2,
+            ..Default::default()
+        });
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.add_appointment(appointment.clone(), user_sig.clone(), false),
+            Err(AddAppointmentFailure::RejectedByPolicy(..))
+        ));
+
+        // Once the user has paid for a bigger subscription the appointment is accepted
+        watcher.register(user_id).unwrap();
+        assert!(watcher
+            .add_appointment(appointment, user_sig, false)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_add_appointments() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+
+        // Batches from unregistered users are rejected as a whole
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.add_appointments(user_id, vec![(appointment, user_sig)], false),
+            Err(AddAppointmentFailure::AuthenticationFailure)
+        ));
+
+        // Once registered, each appointment is checked individually, and only the valid ones take slots
+        watcher.register(user_id).unwrap();
+        let mut batch = Vec::new();
+        for _ in 0..3 {
+            let appointment = generate_dummy_appointment(None).inner;
+            let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+            batch.push((appointment, user_sig));
+        }
+        let (another_sk, _) = get_random_keypair();
+        let wrongly_signed = generate_dummy_appointment(None).inner;
+        let another_sig = cryptography::sign(&wrongly_signed.to_vec(), &another_sk).unwrap();
+        batch.push((wrongly_signed.clone(), another_sig));
+
+        let AddedAppointments {
+            results,
+            available_slots,
+            ..
+        } = watcher
+            .add_appointments(user_id, batch.clone(), false)
+            .unwrap();
+        assert_eq!(available_slots, SLOTS - 3);
+        assert_eq!(results.len(), batch.len());
+        for ((locator, result), (appointment, user_sig)) in results.iter().zip(batch.iter()) {
+            assert_eq!(*locator, appointment.locator);
+            if *locator == wrongly_signed.locator {
+                assert!(matches!(
+                    result,
+                    Err(AddAppointmentFailure::AuthenticationFailure)
+                ));
+                assert!(!watcher
+                    .appointments
+                    .lock()
+                    .unwrap()
+                    .contains_key(&UUID::new(*locator, user_id)));
+            } else {
+                let accepted = result.as_ref().unwrap();
+                assert_eq!(accepted.receipt.user_signature(), user_sig);
+                assert!(!accepted.dispute_on_chain);
+                assert!(watcher
+                    .appointments
+                    .lock()
+                    .unwrap()
+                    .contains_key(&UUID::new(*locator, user_id)));
+            }
+        }
+
+        // Only the first appointment for each locator within a batch is taken into account
+        let appointment = generate_dummy_appointment(None).inner;
+        let mut duplicate = appointment.clone();
+        duplicate.to_self_delay += 1;
+        let batch: Vec<_> = vec![appointment.clone(), duplicate]
+            .into_iter()
+            .map(|a| {
+                let user_sig = cryptography::sign(&a.to_vec(), &user_sk).unwrap();
+                (a, user_sig)
+            })
+            .collect();
+        let AddedAppointments {
+            results,
+            available_slots,
+            ..
+        } = watcher.add_appointments(user_id, batch, false).unwrap();
+        assert_eq!(available_slots, SLOTS - 4);
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|(locator, _)| *locator == appointment.locator));
+        assert!(results[0].1.is_ok());
+        assert!(matches!(
+            results[1].1,
+            Err(AddAppointmentFailure::DuplicateLocator)
+        ));
+        let uuid = UUID::new(appointment.locator, user_id);
+        assert_eq!(
+            watcher
+                .dbm
+                .lock()
+                .unwrap()
+                .load_appointment(uuid)
+                .unwrap()
+                .inner,
+            appointment
+        );
+
+        // If there are not enough slots for the whole batch, nothing is added
+        watcher
+            .gatekeeper
+            .get_registered_users()
+            .lock()
+            .unwrap()
+            .get_mut(&user_id)
+            .unwrap()
+            .available_slots = 1;
+        let mut batch = Vec::new();
+        for _ in 0..2 {
+            let appointment = generate_dummy_appointment(None).inner;
+            let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+            batch.push((appointment, user_sig));
+        }
+        assert!(matches!(
+            watcher.add_appointments(user_id, batch.clone(), false),
+            Err(AddAppointmentFailure::NotEnoughSlots)
+        ));
+        for (appointment, _) in batch {
+            assert!(!watcher
+                .appointments
+                .lock()
+                .unwrap()
+                .contains_key(&UUID::new(appointment.locator, user_id)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_appointment_batched_receipts() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (mut watcher, _s) = init_watcher(&mut chain).await;
+        watcher.receipt_batcher = Some(Mutex::new(ReceiptBatcher::new()));
+
+        let (user_sk, user_pk) = get_random_keypair();
+        watcher.register(UserId(user_pk)).unwrap();
+
+        // Receipts are signed straightaway for users that do not opt in
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        let AddedAppointment { receipt, .. } = watcher
+            .add_appointment(appointment.clone(), user_sig, false)
+            .unwrap();
+        assert!(receipt.verify(&watcher.tower_id));
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        let AddedAppointment { receipt, .. } = watcher
+            .add_appointment(appointment.clone(), user_sig, true)
+            .unwrap();
+
+        // Otherwise, the receipt is not signed straightaway, but added to the current batch
+        assert_eq!(receipt.signature(), None);
+        let message = format!("get batched receipt {}", appointment.locator);
+        let request_sig = cryptography::sign(message.as_bytes(), &user_sk).unwrap();
+        assert!(matches!(
+            watcher.get_batched_receipt(appointment.locator, &request_sig),
+            Err(GetBatchedReceiptFailure::Pending)
+        ));
+
+        // Once a new block is connected, the batch is closed and the batched receipt can be retrieved
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        let batched = watcher
+            .get_batched_receipt(appointment.locator, &request_sig)
+            .unwrap();
+        assert_eq!(batched.receipt(), &receipt);
+        assert!(batched.verify(&watcher.tower_id));
+
+        // Requests for unknown appointments fail
+        let unknown_locator = generate_dummy_appointment(None).locator();
+        let message = format!("get batched receipt {}", unknown_locator);
+        assert!(matches!(
+            watcher.get_batched_receipt(
+                unknown_locator,
+                &cryptography::sign(message.as_bytes(), &user_sk).unwrap()
+            ),
+            Err(GetBatchedReceiptFailure::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batched_receipts_restart() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let (mut watcher, _s) = init_watcher_with_db(&mut chain, dbm.clone()).await;
+        watcher.receipt_batcher = Some(Mutex::new(ReceiptBatcher::new()));
+
+        let (user_sk, user_pk) = get_random_keypair();
+        watcher.register(UserId(user_pk)).unwrap();
+
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+        let AddedAppointment { receipt, .. } = watcher
+            .add_appointment(appointment.clone(), user_sig, true)
+            .unwrap();
+
+        // If the tower restarts before the batch is closed, the pending receipts are loaded from the database
+        let restarted = Watcher::new(
+            watcher.gatekeeper.clone(),
+            watcher.responder.clone(),
+            &get_last_n_blocks(&mut chain, 6).await,
+            chain.get_block_count(),
+            watcher.signing_key,
+            WatcherSettings {
+                batch_receipts: true,
+                ..Default::default()
+            },
+            dbm,
+        );
+        let message = format!("get batched receipt {}", appointment.locator);
+        let request_sig = cryptography::sign(message.as_bytes(), &user_sk).unwrap();
+        assert!(matches!(
+            restarted.get_batched_receipt(appointment.locator, &request_sig),
+            Err(GetBatchedReceiptFailure::Pending)
+        ));
+
+        restarted.block_connected(&chain.generate(None), chain.get_block_count());
+        let batched = restarted
+            .get_batched_receipt(appointment.locator, &request_sig)
+            .unwrap();
+        assert_eq!(batched.receipt(), &receipt);
+        assert!(batched.verify(&restarted.tower_id));
+    }
+
     #[tokio::test]
     async fn test_store_appointment() {
         let mut chain = Blockchain::default().with_height(START_HEIGHT);
@@ -1148,11 +2456,21 @@ mod tests {
 
         let (uuid, appointment) = generate_dummy_appointment_with_user(user_id, None);
 
-        // Storing a new appointment should return New
+        // Storing a new appointment should return New. Appointments are stored once accepted
+        watcher.dbm.lock().unwrap().update_appointment_state(
+            uuid,
+            user_id,
+            AppointmentState::Accepted,
+            START_HEIGHT as u32,
+        );
         assert_eq!(
             watcher.store_appointment(uuid, &appointment),
             StoredAppointment::New,
         );
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Watched
+        );
         assert_eq!(
             *watcher.appointments.lock().unwrap(),
             HashMap::from_iter([(uuid, appointment.get_summary())])
@@ -1212,10 +2530,20 @@ mod tests {
             generate_dummy_appointment_with_user(user_id, Some(&dispute_tx.txid()));
 
         // Valid triggered appointments should be accepted by the Responder
+        watcher.dbm.lock().unwrap().update_appointment_state(
+            uuid,
+            user_id,
+            AppointmentState::Accepted,
+            START_HEIGHT as u32,
+        );
         assert_eq!(
             watcher.store_triggered_appointment(uuid, &appointment, user_id, &dispute_tx),
             TriggeredAppointment::Accepted,
         );
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Triggered
+        );
         // In this case the appointment is kept in the Responder and, therefore, in the database
         assert!(watcher.responder.has_tracker(uuid));
         assert!(matches!(
@@ -1237,6 +2565,10 @@ mod tests {
             watcher.store_triggered_appointment(uuid, &appointment, user_id, &dispute_tx),
             TriggeredAppointment::Rejected,
         );
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
         // In this case the appointment is not kept in the Responder nor in the database
         assert!(!watcher.responder.has_tracker(uuid));
         assert!(matches!(
@@ -1252,6 +2584,10 @@ mod tests {
             watcher.store_triggered_appointment(uuid, &appointment, user_id, &get_random_tx()),
             TriggeredAppointment::Invalid,
         );
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
         // The appointment is not kept anywhere
         assert!(!watcher.responder.has_tracker(uuid));
         assert!(matches!(
@@ -1270,7 +2606,7 @@ mod tests {
         //  If the user cannot be properly identified, the request will fail. This can be simulated by providing a wrong signature
         let wrong_sig = String::from_utf8((0..65).collect()).unwrap();
         assert!(matches!(
-            watcher.get_appointment(appointment.locator, &wrong_sig),
+            watcher.get_appointment(appointment.locator, &wrong_sig, None),
             Err(GetAppointmentFailure::AuthenticationFailure)
         ));
 
@@ -1282,14 +2618,16 @@ mod tests {
             .add_appointment(
                 appointment.clone(),
                 cryptography::sign(&appointment.to_vec(), &user_sk).unwrap(),
+                false,
             )
             .unwrap();
 
         let message = format!("get appointment {}", appointment.locator);
         let signature = cryptography::sign(message.as_bytes(), &user_sk).unwrap();
-        let info = watcher
-            .get_appointment(appointment.locator, &signature)
+        let (info, expiry) = watcher
+            .get_appointment(appointment.locator, &signature, None)
             .unwrap();
+        assert_eq!(expiry, START_HEIGHT as u32 + DURATION);
 
         match info {
             AppointmentInfo::Appointment(a) => assert_eq!(a, appointment),
@@ -1326,8 +2664,8 @@ mod tests {
 
         let tracker_message = format!("get appointment {}", appointment.locator);
         let tracker_signature = cryptography::sign(tracker_message.as_bytes(), &user_sk).unwrap();
-        let info = watcher
-            .get_appointment(appointment.locator, &tracker_signature)
+        let (info, _) = watcher
+            .get_appointment(appointment.locator, &tracker_signature, None)
             .unwrap();
 
         match info {
@@ -1345,7 +2683,7 @@ mod tests {
 
         let signature2 = cryptography::sign(message.as_bytes(), &user2_sk).unwrap();
         assert!(matches!(
-            watcher.get_appointment(appointment.locator, &signature2),
+            watcher.get_appointment(appointment.locator, &signature2, None),
             Err(GetAppointmentFailure::NotFound { .. })
         ));
 
@@ -1360,11 +2698,241 @@ mod tests {
             .subscription_expiry = START_HEIGHT as u32;
 
         assert!(matches!(
-            watcher.get_appointment(appointment.locator, &signature),
+            watcher.get_appointment(appointment.locator, &signature, None),
             Err(GetAppointmentFailure::SubscriptionExpired { .. })
         ));
     }
 
+    #[tokio::test]
+    async fn test_get_breach_log() {
+        // Only blocks that are IRREVOCABLY_RESOLVED deep can be queried
+        let mut chain = Blockchain::default()
+            .with_height(START_HEIGHT + constants::IRREVOCABLY_RESOLVED as usize);
+
+        // The breach log is disabled by default
+        let (watcher, _s) = init_watcher(&mut chain).await;
+        assert_eq!(
+            watcher.get_breach_log(0, None),
+            Err(GetBreachLogFailure::Disabled)
+        );
+
+        let dbm = Arc::new(Mutex::new(DBM::in_memory().unwrap()));
+        let bitcoind_mock = BitcoindMock::new(MockOptions::default());
+        let gk = Arc::new(Gatekeeper::new(
+            chain.get_block_count(),
+            get_gatekeeper_settings(),
+            dbm.clone(),
+        ));
+        let responder = create_responder(
+            &mut chain,
+            gk.clone(),
+            dbm.clone(),
+            bitcoind_mock.url(),
+            true,
+        )
+        .await;
+        let (watcher, _s) =
+            create_watcher(&mut chain, Arc::new(responder), gk, bitcoind_mock, dbm).await;
+
+        let final_height = chain.get_block_count() - constants::IRREVOCABLY_RESOLVED;
+        let entries = vec![
+            (get_random_tx().txid(), final_height - 10),
+            (get_random_tx().txid(), final_height),
+        ];
+        watcher
+            .dbm
+            .lock()
+            .unwrap()
+            .store_breach_log_entries(&entries);
+
+        // The log covers up to the last final block and is signed by the tower
+        let log = watcher.get_breach_log(final_height - 10, None).unwrap();
+        assert_eq!(
+            (log.from_height(), log.to_height()),
+            (final_height - 10, final_height)
+        );
+        assert_eq!(log.entries(), &entries);
+        assert!(log.verify(&watcher.tower_id));
+
+        let log = watcher.get_breach_log(final_height - 5, None).unwrap();
+        assert_eq!(log.entries(), &entries[1..].to_vec());
+
+        // Heights that are not final yet cannot be queried
+        assert_eq!(
+            watcher.get_breach_log(final_height + 1, None),
+            Err(GetBreachLogFailure::FutureHeight(final_height))
+        );
+
+        // The number of requests per client and block is capped, and the limit is reset when a new block is connected
+        for _ in 2..MAX_BREACH_LOG_REQUESTS_PER_BLOCK {
+            assert!(watcher.get_breach_log(0, None).is_ok());
+        }
+        assert_eq!(
+            watcher.get_breach_log(0, None),
+            Err(GetBreachLogFailure::RateLimited)
+        );
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+        assert!(watcher.get_breach_log(0, client).is_ok());
+
+        watcher.block_connected(&chain.generate(None), chain.get_block_count());
+        assert!(watcher.get_breach_log(0, None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_export_import_user() {
+        let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
+        let dispute_tx = chain.blocks.last().unwrap().txdata[0].clone();
+        let (source, _s) = init_watcher(&mut chain).await;
+        // The destination tower has not seen the dispute transaction
+        let mut another_chain = Blockchain::default().with_height(START_HEIGHT);
+        let (destination, _d) = init_watcher(&mut another_chain).await;
+
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        source.register(user_id).unwrap();
+        let mut appointments = Vec::new();
+        for _ in 0..3 {
+            let appointment = generate_dummy_appointment(None).inner;
+            let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+            source
+                .add_appointment(appointment.clone(), user_sig.clone(), false)
+                .unwrap();
+            appointments.push((appointment, user_sig, None));
+        }
+        // Add an appointment that gets triggered straightaway
+        let triggered = generate_dummy_appointment(Some(&dispute_tx.txid())).inner;
+        let user_sig = cryptography::sign(&triggered.to_vec(), &user_sk).unwrap();
+        let AddedAppointment {
+            dispute_on_chain, ..
+        } = source
+            .add_appointment(triggered.clone(), user_sig.clone(), false)
+            .unwrap();
+        assert!(dispute_on_chain);
+        appointments.push((triggered.clone(), user_sig, Some(dispute_tx.clone())));
+
+        // Data cannot be exported without the user consent
+        let consent_expiry = START_HEIGHT as u32 + 6;
+        let mut consent = MigrationConsent::new(user_id, destination.tower_id, consent_expiry);
+        assert_eq!(
+            source.export_user(&consent),
+            Err(ExportUserFailure::InvalidConsent)
+        );
+
+        // Nor for unknown users
+        let (another_sk, another_pk) = get_random_keypair();
+        let mut unknown_user_consent =
+            MigrationConsent::new(UserId(another_pk), destination.tower_id, consent_expiry);
+        unknown_user_consent.sign(&another_sk);
+        assert_eq!(
+            source.export_user(&unknown_user_consent),
+            Err(ExportUserFailure::NotFound)
+        );
+
+        // Nor with an expired consent
+        let mut expired_consent =
+            MigrationConsent::new(user_id, destination.tower_id, START_HEIGHT as u32 - 1);
+        expired_consent.sign(&user_sk);
+        assert_eq!(
+            source.export_user(&expired_consent),
+            Err(ExportUserFailure::ConsentExpired(START_HEIGHT as u32 - 1))
+        );
+
+        // Once the user consents, the appointments (including the triggered ones) are exported alongside their signatures
+        consent.sign(&user_sk);
+        let mut exported = source.export_user(&consent).unwrap();
+        exported.sort_by_key(|(a, _, _)| a.locator.to_vec());
+        appointments.sort_by_key(|(a, _, _)| a.locator.to_vec());
+        assert_eq!(exported, appointments);
+
+        // Data can only be imported by the tower the user consented to
+        assert_eq!(
+            source.import_user(&consent, exported.clone()),
+            Err(ImportUserFailure::WrongTower)
+        );
+        assert_eq!(
+            destination.import_user(
+                &MigrationConsent::new(user_id, destination.tower_id, consent_expiry),
+                exported.clone()
+            ),
+            Err(ImportUserFailure::InvalidConsent)
+        );
+
+        // While the consent is valid
+        let mut expired_consent =
+            MigrationConsent::new(user_id, destination.tower_id, START_HEIGHT as u32 - 1);
+        expired_consent.sign(&user_sk);
+        assert_eq!(
+            destination.import_user(&expired_consent, exported.clone()),
+            Err(ImportUserFailure::ConsentExpired(START_HEIGHT as u32 - 1))
+        );
+
+        // And all appointments must be signed by the user
+        let mut tampered = exported.clone();
+        tampered[0].1 = cryptography::sign(&tampered[0].0.to_vec(), &another_sk).unwrap();
+        assert_eq!(
+            destination.import_user(&consent, tampered),
+            Err(ImportUserFailure::InvalidAppointmentSignature(
+                exported[0].0.locator
+            ))
+        );
+        assert!(destination.get_user_info(user_id).is_none());
+
+        // Otherwise, the user is given a fresh subscription, the appointments are watched by the destination tower and the
+        // triggered ones go straight to its Responder
+        let (imported, skipped, user_info) =
+            destination.import_user(&consent, exported.clone()).unwrap();
+        assert_eq!((imported, skipped), (4, 0));
+        assert_eq!(user_info.available_slots, SLOTS - 4);
+        assert_eq!(
+            user_info.subscription_expiry,
+            START_HEIGHT as u32 + DURATION
+        );
+        for (appointment, _, dispute_tx) in appointments.iter() {
+            let uuid = UUID::new(appointment.locator, user_id);
+            if dispute_tx.is_some() {
+                assert!(destination.responder.has_tracker(uuid));
+                assert!(source.responder.has_tracker(uuid));
+            } else {
+                assert!(destination.appointments.lock().unwrap().contains_key(&uuid));
+                // The source tower keeps watching
+                assert!(source.appointments.lock().unwrap().contains_key(&uuid));
+            }
+        }
+
+        // The user cannot be imported again, so no additional slots are granted
+        assert_eq!(
+            destination.import_user(&consent, exported),
+            Err(ImportUserFailure::AlreadyRegistered)
+        );
+        assert_eq!(destination.get_user_info(user_id).unwrap(), user_info);
+    }
+
+    #[tokio::test]
+    async fn test_import_user_wrong_dispute() {
+        let mut chain = Blockchain::default().with_height(START_HEIGHT);
+        let (watcher, _s) = init_watcher(&mut chain).await;
+
+        // Appointments whose dispute transaction does not match their locator are skipped
+        let (user_sk, user_pk) = get_random_keypair();
+        let user_id = UserId(user_pk);
+        let appointment = generate_dummy_appointment(None).inner;
+        let user_sig = cryptography::sign(&appointment.to_vec(), &user_sk).unwrap();
+
+        let mut consent = MigrationConsent::new(user_id, watcher.tower_id, START_HEIGHT as u32);
+        consent.sign(&user_sk);
+        let (imported, skipped, user_info) = watcher
+            .import_user(
+                &consent,
+                vec![(appointment.clone(), user_sig, Some(get_random_tx()))],
+            )
+            .unwrap();
+        assert_eq!((imported, skipped), (0, 1));
+        assert_eq!(user_info.available_slots, SLOTS);
+        assert!(!watcher
+            .responder
+            .has_tracker(UUID::new(appointment.locator, user_id)));
+    }
+
     #[tokio::test]
     async fn test_get_breaches() {
         let mut chain = Blockchain::default().with_height_and_txs(START_HEIGHT, 10);
@@ -1581,11 +3149,12 @@ mod tests {
             }
         }
 
-        // The deletion reason does not matter here, it only changes the logged message when deleting data
+        // The deletion reason does not matter here, it only changes the logged message and the final state of the data
         watcher.delete_appointments(
             &target_appointments,
             &updated_users,
             DeletionReason::Accepted,
+            START_HEIGHT as u32,
         );
 
         // Only appointments in the target_appointments map should have been removed from
@@ -1690,11 +3259,11 @@ mod tests {
 
         let user_sig = cryptography::sign(&appointment.inner.to_vec(), &user_sk).unwrap();
         watcher
-            .add_appointment(appointment.inner.clone(), user_sig)
+            .add_appointment(appointment.inner.clone(), user_sig, false)
             .unwrap();
         let user2_sig = cryptography::sign(&appointment.inner.to_vec(), &user2_sk).unwrap();
         watcher
-            .add_appointment(appointment.inner.clone(), user2_sig)
+            .add_appointment(appointment.inner.clone(), user2_sig, false)
             .unwrap();
 
         watcher
@@ -1739,6 +3308,14 @@ mod tests {
             watcher.dbm.lock().unwrap().load_appointment(uuid1),
             Ok(ExtendedAppointment { .. })
         ));
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid1),
+            AppointmentState::Outdated
+        );
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid2),
+            AppointmentState::Watched
+        );
 
         assert!(watcher.appointments.lock().unwrap().contains_key(&uuid2));
         assert!(watcher.locator_uuid_map.lock().unwrap()[&appointment.locator()].contains(&uuid2));
@@ -1757,7 +3334,9 @@ mod tests {
         let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
         let sig = cryptography::sign(&appointment.inner.to_vec(), &user2_sk).unwrap();
         let uuid = UUID::new(appointment.locator(), user2_id);
-        watcher.add_appointment(appointment.inner, sig).unwrap();
+        watcher
+            .add_appointment(appointment.inner, sig, false)
+            .unwrap();
 
         assert!(watcher.appointments.lock().unwrap().contains_key(&uuid));
 
@@ -1789,13 +3368,19 @@ mod tests {
             watcher.dbm.lock().unwrap().load_tracker(uuid),
             Ok(TransactionTracker { .. })
         ));
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Triggered
+        );
 
         // Check triggering with a valid formatted transaction but that is rejected by the Responder.
         let dispute_tx = get_random_tx();
         let appointment = generate_dummy_appointment(Some(&dispute_tx.txid()));
         let sig = cryptography::sign(&appointment.inner.to_vec(), &user2_sk).unwrap();
         let uuid = UUID::new(appointment.locator(), user2_id);
-        watcher.add_appointment(appointment.inner, sig).unwrap();
+        watcher
+            .add_appointment(appointment.inner, sig, false)
+            .unwrap();
 
         // Set the carrier response
         let (carrier, _as) = create_carrier(
@@ -1831,6 +3416,10 @@ mod tests {
             watcher.dbm.lock().unwrap().load_tracker(uuid),
             Err(DBError::NotFound)
         ));
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
 
         // Checks invalid triggers. Add a new appointment and trigger it with invalid data.
         let dispute_tx = get_random_tx();
@@ -1841,7 +3430,7 @@ mod tests {
         let sig = cryptography::sign(&appointment.inner.to_vec(), &user2_sk).unwrap();
         let uuid = UUID::new(appointment.locator(), user2_id);
         watcher
-            .add_appointment(appointment.inner.clone(), sig)
+            .add_appointment(appointment.inner.clone(), sig, false)
             .unwrap();
 
         watcher.block_connected(
@@ -1866,6 +3455,10 @@ mod tests {
             watcher.dbm.lock().unwrap().load_appointment(uuid),
             Err(DBError::NotFound)
         ));
+        assert_eq!(
+            watcher.dbm.lock().unwrap().load_appointment_state(uuid),
+            AppointmentState::Rejected
+        );
     }
 
     #[tokio::test]
@@ -262,6 +262,102 @@ impl TryFrom<serde_json::Value> for GetAppointmentParams {
     }
 }
 
+/// Errors related to the `signmigrationconsent` command.
+#[derive(Debug)]
+pub enum SignMigrationConsentError {
+    InvalidId(String),
+    InvalidExpiry(String),
+    InvalidFormat(String),
+}
+
+impl std::fmt::Display for SignMigrationConsentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignMigrationConsentError::InvalidId(x) => write!(f, "{}", x),
+            SignMigrationConsentError::InvalidExpiry(x) => write!(f, "{}", x),
+            SignMigrationConsentError::InvalidFormat(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// Parameters related to the `signmigrationconsent` command.
+#[derive(Debug)]
+pub struct SignMigrationConsentParams {
+    pub tower_id: TowerId,
+    pub expiry: u32,
+}
+
+impl TryFrom<serde_json::Value> for SignMigrationConsentParams {
+    type Error = SignMigrationConsentError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Array(a) => {
+                let param_count = a.len();
+                if param_count != 2 {
+                    Err(SignMigrationConsentError::InvalidFormat(format!(
+                        "Unexpected request format. The request needs 2 parameter. Received: {}",
+                        param_count
+                    )))
+                } else {
+                    let tower_id = if let Some(s) = a.get(0).unwrap().as_str() {
+                        TowerId::from_str(s).map_err(|_| {
+                            SignMigrationConsentError::InvalidId("Invalid tower id".to_owned())
+                        })
+                    } else {
+                        Err(SignMigrationConsentError::InvalidId(
+                            "tower_id must be a hex encoded string".to_owned(),
+                        ))
+                    }?;
+
+                    let expiry = a
+                        .get(1)
+                        .unwrap()
+                        .as_u64()
+                        .and_then(|e| u32::try_from(e).ok())
+                        .ok_or_else(|| {
+                            SignMigrationConsentError::InvalidExpiry(
+                                "expiry must be a block height".to_owned(),
+                            )
+                        })?;
+
+                    Ok(Self { tower_id, expiry })
+                }
+            }
+            serde_json::Value::Object(mut m) => {
+                let allowed_keys = ["tower_id", "expiry"];
+
+                if m.len() > allowed_keys.len() {
+                    return Err(SignMigrationConsentError::InvalidFormat(
+                        "Invalid named argument found in request".to_owned(),
+                    ));
+                }
+
+                for k in allowed_keys.iter() {
+                    if !m.contains_key(*k) {
+                        return Err(SignMigrationConsentError::InvalidFormat(format!(
+                            "{} is mandatory",
+                            k
+                        )));
+                    }
+                }
+
+                let mut params = Vec::with_capacity(allowed_keys.len());
+                for k in allowed_keys {
+                    if let Some(v) = m.remove(k) {
+                        params.push(v);
+                    }
+                }
+                SignMigrationConsentParams::try_from(json!(params))
+            }
+            _ => Err(SignMigrationConsentError::InvalidFormat(format!(
+                "Unexpected request format. Expected: tower_id expiry. Received: '{}'",
+                value
+            ))),
+        }
+    }
+}
+
 /// Data associated with a commitment revocation. Represents the data sent by CoreLN through the `commitment_revocation` hook.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommitmentRevocation {
@@ -562,4 +658,70 @@ mod tests {
             }
         }
     }
+
+    mod sign_migration_consent_command {
+        use super::*;
+
+        #[test]
+        fn test_try_from_array() {
+            let id = json!(VALID_ID);
+            let expiry = json!(500);
+
+            // Valid params
+            let p = SignMigrationConsentParams::try_from(json!(vec![&id, &expiry]));
+            assert!(matches!(
+                p,
+                Ok(SignMigrationConsentParams { expiry: 500, .. })
+            ));
+
+            // Wrong params
+            let p = SignMigrationConsentParams::try_from(json!(vec![&json!(0), &expiry]));
+            assert!(matches!(p, Err(SignMigrationConsentError::InvalidId(..))));
+
+            // Expiry must be a (32-bit) block height
+            for wrong_expiry in [json!("500"), json!(-1), json!(u64::MAX)] {
+                let p = SignMigrationConsentParams::try_from(json!(vec![&id, &wrong_expiry]));
+                assert!(matches!(
+                    p,
+                    Err(SignMigrationConsentError::InvalidExpiry(..))
+                ));
+            }
+        }
+
+        #[test]
+        fn test_try_from_dict() {
+            let id = json!(VALID_ID);
+            let expiry = json!(500);
+
+            // Valid params
+            let p = SignMigrationConsentParams::try_from(json!(HashMap::from([
+                ("tower_id", &id),
+                ("expiry", &expiry)
+            ])));
+            assert!(matches!(p, Ok(..)));
+
+            // Wrong keys
+            let p = SignMigrationConsentParams::try_from(json!(HashMap::from([
+                ("tower_id", &id),
+                ("wrong_expiry", &expiry)
+            ])));
+            assert!(matches!(
+                p,
+                Err(SignMigrationConsentError::InvalidFormat(..))
+            ));
+        }
+
+        #[test]
+        fn test_wrong_param_count() {
+            let params_vec = [vec![], vec![json!(VALID_ID)]];
+
+            for params in params_vec {
+                let p = SignMigrationConsentParams::try_from(json!(params));
+                assert!(matches!(
+                    p,
+                    Err(SignMigrationConsentError::InvalidFormat(..))
+                ));
+            }
+        }
+    }
 }
@@ -18,7 +18,8 @@ const TABLES: [&str; 8] = [
     "CREATE TABLE IF NOT EXISTS towers (
     tower_id INT PRIMARY KEY,
     net_addr TEXT NOT NULL,
-    available_slots INT NOT NULL
+    available_slots INT NOT NULL,
+    subscription_expiry INT NOT NULL DEFAULT 0
 )",
     "CREATE TABLE IF NOT EXISTS appointments (
     locator INT PRIMARY KEY,
@@ -108,11 +109,45 @@ impl DBM {
         let connection = Connection::open(db_path)?;
         connection.execute("PRAGMA foreign_keys=1;", [])?;
         let mut dbm = Self { connection };
+        dbm.migrate_towers_subscription_expiry()?;
         dbm.create_tables(Vec::from_iter(TABLES))?;
 
         Ok(dbm)
     }
 
+    /// Adds the `subscription_expiry` column to the `towers` table of databases created by versions of the plugin that
+    /// did not have it.
+    ///
+    /// Those versions only knew about the expiry of the latest registration receipt, so that's what it is filled with.
+    fn migrate_towers_subscription_expiry(&self) -> Result<(), SqliteError> {
+        let table_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='towers'",
+            [],
+            |row| row.get(0),
+        )?;
+        let column_exists: bool = self.connection.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('towers') WHERE name='subscription_expiry'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if table_exists && !column_exists {
+            self.connection.execute(
+                "ALTER TABLE towers ADD COLUMN subscription_expiry INT NOT NULL DEFAULT 0",
+                [],
+            )?;
+            self.connection.execute(
+                "UPDATE towers SET subscription_expiry = (SELECT MAX(subscription_expiry) 
+                    FROM registration_receipts 
+                    WHERE registration_receipts.tower_id = towers.tower_id)",
+                [],
+            )?;
+            log::info!("Tower subscription expiry column added");
+        }
+
+        Ok(())
+    }
+
     /// Stores the client secret key into the database.
     ///
     /// When a new key is generated, old keys are not overwritten but are not retrievable from the API either.
@@ -152,10 +187,15 @@ impl DBM {
     ) -> Result<(), Error> {
         let tx = self.get_mut_connection().transaction().unwrap();
         tx.execute(
-            "INSERT INTO towers (tower_id, net_addr, available_slots) 
-                VALUES (?1, ?2, ?3) 
-                ON CONFLICT (tower_id) DO UPDATE SET net_addr = ?2, available_slots = ?3",
-            params![tower_id.to_vec(), net_addr, receipt.available_slots()],
+            "INSERT INTO towers (tower_id, net_addr, available_slots, subscription_expiry) 
+                VALUES (?1, ?2, ?3, ?4) 
+                ON CONFLICT (tower_id) DO UPDATE SET net_addr = ?2, available_slots = ?3, subscription_expiry = ?4",
+            params![
+                tower_id.to_vec(),
+                net_addr,
+                receipt.available_slots(),
+                receipt.subscription_expiry()
+            ],
         )
         .map_err(Error::Unknown)?;
         tx.execute(
@@ -166,6 +206,24 @@ impl DBM {
         tx.commit().map_err(Error::Unknown)
     }
 
+    /// Updates the subscription of a given tower with the data reported by the tower itself (e.g. after the subscription
+    /// has been renewed using a different client).
+    ///
+    /// Registration receipts are left untouched, since they are signed by the tower.
+    pub fn update_tower_subscription(
+        &self,
+        tower_id: TowerId,
+        available_slots: u32,
+        subscription_expiry: u32,
+    ) -> Result<(), Error> {
+        let query =
+            "UPDATE towers SET available_slots=?1, subscription_expiry=?2 WHERE tower_id=?3";
+        self.update_data(
+            query,
+            params![available_slots, subscription_expiry, tower_id.to_vec()],
+        )
+    }
+
     /// Loads a tower record from the database.
     ///
     /// Tower records are composed from the tower information and the appointment data. The latter is split in:
@@ -174,7 +232,7 @@ impl DBM {
     pub fn load_tower_record(&self, tower_id: TowerId) -> Result<TowerInfo, Error> {
         let mut stmt = self
         .connection
-        .prepare("SELECT t.net_addr, t.available_slots, r.subscription_start, r.subscription_expiry 
+        .prepare("SELECT t.net_addr, t.available_slots, r.subscription_start, t.subscription_expiry 
                     FROM towers as t, registration_receipts as r 
                     WHERE t.tower_id = r.tower_id AND t.tower_id = ?1 AND r.subscription_expiry = (SELECT MAX(subscription_expiry) 
                         FROM registration_receipts 
@@ -258,7 +316,7 @@ impl DBM {
         let mut towers = HashMap::new();
         let mut stmt = self
             .connection
-            .prepare("SELECT tw.tower_id, tw.net_addr, tw.available_slots, rr.subscription_start, rr.subscription_expiry 
+            .prepare("SELECT tw.tower_id, tw.net_addr, tw.available_slots, rr.subscription_start, tw.subscription_expiry 
                         FROM towers AS tw 
                         JOIN registration_receipts AS rr 
                         JOIN (SELECT tower_id, MAX(subscription_expiry) AS max_se 
@@ -723,6 +781,57 @@ mod tests {
         assert_eq!(dbm.load_tower_record(tower_id).unwrap(), tower_info);
     }
 
+    #[test]
+    fn test_update_tower_subscription() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        // Unknown towers cannot be updated
+        let tower_id = get_random_user_id();
+        assert!(matches!(
+            dbm.update_tower_subscription(tower_id, 21, 420),
+            Err(Error::NotFound)
+        ));
+
+        let receipt = get_random_registration_receipt();
+        dbm.store_tower_record(tower_id, "talaia.watch", &receipt)
+            .unwrap();
+        dbm.update_tower_subscription(tower_id, 21, 420).unwrap();
+
+        let tower_info = dbm.load_tower_record(tower_id).unwrap();
+        assert_eq!(tower_info.available_slots, 21);
+        assert_eq!(tower_info.subscription_expiry, 420);
+        assert_eq!(dbm.load_towers()[&tower_id].subscription_expiry, 420);
+
+        // The registration receipt is not modified
+        assert_eq!(
+            dbm.load_registration_receipt(tower_id, receipt.user_id())
+                .unwrap(),
+            receipt
+        );
+    }
+
+    #[test]
+    fn test_migrate_towers_subscription_expiry() {
+        let mut dbm = DBM::in_memory().unwrap();
+
+        let tower_id = get_random_user_id();
+        let receipt = get_random_registration_receipt();
+        dbm.store_tower_record(tower_id, "talaia.watch", &receipt)
+            .unwrap();
+
+        // Towers stored by versions of the plugin with no subscription expiry column
+        dbm.connection
+            .execute("ALTER TABLE towers DROP COLUMN subscription_expiry", [])
+            .unwrap();
+
+        // The expiry is taken from the latest registration receipt
+        dbm.migrate_towers_subscription_expiry().unwrap();
+        assert_eq!(
+            dbm.load_tower_record(tower_id).unwrap().subscription_expiry,
+            receipt.subscription_expiry()
+        );
+    }
+
     #[test]
     fn test_load_registration_receipt() {
         let mut dbm = DBM::in_memory().unwrap();
@@ -12,28 +12,40 @@ use cln_plugin::options::{ConfigOption, Value};
 use cln_plugin::{anyhow, Builder, Error, Plugin};
 
 use teos_common::appointment::{Appointment, Locator};
-use teos_common::protos as common_msgs;
+use teos_common::client::{ClientError, TowerClient};
+use teos_common::migration::MigrationConsent;
 use teos_common::TowerId;
 use teos_common::{cryptography, errors};
 
-use watchtower_plugin::convert::{CommitmentRevocation, GetAppointmentParams, RegisterParams};
-use watchtower_plugin::net::http::{
-    self, post_request, process_post_response, AddAppointmentError, ApiResponse, RequestError,
+use watchtower_plugin::convert::{
+    CommitmentRevocation, GetAppointmentParams, RegisterParams, SignMigrationConsentParams,
 };
+use watchtower_plugin::net::http::{self, AddAppointmentError};
 use watchtower_plugin::retrier::RetryManager;
 use watchtower_plugin::wt_client::WTClient;
 use watchtower_plugin::TowerStatus;
 
-fn to_cln_error(e: RequestError) -> Error {
-    let e = match e {
-        RequestError::ConnectionError(e) => anyhow!(e),
-        RequestError::DeserializeError(e) => anyhow!(e),
-        RequestError::Unexpected(e) => anyhow!(e),
-    };
+fn to_cln_error(e: ClientError) -> Error {
+    let e = anyhow!(e.to_string());
     log::info!("{}", e);
     e
 }
 
+/// Builds a client for a given tower, acting on behalf of the plugin user.
+fn tower_client(
+    plugin: &Plugin<Arc<Mutex<WTClient>>>,
+    tower_id: TowerId,
+    tower_net_addr: &str,
+) -> Result<TowerClient, ClientError> {
+    let state = plugin.state().lock().unwrap();
+    TowerClient::new(
+        tower_id,
+        tower_net_addr,
+        state.user_sk,
+        state.proxy.as_deref(),
+    )
+}
+
 /// Registers the client to a given tower.
 ///
 /// Accepted tower_id formats:
@@ -48,7 +60,6 @@ async fn register(
     let params = RegisterParams::try_from(v).map_err(|x| anyhow!(x))?;
     let host = params.host.unwrap_or_else(|| "localhost".to_owned());
     let tower_id = params.tower_id;
-    let user_id = plugin.state().lock().unwrap().user_id;
 
     // TODO: The user should pick the start_time or, at least, check the returned start time against it's known block height.
     // Otherwise the tower could just generate a subscription starting far in the future. For this we need to access lightning RPC
@@ -70,23 +81,19 @@ async fn register(
         tower_net_addr = format!("http://{}", tower_net_addr)
     }
 
-    let proxy = plugin.state().lock().unwrap().proxy.clone();
-
-    let receipt = http::register(tower_id, user_id, &tower_net_addr, proxy)
-        .await
-        .map_err(|e| {
-            let mut state = plugin.state().lock().unwrap();
-            if e.is_connection() && state.towers.contains_key(&tower_id) {
-                state.set_tower_status(tower_id, TowerStatus::TemporaryUnreachable);
-            }
-            to_cln_error(e)
-        })?;
-
-    if !receipt.verify(&tower_id) {
-        return Err(anyhow!(
-            "Registration receipt contains bad signature. Are you using the right tower_id?"
-        ));
+    // The receipt (and its attestation, if any) is checked to be signed by the tower
+    log::info!("Registering in the Eye of Satoshi (tower_id={})", tower_id);
+    let receipt = match tower_client(&plugin, tower_id, &tower_net_addr) {
+        Ok(client) => client.register().await,
+        Err(e) => Err(e),
     }
+    .map_err(|e| {
+        let mut state = plugin.state().lock().unwrap();
+        if e.is_connection() && state.towers.contains_key(&tower_id) {
+            state.set_tower_status(tower_id, TowerStatus::TemporaryUnreachable);
+        }
+        to_cln_error(e)
+    })?;
 
     plugin
         .state()
@@ -137,27 +144,19 @@ async fn get_subscription_info(
 ) -> Result<serde_json::Value, Error> {
     let tower_id = TowerId::try_from(v).map_err(|x| anyhow!(x))?;
 
-    let (user_sk, tower_net_addr, proxy) = {
+    let tower_net_addr = {
         let state = plugin.state().lock().unwrap();
         if let Some(info) = state.towers.get(&tower_id) {
-            Ok((state.user_sk, info.net_addr.clone(), state.proxy.clone()))
+            Ok(info.net_addr.clone())
         } else {
             Err(anyhow!("Unknown tower id: {}", tower_id))
         }
     }?;
 
-    let get_subscription_info = format!("{}/get_subscription_info", tower_net_addr);
-    let signature = cryptography::sign("get subscription info".as_bytes(), &user_sk).unwrap();
-
-    let response: common_msgs::GetSubscriptionInfoResponse = process_post_response(
-        post_request(
-            &get_subscription_info,
-            &common_msgs::GetSubscriptionInfoRequest { signature },
-            proxy,
-        )
-        .await,
-    )
-    .await
+    let response = match tower_client(&plugin, tower_id, &tower_net_addr) {
+        Ok(client) => client.get_subscription_info().await,
+        Err(e) => Err(e),
+    }
     .map_err(|e| {
         if e.is_connection() {
             plugin
@@ -169,6 +168,29 @@ async fn get_subscription_info(
         to_cln_error(e)
     })?;
 
+    // Reconcile our view of the subscription with the tower's
+    let mut state = plugin.state().lock().unwrap();
+    if let Some(tower) = state.towers.get(&tower_id) {
+        if tower.available_slots != response.available_slots
+            || tower.subscription_expiry != response.subscription_expiry
+        {
+            log::info!(
+                "Subscription with {} out of sync. Updating available slots ({} -> {}) and expiry ({} -> {})",
+                tower_id,
+                tower.available_slots,
+                response.available_slots,
+                tower.subscription_expiry,
+                response.subscription_expiry
+            );
+            state.update_subscription(
+                tower_id,
+                response.available_slots,
+                response.subscription_expiry,
+            );
+        }
+    }
+    drop(state);
+
     Ok(json!(response))
 }
 
@@ -179,46 +201,35 @@ async fn get_appointment(
 ) -> Result<serde_json::Value, Error> {
     let params = GetAppointmentParams::try_from(v).map_err(|x| anyhow!(x))?;
 
-    let (user_sk, tower_net_addr, proxy) = {
+    let tower_net_addr = {
         let state = plugin.state().lock().unwrap();
         if let Some(info) = state.towers.get(&params.tower_id) {
-            Ok((state.user_sk, info.net_addr.clone(), state.proxy.clone()))
+            Ok(info.net_addr.clone())
         } else {
             Err(anyhow!("Unknown tower id: {}", params.tower_id))
         }
     }?;
 
-    let get_appointment_endpoint = format!("{}/get_appointment", tower_net_addr);
-    let signature = cryptography::sign(
-        format!("get appointment {}", params.locator).as_bytes(),
-        &user_sk,
-    )
-    .unwrap();
+    let response = match tower_client(&plugin, params.tower_id, &tower_net_addr) {
+        Ok(client) => client.get_appointment(params.locator).await,
+        Err(e) => Err(e),
+    };
 
-    let response: ApiResponse<common_msgs::GetAppointmentResponse> = process_post_response(
-        post_request(
-            &get_appointment_endpoint,
-            &common_msgs::GetAppointmentRequest {
-                locator: params.locator.to_vec(),
-                signature,
-            },
-            proxy,
-        )
-        .await,
-    )
-    .await
-    .map_err(|e| {
-        if e.is_connection() {
-            plugin
-                .state()
-                .lock()
-                .unwrap()
-                .set_tower_status(params.tower_id, TowerStatus::TemporaryUnreachable);
+    // Errors returned by the tower (e.g. the appointment not being found) are handed to the user as is
+    match response {
+        Ok(r) => Ok(json!(r)),
+        Err(ClientError::ApiError(e)) => Ok(json!(e)),
+        Err(e) => {
+            if e.is_connection() {
+                plugin
+                    .state()
+                    .lock()
+                    .unwrap()
+                    .set_tower_status(params.tower_id, TowerStatus::TemporaryUnreachable);
+            }
+            Err(to_cln_error(e))
         }
-        to_cln_error(e)
-    })?;
-
-    Ok(json!(response))
+    }
 }
 
 /// Gets an appointment receipt from the client given a tower_id and a locator (if it exists).
@@ -335,6 +346,27 @@ async fn abandon_tower(
     }
 }
 
+/// Signs the consent to have the client data moved to a given tower, up to a given block height.
+///
+/// The consent is handed to the operator of the tower currently holding the data, so it can be exported and then
+/// imported by the given tower.
+async fn sign_migration_consent(
+    plugin: Plugin<Arc<Mutex<WTClient>>>,
+    v: serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let params = SignMigrationConsentParams::try_from(v).map_err(|e| anyhow!(e))?;
+    let state = plugin.state().lock().unwrap();
+    let mut consent = MigrationConsent::new(state.user_id, params.tower_id, params.expiry);
+    consent.sign(&state.user_sk);
+
+    Ok(json!({
+        "user_id": state.user_id,
+        "tower_id": params.tower_id,
+        "consent_expiry": params.expiry,
+        "consent_signature": consent.signature().unwrap(),
+    }))
+}
+
 /// Sends an appointment to all registered towers for every new commitment transaction.
 ///
 /// The appointment is built using the data provided by the backend (dispute txid and penalty transaction).
@@ -562,6 +594,11 @@ async fn main() -> Result<(), Error> {
             "Forgets about a tower and wipes all local data.",
             abandon_tower,
         )
+        .rpcmethod(
+            "signmigrationconsent",
+            "Signs the consent to have the client data moved to a given tower, up to a given block height.",
+            sign_migration_consent,
+        )
         .hook("commitment_revocation", on_commitment_revocation);
 
     // We're unwrapping here given it does not seem we actually have anything to check at the moment.
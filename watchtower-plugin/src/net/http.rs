@@ -4,11 +4,14 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use teos_common::appointment::Appointment;
 use teos_common::cryptography;
 use teos_common::protos as common_msgs;
-use teos_common::receipts::{AppointmentReceipt, RegistrationReceipt};
-use teos_common::{TowerId, UserId};
+use teos_common::receipts::AppointmentReceipt;
+use teos_common::TowerId;
 
 use crate::MisbehaviorProof;
 
+// The rest of the tower API is reached through the teos-common client, which shares its errors with this module.
+pub use teos_common::client::ApiError;
+
 /// Represents a generic api response.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
@@ -17,13 +20,6 @@ pub enum ApiResponse<T> {
     Error(ApiError),
 }
 
-/// API errors that can be received when interacting with the tower. Error codes match `teos_common::errors`.
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ApiError {
-    pub error: String,
-    pub error_code: u8,
-}
-
 /// Errors related to requests sent to the tower.
 #[derive(Debug, PartialEq, Eq)]
 pub enum RequestError {
@@ -52,36 +48,6 @@ impl From<RequestError> for AddAppointmentError {
     }
 }
 
-/// Handles the logic of interacting with the `register` endpoint of the tower.
-pub async fn register(
-    tower_id: TowerId,
-    user_id: UserId,
-    tower_net_addr: &str,
-    proxy: Option<String>,
-) -> Result<RegistrationReceipt, RequestError> {
-    log::info!("Registering in the Eye of Satoshi (tower_id={})", tower_id);
-    process_post_response(
-        post_request(
-            &format!("{}/register", tower_net_addr),
-            &common_msgs::RegisterRequest {
-                user_id: user_id.to_vec(),
-            },
-            proxy,
-        )
-        .await,
-    )
-    .await
-    .map(|r: common_msgs::RegisterResponse| {
-        RegistrationReceipt::with_signature(
-            user_id,
-            r.available_slots,
-            r.subscription_start,
-            r.subscription_expiry,
-            r.subscription_signature,
-        )
-    })
-}
-
 /// Encapsulates the logging and response parsing of sending and appointment to the tower.
 pub async fn add_appointment(
     tower_id: TowerId,
@@ -100,6 +66,13 @@ pub async fn add_appointment(
     log::debug!("Appointment accepted and signed by {}", tower_id);
     log::debug!("Remaining slots: {}", response.available_slots);
     log::debug!("Start block: {}", response.start_block);
+    if response.dispute_on_chain {
+        log::info!(
+            "The dispute for appointment {} is already on chain. {} is responding to it straightaway",
+            appointment.locator,
+            tower_id
+        );
+    }
 
     Ok((response.available_slots, receipt))
 }
@@ -115,6 +88,7 @@ pub async fn send_appointment(
     let request_data = common_msgs::AddAppointmentRequest {
         appointment: Some(appointment.clone().into()),
         signature: signature.to_owned(),
+        batch_receipt: false,
     };
 
     match process_post_response(
@@ -133,8 +107,15 @@ pub async fn send_appointment(
                 r.start_block,
                 r.signature.clone(),
             );
+            // Receipts are only batched (and handed without signature) when requested, so an empty or malformed
+            // signature is treated as an invalid response instead of a misbehavior
             let recovered_id = TowerId(
-                cryptography::recover_pk(&receipt.to_vec(), &receipt.signature().unwrap()).unwrap(),
+                cryptography::recover_pk(&receipt.to_vec(), &r.signature).map_err(|_| {
+                    RequestError::DeserializeError(format!(
+                        "Unexpected receipt signature: {:?}",
+                        r.signature
+                    ))
+                })?,
             );
             if recovered_id == tower_id {
                 Ok((r, receipt))
@@ -209,8 +190,7 @@ mod tests {
 
     use crate::test_utils::get_dummy_add_appointment_response;
     use teos_common::test_utils::{
-        generate_random_appointment, get_random_appointment_receipt,
-        get_random_registration_receipt, get_random_user_id,
+        generate_random_appointment, get_random_appointment_receipt, get_random_user_id,
     };
 
     mod request_error {
@@ -233,70 +213,6 @@ mod tests {
         }
     }
 
-    #[tokio::test]
-    async fn test_register() {
-        let (tower_sk, tower_pk) = cryptography::get_random_keypair();
-        let mut registration_receipt = get_random_registration_receipt();
-        registration_receipt.sign(&tower_sk);
-
-        let server = MockServer::start();
-        let api_mock = server.mock(|when, then| {
-            when.method(POST).path("/register");
-            then.status(200)
-                .header("content-type", "application/json")
-                .json_body(json!(registration_receipt));
-        });
-
-        let receipt = register(
-            TowerId(tower_pk),
-            registration_receipt.user_id(),
-            &format!("http://{}", server.address()),
-            None,
-        )
-        .await
-        .unwrap();
-
-        api_mock.assert();
-        assert_eq!(receipt, registration_receipt);
-    }
-
-    #[tokio::test]
-    async fn test_register_connection_error() {
-        let error = register(
-            get_random_user_id(),
-            get_random_user_id(),
-            "http://server_addr",
-            None,
-        )
-        .await
-        .unwrap_err();
-
-        assert!(matches!(error, RequestError::ConnectionError { .. }))
-    }
-
-    #[tokio::test]
-    async fn test_register_deserialize_error() {
-        let server = MockServer::start();
-        let api_mock = server.mock(|when, then| {
-            when.method(POST).path("/register");
-            then.status(200)
-                .header("content-type", "application/json")
-                .json_body(json!([]));
-        });
-
-        let error = register(
-            get_random_user_id(),
-            get_random_user_id(),
-            &format!("http://{}", server.address()),
-            None,
-        )
-        .await
-        .unwrap_err();
-
-        api_mock.assert();
-        assert!(matches!(error, RequestError::DeserializeError { .. }))
-    }
-
     #[tokio::test]
     async fn test_add_appointment() {
         // `add_appointment` is basically a pass trough function for `send_appointment` with some logging and a parse of the outputs
@@ -406,6 +322,43 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_send_appointment_unsigned_receipt() {
+        let (tower_sk, tower_pk) = cryptography::get_random_keypair();
+        let appointment = generate_random_appointment(None);
+
+        // A receipt with no signature (e.g. a batched one) is rejected instead of panicking
+        let appointment_receipt = get_random_appointment_receipt(tower_sk);
+        let mut add_appointment_response =
+            get_dummy_add_appointment_response(appointment.locator, &appointment_receipt);
+        add_appointment_response.signature = String::new();
+
+        let server = MockServer::start();
+        let api_mock = server.mock(|when, then| {
+            when.method(POST).path("/add_appointment");
+            then.status(200)
+                .header("content-type", "application/json")
+                .json_body(json!(add_appointment_response));
+        });
+
+        let error = send_appointment(
+            TowerId(tower_pk),
+            &server.base_url(),
+            None,
+            &appointment,
+            appointment_receipt.user_signature(),
+        )
+        .await
+        .unwrap_err();
+
+        api_mock.assert();
+        if let AddAppointmentError::RequestError(e) = error {
+            assert!(matches!(e, RequestError::DeserializeError { .. }))
+        } else {
+            panic!("DeserializeError was expected")
+        }
+    }
+
     #[tokio::test]
     async fn test_send_appointment_connection_error() {
         let error = send_appointment(
@@ -7,6 +7,7 @@ use backoff::future::retry_notify;
 use backoff::{Error, ExponentialBackoff};
 
 use teos_common::appointment::Locator;
+use teos_common::client::TowerClient;
 use teos_common::cryptography;
 use teos_common::errors;
 use teos_common::UserId as TowerId;
@@ -238,7 +239,7 @@ impl Retrier {
 
     async fn run(&self) -> Result<(), Error<&'static str>> {
         // Create a new scope so we can get all the data only locking the WTClient once.
-        let (tower_id, status, net_addr, user_id, user_sk, proxy) = {
+        let (tower_id, status, net_addr, user_sk, proxy) = {
             let wt_client = self.wt_client.lock().unwrap();
             if wt_client.towers.get(&self.tower_id).is_none() {
                 return Err(Error::permanent("Tower was abandoned. Skipping retry"));
@@ -249,7 +250,6 @@ impl Retrier {
                 self.tower_id,
                 tower.status,
                 tower.net_addr.clone(),
-                wt_client.user_id,
                 wt_client.user_sk,
                 wt_client.proxy.clone(),
             )
@@ -257,17 +257,15 @@ impl Retrier {
 
         // If the tower state is subscription_error we need to re-register first. If we cannot, then the retry is aborted.
         if status.is_subscription_error() {
-            let receipt = http::register(tower_id, user_id, &net_addr, proxy.clone())
-                .await
-                .map_err(|e| {
-                    log::debug!("Cannot renew registration with tower. Error: {:?}", e);
-                    Error::permanent("Cannot renew registration with tower")
-                })?;
-            if !receipt.verify(&tower_id) {
-                return Err(Error::permanent(
-                        "Registration receipt contains bad signature. Are you using the right tower_id?"
-                    ));
+            // The receipt is checked to be signed by the tower
+            let receipt = match TowerClient::new(tower_id, &net_addr, user_sk, proxy.as_deref()) {
+                Ok(client) => client.register().await,
+                Err(e) => Err(e),
             }
+            .map_err(|e| {
+                log::debug!("Cannot renew registration with tower. Error: {:?}", e);
+                Error::permanent("Cannot renew registration with tower")
+            })?;
             self.wt_client
             .lock()
             .unwrap()
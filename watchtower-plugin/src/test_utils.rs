@@ -12,5 +12,9 @@ pub fn get_dummy_add_appointment_response(
         signature: receipt.signature().unwrap(),
         available_slots: 21,
         subscription_expiry: 1000,
+        renewal_due: false,
+        dispute_on_chain: false,
+        blocks_behind: 0,
+        previous_signature: String::new(),
     }
 }
@@ -169,6 +169,28 @@ impl WTClient {
         }
     }
 
+    /// Updates the subscription of a given tower (both in memory and the database) with the data reported by the tower.
+    pub fn update_subscription(
+        &mut self,
+        tower_id: TowerId,
+        available_slots: u32,
+        subscription_expiry: u32,
+    ) {
+        if let Some(tower) = self.towers.get_mut(&tower_id) {
+            tower.available_slots = available_slots;
+            tower.subscription_expiry = subscription_expiry;
+
+            self.dbm
+                .update_tower_subscription(tower_id, available_slots, subscription_expiry)
+                .unwrap();
+        } else {
+            log::error!(
+                "Cannot update tower subscription. Unknown tower_id: {}",
+                tower_id
+            );
+        }
+    }
+
     /// Gets an appointment receipt from the database (if found).
     pub fn get_appointment_receipt(
         &self,
@@ -460,6 +482,53 @@ mod tests {
         assert_eq!(wt_client.load_tower_info(tower_id).unwrap(), tower_info);
     }
 
+    #[tokio::test]
+    async fn test_update_subscription() {
+        let tmp_path = TempDir::new(&format!("watchtower_{}", get_random_user_id())).unwrap();
+        let mut wt_client =
+            WTClient::new(tmp_path.path().to_path_buf(), unbounded_channel().0).await;
+
+        let tower_id = get_random_user_id();
+        let tower_net_addr = "talaia.watch".to_owned();
+        let registration_receipt = get_random_registration_receipt();
+
+        // If we call this on an unknown tower it will simply do nothing
+        wt_client.update_subscription(tower_id, 21, 420);
+        assert!(!wt_client.towers.contains_key(&tower_id));
+
+        // Add the tower to the state and try again
+        wt_client
+            .add_update_tower(tower_id, &tower_net_addr, &registration_receipt)
+            .unwrap();
+        wt_client.update_subscription(tower_id, 21, 420);
+
+        let tower_info = TowerInfo::empty(
+            tower_net_addr,
+            21,
+            registration_receipt.subscription_start(),
+            420,
+        );
+        assert_eq!(
+            wt_client.towers.get(&tower_id).unwrap(),
+            &TowerSummary::from(tower_info.clone())
+        );
+        assert_eq!(wt_client.load_tower_info(tower_id).unwrap(), tower_info);
+
+        // The update survives restarts, while the registration receipt is left untouched
+        let wt_client = WTClient::new(tmp_path.path().to_path_buf(), unbounded_channel().0).await;
+        assert_eq!(
+            wt_client.towers.get(&tower_id).unwrap(),
+            &TowerSummary::from(tower_info)
+        );
+        assert_eq!(
+            wt_client
+                .get_registration_receipt(tower_id)
+                .unwrap()
+                .subscription_expiry(),
+            registration_receipt.subscription_expiry()
+        );
+    }
+
     #[tokio::test]
     async fn test_add_pending_appointment() {
         let tmp_path = TempDir::new(&format!("watchtower_{}", get_random_user_id())).unwrap();